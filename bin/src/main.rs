@@ -4,19 +4,44 @@ use ahlang::*;
 use termion::{raw::IntoRawMode, input::TermRead};
 use std::io::Write;
 
+/// How errors are printed.
+#[derive(Clone, Copy, PartialEq)]
+enum ErrorFormat {
+    /// Source snippets for people to read.
+    Human,
+    /// A JSON object per line for tools to read.
+    Json,
+}
+
+const USAGE: &str = "Usage: ahlang [--error-format=human|json] <file>";
+
 fn main() {
     // Binary entry point
-    let args: Vec<String> = std::env::args().collect();
+    let mut error_format = ErrorFormat::Human;
+    let mut args: Vec<String> = Vec::new();
+
+    for arg in std::env::args() {
+        match arg.strip_prefix("--error-format=") {
+            Some("human") => error_format = ErrorFormat::Human,
+            Some("json") => error_format = ErrorFormat::Json,
+            Some(format) => {
+                eprintln!("Unknown error format `{}`, expected `human` or `json`", format);
+                std::process::exit(2);
+            }
+            None => args.push(arg),
+        }
+    }
 
     if args.len() < 2 {
-        println!("Usage: ahlang <file>");
+        println!("{}", USAGE);
         return;
     }
 
     if args[1] == "help" {
-        println!("Usage: ahlang <file>");
+        println!("{}", USAGE);
         println!("Version: ahlang version");
         println!("REPL: ahlang repl");
+        println!("Explain an error code: ahlang explain <code>");
         return;
     }
 
@@ -30,39 +55,48 @@ fn main() {
         return;
     }
 
-    let file = std::fs::read_to_string(&args[1]).unwrap();
-    let source_str = file.as_str();
-    
-    let mut l = Lexer::new();
-    l.set_input(source_str);
-
-    let res = l.tokenize();
-    let tokens: &lexer::Tokens;
-    
-    if res.is_err() {
-        println!("Error: {}", res.unwrap_err());
+    if args[1] == "explain" {
+        explain(args.get(2).map(String::as_str));
         return;
-    } else {
-        tokens = res.unwrap();
     }
-    
-    let mut p = Parser::new();
-    p.set_tokens(tokens);
-    let res = p.parse();
 
-    if res.is_err() {
-        println!("Error: {}", res.unwrap_err());
-        return;
-    }
+    let mut sources = source::SourceMap::new();
+    let file = sources.load(&args[1]).unwrap();
 
-    let mut i = Interpreter::new();
-    match i.run(res.unwrap()) {
-        Ok(_) => {},
-        Err(e) => {
-            println!("Error: {}", e);
-            return;
+    if let Err(diagnostics) = ahlang::run_file(&sources, file) {
+        for diagnostic in diagnostics {
+            match error_format {
+                ErrorFormat::Human => diagnostic
+                    .render(&sources, true, io::stderr())
+                    .unwrap(),
+                ErrorFormat::Json => eprintln!("{}", diagnostic.to_json(&sources)),
+            }
         }
+        if error_format == ErrorFormat::Human {
+            eprintln!("For more information about an error, try `ahlang explain <code>`.");
+        }
+        std::process::exit(1);
+    }
+}
+
+/// Prints the long explanation of an error code.
+fn explain(code: Option<&str>) {
+    let Some(code) = code else {
+        println!("Usage: ahlang explain <code>");
+        return;
     };
+
+    match diagnostic::codes::lookup(code) {
+        Some(error) => {
+            println!("{}: {}", error.code, error.summary);
+            println!();
+            print!("{}", error.explanation);
+        }
+        None => {
+            eprintln!("`{}` is not an error code", code);
+            std::process::exit(1);
+        }
+    }
 }
 
 // fn next_line() {
@@ -105,9 +139,33 @@ fn print_continuation_start() {
     print!("\x1B[32m{}\x1B[0m", CONT_LINE);
 }
 
+/// Writes the output of the program to stdout. The terminal is in raw mode, so new lines have to
+/// go back to the start of the line themselves.
+struct RawOutput;
+
+impl Write for RawOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut stdout = io::stdout();
+        for line in buf.split_inclusive(|&byte| byte == b'\n') {
+            match line.strip_suffix(b"\n") {
+                Some(line) => {
+                    stdout.write_all(line)?;
+                    stdout.write_all(b"\r\n")?;
+                }
+                None => stdout.write_all(line)?,
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stdout().flush()
+    }
+}
+
 // TODO: Paste, move left and right, tab
 fn repl() {
-    let mut engine = ReplEngine::new();
+    let mut engine = ReplEngine::with_output(RawOutput);
 
     let mut history_stack: Vec<String> = Vec::new();
     #[allow(unused_assignments)]
@@ -150,7 +208,8 @@ fn repl() {
                                     next_line_start();
                                     // if input isnt ending in semicolon or rbrace, don't break, go to next line and print ..
                                     let is_comment = input.starts_with("//");
-                                    if ((input.ends_with(';') || input.ends_with('}')) && in_block < 1) || is_comment || input.is_empty() {
+                                    let is_command = input.starts_with(':');
+                                    if ((input.ends_with(';') || input.ends_with('}')) && in_block < 1) || is_comment || is_command || input.is_empty() {
                                         break;
                                     } else {
                                         // add \n to input
@@ -187,6 +246,8 @@ fn repl() {
                                 next_line_start();
                                 print!("- Ctrl + T to inspect variables");
                                 next_line_start();
+                                print!("- :doc <name> to show the doc comment of a function or type");
+                                next_line_start();
                                 print!("- To write multiline statements, press enter before ending the statement.");
                                 next_line_start();
                                 break;
@@ -197,9 +258,9 @@ fn repl() {
                                 print!("\x1B[33;4m! Variables:\x1B[0m");
                                 next_line_start();
                                 
-                                // Assuming you have an `engine` instance of `YourEngineType`
-                                for (k, v) in engine.get_vars() {
-                                    print!("\x1B[33m {}: \x1B[33;2m{:?}\x1B[0m", k, v);
+                                for (name, var) in engine.get_vars() {
+                                    let mutable = if var.mutable { "mut " } else { "" };
+                                    print!("\x1B[33m {}{}: \x1B[33;2m{}\x1B[0m", mutable, name, var);
                                     next_line_start();
                                 }
                                 break;
@@ -392,11 +453,34 @@ fn repl() {
             continue;
         }
 
+        if let Some(name) = input.strip_prefix(":doc") {
+            let name = name.trim();
+            match engine.doc(name) {
+                Some(doc) => print!("\x1B[33m{}\x1B[0m", doc.replace('\n', "\r\n")),
+                None => print!("\x1B[33m! No documentation for `{}`\x1B[0m", name),
+            }
+            next_line_start();
+            continue;
+        }
+
         let res = engine.eval(input.as_str());
         //let res: Result<(), String> = Ok(());
 
-        if res.is_err() {
-            println!("\x1B[31m! {}\x1B[0m", res.unwrap_err());
+        if let Ok(Some((value, ty))) = &res {
+            print!("{}\x1B[2m: {}\x1B[0m", value, ty);
+            next_line_start();
+        }
+
+        if let Err(diagnostics) = res {
+            for diagnostic in diagnostics {
+                let mut rendered = Vec::new();
+                diagnostic
+                    .render(engine.sources(), true, &mut rendered)
+                    .unwrap();
+
+                // The terminal is in raw mode, so new lines don't go back to the start of the line
+                print!("{}", String::from_utf8_lossy(&rendered).replace('\n', "\r\n"));
+            }
             line_start();
             continue;
         }
@@ -0,0 +1,251 @@
+//! Unification of type variables, for the types that aren't written in the source.
+
+use super::types::{FnParam, FnType, Type, TypeVar, VarKind};
+
+/// Two types that can't be unified.
+#[derive(Debug)]
+pub struct Mismatch;
+
+/// Keeps track of what every type variable has been inferred to be.
+#[derive(Debug, Default)]
+pub struct InferenceTable {
+    /// What each variable was unified with, indexed by the variable. Variables can point to other
+    /// variables, [`InferenceTable::resolve`] follows them.
+    bindings: Vec<Option<Type>>,
+    /// The kind of each variable, so the ones for literals can be found at the end.
+    kinds: Vec<VarKind>,
+}
+
+impl InferenceTable {
+    pub fn fresh(&mut self) -> Type {
+        self.fresh_of(VarKind::Any)
+    }
+
+    /// A variable for an int literal, which can only become an int.
+    pub fn fresh_int(&mut self) -> Type {
+        self.fresh_of(VarKind::Int)
+    }
+
+    /// A variable for a float literal, which can only become a float.
+    pub fn fresh_float(&mut self) -> Type {
+        self.fresh_of(VarKind::Float)
+    }
+
+    fn fresh_of(&mut self, kind: VarKind) -> Type {
+        self.bindings.push(None);
+        self.kinds.push(kind);
+        Type::Var(TypeVar {
+            index: self.bindings.len() as u32 - 1,
+            kind,
+        })
+    }
+
+    /// Makes the number literals that weren't used as a specific size `int` and `float`.
+    pub fn default_literals(&mut self) {
+        for index in 0..self.bindings.len() {
+            if self.bindings[index].is_some() {
+                continue;
+            }
+            self.bindings[index] = match self.kinds[index] {
+                VarKind::Any => continue,
+                VarKind::Int => Some(Type::INT),
+                VarKind::Float => Some(Type::FLOAT),
+            };
+        }
+    }
+
+    /// Follows variables until reaching a type that isn't a known variable. Only the outermost
+    /// type is resolved, use [`InferenceTable::zonk`] to resolve the types inside of it too.
+    pub fn resolve(&self, ty: &Type) -> Type {
+        let mut ty = ty.clone();
+        while let Type::Var(var) = ty {
+            match &self.bindings[var.index as usize] {
+                Some(bound) => ty = bound.clone(),
+                None => break,
+            }
+        }
+        ty
+    }
+
+    /// Replaces every variable in a type that has been inferred with what it was inferred to be.
+    pub fn zonk(&self, ty: &Type) -> Type {
+        match self.resolve(ty) {
+            Type::Array(element) => Type::Array(Box::new(self.zonk(&element))),
+            Type::Fn(FnType { params, ret }) => Type::Fn(FnType {
+                params: params
+                    .iter()
+                    .map(|param| FnParam {
+                        ty: self.zonk(&param.ty),
+                        mutable: param.mutable,
+                    })
+                    .collect(),
+                ret: Box::new(self.zonk(&ret)),
+            }),
+            ty => ty,
+        }
+    }
+
+    /// Like [`InferenceTable::zonk`], but variables that haven't been inferred become
+    /// `{unknown}`.
+    pub fn zonk_or_error(&self, ty: &Type) -> Type {
+        match self.zonk(ty) {
+            Type::Var(_) => Type::Error,
+            Type::Array(element) => Type::Array(Box::new(self.zonk_or_error(&element))),
+            Type::Fn(FnType { params, ret }) => Type::Fn(FnType {
+                params: params
+                    .iter()
+                    .map(|param| FnParam {
+                        ty: self.zonk_or_error(&param.ty),
+                        mutable: param.mutable,
+                    })
+                    .collect(),
+                ret: Box::new(self.zonk_or_error(&ret)),
+            }),
+            ty => ty,
+        }
+    }
+
+    /// The variables in a type that haven't been inferred yet.
+    pub fn unresolved(&self, ty: &Type) -> Vec<TypeVar> {
+        match self.resolve(ty) {
+            Type::Var(var) => vec![var],
+            Type::Array(element) => self.unresolved(&element),
+            Type::Fn(FnType { params, ret }) => params
+                .iter()
+                .map(|param| &param.ty)
+                .chain(std::iter::once(&*ret))
+                .flat_map(|ty| self.unresolved(ty))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Makes two types the same, inferring the variables in them.
+    ///
+    /// `!` and `{unknown}` unify with everything without inferring anything from it, except that
+    /// a variable unified with `{unknown}` becomes `{unknown}` to prevent more errors about it.
+    pub fn unify(&mut self, a: &Type, b: &Type) -> Result<(), Mismatch> {
+        match (self.resolve(a), self.resolve(b)) {
+            (Type::Never, _) | (_, Type::Never) => Ok(()),
+            (Type::Var(a), Type::Var(b)) if a == b => Ok(()),
+            // The variable that can be anything is the one that's inferred
+            (Type::Var(var), ty) | (ty, Type::Var(var)) if var.kind == VarKind::Any => {
+                if self.occurs(var, &ty) {
+                    return Err(Mismatch);
+                }
+                self.bindings[var.index as usize] = Some(ty);
+                Ok(())
+            }
+            // A literal only becomes a number of its kind
+            (Type::Var(var), ty) | (ty, Type::Var(var)) => {
+                let fits = match (&ty, var.kind) {
+                    (Type::Var(other), kind) => other.kind == kind,
+                    (Type::Int(_), VarKind::Int) | (Type::Float(_), VarKind::Float) => true,
+                    (Type::Error, _) => true,
+                    _ => false,
+                };
+                if !fits {
+                    return Err(Mismatch);
+                }
+                self.bindings[var.index as usize] = Some(ty);
+                Ok(())
+            }
+            (Type::Error, _) | (_, Type::Error) => Ok(()),
+            (Type::Array(a), Type::Array(b)) => self.unify(&a, &b),
+            (Type::Fn(a), Type::Fn(b)) => {
+                if a.params.len() != b.params.len() {
+                    return Err(Mismatch);
+                }
+                for (a, b) in a.params.iter().zip(&b.params) {
+                    if a.mutable != b.mutable {
+                        return Err(Mismatch);
+                    }
+                    self.unify(&a.ty, &b.ty)?;
+                }
+                self.unify(&a.ret, &b.ret)
+            }
+            (a, b) if a == b => Ok(()),
+            _ => Err(Mismatch),
+        }
+    }
+
+    /// The type of two values that both have to be the result of the same expression, like the
+    /// branches of an `if`.
+    pub fn join(&mut self, a: &Type, b: &Type) -> Result<Type, Mismatch> {
+        match (self.resolve(a), self.resolve(b)) {
+            (Type::Never, ty) | (ty, Type::Never) => Ok(ty),
+            (a, b) => {
+                self.unify(&a, &b)?;
+                Ok(a)
+            }
+        }
+    }
+
+    /// Whether a variable appears in a type, which would make unifying them create an infinite
+    /// type like `[[[...]]]`.
+    fn occurs(&self, var: TypeVar, ty: &Type) -> bool {
+        match self.resolve(ty) {
+            Type::Var(other) => other == var,
+            Type::Array(element) => self.occurs(var, &element),
+            Type::Fn(FnType { params, ret }) => {
+                params.iter().any(|param| self.occurs(var, &param.ty)) || self.occurs(var, &ret)
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checker::types::IntTy;
+
+    #[test]
+    fn test_unify() {
+        let mut table = InferenceTable::default();
+        let a = table.fresh();
+        let b = table.fresh();
+
+        table.unify(&a, &b).unwrap();
+        table
+            .unify(
+                &Type::Array(Box::new(b.clone())),
+                &Type::Array(Box::new(Type::INT)),
+            )
+            .unwrap();
+        assert_eq!(table.zonk(&a), Type::INT);
+        assert!(table.unify(&a, &Type::FLOAT).is_err());
+
+        // `!` doesn't infer anything, `{unknown}` does
+        let c = table.fresh();
+        table.unify(&c, &Type::Never).unwrap();
+        assert!(matches!(table.resolve(&c), Type::Var(_)));
+        table.unify(&c, &Type::Error).unwrap();
+        assert_eq!(table.resolve(&c), Type::Error);
+
+        let d = table.fresh();
+        assert!(table.unify(&d, &Type::Array(Box::new(d.clone()))).is_err());
+        assert_eq!(table.join(&Type::Never, &Type::Bool).unwrap(), Type::Bool);
+    }
+
+    #[test]
+    fn test_literals() {
+        let mut table = InferenceTable::default();
+        let int = table.fresh_int();
+        let float = table.fresh_float();
+        let any = table.fresh();
+
+        assert!(table.unify(&int, &float).is_err());
+        assert!(table.unify(&int, &Type::Bool).is_err());
+        table.unify(&any, &int).unwrap();
+        assert_eq!(table.resolve(&any), int);
+
+        // A literal used as another size of int becomes it, otherwise it's an `int`
+        let byte = table.fresh_int();
+        table.unify(&byte, &Type::Int(IntTy::U8)).unwrap();
+        table.default_literals();
+        assert_eq!(table.zonk(&any), Type::INT);
+        assert_eq!(table.zonk(&float), Type::FLOAT);
+        assert_eq!(table.zonk(&byte), Type::Int(IntTy::U8));
+    }
+}
@@ -0,0 +1,3803 @@
+//! Checks the types of a program before it runs.
+//!
+//! Types that aren't written, like those of unannotated `let`s, closure parameters and empty
+//! arrays, are inferred from how the values are used. They start out as type variables which get
+//! unified with the types they're used as, and the checks that need to know a type are put off
+//! until the end of the program if it isn't known yet.
+
+pub mod infer;
+mod patterns;
+pub mod types;
+
+use std::collections::{HashMap, HashSet};
+
+use chumsky::span::Span as _;
+use smol_str::SmolStr;
+
+use crate::{
+    diagnostic::{codes, Diagnostic},
+    lexer::Span,
+    parser::ast::{
+        Ast, BinaryOp, Block, ClosureParam, Enum, Expr, ExprKind, Function, Ident, Impl, ItemKind,
+        Literal, MatchArm, NodeId, Param, Pattern, PatternKind, PayloadPattern, StepOp, Stmt,
+        StmtKind, Struct, Trait, TypeExpr, TypeExprKind, UnaryOp, VariantKind,
+    },
+    resolver::{Builtin, Res, Resolutions},
+};
+
+use infer::InferenceTable;
+use patterns::{Ctor, Pat, Usefulness};
+pub use types::{FloatTy, FnParam, FnType, IntTy, Type, TypeVar};
+
+/// What the checker found out about the program.
+#[derive(Debug, Default)]
+pub struct TypeInfo {
+    expr_types: HashMap<NodeId, Type>,
+    /// The functions that method calls and paths like `Point::origin` refer to, and the variants
+    /// that paths like `Shape::Circle` do.
+    methods: HashMap<NodeId, Callee>,
+    /// The methods that each struct implements the methods of its traits with, by name.
+    impls: HashMap<NodeId, HashMap<SmolStr, NodeId>>,
+}
+
+/// The function that a method call or path calls.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Callee {
+    /// A method or associated function of a struct by its method, or a variant of an enum.
+    Static(NodeId),
+    /// A trait method called on a trait object or a type parameter, which calls the method of
+    /// the struct the value turns out to be.
+    Dynamic(SmolStr),
+}
+
+impl TypeInfo {
+    /// The type of an expression.
+    pub fn expr(&self, id: NodeId) -> Option<&Type> {
+        self.expr_types.get(&id)
+    }
+
+    /// The function a method call or path expression calls.
+    pub fn method(&self, id: NodeId) -> Option<&Callee> {
+        self.methods.get(&id)
+    }
+
+    /// The method a struct implements a trait method with, which is the trait's own method if
+    /// the struct uses its default.
+    pub fn trait_method(&self, ty: NodeId, name: &str) -> Option<NodeId> {
+        self.impls.get(&ty)?.get(name).copied()
+    }
+
+    /// Adds what was found out about another program, like the next input of the REPL.
+    pub fn extend(&mut self, other: TypeInfo) {
+        self.expr_types.extend(other.expr_types);
+        self.methods.extend(other.methods);
+        for (ty, methods) in other.impls {
+            self.impls.entry(ty).or_default().extend(methods);
+        }
+    }
+}
+
+/// Checks the types of the program, returning the errors that were found. Names that couldn't be
+/// resolved have already been reported by the resolver, and are silently given an unknown type.
+pub fn check(ast: &Ast, names: &Resolutions) -> (TypeInfo, Vec<Diagnostic>) {
+    check_with(ast, names, &mut Environment::default())
+}
+
+/// The declarations of programs that were already checked, so that another program can continue
+/// where they left off, like the inputs of the REPL.
+#[derive(Debug, Clone, Default)]
+pub struct Environment {
+    bindings: HashMap<NodeId, Binding>,
+    structs: HashMap<NodeId, StructInfo>,
+    traits: HashMap<NodeId, TraitInfo>,
+    enums: HashMap<NodeId, EnumInfo>,
+    generics: HashMap<NodeId, Vec<NodeId>>,
+}
+
+impl Environment {
+    /// The type of a declaration, and whether it's mutable.
+    pub fn get(&self, id: NodeId) -> Option<(&Type, bool)> {
+        self.bindings
+            .get(&id)
+            .map(|binding| (&binding.ty, binding.mutable))
+    }
+}
+
+/// Like [`check`], but the program can use the declarations in `env`, which the program's own
+/// declarations are added to.
+pub fn check_with(
+    ast: &Ast,
+    names: &Resolutions,
+    env: &mut Environment,
+) -> (TypeInfo, Vec<Diagnostic>) {
+    let mut checker = Checker {
+        names,
+        bindings: std::mem::take(&mut env.bindings),
+        structs: std::mem::take(&mut env.structs),
+        traits: std::mem::take(&mut env.traits),
+        enums: std::mem::take(&mut env.enums),
+        generics: std::mem::take(&mut env.generics),
+        declared_methods: HashSet::new(),
+        declared_impls: HashSet::new(),
+        blocks: 0,
+        returns: Vec::new(),
+        loops: Vec::new(),
+        table: InferenceTable::default(),
+        unknowns: Vec::new(),
+        deferred: Vec::new(),
+        bounds: Vec::new(),
+        literals: Vec::new(),
+        matches: Vec::new(),
+        finished: false,
+        info: TypeInfo::default(),
+        diagnostics: Vec::new(),
+    };
+    checker.check_block(&ast.body);
+    checker.finish();
+
+    // The inference table isn't kept, so the types have to be complete
+    env.bindings = checker
+        .bindings
+        .into_iter()
+        .map(|(id, mut binding)| {
+            binding.ty = checker.table.zonk_or_error(&binding.ty);
+            (id, binding)
+        })
+        .collect();
+    env.structs = checker.structs;
+    env.traits = checker.traits;
+    env.enums = checker.enums;
+    env.generics = checker.generics;
+
+    (checker.info, checker.diagnostics)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BindingKind {
+    Let,
+    Param,
+    /// The variable of a `for` loop or a binding in a pattern.
+    Pattern,
+    Const,
+    Fn,
+    /// The name of a struct, which calls its constructor.
+    Struct,
+}
+
+#[derive(Debug, Clone)]
+struct Binding {
+    ty: Type,
+    kind: BindingKind,
+    mutable: bool,
+    /// Where the binding was declared.
+    span: Span,
+    /// Set for immutable `let`s declared without a value, which can be assigned once. The
+    /// assignment has to be in the same function and loop as the `let`, so that it only happens
+    /// once.
+    uninit: Option<(usize, usize)>,
+}
+
+impl Binding {
+    fn new(kind: BindingKind, ty: Type, span: Span) -> Self {
+        Self {
+            ty,
+            kind,
+            mutable: false,
+            span,
+            uninit: None,
+        }
+    }
+
+    fn mutable(mut self, mutable: bool) -> Self {
+        self.mutable = mutable;
+        self
+    }
+}
+
+/// The fields of a struct, in the order they're declared, and the functions in its `impl`s.
+#[derive(Debug, Clone)]
+struct StructInfo {
+    name: SmolStr,
+    fields: Vec<(SmolStr, Type)>,
+    /// `None` if the struct has no constructor, then it can't be created.
+    constructor: Option<FnType>,
+    /// Includes the methods of the traits it implements.
+    methods: HashMap<SmolStr, MethodInfo>,
+    traits: HashSet<NodeId>,
+}
+
+/// A function in an `impl` or `trait` block.
+#[derive(Debug, Clone)]
+struct MethodInfo {
+    id: NodeId,
+    /// `self` is the first parameter of methods.
+    ty: FnType,
+    /// Whether it takes `self`, otherwise it's an associated function.
+    receiver: bool,
+    /// The type parameters of a generic method, in order.
+    generics: Vec<(NodeId, SmolStr)>,
+    span: Span,
+}
+
+impl MethodInfo {
+    fn new(function: &Function, id: NodeId, ty: FnType) -> Self {
+        Self {
+            id,
+            ty,
+            receiver: function.receiver.is_some(),
+            generics: function
+                .generics
+                .iter()
+                .map(|generic| (generic.id, generic.name.name.clone()))
+                .collect(),
+            span: function.name.span,
+        }
+    }
+}
+
+/// The variants of an enum, in the order they're declared.
+#[derive(Debug, Clone)]
+struct EnumInfo {
+    name: SmolStr,
+    variants: Vec<VariantInfo>,
+}
+
+#[derive(Debug, Clone)]
+struct VariantInfo {
+    id: NodeId,
+    name: SmolStr,
+    /// The types of the values in its payload, in order.
+    fields: Vec<Type>,
+    payload: Payload,
+}
+
+/// How the payload of a variant is written.
+#[derive(Debug, Clone, PartialEq)]
+enum Payload {
+    Unit,
+    Tuple,
+    /// The names of the fields, in the same order as their types.
+    Struct(Vec<SmolStr>),
+}
+
+impl VariantInfo {
+    /// The type of the variant used as a value, which is a function that creates the enum
+    /// unless it's a unit variant.
+    fn value_type(&self, ty: &Type) -> Type {
+        match self.payload {
+            Payload::Unit => ty.clone(),
+            _ => Type::Fn(FnType {
+                params: self.fields.iter().cloned().map(FnParam::new).collect(),
+                ret: Box::new(ty.clone()),
+            }),
+        }
+    }
+
+    /// How a pattern for the variant is written, like `Shape::Circle(_)`.
+    fn pattern(&self, ty: &Type) -> String {
+        match &self.payload {
+            Payload::Unit => format!("{}::{}", ty, self.name),
+            Payload::Tuple => format!(
+                "{}::{}({})",
+                ty,
+                self.name,
+                vec!["_"; self.fields.len()].join(", ")
+            ),
+            Payload::Struct(_) => format!("{}::{} {{ .. }}", ty, self.name),
+        }
+    }
+}
+
+/// The methods of a trait, where `self` is a trait object.
+#[derive(Debug, Clone)]
+struct TraitInfo {
+    name: SmolStr,
+    methods: HashMap<SmolStr, MethodInfo>,
+    /// The methods without a default, in the order they're declared.
+    required: Vec<SmolStr>,
+}
+
+/// What the `impl`s of a block added to structs, which is taken away at the end of a nested
+/// block.
+#[derive(Debug, Default)]
+struct Implemented {
+    /// The struct and name of each method.
+    methods: Vec<(NodeId, SmolStr)>,
+    /// The struct and trait of each `impl` of a trait.
+    traits: Vec<(NodeId, NodeId)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LoopKind {
+    /// `loop`, which can break with a value.
+    Loop,
+    /// `while` and `for`, which can only break without a value.
+    Conditional,
+}
+
+#[derive(Debug)]
+struct LoopContext {
+    kind: LoopKind,
+    /// The type of the values the loop breaks with, `None` if it never breaks.
+    break_ty: Option<Type>,
+}
+
+/// Something whose type has to be inferred by the end of the program.
+#[derive(Debug)]
+struct Unknown {
+    ty: Type,
+    span: Span,
+    /// What has the unknown type, like "the type of `x`".
+    what: String,
+}
+
+/// An int literal, whose value has to fit in the type it's inferred to be.
+#[derive(Debug)]
+struct IntLiteral {
+    span: Span,
+    value: u64,
+    /// Set for literals with a `-` in front of them, like `-128i8`, which fits even though
+    /// `128i8` doesn't.
+    negated: bool,
+    ty: Type,
+}
+
+/// A check that needs to know a type that wasn't known yet when the expression was checked.
+#[derive(Debug)]
+enum Deferred {
+    Unary {
+        op: UnaryOp,
+        operand: Span,
+        ty: Type,
+    },
+    Binary {
+        span: Span,
+        op: BinaryOp,
+        lhs: (Span, Type),
+        rhs: (Span, Type),
+    },
+    Step {
+        target: Span,
+        ty: Type,
+    },
+}
+
+/// The arms of a `match`, whose exhaustiveness is checked once the type of its value is known.
+#[derive(Debug)]
+struct MatchCheck {
+    /// The value being matched.
+    span: Span,
+    ty: Type,
+    arms: Vec<CheckedArm>,
+}
+
+#[derive(Debug)]
+struct CheckedArm {
+    pattern: Pat,
+    span: Span,
+    /// The spans of the alternatives, if the pattern is a `|` pattern.
+    alternatives: Vec<Span>,
+    guarded: bool,
+}
+
+/// A type that has to implement a trait, because it was chosen for a type parameter with the
+/// trait as a bound.
+#[derive(Debug)]
+struct Bound {
+    span: Span,
+    ty: Type,
+    trait_: NodeId,
+}
+
+#[derive(Debug)]
+struct Checker<'a> {
+    names: &'a Resolutions,
+    /// The declarations that have been checked, by the node that declares them.
+    bindings: HashMap<NodeId, Binding>,
+    /// The structs that have been declared, by their item.
+    structs: HashMap<NodeId, StructInfo>,
+    /// The traits that have been declared, by their item.
+    traits: HashMap<NodeId, TraitInfo>,
+    /// The enums that have been declared, by their item.
+    enums: HashMap<NodeId, EnumInfo>,
+    /// The traits that each type parameter is bounded by.
+    generics: HashMap<NodeId, Vec<NodeId>>,
+    /// The methods declared by this program, by their struct. Methods from before can be
+    /// redefined, like functions.
+    declared_methods: HashSet<(NodeId, SmolStr)>,
+    /// The traits implemented by this program, with their struct. Like methods, the ones from
+    /// before can be implemented again.
+    declared_impls: HashSet<(NodeId, NodeId)>,
+    /// How many blocks deep the checker is, the program's own block is 1.
+    blocks: usize,
+    /// The return types of the functions and closures being checked, the innermost last.
+    returns: Vec<Type>,
+    loops: Vec<LoopContext>,
+    table: InferenceTable,
+    unknowns: Vec<Unknown>,
+    deferred: Vec<Deferred>,
+    /// The bounds that are checked once their types are known.
+    bounds: Vec<Bound>,
+    literals: Vec<IntLiteral>,
+    matches: Vec<MatchCheck>,
+    /// Set once the whole program has been checked, after which checks can't be put off anymore.
+    finished: bool,
+    info: TypeInfo,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker<'_> {
+    fn error(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    fn mismatch(&mut self, span: Span, expected: &Type, found: &Type) {
+        let expected = self.table.zonk(expected);
+        let found = self.table.zonk(found);
+        self.error(
+            Diagnostic::error("Mismatched types")
+                .with_code(codes::E0301)
+                .with_label(span, format!("expected `{}`, found `{}`", expected, found)),
+        );
+    }
+
+    /// Reports an error if a value of type `found` can't be used as `expected`. Structs and type
+    /// parameters can be used as trait objects of the traits they implement.
+    fn expect(&mut self, span: Span, expected: &Type, found: &Type) {
+        if self.coerce(span, expected, found) {
+            return;
+        }
+        if self.table.unify(expected, found).is_err() {
+            self.mismatch(span, expected, found);
+        }
+    }
+
+    /// Checks a value used as a trait object, returning whether `found` is a type that can be
+    /// one. The error if it doesn't implement the trait is reported here.
+    fn coerce(&mut self, span: Span, expected: &Type, found: &Type) -> bool {
+        let Type::Dyn { id, .. } = self.table.resolve(expected) else {
+            return false;
+        };
+        let found = self.table.resolve(found);
+        if !matches!(found, Type::Struct { .. } | Type::Param { .. }) {
+            return false;
+        }
+
+        if !self.implements(&found, id) {
+            self.not_implemented(span, &found, id);
+        }
+        true
+    }
+
+    /// Whether a type implements a trait. Trait objects implement their own trait, and type
+    /// parameters the traits of their bounds.
+    fn implements(&self, ty: &Type, trait_: NodeId) -> bool {
+        match ty {
+            Type::Struct { id, .. } => self.structs[id].traits.contains(&trait_),
+            Type::Dyn { id, .. } => *id == trait_,
+            Type::Param { id, .. } => self
+                .generics
+                .get(id)
+                .is_some_and(|bounds| bounds.contains(&trait_)),
+            Type::Never | Type::Error => true,
+            _ => false,
+        }
+    }
+
+    fn not_implemented(&mut self, span: Span, ty: &Type, trait_: NodeId) {
+        let name = self.traits[&trait_].name.clone();
+        let mut diagnostic = Diagnostic::error(format!(
+            "The trait `{}` isn't implemented for `{}`",
+            name, ty
+        ))
+        .with_code(codes::E0325)
+        .with_label(span, format!("`{}` doesn't implement `{}`", ty, name));
+        if matches!(ty, Type::Struct { .. }) {
+            diagnostic = diagnostic.with_help(format!(
+                "implement it with `impl {} for {} {{ ... }}`",
+                name, ty
+            ));
+        }
+        self.error(diagnostic);
+    }
+
+    /// Checks the bounds whose types are known, the others wait until they are.
+    fn check_bounds(&mut self) {
+        for bound in std::mem::take(&mut self.bounds) {
+            let ty = self.table.zonk(&bound.ty);
+            if !self.table.unresolved(&ty).is_empty() {
+                // The types that are never inferred have their own error
+                if !self.finished {
+                    self.bounds.push(bound);
+                }
+                continue;
+            }
+            if !self.implements(&ty, bound.trait_) {
+                self.not_implemented(bound.span, &ty, bound.trait_);
+            }
+        }
+    }
+
+    /// Replaces the type parameters in the type of a generic function with new variables, which
+    /// are inferred from how it's used. Whatever they turn out to be has to satisfy the bounds.
+    fn instantiate(&mut self, span: Span, ty: &FnType) -> FnType {
+        let params = Type::Fn(ty.clone()).type_params();
+        if params.is_empty() {
+            return ty.clone();
+        }
+
+        let mut types = HashMap::new();
+        for (id, name) in params {
+            let var = self.unknown(span, format!("the type of `{}`", name));
+            for trait_ in self.generics.get(&id).cloned().unwrap_or_default() {
+                self.bounds.push(Bound {
+                    span,
+                    ty: var.clone(),
+                    trait_,
+                });
+            }
+            types.insert(id, var);
+        }
+        ty.substitute(&types)
+    }
+
+    /// A new type variable for something whose type has to be inferred.
+    fn unknown(&mut self, span: Span, what: String) -> Type {
+        let ty = self.table.fresh();
+        self.unknowns.push(Unknown {
+            ty: ty.clone(),
+            span,
+            what,
+        });
+        ty
+    }
+
+    fn annotations_needed(&mut self, span: Span, what: &str) {
+        self.error(
+            Diagnostic::error("Type annotations needed")
+                .with_code(codes::E0315)
+                .with_label(span, format!("cannot infer {}", what))
+                .with_help("add a type annotation, like `let x: int`"),
+        );
+    }
+
+    /// Reports the types that couldn't be inferred and runs the checks that were put off.
+    fn finish(&mut self) {
+        self.finished = true;
+        self.table.default_literals();
+
+        // Every variable is only reported once, at the first thing that has it
+        let mut reported = Vec::new();
+        for unknown in std::mem::take(&mut self.unknowns) {
+            let vars = self.table.unresolved(&unknown.ty);
+            if vars.iter().any(|var| !reported.contains(var)) {
+                self.annotations_needed(unknown.span, &unknown.what);
+                reported.extend(vars);
+            }
+        }
+
+        for deferred in std::mem::take(&mut self.deferred) {
+            let ty = match &deferred {
+                Deferred::Unary { ty, .. } | Deferred::Step { ty, .. } => ty,
+                Deferred::Binary { lhs, .. } => &lhs.1,
+            };
+            let vars = self.table.unresolved(ty);
+            if !vars.is_empty() {
+                if vars.iter().any(|var| !reported.contains(var)) {
+                    let span = match &deferred {
+                        Deferred::Unary { operand, .. } => *operand,
+                        Deferred::Step { target, .. } => *target,
+                        Deferred::Binary { span, .. } => *span,
+                    };
+                    self.annotations_needed(span, "the types of the operands");
+                    reported.extend(vars);
+                }
+                continue;
+            }
+
+            match deferred {
+                Deferred::Unary { op, operand, ty } => {
+                    self.check_unary(op, operand, &ty);
+                }
+                Deferred::Binary { span, op, lhs, rhs } => {
+                    self.check_binary(span, op, lhs, rhs);
+                }
+                Deferred::Step { target, ty } => self.check_step(target, &ty),
+            }
+        }
+
+        self.check_bounds();
+        for literal in std::mem::take(&mut self.literals) {
+            self.check_int_literal(literal);
+        }
+        for check in std::mem::take(&mut self.matches) {
+            self.check_exhaustive(check);
+        }
+
+        // Anything that still isn't known has had an error reported
+        for ty in self.info.expr_types.values_mut() {
+            *ty = self.table.zonk_or_error(ty);
+        }
+    }
+
+    fn check_int_literal(&mut self, literal: IntLiteral) {
+        let Type::Int(ty) = self.table.resolve(&literal.ty) else {
+            return;
+        };
+        // Negative unsigned ints are already an error for the `-`
+        if literal.negated && !ty.is_signed() {
+            return;
+        }
+
+        let value = match literal.negated {
+            true => -i128::from(literal.value),
+            false => i128::from(literal.value),
+        };
+        if value < ty.min() || value > ty.max() {
+            self.error(
+                Diagnostic::error(format!("Literal out of range for `{}`", ty.name()))
+                    .with_code(codes::E0319)
+                    .with_label(literal.span, format!("this doesn't fit in `{}`", ty.name()))
+                    .with_note(format!(
+                        "`{}` goes from {} to {}",
+                        ty.name(),
+                        ty.min(),
+                        ty.max()
+                    )),
+            );
+        }
+    }
+
+    fn declare(&mut self, id: NodeId, binding: Binding) {
+        self.bindings.insert(id, binding);
+    }
+
+    /// How deep in functions, closures and loops the checker currently is.
+    fn depth(&self) -> (usize, usize) {
+        (self.returns.len(), self.loops.len())
+    }
+
+    /// The declaration an identifier expression refers to.
+    fn lookup(&mut self, id: NodeId) -> Option<&mut Binding> {
+        match self.names.get(id)? {
+            Res::Local(id) | Res::Item(id) => self.bindings.get_mut(&id),
+            Res::Builtin(_) | Res::TypeParam(_) => None,
+        }
+    }
+
+    fn resolve_type(&mut self, ty: &TypeExpr) -> Type {
+        match &ty.kind {
+            TypeExprKind::Unit => Type::Unit,
+            TypeExprKind::Array(element) => Type::Array(Box::new(self.resolve_type(element))),
+            TypeExprKind::Dyn(name) => match self.resolve_trait(ty) {
+                Some(id) => Type::Dyn {
+                    id,
+                    name: name.clone(),
+                },
+                None => Type::Error,
+            },
+            TypeExprKind::Named(name) => match self.names.get(ty.id) {
+                Some(Res::TypeParam(id)) => Type::Param {
+                    id,
+                    name: name.clone(),
+                },
+                Some(Res::Item(id)) if self.traits.contains_key(&id) => {
+                    self.error(
+                        Diagnostic::error(format!("Expected a type, found trait `{}`", name))
+                            .with_code(codes::E0323)
+                            .with_label(ty.span, "not a type")
+                            .with_help(format!(
+                                "use `dyn {}` for a value of any type that implements it",
+                                name
+                            )),
+                    );
+                    Type::Error
+                }
+                _ => self.named_type(ty, name),
+            },
+        }
+    }
+
+    /// A struct, an enum or a built-in type by its name.
+    fn named_type(&mut self, ty: &TypeExpr, name: &str) -> Type {
+        self.struct_type(ty.id)
+            .or_else(|| self.enum_type(ty.id))
+            .or_else(|| Type::primitive(name))
+            .unwrap_or_else(|| {
+                self.error(
+                    Diagnostic::error(format!("Cannot find type `{}`", name))
+                        .with_code(codes::E0302)
+                        .with_label(ty.span, "not a known type"),
+                );
+                Type::Error
+            })
+    }
+
+    /// The trait that a type expression names, reporting an error if it isn't one.
+    fn resolve_trait(&mut self, ty: &TypeExpr) -> Option<NodeId> {
+        let found = match &ty.kind {
+            TypeExprKind::Named(name) | TypeExprKind::Dyn(name) => match self.names.get(ty.id) {
+                Some(Res::Item(id)) if self.traits.contains_key(&id) => return Some(id),
+                None if Type::primitive(name).is_none() => {
+                    self.error(
+                        Diagnostic::error(format!("Cannot find trait `{}`", name))
+                            .with_code(codes::E0323)
+                            .with_label(ty.span, "not a known trait"),
+                    );
+                    return None;
+                }
+                _ => name.to_string(),
+            },
+            _ => self.resolve_type(ty).to_string(),
+        };
+
+        self.error(
+            Diagnostic::error(format!("Expected a trait, found `{}`", found))
+                .with_code(codes::E0323)
+                .with_label(ty.span, "not a trait"),
+        );
+        None
+    }
+
+    /// The type of the struct a type name or an expression refers to, if it's a struct.
+    fn struct_type(&self, id: NodeId) -> Option<Type> {
+        let Some(Res::Item(id)) = self.names.get(id) else {
+            return None;
+        };
+        let info = self.structs.get(&id)?;
+        Some(Type::Struct {
+            id,
+            name: info.name.clone(),
+        })
+    }
+
+    /// The type of the enum a type name refers to, if it's an enum.
+    fn enum_type(&self, id: NodeId) -> Option<Type> {
+        let Some(Res::Item(id)) = self.names.get(id) else {
+            return None;
+        };
+        let info = self.enums.get(&id)?;
+        Some(Type::Enum {
+            id,
+            name: info.name.clone(),
+        })
+    }
+
+    /// The type of a function, its type parameters' bounds are recorded too.
+    fn signature(&mut self, function: &Function) -> FnType {
+        for generic in &function.generics {
+            let bounds = generic
+                .bounds
+                .iter()
+                .filter_map(|bound| self.resolve_trait(bound))
+                .collect();
+            self.generics.insert(generic.id, bounds);
+        }
+
+        FnType {
+            params: function
+                .params
+                .iter()
+                .map(|param| FnParam {
+                    ty: self.resolve_type(&param.ty),
+                    mutable: param.mutable,
+                })
+                .collect(),
+            ret: Box::new(
+                function
+                    .ret
+                    .as_ref()
+                    .map_or(Type::Unit, |ret| self.resolve_type(ret)),
+            ),
+        }
+    }
+
+    /// The signature of a method, which starts with `self` if it takes it.
+    fn method_signature(&mut self, function: &Function, self_ty: &Type) -> FnType {
+        let mut signature = self.signature(function);
+        if let Some(receiver) = &function.receiver {
+            let receiver = FnParam {
+                ty: self_ty.clone(),
+                mutable: receiver.mutable,
+            };
+            signature.params.insert(0, receiver);
+        }
+        signature
+    }
+
+    fn check_block(&mut self, block: &Block) -> Type {
+        // Structs, traits and enums can be used before they're declared, even by the fields of
+        // other structs, so they're all known before any of their fields or methods are resolved
+        let items = block
+            .stmts
+            .iter()
+            .filter_map(|stmt| match &stmt.kind {
+                StmtKind::Item(item) => Some(item),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let structs = items
+            .iter()
+            .filter_map(|item| match &item.kind {
+                ItemKind::Struct(def) => Some((item.id, def)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let traits = items
+            .iter()
+            .filter_map(|item| match &item.kind {
+                ItemKind::Trait(def) => Some((item.id, def)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        for (id, def) in &structs {
+            self.structs.insert(
+                *id,
+                StructInfo {
+                    name: def.name.name.clone(),
+                    fields: Vec::new(),
+                    constructor: None,
+                    methods: HashMap::new(),
+                    traits: HashSet::new(),
+                },
+            );
+        }
+        let enums = items
+            .iter()
+            .filter_map(|item| match &item.kind {
+                ItemKind::Enum(def) => Some((item.id, def)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        for (id, def) in &enums {
+            self.enums.insert(
+                *id,
+                EnumInfo {
+                    name: def.name.name.clone(),
+                    variants: Vec::new(),
+                },
+            );
+        }
+        for (id, def) in &traits {
+            self.traits.insert(
+                *id,
+                TraitInfo {
+                    name: def.name.name.clone(),
+                    methods: HashMap::new(),
+                    required: Vec::new(),
+                },
+            );
+        }
+
+        // Functions and methods can be called before they're declared
+        let mut signatures = HashMap::new();
+        for (id, def) in &traits {
+            self.declare_trait(*id, def, &mut signatures);
+        }
+        for (id, def) in &structs {
+            self.declare_struct(*id, def);
+        }
+        for (id, def) in &enums {
+            self.declare_enum(*id, def);
+        }
+
+        let mut implemented = Implemented::default();
+        for item in &items {
+            match &item.kind {
+                ItemKind::Fn(function) => {
+                    let signature = self.signature(function);
+                    self.declare(
+                        item.id,
+                        Binding::new(
+                            BindingKind::Fn,
+                            Type::Fn(signature.clone()),
+                            function.name.span,
+                        ),
+                    );
+                    signatures.insert(item.id, signature);
+                }
+                ItemKind::Impl(def) => self.declare_impl(def, &mut signatures, &mut implemented),
+                ItemKind::Const { .. }
+                | ItemKind::Struct(_)
+                | ItemKind::Trait(_)
+                | ItemKind::Enum(_) => {}
+            }
+        }
+
+        self.blocks += 1;
+        let mut diverges = false;
+        for stmt in &block.stmts {
+            if let StmtKind::Item(item) = &stmt.kind {
+                match &item.kind {
+                    ItemKind::Fn(function) => {
+                        self.check_fn(function, &signatures[&item.id]);
+                        continue;
+                    }
+                    ItemKind::Struct(def) => {
+                        self.check_struct(item.id, def);
+                        continue;
+                    }
+                    ItemKind::Impl(def) => {
+                        for method in &def.methods {
+                            self.check_fn(&method.function, &signatures[&method.id]);
+                        }
+                        continue;
+                    }
+                    ItemKind::Trait(def) => {
+                        for method in &def.methods {
+                            self.check_fn(&method.function, &signatures[&method.id]);
+                        }
+                        continue;
+                    }
+                    ItemKind::Enum(_) => continue,
+                    ItemKind::Const { .. } => {}
+                }
+            }
+
+            let ty = self.check_stmt(stmt);
+            diverges |= self.table.resolve(&ty) == Type::Never;
+        }
+
+        let ty = match &block.tail {
+            Some(tail) => self.check_expr(tail),
+            None if diverges => Type::Never,
+            None => Type::Unit,
+        };
+        self.blocks -= 1;
+        self.check_bounds();
+
+        // Like functions, the methods of an `impl` in a nested block can only be used in it, the
+        // interpreter only knows them once the block runs
+        if self.blocks > 0 {
+            for (id, name) in implemented.methods {
+                if let Some(info) = self.structs.get_mut(&id) {
+                    info.methods.remove(&name);
+                }
+                self.declared_methods.remove(&(id, name));
+            }
+            for (id, trait_) in implemented.traits {
+                if let Some(info) = self.structs.get_mut(&id) {
+                    info.traits.remove(&trait_);
+                }
+                self.declared_impls.remove(&(id, trait_));
+            }
+        }
+
+        ty
+    }
+
+    /// Resolves the signatures of a trait's methods, and adds them to `signatures` by their
+    /// method with a trait object as `self`.
+    fn declare_trait(&mut self, id: NodeId, def: &Trait, signatures: &mut HashMap<NodeId, FnType>) {
+        let ty = Type::Dyn {
+            id,
+            name: def.name.name.clone(),
+        };
+
+        let mut methods = HashMap::<SmolStr, MethodInfo>::new();
+        let mut required = Vec::new();
+        for method in &def.methods {
+            let function = &method.function;
+            let signature = self.method_signature(function, &ty);
+            signatures.insert(method.id, signature.clone());
+
+            let name = function.name.name.clone();
+            if let Some(first) = methods.get(&name) {
+                let first = first.span;
+                self.already_defined(&function.name, &def.name.name, first);
+                continue;
+            }
+            if function.body.is_none() {
+                required.push(name.clone());
+            }
+            methods.insert(name, MethodInfo::new(function, method.id, signature));
+        }
+
+        let info = self.traits.get_mut(&id).expect("the trait was added");
+        info.methods = methods;
+        info.required = required;
+    }
+
+    /// Adds the functions of an `impl` block to its struct, recording what was added in
+    /// `implemented`. Their signatures are added to `signatures` by their method, with `self` as
+    /// the first parameter of methods. The methods of an `impl` of a trait are checked against
+    /// the trait, and the struct gets the trait's defaults for the ones it leaves out.
+    fn declare_impl(
+        &mut self,
+        def: &Impl,
+        signatures: &mut HashMap<NodeId, FnType>,
+        implemented: &mut Implemented,
+    ) {
+        let trait_ = def
+            .trait_
+            .as_ref()
+            .and_then(|trait_| Some((trait_.span, self.resolve_trait(trait_)?)));
+        let ty = self.resolve_type(&def.ty);
+        let id = match &ty {
+            Type::Struct { id, .. } => Some(*id),
+            Type::Error => None,
+            _ => {
+                self.error(
+                    Diagnostic::error(format!("Cannot define methods for `{}`", ty))
+                        .with_code(codes::E0321)
+                        .with_label(def.ty.span, "not a struct")
+                        .with_help("methods can only be defined for structs"),
+                );
+                None
+            }
+        };
+
+        let self_ty = match id {
+            Some(_) => ty.clone(),
+            None => Type::Error,
+        };
+        let mut methods = Vec::new();
+        for method in &def.methods {
+            let function = &method.function;
+            let signature = self.method_signature(function, &self_ty);
+            signatures.insert(method.id, signature.clone());
+            methods.push((
+                function.name.clone(),
+                MethodInfo::new(function, method.id, signature),
+            ));
+        }
+
+        let Some(id) = id else {
+            return;
+        };
+        if let Some((span, trait_)) = trait_ {
+            if !self.declared_impls.insert((id, trait_)) {
+                self.error(
+                    Diagnostic::error(format!(
+                        "`{}` is already implemented for `{}`",
+                        self.traits[&trait_].name, ty
+                    ))
+                    .with_code(codes::E0402)
+                    .with_label(span, "implemented again here"),
+                );
+                return;
+            }
+
+            methods = self.check_impl(span, &ty, trait_, methods);
+            self.structs
+                .get_mut(&id)
+                .expect("the type of an `impl` is a declared struct")
+                .traits
+                .insert(trait_);
+            implemented.traits.push((id, trait_));
+
+            // Calls through trait objects find the methods when they run
+            let impls = self.info.impls.entry(id).or_default();
+            for (name, method) in &methods {
+                impls.insert(name.name.clone(), method.id);
+            }
+        }
+
+        for (name, method) in methods {
+            if !self.declared_methods.insert((id, name.name.clone())) {
+                let first = self.structs[&id].methods[&name.name].span;
+                self.already_defined(&name, &ty, first);
+                continue;
+            }
+
+            self.structs
+                .get_mut(&id)
+                .expect("the type of an `impl` is a declared struct")
+                .methods
+                .insert(name.name.clone(), method);
+            implemented.methods.push((id, name.name));
+        }
+    }
+
+    /// Checks the methods of an `impl` of a trait against the trait's declarations. They're
+    /// returned with the trait's defaults for the methods the `impl` leaves out, which are named
+    /// at `span`.
+    fn check_impl(
+        &mut self,
+        span: Span,
+        ty: &Type,
+        trait_: NodeId,
+        methods: Vec<(Ident, MethodInfo)>,
+    ) -> Vec<(Ident, MethodInfo)> {
+        let info = self.traits[&trait_].clone();
+
+        let mut checked = Vec::new();
+        for (name, method) in methods {
+            let Some(expected) = info.methods.get(&name.name) else {
+                self.error(
+                    Diagnostic::error(format!(
+                        "`{}` isn't a method of trait `{}`",
+                        name.name, info.name
+                    ))
+                    .with_code(codes::E0324)
+                    .with_label(name.span, format!("not in `{}`", info.name))
+                    .with_help(format!("move it to an `impl {}` block", ty)),
+                );
+                continue;
+            };
+
+            if !self.matches_trait(expected, &method) {
+                let mut expected_ty = expected.ty.clone();
+                if expected.receiver {
+                    expected_ty.params[0].ty = ty.clone();
+                }
+                self.error(
+                    Diagnostic::error(format!(
+                        "Method `{}` doesn't match its declaration in trait `{}`",
+                        name.name, info.name
+                    ))
+                    .with_code(codes::E0324)
+                    .with_label(
+                        name.span,
+                        format!(
+                            "expected `{}`, found `{}`",
+                            Type::Fn(expected_ty),
+                            Type::Fn(method.ty.clone())
+                        ),
+                    )
+                    .with_secondary_label(expected.span, "declared here"),
+                );
+            }
+            checked.push((name, method));
+        }
+
+        let mut missing = Vec::new();
+        let mut declared = info.methods.iter().collect::<Vec<_>>();
+        declared.sort_by_key(|(_, method)| method.span.start);
+        for (name, method) in declared {
+            if checked.iter().any(|(defined, _)| defined.name == *name) {
+                continue;
+            }
+            if info.required.contains(name) {
+                missing.push(format!("`{}`", name));
+                continue;
+            }
+
+            let mut method = method.clone();
+            if method.receiver {
+                method.ty.params[0].ty = ty.clone();
+            }
+            let name = Ident {
+                name: name.clone(),
+                span,
+            };
+            checked.push((name, method));
+        }
+
+        if !missing.is_empty() {
+            self.error(
+                Diagnostic::error(format!(
+                    "Not all methods of trait `{}` are implemented for `{}`",
+                    info.name, ty
+                ))
+                .with_code(codes::E0324)
+                .with_label(span, format!("missing {}", missing.join(", ")))
+                .with_help("add them to the `impl` block"),
+            );
+        }
+        checked
+    }
+
+    /// Whether a method of an `impl` has the signature its trait declares for it, other than
+    /// the type of `self`. Type parameters are matched up by their position.
+    fn matches_trait(&self, expected: &MethodInfo, found: &MethodInfo) -> bool {
+        if expected.receiver != found.receiver
+            || expected.generics.len() != found.generics.len()
+            || expected.ty.params.len() != found.ty.params.len()
+        {
+            return false;
+        }
+
+        let mut types = HashMap::new();
+        for ((expected, name), (found, _)) in expected.generics.iter().zip(&found.generics) {
+            let bounds = |id| {
+                self.generics
+                    .get(id)
+                    .map(|bounds| bounds.iter().collect::<HashSet<_>>())
+            };
+            if bounds(expected) != bounds(found) {
+                return false;
+            }
+            let param = Type::Param {
+                id: *expected,
+                name: name.clone(),
+            };
+            types.insert(*found, param);
+        }
+
+        let found_ty = found.ty.substitute(&types);
+        let receiver = usize::from(expected.receiver);
+        expected.ty.params[..receiver]
+            .iter()
+            .zip(&found_ty.params)
+            .all(|(expected, found)| expected.mutable == found.mutable)
+            && expected.ty.params[receiver..] == found_ty.params[receiver..]
+            && expected.ty.ret == found_ty.ret
+    }
+
+    fn already_defined(&mut self, name: &Ident, owner: &dyn std::fmt::Display, first: Span) {
+        self.error(
+            Diagnostic::error(format!(
+                "`{}` is already defined for `{}`",
+                name.name, owner
+            ))
+            .with_code(codes::E0402)
+            .with_label(name.span, "defined again here")
+            .with_secondary_label(first, "first defined here"),
+        );
+    }
+
+    /// Resolves the types of a struct's fields and its constructor, and declares its name.
+    fn declare_struct(&mut self, id: NodeId, def: &Struct) {
+        let fields = def
+            .fields
+            .iter()
+            .map(|field| (field.name.name.clone(), self.resolve_type(&field.ty)))
+            .collect::<Vec<_>>();
+        let ty = Type::Struct {
+            id,
+            name: def.name.name.clone(),
+        };
+
+        // A constructor without a body takes the fields in order
+        let constructor = def.constructor.as_ref().map(|constructor| FnType {
+            params: match &constructor.body {
+                Some(_) => constructor
+                    .params
+                    .iter()
+                    .map(|param| FnParam {
+                        ty: self.resolve_type(&param.ty),
+                        mutable: param.mutable,
+                    })
+                    .collect(),
+                None => fields
+                    .iter()
+                    .map(|(_, ty)| FnParam::new(ty.clone()))
+                    .collect(),
+            },
+            ret: Box::new(ty),
+        });
+
+        self.declare(
+            id,
+            Binding::new(
+                BindingKind::Struct,
+                constructor.clone().map_or(Type::Error, Type::Fn),
+                def.name.span,
+            ),
+        );
+        let info = self.structs.get_mut(&id).expect("the struct was added");
+        info.fields = fields;
+        info.constructor = constructor;
+    }
+
+    /// Resolves the types of the payloads of an enum's variants.
+    fn declare_enum(&mut self, id: NodeId, def: &Enum) {
+        let variants = def
+            .variants
+            .iter()
+            .map(|variant| {
+                let (fields, payload) = match &variant.kind {
+                    VariantKind::Unit => (Vec::new(), Payload::Unit),
+                    VariantKind::Tuple(types) => (
+                        types.iter().map(|ty| self.resolve_type(ty)).collect(),
+                        Payload::Tuple,
+                    ),
+                    VariantKind::Struct(fields) => (
+                        fields
+                            .iter()
+                            .map(|field| self.resolve_type(&field.ty))
+                            .collect(),
+                        Payload::Struct(
+                            fields.iter().map(|field| field.name.name.clone()).collect(),
+                        ),
+                    ),
+                };
+                VariantInfo {
+                    id: variant.id,
+                    name: variant.name.name.clone(),
+                    fields,
+                    payload,
+                }
+            })
+            .collect();
+
+        self.enums
+            .get_mut(&id)
+            .expect("the enum was added")
+            .variants = variants;
+    }
+
+    /// Checks the bodies of a struct's constructor and destructor, which have `self` in them.
+    fn check_struct(&mut self, id: NodeId, def: &Struct) {
+        let ty = Type::Struct {
+            id,
+            name: def.name.name.clone(),
+        };
+
+        if let Some(constructor) = &def.constructor {
+            if let Some(body) = &constructor.body {
+                self.declare(
+                    constructor.id,
+                    Binding::new(BindingKind::Param, ty.clone(), constructor.name.span)
+                        .mutable(true),
+                );
+                let params = self.structs[&id]
+                    .constructor
+                    .as_ref()
+                    .expect("structs with a constructor have its signature")
+                    .params
+                    .clone();
+                self.check_body(&constructor.params, &params, &Type::Unit, body);
+            }
+        }
+
+        if let Some(destructor) = &def.destructor {
+            if let Some(body) = &destructor.body {
+                self.declare(
+                    destructor.id,
+                    Binding::new(BindingKind::Param, ty, destructor.name.span).mutable(true),
+                );
+                self.check_body(&[], &[], &Type::Unit, body);
+            }
+        }
+    }
+
+    /// Checks the body of a function, or of a method whose signature starts with `self`. Required
+    /// trait methods don't have a body to check.
+    fn check_fn(&mut self, function: &Function, signature: &FnType) {
+        let Some(body) = &function.body else {
+            return;
+        };
+        let mut param_tys = signature.params.as_slice();
+        if let Some(receiver) = &function.receiver {
+            self.declare(
+                receiver.id,
+                Binding::new(BindingKind::Param, param_tys[0].ty.clone(), receiver.span)
+                    .mutable(receiver.mutable),
+            );
+            param_tys = &param_tys[1..];
+        }
+        self.check_body(&function.params, param_tys, &signature.ret, body);
+    }
+
+    /// Checks the body of a function, or of something that's run like one.
+    fn check_body(&mut self, params: &[Param], param_tys: &[FnParam], ret: &Type, body: &Block) {
+        for (param, ty) in params.iter().zip(param_tys) {
+            self.declare(
+                param.id,
+                Binding::new(BindingKind::Param, ty.ty.clone(), param.span).mutable(param.mutable),
+            );
+        }
+
+        // Loops outside of the function can't be broken out of from inside it
+        let loops = std::mem::take(&mut self.loops);
+        self.returns.push(ret.clone());
+
+        let body_ty = self.check_block(body);
+        let span = match &body.tail {
+            Some(tail) => tail.span,
+            None => body.span.context().span(body.span.end - 1..body.span.end),
+        };
+        self.expect(span, ret, &body_ty);
+
+        self.returns.pop();
+        self.loops = loops;
+    }
+
+    /// Checks a statement, returning the type of its expression or `()` if it doesn't have one.
+    fn check_stmt(&mut self, stmt: &Stmt) -> Type {
+        match &stmt.kind {
+            StmtKind::Let {
+                name,
+                mutable,
+                ty,
+                value,
+            } => {
+                let annotation = ty.as_ref().map(|ty| self.resolve_type(ty));
+                let ty = match (annotation, value) {
+                    (Some(annotation), Some(value)) => {
+                        self.check_expr_as(value, &annotation);
+                        annotation
+                    }
+                    (Some(ty), None) => ty,
+                    (None, Some(value)) => self.check_expr(value),
+                    // Inferred from what gets assigned to it later
+                    (None, None) => self.unknown(name.span, format!("the type of `{}`", name.name)),
+                };
+
+                let mut binding = Binding::new(BindingKind::Let, ty, stmt.span).mutable(*mutable);
+                if value.is_none() && !mutable {
+                    binding.uninit = Some(self.depth());
+                }
+                self.declare(stmt.id, binding);
+                Type::Unit
+            }
+            StmtKind::Item(item) => match &item.kind {
+                ItemKind::Fn(_)
+                | ItemKind::Struct(_)
+                | ItemKind::Impl(_)
+                | ItemKind::Trait(_)
+                | ItemKind::Enum(_) => Type::Unit,
+                ItemKind::Const { ty, value, .. } => {
+                    let ty = match ty.as_ref().map(|ty| self.resolve_type(ty)) {
+                        Some(annotation) => {
+                            self.check_expr_as(value, &annotation);
+                            annotation
+                        }
+                        None => self.check_expr(value),
+                    };
+
+                    self.declare(item.id, Binding::new(BindingKind::Const, ty, item.span));
+                    Type::Unit
+                }
+            },
+            StmtKind::Expr(expr) => {
+                // Block-like expressions without a semicolon can't have a value
+                let ty = self.check_expr(expr);
+                if self.table.resolve(&ty) != Type::Never {
+                    self.expect(expr.span, &Type::Unit, &ty);
+                }
+                ty
+            }
+            StmtKind::Semi(expr) => self.check_expr(expr),
+        }
+    }
+
+    fn check_expr(&mut self, expr: &Expr) -> Type {
+        let ty = self.check_expr_kind(expr);
+        self.info.expr_types.insert(expr.id, ty.clone());
+        ty
+    }
+
+    /// Checks an expression whose value has to be used as `expected`. When the elements of an
+    /// array literal have to be trait objects, each of them is checked on its own so that they
+    /// can be different structs.
+    fn check_expr_as(&mut self, expr: &Expr, expected: &Type) {
+        if let (ExprKind::Array(elements), Type::Array(element)) =
+            (&expr.kind, self.table.zonk(expected))
+        {
+            if !elements.is_empty() && contains_dyn(&element) {
+                for value in elements {
+                    self.check_expr_as(value, &element);
+                }
+                self.info.expr_types.insert(expr.id, Type::Array(element));
+                return;
+            }
+        }
+
+        let ty = self.check_expr(expr);
+        self.expect(expr.span, expected, &ty);
+    }
+
+    fn check_expr_kind(&mut self, expr: &Expr) -> Type {
+        match &expr.kind {
+            ExprKind::Error => Type::Error,
+            ExprKind::Literal(literal) => match literal {
+                Literal::Int { value, suffix } => {
+                    // Without a suffix, the type is whatever kind of int the literal is used as
+                    let ty = match suffix.map(Type::from) {
+                        Some(ty) => ty,
+                        None => self.table.fresh_int(),
+                    };
+                    self.literals.push(IntLiteral {
+                        span: expr.span,
+                        value: *value,
+                        negated: false,
+                        ty: ty.clone(),
+                    });
+                    ty
+                }
+                Literal::Float { suffix, .. } => match suffix.map(Type::from) {
+                    Some(ty) => ty,
+                    None => self.table.fresh_float(),
+                },
+                Literal::String(_) => Type::String,
+                Literal::Char(_) => Type::Char,
+                Literal::Bool(_) => Type::Bool,
+                Literal::Unit => Type::Unit,
+            },
+            ExprKind::Ident(name) => match self.names.get(expr.id) {
+                Some(Res::Builtin(_)) => {
+                    self.error(
+                        Diagnostic::error(format!(
+                            "Builtin function `{}` can only be called",
+                            name
+                        ))
+                        .with_code(codes::E0318)
+                        .with_label(expr.span, "not called")
+                        .with_help(format!("wrap it in a closure: `|x| {}(x)`", name)),
+                    );
+                    Type::Error
+                }
+                Some(Res::Item(id)) if self.traits.contains_key(&id) => {
+                    self.error(
+                        Diagnostic::error(format!("Expected a value, found trait `{}`", name))
+                            .with_code(codes::E0323)
+                            .with_label(expr.span, "not a value"),
+                    );
+                    Type::Error
+                }
+                Some(Res::Item(id)) if self.enums.contains_key(&id) => {
+                    let mut diagnostic =
+                        Diagnostic::error(format!("Expected a value, found enum `{}`", name))
+                            .with_code(codes::E0330)
+                            .with_label(expr.span, "not a value");
+                    if let Some(variant) = self.enums[&id].variants.first() {
+                        diagnostic = diagnostic.with_help(format!(
+                            "use one of its variants, like `{}::{}`",
+                            name, variant.name
+                        ));
+                    }
+                    self.error(diagnostic);
+                    Type::Error
+                }
+                Some(Res::Item(id))
+                    if self
+                        .structs
+                        .get(&id)
+                        .is_some_and(|info| info.constructor.is_none()) =>
+                {
+                    self.error(
+                        Diagnostic::error(format!("Struct `{}` doesn't have a constructor", name))
+                            .with_code(codes::E0320)
+                            .with_label(expr.span, "can't be created")
+                            .with_help(format!(
+                                "declare a constructor in the struct, like `{}();`",
+                                name
+                            )),
+                    );
+                    Type::Error
+                }
+                // Each use of a generic function chooses its own types for the type parameters
+                _ => match self
+                    .lookup(expr.id)
+                    .map(|binding| (binding.kind, binding.ty.clone()))
+                {
+                    Some((BindingKind::Fn, Type::Fn(ty))) => {
+                        Type::Fn(self.instantiate(expr.span, &ty))
+                    }
+                    Some((_, ty)) => ty,
+                    None => Type::Error,
+                },
+            },
+            ExprKind::Array(elements) => {
+                // The element type of an empty array is inferred from how it's used
+                let mut element_ty = match elements.is_empty() {
+                    true => self.unknown(expr.span, "the element type of this array".into()),
+                    false => Type::Never,
+                };
+                for element in elements {
+                    let ty = self.check_expr(element);
+                    element_ty = match self.table.join(&element_ty, &ty) {
+                        Ok(ty) => ty,
+                        Err(_) => {
+                            self.mismatch(element.span, &element_ty, &ty);
+                            element_ty
+                        }
+                    };
+                }
+                Type::Array(Box::new(element_ty))
+            }
+            ExprKind::Unary(op, operand) => {
+                let ty = self.check_expr(operand);
+                if let (UnaryOp::Neg, ExprKind::Literal(Literal::Int { .. })) = (op, &operand.kind)
+                {
+                    let literal = self
+                        .literals
+                        .last_mut()
+                        .expect("the literal was just checked");
+                    literal.negated = true;
+                }
+                self.check_unary(*op, operand.span, &ty)
+            }
+            ExprKind::Binary(lhs, op, rhs) => {
+                let lhs_ty = self.check_expr(lhs);
+                let rhs_ty = self.check_expr(rhs);
+                self.check_binary(expr.span, *op, (lhs.span, lhs_ty), (rhs.span, rhs_ty))
+            }
+            ExprKind::Assign { target, op, value } => {
+                let target_ty = self.check_place(target, "assign to", op.is_none());
+
+                match op {
+                    Some(op) => {
+                        let value_ty = self.check_expr(value);
+                        let ty = self.check_binary(
+                            expr.span,
+                            *op,
+                            (target.span, target_ty.clone()),
+                            (value.span, value_ty),
+                        );
+                        self.expect(value.span, &target_ty, &ty);
+                    }
+                    None => self.check_expr_as(value, &target_ty),
+                }
+                Type::Unit
+            }
+            ExprKind::Step(target, op) => {
+                let verb = match op {
+                    StepOp::Increment => "increment",
+                    StepOp::Decrement => "decrement",
+                };
+                let ty = self.check_place(target, verb, false);
+                self.check_step(target.span, &ty);
+                Type::Unit
+            }
+            ExprKind::Cast(value, ty) => {
+                let from = self.check_expr(value);
+                let from = self.table.resolve(&from);
+                let to = self.resolve_type(ty);
+
+                if from.is_unknown() {
+                    self.annotations_needed(value.span, "the type of the value being cast");
+                    return to;
+                }
+
+                let valid = from == to
+                    || ((from.is_numeric() || matches!(from, Type::Bool | Type::Char))
+                        && to.is_numeric());
+                if !valid && !from.is_error() && !to.is_error() {
+                    self.error(
+                        Diagnostic::error(format!("Cannot cast `{}` as `{}`", from, to))
+                            .with_code(codes::E0305)
+                            .with_label(expr.span, "invalid cast")
+                            .with_note("only numbers, bools and chars can be cast to numbers"),
+                    );
+                }
+                to
+            }
+            ExprKind::Call { func, args } => self.check_call(expr, func, args),
+            ExprKind::MethodCall {
+                receiver,
+                method,
+                args,
+            } => self.check_method_call(expr, receiver, method, args),
+            ExprKind::Path(ty, name) => {
+                let ty = self.resolve_type(ty);
+                if let Type::Enum { id, .. } = &ty {
+                    return match self.find_variant(*id, &ty, name) {
+                        Some((_, variant)) => {
+                            self.info
+                                .methods
+                                .insert(expr.id, Callee::Static(variant.id));
+                            variant.value_type(&ty)
+                        }
+                        None => Type::Error,
+                    };
+                }
+                match self.find_method(&ty, name, "function") {
+                    Some(method) => {
+                        self.info.methods.insert(expr.id, Callee::Static(method.id));
+                        Type::Fn(self.instantiate(expr.span, &method.ty))
+                    }
+                    None => Type::Error,
+                }
+            }
+            ExprKind::Closure { params, ret, body } => self.check_closure(params, ret, body),
+            ExprKind::Macro { name, args } => {
+                let arg_tys = args
+                    .iter()
+                    .map(|arg| (arg.span, self.check_expr(arg)))
+                    .collect::<Vec<_>>();
+
+                let ret = match name.name.as_str() {
+                    "println" | "print" => Type::Unit,
+                    "format" => Type::String,
+                    _ => {
+                        self.error(
+                            Diagnostic::error(format!("Cannot find macro `{}`", name.name))
+                                .with_code(codes::E0314)
+                                .with_label(name.span, "not a known macro")
+                                .with_help("the macros are `println!`, `print!` and `format!`"),
+                        );
+                        return Type::Error;
+                    }
+                };
+
+                self.check_format_args(expr.span, args, &arg_tys);
+                ret
+            }
+            ExprKind::Index(value, index) => {
+                let value_ty = self.check_expr(value);
+                let index_ty = self.check_expr(index);
+                self.expect(index.span, &Type::INT, &index_ty);
+
+                match self.table.resolve(&value_ty) {
+                    Type::Array(element) => *element,
+                    ty if ty.is_unknown() => {
+                        let element = self.table.fresh();
+                        self.expect(
+                            value.span,
+                            &Type::Array(Box::new(element.clone())),
+                            &value_ty,
+                        );
+                        element
+                    }
+                    Type::Error => Type::Error,
+                    ty => {
+                        self.error(
+                            Diagnostic::error(format!(
+                                "Cannot index into a value of type `{}`",
+                                ty
+                            ))
+                            .with_code(codes::E0308)
+                            .with_label(value.span, format!("this is `{}`", ty)),
+                        );
+                        Type::Error
+                    }
+                }
+            }
+            ExprKind::Field(value, field) => {
+                let ty = self.check_expr(value);
+                let ty = self.table.resolve(&ty);
+                if let Type::Struct { id, .. } = &ty {
+                    let found = self.structs[id]
+                        .fields
+                        .iter()
+                        .find(|(name, _)| *name == field.name);
+                    if let Some((_, ty)) = found {
+                        return ty.clone();
+                    }
+                }
+
+                if ty.is_unknown() {
+                    self.annotations_needed(value.span, "the type of this value");
+                } else if !ty.is_error() {
+                    let mut diagnostic =
+                        Diagnostic::error(format!("No field `{}` on type `{}`", field.name, ty))
+                            .with_code(codes::E0309)
+                            .with_label(field.span, "unknown field");
+                    if let Type::Struct { id, .. } = &ty {
+                        if self.structs[id].methods.contains_key(&field.name) {
+                            diagnostic = diagnostic.with_help(format!(
+                                "to call the method, write `.{}()`",
+                                field.name
+                            ));
+                        }
+                    }
+                    self.error(diagnostic);
+                }
+                Type::Error
+            }
+            ExprKind::Block(block) => self.check_block(block),
+            ExprKind::If {
+                cond,
+                then,
+                otherwise,
+            } => {
+                let cond_ty = self.check_expr(cond);
+                self.expect(cond.span, &Type::Bool, &cond_ty);
+
+                let then_ty = self.check_block(then);
+                match otherwise {
+                    Some(otherwise) => {
+                        let otherwise_ty = self.check_expr(otherwise);
+                        match self.table.join(&then_ty, &otherwise_ty) {
+                            Ok(ty) => ty,
+                            Err(_) => {
+                                let then_ty = self.table.zonk(&then_ty);
+                                let otherwise_ty = self.table.zonk(&otherwise_ty);
+                                self.error(
+                                    Diagnostic::error("`if` and `else` have different types")
+                                        .with_code(codes::E0301)
+                                        .with_label(
+                                            otherwise.span,
+                                            format!(
+                                                "expected `{}`, found `{}`",
+                                                then_ty, otherwise_ty
+                                            ),
+                                        )
+                                        .with_secondary_label(
+                                            then.span,
+                                            format!("this is `{}`", then_ty),
+                                        ),
+                                );
+                                Type::Error
+                            }
+                        }
+                    }
+                    None => {
+                        if self.table.unify(&Type::Unit, &then_ty).is_err() {
+                            self.error(
+                                Diagnostic::error("`if` without an `else` can't have a value")
+                                    .with_code(codes::E0301)
+                                    .with_label(
+                                        then.span,
+                                        format!("expected `()`, found `{}`", then_ty),
+                                    )
+                                    .with_help("add an `else` block with a value of the same type"),
+                            );
+                        }
+                        Type::Unit
+                    }
+                }
+            }
+            ExprKind::Loop(body) => {
+                self.loops.push(LoopContext {
+                    kind: LoopKind::Loop,
+                    break_ty: None,
+                });
+                self.check_block(body);
+                let context = self.loops.pop().expect("the loop was pushed");
+
+                // A loop that never breaks never finishes
+                context.break_ty.unwrap_or(Type::Never)
+            }
+            ExprKind::While { cond, body } => {
+                let cond_ty = self.check_expr(cond);
+                self.expect(cond.span, &Type::Bool, &cond_ty);
+
+                self.check_loop_body(body);
+                Type::Unit
+            }
+            ExprKind::For {
+                binding,
+                iter,
+                body,
+            } => {
+                let iter_ty = self.check_expr(iter);
+                let element = match self.table.zonk(&iter_ty) {
+                    Type::Array(element) => *element,
+                    Type::Range => Type::INT,
+                    Type::Error => Type::Error,
+                    ty if ty.is_unknown() => {
+                        self.annotations_needed(iter.span, "the type of the value being iterated");
+                        Type::Error
+                    }
+                    ty => {
+                        self.error(
+                            Diagnostic::error(format!("Cannot iterate over type `{}`", ty))
+                                .with_code(codes::E0311)
+                                .with_label(iter.span, format!("this is `{}`", ty))
+                                .with_help("arrays and `range(start, end)` can be iterated over"),
+                        );
+                        Type::Error
+                    }
+                };
+
+                self.declare(
+                    expr.id,
+                    Binding::new(BindingKind::Pattern, element, binding.span),
+                );
+                self.check_loop_body(body);
+                Type::Unit
+            }
+            ExprKind::Match { scrutinee, arms } => {
+                let scrutinee_ty = self.check_expr(scrutinee);
+                let mut ty = Type::Never;
+
+                // Patterns with errors would be reported again as missing or unreachable
+                let mut checked = Some(Vec::new());
+                for arm in arms {
+                    let (checked_arm, arm_ty) = self.check_arm(arm, &scrutinee_ty);
+                    match (&mut checked, checked_arm) {
+                        (Some(checked), Some(arm)) => checked.push(arm),
+                        _ => checked = None,
+                    }
+                    ty = match self.table.join(&ty, &arm_ty) {
+                        Ok(ty) => ty,
+                        Err(_) => {
+                            let ty = self.table.zonk(&ty);
+                            let arm_ty = self.table.zonk(&arm_ty);
+                            self.error(
+                                Diagnostic::error("`match` arms have different types")
+                                    .with_code(codes::E0301)
+                                    .with_label(
+                                        arm.body.span,
+                                        format!("expected `{}`, found `{}`", ty, arm_ty),
+                                    ),
+                            );
+                            ty
+                        }
+                    };
+                }
+
+                if let Some(arms) = checked {
+                    self.matches.push(MatchCheck {
+                        span: scrutinee.span,
+                        ty: scrutinee_ty,
+                        arms,
+                    });
+                }
+                ty
+            }
+            ExprKind::Return(value) => {
+                // Returning from the top level of a script ends it, with any value
+                match (value, self.returns.last().cloned()) {
+                    (Some(value), Some(expected)) => self.check_expr_as(value, &expected),
+                    (Some(value), None) => {
+                        self.check_expr(value);
+                    }
+                    (None, Some(expected)) => self.expect(expr.span, &expected, &Type::Unit),
+                    (None, None) => {}
+                }
+                Type::Never
+            }
+            ExprKind::Break(value) => {
+                let ty = match value {
+                    Some(value) => self.check_expr(value),
+                    None => Type::Unit,
+                };
+
+                let Some(context) = self.loops.last_mut() else {
+                    self.error(
+                        Diagnostic::error("`break` outside of a loop")
+                            .with_code(codes::E0312)
+                            .with_label(expr.span, "cannot `break` outside of a loop"),
+                    );
+                    return Type::Never;
+                };
+
+                if value.is_some() && context.kind == LoopKind::Conditional {
+                    self.error(
+                        Diagnostic::error("`break` with a value in a `while` or `for` loop")
+                            .with_code(codes::E0313)
+                            .with_label(expr.span, "can only break with a value from a `loop`"),
+                    );
+                    return Type::Never;
+                }
+
+                match context.break_ty.clone() {
+                    None => context.break_ty = Some(ty),
+                    Some(expected) => match self.table.join(&expected, &ty) {
+                        Ok(joined) => self.loops.last_mut().unwrap().break_ty = Some(joined),
+                        Err(_) => {
+                            let span = value.as_ref().map_or(expr.span, |value| value.span);
+                            self.mismatch(span, &expected, &ty);
+                        }
+                    },
+                }
+                Type::Never
+            }
+            ExprKind::Continue => {
+                if self.loops.is_empty() {
+                    self.error(
+                        Diagnostic::error("`continue` outside of a loop")
+                            .with_code(codes::E0312)
+                            .with_label(expr.span, "cannot `continue` outside of a loop"),
+                    );
+                }
+                Type::Never
+            }
+        }
+    }
+
+    /// Checks the target of an assignment, which has to be something that can be assigned to.
+    ///
+    /// `initializing` is set for plain assignments, which can give an immutable `let` without a
+    /// value its value.
+    fn check_place(&mut self, target: &Expr, verb: &str, initializing: bool) -> Type {
+        match &target.kind {
+            ExprKind::Ident(_) | ExprKind::Index(..) | ExprKind::Field(..) => {
+                let ty = self.check_expr(target);
+                if let Some((name, binding)) = self.immutable_root(target, initializing) {
+                    let (what, reason) = match binding.kind {
+                        BindingKind::Let | BindingKind::Param | BindingKind::Pattern => {
+                            ("immutable variable", format!("`{}` isn't mutable", name))
+                        }
+                        BindingKind::Const => ("constant", "constants can't be changed".into()),
+                        BindingKind::Fn => ("function", "functions can't be changed".into()),
+                        BindingKind::Struct => ("struct", "structs can't be changed".into()),
+                    };
+
+                    let mut diagnostic =
+                        Diagnostic::error(format!("Cannot {} {} `{}`", verb, what, name))
+                            .with_code(codes::E0316)
+                            .with_label(target.span, reason)
+                            .with_secondary_label(binding.span, "declared here");
+                    if matches!(binding.kind, BindingKind::Let | BindingKind::Param) {
+                        diagnostic = diagnostic.with_help(format!(
+                            "make it mutable by declaring it as `mut {}`",
+                            name
+                        ));
+                    }
+                    self.error(diagnostic);
+                }
+                ty
+            }
+            ExprKind::Error => Type::Error,
+            _ => {
+                self.check_expr(target);
+                self.error(
+                    Diagnostic::error("Invalid left-hand side of assignment")
+                        .with_code(codes::E0310)
+                        .with_label(target.span, "cannot assign to this expression"),
+                );
+                Type::Error
+            }
+        }
+    }
+
+    /// Finds the variable that a place is a part of, returning it if it can't be changed. Places
+    /// in temporary values, like `f()[0]`, can always be changed.
+    fn immutable_root(&mut self, place: &Expr, initializing: bool) -> Option<(SmolStr, Binding)> {
+        match &place.kind {
+            ExprKind::Ident(name) => {
+                let depth = self.depth();
+                let binding = self.lookup(place.id)?;
+                if binding.mutable {
+                    return None;
+                }
+                if initializing && binding.uninit == Some(depth) {
+                    binding.uninit = None;
+                    return None;
+                }
+                Some((name.clone(), binding.clone()))
+            }
+            ExprKind::Index(base, _) | ExprKind::Field(base, _) => self.immutable_root(base, false),
+            _ => None,
+        }
+    }
+
+    fn check_unary(&mut self, op: UnaryOp, operand: Span, ty: &Type) -> Type {
+        let ty = self.table.resolve(ty);
+        let valid = match (op, &ty) {
+            (_, Type::Error) => true,
+            (UnaryOp::Neg, Type::Var(_)) if !self.finished => {
+                self.deferred.push(Deferred::Unary {
+                    op,
+                    operand,
+                    ty: ty.clone(),
+                });
+                true
+            }
+            (UnaryOp::Not, Type::Var(_)) => self.table.unify(&ty, &Type::Bool).is_ok(),
+            (UnaryOp::BitNot, ty) if ty.is_unknown() => self.table.unify(ty, &Type::INT).is_ok(),
+            (UnaryOp::Neg, Type::Int(ty)) => ty.is_signed(),
+            (UnaryOp::Neg, ty) => ty.is_numeric(),
+            (UnaryOp::Not, ty) => *ty == Type::Bool,
+            (UnaryOp::BitNot, ty) => ty.is_int(),
+        };
+
+        if valid {
+            ty
+        } else {
+            self.error(
+                Diagnostic::error(format!(
+                    "Cannot apply unary operator `{}` to type `{}`",
+                    unary_op_str(op),
+                    ty
+                ))
+                .with_code(codes::E0304)
+                .with_label(operand, format!("this is `{}`", ty)),
+            );
+            Type::Error
+        }
+    }
+
+    fn check_step(&mut self, target: Span, ty: &Type) {
+        let ty = self.table.resolve(ty);
+        match ty {
+            ty if ty.is_unknown() && !self.finished => {
+                self.deferred.push(Deferred::Step { target, ty })
+            }
+            ty if ty.is_numeric() || ty.is_error() => {}
+            ty => self.error(
+                Diagnostic::error(format!("Cannot increment or decrement type `{}`", ty))
+                    .with_code(codes::E0304)
+                    .with_label(target, format!("this is `{}`", ty)),
+            ),
+        }
+    }
+
+    fn check_binary(
+        &mut self,
+        span: Span,
+        op: BinaryOp,
+        (lhs, lhs_ty): (Span, Type),
+        (rhs, rhs_ty): (Span, Type),
+    ) -> Type {
+        use BinaryOp::*;
+
+        let mut lhs_ty = self.table.resolve(&lhs_ty);
+        let mut rhs_ty = self.table.resolve(&rhs_ty);
+
+        if lhs_ty.is_error() || rhs_ty.is_error() {
+            return match op {
+                Eq | Ne | Lt | Gt | Le | Ge | And | Or => Type::Bool,
+                _ => Type::Error,
+            };
+        }
+
+        let unknown = matches!(lhs_ty, Type::Var(_)) || matches!(rhs_ty, Type::Var(_));
+        if unknown {
+            match op {
+                // The operands of these are always bools
+                And | Or => {
+                    for (span, ty) in [(lhs, &mut lhs_ty), (rhs, &mut rhs_ty)] {
+                        if ty.is_unknown() {
+                            self.expect(span, &Type::Bool, ty);
+                            *ty = Type::Bool;
+                        }
+                    }
+                }
+                // The amount can be any kind of int, it's `int` if nothing says otherwise
+                Shl | Shr => {
+                    for (span, ty) in [(lhs, &mut lhs_ty), (rhs, &mut rhs_ty)] {
+                        if ty.is_unknown() {
+                            self.expect(span, &Type::INT, ty);
+                            *ty = Type::INT;
+                        }
+                    }
+                }
+                // Both operands have the same type, so one tells what the other is
+                _ => {
+                    if self.table.unify(&lhs_ty, &rhs_ty).is_ok() {
+                        lhs_ty = self.table.resolve(&lhs_ty);
+                        rhs_ty = self.table.resolve(&rhs_ty);
+                    }
+                }
+            }
+        }
+
+        // Number literals whose size isn't known yet are checked like any other number
+        if lhs_ty.is_unknown() && rhs_ty.is_unknown() {
+            if !self.finished && !matches!(op, Eq | Ne) {
+                self.deferred.push(Deferred::Binary {
+                    span,
+                    op,
+                    lhs: (lhs, lhs_ty.clone()),
+                    rhs: (rhs, rhs_ty),
+                });
+            }
+
+            return match op {
+                Eq | Ne | Lt | Gt | Le | Ge => Type::Bool,
+                _ => lhs_ty,
+            };
+        }
+
+        let ty = match (op, &lhs_ty, &rhs_ty) {
+            (Add, Type::String, Type::String) => Some(Type::String),
+            (Add | Sub | Mul | Div | Rem, a, b) if a.is_numeric() && a == b => Some(a.clone()),
+            (And | Or, Type::Bool, Type::Bool) => Some(Type::Bool),
+            (BitAnd | BitOr | BitXor, a, b) if (a.is_int() || *a == Type::Bool) && a == b => {
+                Some(a.clone())
+            }
+            (Shl | Shr, a, b) if a.is_int() && b.is_int() => Some(a.clone()),
+            (Eq | Ne, a, b) if !matches!(a, Type::Fn(_)) && self.table.unify(a, b).is_ok() => {
+                Some(Type::Bool)
+            }
+            (Lt | Gt | Le | Ge, a, b)
+                if a == b && (a.is_numeric() || matches!(a, Type::Char | Type::String)) =>
+            {
+                Some(Type::Bool)
+            }
+            _ => None,
+        };
+
+        ty.unwrap_or_else(|| {
+            let lhs_ty = self.table.zonk(&lhs_ty);
+            let rhs_ty = self.table.zonk(&rhs_ty);
+            self.error(
+                Diagnostic::error(format!(
+                    "Cannot apply `{}` to `{}` and `{}`",
+                    binary_op_str(op),
+                    lhs_ty,
+                    rhs_ty
+                ))
+                .with_code(codes::E0303)
+                .with_label(span, "invalid operand types")
+                .with_secondary_label(lhs, format!("this is `{}`", lhs_ty))
+                .with_secondary_label(rhs, format!("this is `{}`", rhs_ty)),
+            );
+            Type::Error
+        })
+    }
+
+    fn check_call(&mut self, expr: &Expr, func: &Expr, args: &[Expr]) -> Type {
+        if let Some(Res::Builtin(builtin)) = self.names.get(func.id) {
+            return self.check_builtin(expr, builtin, args);
+        }
+
+        let func_ty = self.check_expr(func);
+        let signature = match self.table.resolve(&func_ty) {
+            Type::Fn(signature) => signature,
+            // Calling a closure parameter tells what kind of function it is
+            ty if ty.is_unknown() => {
+                let ret = self.table.fresh();
+                let signature = Type::Fn(FnType {
+                    params: args
+                        .iter()
+                        .map(|arg| FnParam::new(self.check_expr(arg)))
+                        .collect(),
+                    ret: Box::new(ret.clone()),
+                });
+                self.expect(func.span, &signature, &func_ty);
+                return ret;
+            }
+            ty => {
+                for arg in args {
+                    self.check_expr(arg);
+                }
+                if !ty.is_error() {
+                    self.error(
+                        Diagnostic::error(format!("Expected a function, found `{}`", ty))
+                            .with_code(codes::E0307)
+                            .with_label(func.span, "this isn't a function"),
+                    );
+                }
+                return Type::Error;
+            }
+        };
+
+        self.check_args(expr.span, &signature.params, args);
+        *signature.ret
+    }
+
+    /// Checks the arguments of a call against the parameters of the function.
+    fn check_args(&mut self, span: Span, params: &[FnParam], args: &[Expr]) {
+        if params.len() != args.len() {
+            self.wrong_arg_count(span, params.len(), args.len());
+        }
+
+        for (i, arg) in args.iter().enumerate() {
+            match params.get(i) {
+                // The final value is written back to the argument, so it has to be the same type
+                Some(param) if param.mutable => {
+                    let ty = self.check_expr(arg);
+                    if self.table.unify(&param.ty, &ty).is_err() {
+                        self.mismatch(arg.span, &param.ty, &ty);
+                    }
+                    self.check_mut_arg(arg);
+                }
+                Some(param) => self.check_expr_as(arg, &param.ty),
+                None => {
+                    self.check_expr(arg);
+                }
+            }
+        }
+    }
+
+    /// Checks a call like `value.method(args)`, which calls the method of the value's type with
+    /// the value as `self`. Methods of trait objects and type parameters are found in their
+    /// traits.
+    fn check_method_call(
+        &mut self,
+        expr: &Expr,
+        receiver: &Expr,
+        method: &Ident,
+        args: &[Expr],
+    ) -> Type {
+        let ty = self.check_expr(receiver);
+        let ty = self.table.resolve(&ty);
+
+        let found = if ty.is_unknown() {
+            self.annotations_needed(receiver.span, "the type of this value");
+            // It's reported here, not again at the end
+            let _ = self.table.unify(&ty, &Type::Error);
+            None
+        } else if let Type::Dyn { .. } | Type::Param { .. } = ty {
+            self.find_trait_method(&ty, method)
+                .map(|found| (Callee::Dynamic(method.name.clone()), found))
+        } else {
+            self.find_method(&ty, method, "method")
+                .map(|found| (Callee::Static(found.id), found))
+        };
+        let Some((callee, found)) = found else {
+            for arg in args {
+                self.check_expr(arg);
+            }
+            return Type::Error;
+        };
+        if !found.receiver {
+            self.error(
+                Diagnostic::error(format!(
+                    "`{}` is an associated function of `{}`, not a method",
+                    method.name, ty
+                ))
+                .with_code(codes::E0322)
+                .with_label(method.span, "doesn't take `self`")
+                .with_help(format!("call it as `{}::{}()`", ty, method.name)),
+            );
+            for arg in args {
+                self.check_expr(arg);
+            }
+            return *found.ty.ret;
+        }
+
+        self.info.methods.insert(expr.id, callee);
+        let signature = self.instantiate(expr.span, &found.ty);
+        if signature.params[0].mutable {
+            self.check_mut_arg(receiver);
+        }
+        self.check_args(expr.span, &signature.params[1..], args);
+        *signature.ret
+    }
+
+    /// Finds a method of a trait object or a type parameter in its traits.
+    fn find_trait_method(&mut self, ty: &Type, name: &Ident) -> Option<MethodInfo> {
+        let traits = match ty {
+            Type::Dyn { id, .. } => vec![*id],
+            Type::Param { id, .. } => self.generics.get(id).cloned().unwrap_or_default(),
+            _ => Vec::new(),
+        };
+        let found = traits
+            .iter()
+            .find_map(|trait_| self.traits[trait_].methods.get(&name.name));
+        if let Some(found) = found {
+            return Some(found.clone());
+        }
+
+        let mut diagnostic =
+            Diagnostic::error(format!("No method `{}` on type `{}`", name.name, ty))
+                .with_code(codes::E0322)
+                .with_label(name.span, "unknown method");
+        if let Type::Param { name, .. } = ty {
+            diagnostic = diagnostic.with_help(format!(
+                "the methods of `{}` are the ones of the traits in its bounds, like `{}: Trait`",
+                name, name
+            ));
+        }
+        self.error(diagnostic);
+        None
+    }
+
+    /// Finds a function in the `impl`s of a type, `what` is "method" or "function" for the
+    /// error when there's none with the name.
+    fn find_method(&mut self, ty: &Type, name: &Ident, what: &str) -> Option<MethodInfo> {
+        if let Type::Struct { id, .. } = ty {
+            if let Some(method) = self.structs[id].methods.get(&name.name) {
+                return Some(method.clone());
+            }
+        }
+
+        if !ty.is_error() {
+            self.error(
+                Diagnostic::error(format!("No {} `{}` on type `{}`", what, name.name, ty))
+                    .with_code(codes::E0322)
+                    .with_label(name.span, format!("unknown {}", what)),
+            );
+        }
+        None
+    }
+
+    /// The variant of an enum with a name, and its index.
+    fn find_variant(
+        &mut self,
+        id: NodeId,
+        ty: &Type,
+        name: &Ident,
+    ) -> Option<(usize, VariantInfo)> {
+        let found = self.enums[&id]
+            .variants
+            .iter()
+            .enumerate()
+            .find(|(_, variant)| variant.name == name.name);
+        if let Some((index, variant)) = found {
+            return Some((index, variant.clone()));
+        }
+
+        self.error(
+            Diagnostic::error(format!("No variant `{}` on enum `{}`", name.name, ty))
+                .with_code(codes::E0322)
+                .with_label(name.span, "unknown variant"),
+        );
+        None
+    }
+
+    /// Checks an argument for a `mut` parameter, which has to be a place the function can change.
+    fn check_mut_arg(&mut self, arg: &Expr) {
+        if !matches!(
+            arg.kind,
+            ExprKind::Ident(_) | ExprKind::Index(..) | ExprKind::Field(..) | ExprKind::Error
+        ) {
+            self.error(
+                Diagnostic::error("Expected a mutable place for a `mut` parameter")
+                    .with_code(codes::E0317)
+                    .with_label(arg.span, "this is a temporary value")
+                    .with_help("store it in a `let mut` variable and pass that"),
+            );
+            return;
+        }
+
+        if let Some((name, binding)) = self.immutable_root(arg, false) {
+            let mut diagnostic = Diagnostic::error(format!(
+                "Cannot pass immutable `{}` to a `mut` parameter",
+                name
+            ))
+            .with_code(codes::E0317)
+            .with_label(arg.span, format!("`{}` isn't mutable", name))
+            .with_secondary_label(binding.span, "declared here");
+            if matches!(binding.kind, BindingKind::Let | BindingKind::Param) {
+                diagnostic = diagnostic
+                    .with_help(format!("make it mutable by declaring it as `mut {}`", name));
+            }
+            self.error(diagnostic);
+        }
+    }
+
+    fn check_closure(
+        &mut self,
+        params: &[ClosureParam],
+        ret: &Option<TypeExpr>,
+        body: &Expr,
+    ) -> Type {
+        let mut param_tys = Vec::new();
+        for param in params {
+            let ty = match &param.ty {
+                Some(ty) => self.resolve_type(ty),
+                None => self.unknown(
+                    param.name.span,
+                    format!("the type of `{}`", param.name.name),
+                ),
+            };
+            self.declare(
+                param.id,
+                Binding::new(BindingKind::Param, ty.clone(), param.span).mutable(param.mutable),
+            );
+            param_tys.push(FnParam {
+                ty,
+                mutable: param.mutable,
+            });
+        }
+
+        let ret = match ret {
+            Some(ret) => self.resolve_type(ret),
+            None => self.table.fresh(),
+        };
+
+        let loops = std::mem::take(&mut self.loops);
+        self.returns.push(ret.clone());
+
+        let body_ty = self.check_expr(body);
+        self.expect(body.span, &ret, &body_ty);
+
+        self.returns.pop();
+        self.loops = loops;
+
+        Type::Fn(FnType {
+            params: param_tys,
+            ret: Box::new(ret),
+        })
+    }
+
+    fn check_builtin(&mut self, expr: &Expr, builtin: Builtin, args: &[Expr]) -> Type {
+        let arg_tys = args
+            .iter()
+            .map(|arg| (arg.span, self.check_expr(arg)))
+            .collect::<Vec<_>>();
+
+        match builtin {
+            Builtin::Range => {
+                if arg_tys.len() != 2 {
+                    self.wrong_arg_count(expr.span, 2, arg_tys.len());
+                }
+                for (span, ty) in &arg_tys {
+                    self.expect(*span, &Type::INT, ty);
+                }
+                Type::Range
+            }
+            Builtin::Len => {
+                if arg_tys.len() != 1 {
+                    self.wrong_arg_count(expr.span, 1, arg_tys.len());
+                }
+                if let Some((span, ty)) = arg_tys.first() {
+                    let ty = self.table.resolve(ty);
+                    if ty.is_unknown() {
+                        self.annotations_needed(*span, "the type of the value");
+                    } else if !matches!(ty, Type::Array(_) | Type::String | Type::Error) {
+                        self.error(
+                            Diagnostic::error(format!("Cannot get the length of `{}`", ty))
+                                .with_code(codes::E0301)
+                                .with_label(*span, "expected an array or a string"),
+                        );
+                    }
+                }
+                Type::INT
+            }
+            Builtin::Fmt => {
+                self.check_format_args(expr.span, args, &arg_tys);
+                Type::String
+            }
+            Builtin::Print => {
+                self.check_format_args(expr.span, args, &arg_tys);
+                Type::Unit
+            }
+            Builtin::WrappingAdd
+            | Builtin::WrappingSub
+            | Builtin::WrappingMul
+            | Builtin::SaturatingAdd
+            | Builtin::SaturatingSub
+            | Builtin::SaturatingMul => {
+                if arg_tys.len() != 2 {
+                    self.wrong_arg_count(expr.span, 2, arg_tys.len());
+                }
+
+                // Both are the same kind of int, which is also the result
+                let mut ty = match arg_tys.first() {
+                    Some((_, ty)) => ty.clone(),
+                    None => return Type::Error,
+                };
+                for (span, arg_ty) in &arg_tys[1..] {
+                    self.expect(*span, &ty, arg_ty);
+                }
+
+                ty = self.table.resolve(&ty);
+                if ty.is_unknown() {
+                    self.expect(arg_tys[0].0, &Type::INT, &ty);
+                    ty = Type::INT;
+                } else if !ty.is_int() && !ty.is_error() {
+                    self.error(
+                        Diagnostic::error(format!("Cannot call `{}` on `{}`", builtin.name(), ty))
+                            .with_code(codes::E0301)
+                            .with_label(arg_tys[0].0, "expected an int"),
+                    );
+                    return Type::Error;
+                }
+                ty
+            }
+        }
+    }
+
+    /// Checks the arguments of formatting functions, which start with the format string. A literal
+    /// format string must have a `{}` for every other argument, others are checked when they're
+    /// formatted.
+    fn check_format_args(&mut self, span: Span, args: &[Expr], arg_tys: &[(Span, Type)]) {
+        let Some((format_span, ty)) = arg_tys.first() else {
+            self.error(
+                Diagnostic::error("Missing format string")
+                    .with_code(codes::E0306)
+                    .with_label(span, "expected a format string like `\"{}\"`"),
+            );
+            return;
+        };
+        self.expect(*format_span, &Type::String, ty);
+
+        let ExprKind::Literal(Literal::String(format)) = &args[0].kind else {
+            return;
+        };
+        let placeholders = placeholders(format);
+        let given = args.len() - 1;
+        if placeholders != given {
+            let plural = |n: usize| if n == 1 { "" } else { "s" };
+            let mut diagnostic = Diagnostic::error(format!(
+                "The format string has {} `{{}}` but {} argument{} {} given",
+                placeholders,
+                given,
+                plural(given),
+                if given == 1 { "was" } else { "were" }
+            ))
+            .with_code(codes::E0331)
+            .with_label(
+                *format_span,
+                format!("{} placeholder{}", placeholders, plural(placeholders)),
+            );
+            for arg in args.iter().skip(placeholders + 1) {
+                diagnostic = diagnostic.with_secondary_label(arg.span, "argument never used");
+            }
+            self.error(diagnostic);
+        }
+    }
+
+    fn wrong_arg_count(&mut self, span: Span, expected: usize, found: usize) {
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        self.error(
+            Diagnostic::error(format!(
+                "Expected {} argument{}, found {}",
+                expected,
+                plural(expected),
+                found
+            ))
+            .with_code(codes::E0306)
+            .with_label(
+                span,
+                format!("called with {} argument{}", found, plural(found)),
+            ),
+        );
+    }
+
+    fn check_loop_body(&mut self, body: &Block) {
+        self.loops.push(LoopContext {
+            kind: LoopKind::Conditional,
+            break_ty: None,
+        });
+        let ty = self.check_block(body);
+        self.expect(
+            body.tail.as_ref().map_or(body.span, |tail| tail.span),
+            &Type::Unit,
+            &ty,
+        );
+        self.loops.pop();
+    }
+
+    /// Checks an arm of a `match`, returning the type of its body and the arm for the
+    /// exhaustiveness check, which is `None` if its pattern has errors.
+    fn check_arm(&mut self, arm: &MatchArm, scrutinee: &Type) -> (Option<CheckedArm>, Type) {
+        let errors = self.diagnostics.len();
+        let pattern = self.check_pattern(&arm.pattern, scrutinee);
+        let checked = (self.diagnostics.len() == errors).then(|| CheckedArm {
+            pattern,
+            span: arm.pattern.span,
+            alternatives: match &arm.pattern.kind {
+                PatternKind::Or(alternatives) => alternatives.iter().map(|alt| alt.span).collect(),
+                _ => Vec::new(),
+            },
+            guarded: arm.guard.is_some(),
+        });
+
+        if let Some(guard) = &arm.guard {
+            let ty = self.check_expr(guard);
+            self.expect(guard.span, &Type::Bool, &ty);
+        }
+        (checked, self.check_expr(&arm.body))
+    }
+
+    /// Checks a pattern that matches values of type `expected` and declares its bindings,
+    /// returning it lowered for the exhaustiveness check.
+    fn check_pattern(&mut self, pattern: &Pattern, expected: &Type) -> Pat {
+        match &pattern.kind {
+            PatternKind::Wildcard => Pat::Wild,
+            PatternKind::Binding(_, subpattern) => {
+                // The later alternatives of a `|` pattern bind the first one's variables
+                match self.names.get(pattern.id) {
+                    Some(Res::Local(id)) if id != pattern.id => {
+                        if let Some(binding) = self.bindings.get(&id) {
+                            let ty = binding.ty.clone();
+                            self.expect(pattern.span, &ty, expected);
+                        }
+                    }
+                    _ => self.declare(
+                        pattern.id,
+                        Binding::new(BindingKind::Pattern, expected.clone(), pattern.span),
+                    ),
+                }
+                match subpattern {
+                    Some(subpattern) => self.check_pattern(subpattern, expected),
+                    None => Pat::Wild,
+                }
+            }
+            PatternKind::Literal(literal) => {
+                let ty = self.check_expr(literal);
+                self.expect(literal.span, expected, &ty);
+                match pattern_value(literal) {
+                    Some(PatternValue::Int(value)) => Pat::Ctor(Ctor::Range(value, value), vec![]),
+                    Some(PatternValue::Float(value)) => {
+                        Pat::Ctor(Ctor::Opaque(value.to_string()), vec![])
+                    }
+                    Some(PatternValue::Other(ctor)) => Pat::Ctor(ctor, vec![]),
+                    // `()`
+                    None => Pat::Wild,
+                }
+            }
+            PatternKind::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                for bound in [start, end] {
+                    let ty = self.check_expr(bound);
+                    self.expect(bound.span, expected, &ty);
+                }
+                self.check_range(pattern.span, expected, start, end, *inclusive)
+            }
+            PatternKind::Or(alternatives) => Pat::Or(
+                alternatives
+                    .iter()
+                    .map(|alternative| self.check_pattern(alternative, expected))
+                    .collect(),
+            ),
+            PatternKind::Variant { ty, name, payload } => {
+                let span = ty.span;
+                let ty = self.resolve_type(ty);
+                let variant = match &ty {
+                    Type::Enum { id, .. } => {
+                        self.expect(pattern.span, expected, &ty);
+                        self.find_variant(*id, &ty, name)
+                    }
+                    Type::Error => None,
+                    _ => {
+                        self.error(
+                            Diagnostic::error(format!("Expected an enum, found `{}`", ty))
+                                .with_code(codes::E0327)
+                                .with_label(span, "not an enum")
+                                .with_help("only the variants of enums can be matched by name"),
+                        );
+                        None
+                    }
+                };
+
+                match variant {
+                    Some((index, variant)) => {
+                        let fields = self.check_payload(pattern.span, payload, &variant, &ty);
+                        Pat::Ctor(Ctor::Variant(index), fields)
+                    }
+                    None => {
+                        // The bindings are still declared, so their uses aren't errors too
+                        for subpattern in payload_patterns(payload) {
+                            self.check_pattern(subpattern, &Type::Error);
+                        }
+                        Pat::Wild
+                    }
+                }
+            }
+        }
+    }
+
+    /// Checks a range pattern whose bounds have been checked.
+    fn check_range(
+        &mut self,
+        span: Span,
+        expected: &Type,
+        start: &Expr,
+        end: &Expr,
+        inclusive: bool,
+    ) -> Pat {
+        let ty = self.table.resolve(expected);
+        if !(ty.is_numeric() || matches!(ty, Type::Char | Type::Error)) {
+            self.error(
+                Diagnostic::error(format!("Range patterns can't match `{}`", ty))
+                    .with_code(codes::E0326)
+                    .with_label(span, format!("this is a range, but the value is `{}`", ty))
+                    .with_note("ranges can only match numbers and chars"),
+            );
+            return Pat::Wild;
+        }
+
+        let op = if inclusive { "..=" } else { ".." };
+        let (pattern, empty) = match (pattern_value(start), pattern_value(end)) {
+            (Some(PatternValue::Int(lo)), Some(PatternValue::Int(hi))) => {
+                let hi = if inclusive { hi } else { hi - 1 };
+                (Pat::Ctor(Ctor::Range(lo, hi), vec![]), lo > hi)
+            }
+            (Some(PatternValue::Float(lo)), Some(PatternValue::Float(hi))) => {
+                let range = format!("{}{}{}", lo, op, hi);
+                let empty = lo > hi || (lo == hi && !inclusive);
+                (Pat::Ctor(Ctor::Opaque(range), vec![]), empty)
+            }
+            // The bounds have the wrong types, which was already reported
+            _ => return Pat::Wild,
+        };
+
+        if empty {
+            self.error(
+                Diagnostic::error("Empty range pattern")
+                    .with_code(codes::E0326)
+                    .with_label(span, "this range doesn't contain any values")
+                    .with_help(match inclusive {
+                        true => "the start of a range has to be at most its end",
+                        false => "the start of a `..` range has to be less than its end",
+                    }),
+            );
+        }
+        pattern
+    }
+
+    /// Checks the patterns for the payload of a variant against its declaration, returning the
+    /// patterns of its fields in order.
+    fn check_payload(
+        &mut self,
+        span: Span,
+        payload: &PayloadPattern,
+        variant: &VariantInfo,
+        ty: &Type,
+    ) -> Vec<Pat> {
+        let mut fields = vec![Pat::Wild; variant.fields.len()];
+        match (payload, &variant.payload) {
+            (PayloadPattern::Unit, Payload::Unit) => {}
+            (PayloadPattern::Tuple(patterns), Payload::Tuple) => {
+                if patterns.len() != variant.fields.len() {
+                    let plural = if patterns.len() == 1 { "" } else { "s" };
+                    self.error(
+                        Diagnostic::error(format!(
+                            "This pattern has {} field{}, but `{}::{}` has {}",
+                            patterns.len(),
+                            plural,
+                            ty,
+                            variant.name,
+                            variant.fields.len()
+                        ))
+                        .with_code(codes::E0327)
+                        .with_label(span, format!("expected `{}`", variant.pattern(ty))),
+                    );
+                }
+                for (i, pattern) in patterns.iter().enumerate() {
+                    let field_ty = variant.fields.get(i).cloned().unwrap_or(Type::Error);
+                    let field = self.check_pattern(pattern, &field_ty);
+                    if let Some(slot) = fields.get_mut(i) {
+                        *slot = field;
+                    }
+                }
+            }
+            (
+                PayloadPattern::Struct {
+                    fields: patterns,
+                    rest,
+                },
+                Payload::Struct(names),
+            ) => {
+                let mut matched = vec![false; names.len()];
+                for field in patterns {
+                    let Some(i) = names.iter().position(|name| *name == field.name.name) else {
+                        self.error(
+                            Diagnostic::error(format!(
+                                "Variant `{}::{}` doesn't have a field named `{}`",
+                                ty, variant.name, field.name.name
+                            ))
+                            .with_code(codes::E0327)
+                            .with_label(field.name.span, "unknown field"),
+                        );
+                        self.check_pattern(&field.pattern, &Type::Error);
+                        continue;
+                    };
+                    if matched[i] {
+                        self.error(
+                            Diagnostic::error(format!(
+                                "Field `{}` is matched more than once",
+                                field.name.name
+                            ))
+                            .with_code(codes::E0327)
+                            .with_label(field.name.span, "matched again here"),
+                        );
+                    }
+                    matched[i] = true;
+                    fields[i] = self.check_pattern(&field.pattern, &variant.fields[i]);
+                }
+
+                let missing = names
+                    .iter()
+                    .zip(&matched)
+                    .filter(|(_, matched)| !**matched)
+                    .map(|(name, _)| format!("`{}`", name))
+                    .collect::<Vec<_>>();
+                if !missing.is_empty() && !rest {
+                    self.error(
+                        Diagnostic::error(format!(
+                            "Pattern doesn't mention all fields of `{}::{}`",
+                            ty, variant.name
+                        ))
+                        .with_code(codes::E0327)
+                        .with_label(span, format!("missing {}", missing.join(", ")))
+                        .with_help("add `..` at the end to ignore the other fields"),
+                    );
+                }
+            }
+            _ => {
+                let kind = match variant.payload {
+                    Payload::Unit => "unit",
+                    Payload::Tuple => "tuple",
+                    Payload::Struct(_) => "struct",
+                };
+                self.error(
+                    Diagnostic::error(format!("`{}::{}` is a {} variant", ty, variant.name, kind))
+                        .with_code(codes::E0327)
+                        .with_label(span, format!("expected `{}`", variant.pattern(ty))),
+                );
+                for subpattern in payload_patterns(payload) {
+                    self.check_pattern(subpattern, &Type::Error);
+                }
+            }
+        }
+        fields
+    }
+
+    /// Reports the arms of a `match` that can't match anything the arms before them don't, and
+    /// the values that none of its arms match.
+    fn check_exhaustive(&mut self, check: MatchCheck) {
+        let ty = self.table.zonk(&check.ty);
+        // Values of unknown types have already been reported
+        if ty.is_error() || !self.table.unresolved(&ty).is_empty() {
+            return;
+        }
+
+        let usefulness = Usefulness::new(&self.enums);
+        let tys = [ty.clone()];
+        let mut rows = Vec::new();
+        let mut unreachable = Vec::new();
+        for arm in &check.arms {
+            match &arm.pattern {
+                Pat::Or(alternatives) if !arm.alternatives.is_empty() => {
+                    // Each alternative has to match something the ones before it don't
+                    let mut seen = rows.clone();
+                    let mut reachable = Vec::new();
+                    for (alternative, span) in alternatives.iter().zip(&arm.alternatives) {
+                        let row = vec![alternative.clone()];
+                        reachable.push((*span, usefulness.is_useful(&seen, &row, &tys)));
+                        seen.push(row);
+                    }
+                    if reachable.iter().all(|(_, reachable)| !reachable) {
+                        unreachable.push((arm.span, true));
+                    } else {
+                        unreachable.extend(
+                            reachable
+                                .into_iter()
+                                .filter(|(_, reachable)| !reachable)
+                                .map(|(span, _)| (span, false)),
+                        );
+                    }
+                }
+                pattern => {
+                    if !usefulness.is_useful(&rows, std::slice::from_ref(pattern), &tys) {
+                        unreachable.push((arm.span, true));
+                    }
+                }
+            }
+
+            // A guard could be false, so the arms after it still get the values it matches
+            if !arm.guarded {
+                rows.push(vec![arm.pattern.clone()]);
+            }
+        }
+
+        let missing = usefulness
+            .missing(&rows, &tys)
+            .iter()
+            .map(|witness| format!("`{}`", usefulness.display(&witness[0], &ty)))
+            .collect::<Vec<_>>();
+
+        for (span, arm) in unreachable {
+            let (message, label) = match arm {
+                true => ("Unreachable match arm", "this arm never matches"),
+                false => ("Unreachable pattern", "this alternative never matches"),
+            };
+            self.error(
+                Diagnostic::error(message)
+                    .with_code(codes::E0329)
+                    .with_label(span, label)
+                    .with_note("the arms before it already match every value it does"),
+            );
+        }
+
+        if !missing.is_empty() {
+            let patterns = match missing.len() {
+                1 => format!("pattern {}", missing[0]),
+                2..=3 => format!(
+                    "patterns {} and {}",
+                    missing[..missing.len() - 1].join(", "),
+                    missing[missing.len() - 1]
+                ),
+                n => format!("patterns {} and {} more", missing[..3].join(", "), n - 3),
+            };
+            let mut diagnostic = Diagnostic::error("Non-exhaustive match")
+                .with_code(codes::E0328)
+                .with_label(check.span, format!("{} not covered", patterns))
+                .with_help("add arms for them, or a `_` arm for everything else");
+            if check.arms.iter().any(|arm| arm.guarded) {
+                diagnostic = diagnostic
+                    .with_note("arms with an `if` guard don't count, since it could be false");
+            }
+            self.error(diagnostic);
+        }
+    }
+}
+
+/// The value of a literal pattern or a bound of a range pattern.
+enum PatternValue {
+    /// An int or a char, by its code point.
+    Int(i128),
+    Float(f64),
+    Other(Ctor),
+}
+
+/// The value of a literal in a pattern, `None` for `()` or if the pattern is an error.
+fn pattern_value(expr: &Expr) -> Option<PatternValue> {
+    let (literal, negated) = match &expr.kind {
+        ExprKind::Literal(literal) => (literal, false),
+        ExprKind::Unary(UnaryOp::Neg, operand) => match &operand.kind {
+            ExprKind::Literal(literal) => (literal, true),
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    Some(match literal {
+        Literal::Int { value, .. } => match negated {
+            true => PatternValue::Int(-i128::from(*value)),
+            false => PatternValue::Int(i128::from(*value)),
+        },
+        Literal::Float { value, .. } => match negated {
+            true => PatternValue::Float(-value),
+            false => PatternValue::Float(*value),
+        },
+        Literal::Char(value) => PatternValue::Int(i128::from(u32::from(*value))),
+        Literal::Bool(value) => PatternValue::Other(Ctor::Bool(*value)),
+        Literal::String(value) => PatternValue::Other(Ctor::Opaque(format!("{:?}", value))),
+        Literal::Unit => return None,
+    })
+}
+
+/// The patterns in the payload of a variant pattern.
+fn payload_patterns(payload: &PayloadPattern) -> Vec<&Pattern> {
+    match payload {
+        PayloadPattern::Unit => Vec::new(),
+        PayloadPattern::Tuple(patterns) => patterns.iter().collect(),
+        PayloadPattern::Struct { fields, .. } => {
+            fields.iter().map(|field| &field.pattern).collect()
+        }
+    }
+}
+/// Whether a type is a trait object, or an array of them.
+fn contains_dyn(ty: &Type) -> bool {
+    match ty {
+        Type::Dyn { .. } => true,
+        Type::Array(element) => contains_dyn(element),
+        _ => false,
+    }
+}
+
+fn unary_op_str(op: UnaryOp) -> &'static str {
+    match op {
+        UnaryOp::Neg => "-",
+        UnaryOp::Not => "!",
+        UnaryOp::BitNot => "~",
+    }
+}
+
+fn binary_op_str(op: BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Add => "+",
+        BinaryOp::Sub => "-",
+        BinaryOp::Mul => "*",
+        BinaryOp::Div => "/",
+        BinaryOp::Rem => "%",
+        BinaryOp::And => "&&",
+        BinaryOp::Or => "||",
+        BinaryOp::BitAnd => "&",
+        BinaryOp::BitOr => "|",
+        BinaryOp::BitXor => "^",
+        BinaryOp::Shl => "<<",
+        BinaryOp::Shr => ">>",
+        BinaryOp::Eq => "==",
+        BinaryOp::Ne => "!=",
+        BinaryOp::Lt => "<",
+        BinaryOp::Gt => ">",
+        BinaryOp::Le => "<=",
+        BinaryOp::Ge => ">=",
+    }
+}
+
+/// Counts the `{}` in a format string, skipping the escaped braces `{{` and `}}`.
+fn placeholders(format: &str) -> usize {
+    let mut count = 0;
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+            }
+            ('{', Some('}')) => {
+                chars.next();
+                count += 1;
+            }
+            _ => {}
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cst, diagnostic::ErrorCode, lexer, parser, resolver, source::FileId};
+
+    /// Checks the source, returning the errors from both name resolution and type checking.
+    fn check_source(source: &str) -> (TypeInfo, Vec<Diagnostic>) {
+        cst::assert_agrees(source);
+        let (tokens, errors) = lexer::lex(source, FileId::TEST);
+        assert!(errors.is_empty(), "{:?}", errors);
+        let (ast, errors) = parser::parse(&tokens, FileId::TEST);
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let ast = ast.unwrap();
+        let (names, mut errors) = resolver::resolve(&ast);
+        let (info, type_errors) = check(&ast, &names);
+        errors.extend(type_errors);
+        errors.sort_by_key(|error| error.span().map(|span| span.start));
+        (info, errors)
+    }
+
+    fn codes(source: &str) -> Vec<ErrorCode> {
+        check_source(source)
+            .1
+            .into_iter()
+            .map(|diagnostic| diagnostic.code.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_valid() {
+        let source = r#"
+            fn fib(n: int) -> int {
+                if n < 2 { return n; }
+                fib(n - 1) + fib(n - 2)
+            }
+
+            fn greet(name: string) {
+                println!("Hello, {}!", name);
+            }
+
+            const LIMIT: int = 10;
+            let mut total: float = 0.0;
+            for i in range(0, LIMIT) {
+                total += fib(i) as float / 2.0;
+            }
+
+            let values: [int] = [1, 2, 3];
+            let first = values[0];
+            let found = loop {
+                if first > 0 { break true; }
+                break false;
+            };
+            let kind = match first {
+                0 => "zero",
+                -1 => "minus one",
+                n => fmt("{}", n),
+            };
+            greet(kind + "!");
+            while !found && total > 1.5 { total -= 1.0; }
+            len(values) == 3 && 'a' < 'b'
+        "#;
+
+        let (info, errors) = check_source(source);
+        assert!(errors.is_empty(), "{:#?}", errors);
+        assert!(!info.expr_types.is_empty());
+    }
+
+    #[test]
+    fn test_let_annotations() {
+        assert_eq!(codes("let x: int = 1.5;"), [codes::E0301]);
+        assert_eq!(codes("let x: [int] = [1, 2];"), []);
+        assert_eq!(codes("let x: [string] = [1, 2];"), [codes::E0301]);
+        assert_eq!(codes("let x: i33 = 1;"), [codes::E0302]);
+        assert_eq!(codes("let x = [1, 'a'];"), [codes::E0301]);
+    }
+
+    #[test]
+    fn test_functions() {
+        assert_eq!(codes("fn f(a: int) -> int { a } f(1.0);"), [codes::E0301]);
+        assert_eq!(codes("fn f(a: int) -> int { a } f(1, 2);"), [codes::E0306]);
+        assert_eq!(codes("fn f() -> int { \"no\" }"), [codes::E0301]);
+        assert_eq!(codes("fn f() -> int { return true; }"), [codes::E0301]);
+        assert_eq!(codes("fn f() -> int { }"), [codes::E0301]);
+        assert_eq!(codes("let x = 1; x();"), [codes::E0307]);
+        assert_eq!(codes("let f = len;"), [codes::E0318]);
+        // Functions can't see the locals around them, but can call each other
+        assert_eq!(codes("let x = 1; fn f() -> int { x }"), [codes::E0401]);
+        assert_eq!(codes("fn f() -> int { g() } fn g() -> int { 1 }"), []);
+    }
+
+    #[test]
+    fn test_format_args() {
+        assert_eq!(codes("println!(\"{} {}\", 1, 2); fmt(\"{{}}\");"), []);
+        assert_eq!(codes("let x = 1; println!(\"{} {}\", x);"), [codes::E0331]);
+        assert_eq!(codes("print(\"{}\", 1, 2);"), [codes::E0331]);
+        assert_eq!(codes("format!(\"{{}} {}\");"), [codes::E0331]);
+        assert_eq!(codes("println!();"), [codes::E0306]);
+        assert_eq!(codes("println!(1);"), [codes::E0301]);
+        // Format strings that aren't literals are only checked while the program runs
+        assert_eq!(codes("let f = \"{} {}\"; println!(f, 1);"), []);
+
+        let (_, errors) = check_source("fmt(\"{}\", 1, 2, 3);");
+        assert_eq!(
+            errors[0].message,
+            "The format string has 1 `{}` but 3 arguments were given"
+        );
+        assert_eq!(errors[0].labels.len(), 3);
+    }
+
+    #[test]
+    fn test_operators() {
+        assert_eq!(codes("1 + 1.0;"), [codes::E0303]);
+        assert_eq!(codes("\"a\" + \"b\";"), []);
+        assert_eq!(codes("\"a\" - \"b\";"), [codes::E0303]);
+        assert_eq!(codes("1 && true;"), [codes::E0303]);
+        assert_eq!(codes("-true;"), [codes::E0304]);
+        assert_eq!(codes("!1;"), [codes::E0304]);
+        assert_eq!(codes("let mut x = 1; x += 1.0;"), [codes::E0303]);
+        assert_eq!(codes("1 = 2;"), [codes::E0310]);
+        // Errors in operands don't cascade
+        assert_eq!(codes("(1 + true) * 2 + y;"), [codes::E0303, codes::E0401]);
+    }
+
+    #[test]
+    fn test_conditions() {
+        assert_eq!(codes("if 1 { }"), [codes::E0301]);
+        assert_eq!(codes("while \"yes\" { }"), [codes::E0301]);
+        assert_eq!(
+            codes("let x = if true { 1 } else { \"a\" };"),
+            [codes::E0301]
+        );
+        assert_eq!(codes("if true { 1 }"), [codes::E0301]);
+        assert_eq!(codes("let x: int = if true { 1 } else { return; };"), []);
+    }
+
+    #[test]
+    fn test_casts() {
+        assert_eq!(
+            codes("1 as float; 1.5 as int; true as int; 'a' as int;"),
+            []
+        );
+        assert_eq!(codes("\"1\" as int;"), [codes::E0305]);
+        assert_eq!(codes("1 as bool;"), [codes::E0305]);
+        assert_eq!(codes("let x: int = 1 as float;"), [codes::E0301]);
+    }
+
+    #[test]
+    fn test_numbers() {
+        assert_eq!(
+            codes("let a: u8 = 5; let b = a + 1; let c: i64 = 2; let d: int = c << a;"),
+            []
+        );
+        assert_eq!(codes("let x: f32 = 1.5; let y: f32 = x * 2.0;"), []);
+        assert_eq!(codes("1u8 as f32; 2.5f32 as i16; 300 as u8;"), []);
+        assert_eq!(
+            codes("let a: u8 = 1; let b: u16 = 2; a + b;"),
+            [codes::E0303]
+        );
+        assert_eq!(codes("let a: i32 = 1; let b: int = a;"), [codes::E0301]);
+        assert_eq!(codes("-1u8;"), [codes::E0304]);
+        assert_eq!(codes("~1.0;"), [codes::E0304]);
+
+        // Literals have to fit in the type they're inferred to be
+        assert_eq!(
+            codes("let x: i8 = -128; let y: u64 = 18446744073709551615;"),
+            []
+        );
+        assert_eq!(codes("let x: u8 = 256;"), [codes::E0319]);
+        assert_eq!(codes("let x: i8 = -129;"), [codes::E0319]);
+        assert_eq!(codes("200i8;"), [codes::E0319]);
+        assert_eq!(codes("9223372036854775808;"), [codes::E0319]);
+
+        assert_eq!(
+            codes("wrapping_add(1u8, 2); saturating_mul(3, 4); wrapping_sub(1i16, 2i16);"),
+            []
+        );
+        assert_eq!(codes("wrapping_add(1.0, 2.0);"), [codes::E0301]);
+        assert_eq!(codes("saturating_sub(1u8, 2i8);"), [codes::E0301]);
+
+        // Literals are `int` and `float` unless they're used as another size
+        assert_eq!(
+            codes("let a = 1; let b: int = a; let c = 1.5; let d: float = c;"),
+            []
+        );
+        assert_eq!(codes("let a = 1; let b: u16 = a; let c: f32 = 1.5;"), []);
+        assert_eq!(
+            codes("let a = 1; let b: u16 = a; let c: int = a;"),
+            [codes::E0301]
+        );
+        assert_eq!(codes("let a = 1; let b: f32 = a;"), [codes::E0301]);
+
+        let (_, errors) = check_source("let x: u8 = 256;");
+        assert_eq!(errors[0].message, "Literal out of range for `u8`");
+        assert_eq!(errors[0].notes[0], "`u8` goes from 0 to 255");
+    }
+
+    #[test]
+    fn test_loops() {
+        assert_eq!(codes("break;"), [codes::E0312]);
+        assert_eq!(codes("while true { break 1; }"), [codes::E0313]);
+        assert_eq!(codes("let x: int = loop { break 1; };"), []);
+        assert_eq!(codes("loop { break 1; break \"a\"; }"), [codes::E0301]);
+        assert_eq!(codes("for x in 5 { }"), [codes::E0311]);
+        assert_eq!(codes("loop { fn f() { break; } }"), [codes::E0312]);
+    }
+
+    #[test]
+    fn test_inference() {
+        let (info, errors) = check_source(
+            "let mut x; x = 5; let y = x + 1;
+            let mut values = []; values = [1.5];
+            let double = |n| n * 2; double(4);
+            let add = |a, b| a + b; add(\"a\", \"b\");
+            let apply = |f, v| f(v) && true; apply(|v| v > 1, 2);
+            y",
+        );
+        assert!(errors.is_empty(), "{:#?}", errors);
+        assert_eq!(info.expr(NodeId(0)), None);
+        assert!(info.expr_types.values().all(|ty| !ty.is_error()));
+
+        assert_eq!(codes("let x = []; x[0] + 1;"), []);
+        assert_eq!(
+            codes("let x: [string] = []; let y = x; y[0] - 1;"),
+            [codes::E0303]
+        );
+        assert_eq!(codes("let f = |x| x; f(1); f(true);"), [codes::E0301]);
+        assert_eq!(codes("let mut x; x = 1; x = 'a';"), [codes::E0301]);
+        assert_eq!(codes("let f = |x: int| -> int { x }; f(1);"), []);
+        assert_eq!(codes("let f = |x| { return x; }; let y: int = f(1);"), []);
+    }
+
+    #[test]
+    fn test_annotations_needed() {
+        assert_eq!(codes("let x;"), [codes::E0315]);
+        assert_eq!(codes("let x = [];"), [codes::E0315]);
+        // Only reported once for everything that has the same unknown type
+        assert_eq!(codes("let a = |x, y| x + y;"), [codes::E0315]);
+        assert_eq!(codes("let f = |x| -x;"), [codes::E0315]);
+        assert_eq!(codes("let f = |x| x as int; f(1);"), [codes::E0315]);
+        // Not ambiguous when the use decides the type
+        assert_eq!(codes("let f = |x| !x;"), []);
+        assert_eq!(codes("let f = |x| x + 1;"), []);
+
+        let (_, errors) = check_source("let double = |x| x + x;");
+        assert_eq!(errors[0].span(), Some(FileId::TEST.span(14..15)));
+        assert_eq!(
+            errors[0].labels[0].message.as_deref(),
+            Some("cannot infer the type of `x`")
+        );
+    }
+
+    #[test]
+    fn test_mutability() {
+        assert_eq!(codes("let x = 1; x = 2;"), [codes::E0316]);
+        assert_eq!(codes("let mut x = 1; x = 2; x += 1; x++;"), []);
+        assert_eq!(codes("let x = 1; x <<= 2;"), [codes::E0316]);
+        assert_eq!(codes("let x = 1; x++; x--;"), [codes::E0316, codes::E0316]);
+        assert_eq!(codes("const X = 1; X = 2;"), [codes::E0316]);
+        assert_eq!(codes("fn f() {} f = f;"), [codes::E0316]);
+        assert_eq!(codes("let xs = [1]; xs[0] = 2;"), [codes::E0316]);
+        assert_eq!(codes("fn f(a: int) { a = 1; }"), [codes::E0316]);
+        assert_eq!(codes("fn f(mut a: int) { a = 1; }"), []);
+        assert_eq!(codes("for i in range(0, 2) { i += 1; }"), [codes::E0316]);
+        assert_eq!(codes("let f = |x: int| { x = 1; };"), [codes::E0316]);
+
+        // Immutable variables without a value can be assigned once, but not in a loop
+        assert_eq!(codes("let x; x = 1;"), []);
+        assert_eq!(codes("let x; x = 1; x = 2;"), [codes::E0316]);
+        assert_eq!(codes("let x; loop { x = 1; }"), [codes::E0316]);
+        assert_eq!(codes("let x; x += 1;"), [codes::E0316]);
+
+        let (_, errors) = check_source("let x = 1;\nx = 2;");
+        assert_eq!(errors[0].message, "Cannot assign to immutable variable `x`");
+        assert_eq!(errors[0].span(), Some(FileId::TEST.span(11..12)));
+        assert_eq!(errors[0].labels[1].span, FileId::TEST.span(0..10));
+        assert!(errors[0].help.as_deref().unwrap().contains("mut x"));
+    }
+
+    #[test]
+    fn test_structs() {
+        let point = "struct Point { x: int, y: float, Point(); } ";
+        let check = |source: &str| codes(&format!("{}{}", point, source));
+
+        assert_eq!(check("let p: Point = Point(1, 2.0); p.x + 1;"), []);
+        assert_eq!(check("let p = Point(1, 2.0); p.y + 1;"), [codes::E0303]);
+        assert_eq!(check("Point(1.0, 2.0);"), [codes::E0301]);
+        assert_eq!(check("Point(1);"), [codes::E0306]);
+        assert_eq!(check("let p = Point(1, 2.0); p.z;"), [codes::E0309]);
+        assert_eq!(check("let x: int = Point(1, 2.0);"), [codes::E0301]);
+        assert_eq!(
+            check("fn origin() -> Point { Point(0, 0.0) } origin().x;"),
+            []
+        );
+
+        // Fields are changed through a mutable place, `self` always is one
+        assert_eq!(check("let mut p = Point(1, 2.0); p.x = 2; p.y += 1.0;"), []);
+        assert_eq!(check("let p = Point(1, 2.0); p.x = 2;"), [codes::E0316]);
+        assert_eq!(check("Point = Point;"), [codes::E0316]);
+        assert_eq!(
+            codes("struct C { n: int, C(n: int) { self.n = n * 2; } ~C() { self.n = 0; } }"),
+            []
+        );
+        assert_eq!(
+            codes("struct C { n: int, C() { self.n = true; } }"),
+            [codes::E0301]
+        );
+        assert_eq!(
+            codes("struct C { n: int, C() { self.m = 1; } }"),
+            [codes::E0309]
+        );
+
+        assert_eq!(codes("struct Marker {} Marker();"), [codes::E0320]);
+        assert_eq!(codes("struct Marker {} let m: [Marker] = [];"), []);
+    }
+
+    #[test]
+    fn test_methods() {
+        let point = "
+            struct Point { x: int, y: int, Point(); }
+            impl Point {
+                fn origin() -> Point { Point(0, 0) }
+                fn sum(self) -> int { self.x + self.y }
+                fn scale(mut self, by: int) { self.x *= by; self.y *= by; }
+            }
+        ";
+        let check = |source: &str| codes(&format!("{}{}", point, source));
+
+        assert_eq!(
+            check("let mut p = Point::origin(); p.scale(2); p.sum() + 1;"),
+            []
+        );
+        assert_eq!(check("let f = Point::sum; f(Point(1, 2)) + 1;"), []);
+        assert_eq!(check("Point::sum(Point(1, 2)) + Point(3, 4).sum();"), []);
+        assert_eq!(check("Point::origin().sum() == 1.0;"), [codes::E0303]);
+        assert_eq!(
+            check("let mut p = Point::origin(); p.scale(true);"),
+            [codes::E0301]
+        );
+        assert_eq!(check("Point::origin().sum(1);"), [codes::E0306]);
+
+        // `mut self` needs a place that can be changed, like other `mut` parameters
+        assert_eq!(
+            check("let p = Point::origin(); p.scale(2);"),
+            [codes::E0317]
+        );
+        assert_eq!(check("Point::origin().scale(2);"), [codes::E0317]);
+        assert_eq!(
+            codes("struct P { x: int, P(); } impl P { fn reset(self) { self.x = 0; } }"),
+            [codes::E0316]
+        );
+
+        assert_eq!(check("Point::origin().len();"), [codes::E0322]);
+        assert_eq!(check("Point::new();"), [codes::E0322]);
+        assert_eq!(check("Point::origin().origin();"), [codes::E0322]);
+        assert_eq!(check("Point::origin().sum;"), [codes::E0309]);
+        assert_eq!(check("1.sum();"), [codes::E0322]);
+        assert_eq!(
+            check("impl Point { fn sum(self) -> int { 0 } }"),
+            [codes::E0402]
+        );
+        assert_eq!(check("impl Line {}"), [codes::E0302]);
+        assert_eq!(
+            check("impl int { fn double(self) -> int { self * 2 } }"),
+            [codes::E0321]
+        );
+        assert_eq!(check("let f = |p| p.sum();"), [codes::E0315]);
+
+        // Methods can be used before the `impl`, but not outside of the block it's in
+        assert_eq!(
+            codes("struct P { P(); } P().get(); impl P { fn get(self) {} }"),
+            []
+        );
+        assert_eq!(
+            check(
+                "{ impl Point { fn get(self) {} } Point::origin().get(); } Point::origin().get();"
+            ),
+            [codes::E0322]
+        );
+        assert_eq!(
+            check("{ impl Point { fn get(self) {} } } { impl Point { fn get(self) {} } }"),
+            []
+        );
+
+        let (_, errors) = check_source(&format!("{} Point::origin().origin();", point));
+        assert_eq!(
+            errors[0].help.as_deref(),
+            Some("call it as `Point::origin()`")
+        );
+    }
+
+    #[test]
+    fn test_traits() {
+        let shapes = "
+            trait Shape {
+                fn area(self) -> float;
+                fn describe(self) -> string { \"a shape\" }
+            }
+            struct Circle { r: float, Circle(); }
+            struct Square { side: float, Square(); }
+            struct Point { x: int, Point(); }
+            impl Shape for Circle { fn area(self) -> float { 3.0 * self.r * self.r } }
+            impl Shape for Square {
+                fn area(self) -> float { self.side * self.side }
+                fn describe(self) -> string { \"a square\" }
+            }
+        ";
+        let check = |source: &str| codes(&format!("{}{}", shapes, source));
+
+        assert_eq!(
+            check("let shapes: [dyn Shape] = [Circle(1.0), Square(2.0)]; shapes[0].area() + 1.0;"),
+            []
+        );
+        assert_eq!(
+            check("Circle(1.0).describe() + Circle(1.0).describe();"),
+            []
+        );
+        assert_eq!(check("let s: dyn Shape = Circle(1.0); s.describe();"), []);
+        assert_eq!(check("fn f(s: dyn Shape) {} f(Square(1.0));"), []);
+        assert_eq!(check("let s: dyn Shape = Point(1);"), [codes::E0325]);
+        assert_eq!(
+            check("let s: dyn Shape = Circle(1.0); s.r;"),
+            [codes::E0309]
+        );
+        assert_eq!(
+            check("let s: dyn Shape = Circle(1.0); s.len();"),
+            [codes::E0322]
+        );
+        assert_eq!(check("let s: Shape = Circle(1.0);"), [codes::E0323]);
+        assert_eq!(check("Shape;"), [codes::E0323]);
+        assert_eq!(
+            check("impl Point for Shape {}"),
+            [codes::E0323, codes::E0323]
+        );
+        assert_eq!(check("let s: dyn Point = Point(1);"), [codes::E0323]);
+
+        // Bounds are checked where generic functions are called
+        let largest = "
+            fn largest<T: Shape>(a: T, b: T) -> T { if a.area() > b.area() { a } else { b } }
+        ";
+        assert_eq!(
+            check(&format!("{} largest(Circle(1.0), Circle(2.0)).r;", largest)),
+            []
+        );
+        assert_eq!(
+            check(&format!("{} largest(Point(1), Point(2));", largest)),
+            [codes::E0325]
+        );
+        assert_eq!(
+            check(&format!("{} largest(Circle(1.0), Square(2.0));", largest)),
+            [codes::E0301]
+        );
+        assert_eq!(check("fn f<T>(x: T) -> float { x.area() }"), [codes::E0322]);
+        assert_eq!(check("fn f<T>(x: T) -> int { x + 1 }"), [codes::E0303]);
+        assert_eq!(check("fn f<T: Point>(x: T) {}"), [codes::E0323]);
+
+        // Every required method is implemented, with the signature from the trait
+        assert_eq!(check("impl Shape for Point {}"), [codes::E0324]);
+        assert_eq!(
+            check("impl Shape for Point { fn area(self) -> int { 0 } }"),
+            [codes::E0324]
+        );
+        assert_eq!(
+            check("impl Shape for Point { fn area(self) -> float { 0.0 } fn x(self) {} }"),
+            [codes::E0324]
+        );
+        assert_eq!(
+            check("impl Shape for Point { fn area(self) -> float { 0.0 } }"),
+            []
+        );
+        assert_eq!(
+            check("impl Shape for Circle { fn area(self) -> float { 0.0 } }"),
+            [codes::E0402]
+        );
+
+        let (_, errors) = check_source(&format!("{} let s: dyn Shape = Point(1);", shapes));
+        assert_eq!(
+            errors[0].help.as_deref(),
+            Some("implement it with `impl Shape for Point { ... }`")
+        );
+        let (_, errors) = check_source(&format!("{} impl Shape for Point {{}}", shapes));
+        assert_eq!(
+            errors[0].labels[0].message.as_deref(),
+            Some("missing `area`")
+        );
+    }
+
+    #[test]
+    fn test_enums() {
+        let shape = "
+            enum Shape { Circle(float), Rect { w: float, h: float }, Empty }
+        ";
+        let check = |source: &str| codes(&format!("{}{}", shape, source));
+
+        assert_eq!(
+            check("let s = Shape::Circle(1.0); let t: Shape = Shape::Empty; s == t;"),
+            []
+        );
+        assert_eq!(check("let r = Shape::Rect(1.0, 2.0);"), []);
+        assert_eq!(check("Shape::Circle(1);"), [codes::E0301]);
+        assert_eq!(check("Shape::Circle(1.0, 2.0);"), [codes::E0306]);
+        assert_eq!(check("Shape::Square;"), [codes::E0322]);
+        assert_eq!(check("let s = Shape;"), [codes::E0330]);
+        assert_eq!(check("impl Shape {}"), [codes::E0321]);
+
+        // Variants and the fields of struct variants need different names
+        assert_eq!(codes("enum E { A, A }"), [codes::E0402]);
+        assert_eq!(codes("enum E { A { x: int, x: int } }"), [codes::E0402]);
+        assert_eq!(codes("enum E { A(Missing) }"), [codes::E0302]);
+
+        // Enums can contain themselves
+        assert_eq!(
+            codes("enum List { Cons(int, List), Nil } List::Cons(1, List::Nil);"),
+            []
+        );
+    }
+
+    #[test]
+    fn test_match() {
+        let shape = "
+            enum Shape { Circle(float), Rect { w: float, h: float }, Empty }
+            let s = Shape::Empty;
+        ";
+        let check = |source: &str| codes(&format!("{}{}", shape, source));
+
+        assert_eq!(
+            check(
+                "let area = match s {
+                    Shape::Circle(r) if r > 0.0 => 3.0 * r * r,
+                    Shape::Circle(_) => 0.0,
+                    Shape::Rect { w, h: height } => w * height,
+                    Shape::Empty => 0.0,
+                }; area + 1.0;"
+            ),
+            []
+        );
+        assert_eq!(
+            check("match s { Shape::Circle(x) | Shape::Rect { w: x, .. } => x, _ => 0.0, };"),
+            []
+        );
+        assert_eq!(
+            check("match s { all @ Shape::Circle(_) => all, other => other, };"),
+            []
+        );
+
+        // Patterns have to fit the variant they name
+        assert_eq!(
+            check("match s { Shape::Circle => 1, _ => 2, };"),
+            [codes::E0327]
+        );
+        assert_eq!(
+            check("match s { Shape::Circle(a, b) => 1, _ => 2, };"),
+            [codes::E0327]
+        );
+        assert_eq!(
+            check("match s { Shape::Rect { w } => 1, _ => 2, };"),
+            [codes::E0327]
+        );
+        assert_eq!(
+            check("match s { Shape::Rect { d, .. } => 1, _ => 2, };"),
+            [codes::E0327]
+        );
+        assert_eq!(
+            check("match 1 { Shape::Empty => 1, _ => 2, };"),
+            [codes::E0301]
+        );
+        assert_eq!(
+            check("match s { Shape::Square => 1, _ => 2, };"),
+            [codes::E0322]
+        );
+        assert_eq!(
+            check("match s { Shape::Circle(1) => 1, _ => 2, };"),
+            [codes::E0301]
+        );
+        assert_eq!(check("match s { _ if 1 => 1, _ => 2, };"), [codes::E0301]);
+
+        // Bindings
+        assert_eq!(
+            check("match s { Shape::Rect { w: x, h: x } => x, _ => 0.0, };"),
+            [codes::E0402]
+        );
+        assert_eq!(
+            check("match s { Shape::Circle(r) | Shape::Empty => r, _ => 0.0, };"),
+            [codes::E0403]
+        );
+        assert_eq!(codes("match 1 { x @ 1..=5 => x, y => y + 1, };"), []);
+
+        // Ranges
+        assert_eq!(codes("match 'c' { 'a'..='z' => 1, _ => 2, };"), []);
+        assert_eq!(codes("match 1.5 { 0.0..1.0 => 1, _ => 2, };"), []);
+        assert_eq!(codes("match 1 { 5..=1 => 1, _ => 2, };"), [codes::E0326]);
+        assert_eq!(codes("match 1 { 1..1 => 1, _ => 2, };"), [codes::E0326]);
+        assert_eq!(
+            codes("match \"a\" { \"a\"..=\"b\" => 1, _ => 2, };"),
+            [codes::E0326]
+        );
+        assert_eq!(codes("match 1 { 1..='a' => 1, _ => 2, };"), [codes::E0301]);
+    }
+
+    #[test]
+    fn test_exhaustiveness() {
+        let shape = "
+            enum Shape { Circle(float), Rect { w: float, h: float }, Empty }
+            let s = Shape::Empty;
+        ";
+        let missing = |source: &str| {
+            let (_, errors) = check_source(source);
+            let error = errors
+                .iter()
+                .find(|error| error.code == Some(codes::E0328))
+                .unwrap_or_else(|| panic!("no E0328 in {:?}", errors));
+            error.labels[0].message.clone().unwrap()
+        };
+
+        assert_eq!(
+            missing(&format!("{} match s {{ Shape::Circle(_) => 1, }};", shape)),
+            "patterns `Shape::Rect { .. }` and `Shape::Empty` not covered"
+        );
+        assert_eq!(
+            missing(&format!(
+                "{} match s {{ Shape::Circle(_) => 1, Shape::Empty => 2, Shape::Rect {{ w: 1.0, .. }} => 3, }};",
+                shape
+            )),
+            "pattern `Shape::Rect { .. }` not covered"
+        );
+        assert_eq!(
+            missing("match true { true => 1, };"),
+            "pattern `false` not covered"
+        );
+        assert_eq!(
+            missing("match 1u8 { 0..=9 => 1, 20..=255 => 2, };"),
+            "pattern `10..=19` not covered"
+        );
+        assert_eq!(
+            missing("match 1 { 0 => 1, };"),
+            "patterns `-9223372036854775808..=-1` and `1..=9223372036854775807` not covered"
+        );
+        assert_eq!(
+            missing("match 'b' { 'a' => 1, 'c'..='z' => 2, };"),
+            "patterns `'\\0'..='`'`, `'b'`, `'{'..='\\u{d7ff}'` and 1 more not covered"
+        );
+        assert_eq!(
+            missing("match \"x\" { \"a\" => 1, };"),
+            "pattern `_` not covered"
+        );
+        assert_eq!(
+            missing("enum D { N, E, S, W } match D::N { D::N => 1, };"),
+            "patterns `D::E`, `D::S` and `D::W` not covered"
+        );
+        assert_eq!(
+            missing("enum D { A, B, C, D, E } match D::A { D::A => 1, };"),
+            "patterns `D::B`, `D::C`, `D::D` and 1 more not covered"
+        );
+        assert_eq!(
+            missing("match true { b if b => 1, false => 2, };"),
+            "pattern `true` not covered"
+        );
+        assert_eq!(
+            missing(
+                "enum O { Some(bool), None } match O::None { O::Some(true) => 1, O::None => 2, };"
+            ),
+            "pattern `O::Some(false)` not covered"
+        );
+
+        // Every value is covered
+        assert_eq!(codes("match true { true => 1, false => 2, };"), []);
+        assert_eq!(codes("match 1u8 { 0..=127 => 1, 128..=255 => 2, };"), []);
+        assert_eq!(codes("match 1i8 { -128..0 => 1, 0 | 1..=127 => 2, };"), []);
+        assert_eq!(
+            codes(&format!(
+                "{} match s {{ Shape::Circle(_) | Shape::Empty => 1, Shape::Rect {{ .. }} => 2, }};",
+                shape
+            )),
+            []
+        );
+        assert_eq!(
+            codes("enum O { Some(bool), None } match O::None { O::Some(true) => 1, O::Some(false) | O::None => 2, };"),
+            []
+        );
+
+        // Arms and alternatives that can never match
+        assert_eq!(codes("match 1 { _ => 1, 0 => 2, };"), [codes::E0329]);
+        assert_eq!(
+            codes("match 1u8 { 0..=200 => 1, 100..=150 => 2, _ => 3, };"),
+            [codes::E0329]
+        );
+        assert_eq!(
+            codes("match 1 { 1 | 2 | 1 => 1, _ => 2, };"),
+            [codes::E0329]
+        );
+        assert_eq!(
+            codes("match true { true => 1, false => 2, _ => 3, };"),
+            [codes::E0329]
+        );
+        assert_eq!(
+            codes("match 1 { x if x > 1 => 1, x if x > 1 => 2, _ => 3, };"),
+            []
+        );
+
+        // Patterns with errors aren't checked for exhaustiveness
+        assert_eq!(codes("match 1 { 5..=1 => 1, };"), [codes::E0326]);
+    }
+
+    #[test]
+    fn test_mut_params() {
+        let reset = "fn reset(mut values: [int]) { values[0] = 0; }";
+        assert_eq!(codes(&format!("{} let mut v = [1]; reset(v);", reset)), []);
+        assert_eq!(
+            codes(&format!("{} let v = [1]; reset(v);", reset)),
+            [codes::E0317]
+        );
+        assert_eq!(codes(&format!("{} reset([1]);", reset)), [codes::E0317]);
+        assert_eq!(
+            codes(&format!("{} let mut v = [[1]]; reset(v[0]);", reset)),
+            []
+        );
+        assert_eq!(
+            codes(&format!("{} fn f(vs: [int]) {{ reset(vs); }}", reset)),
+            [codes::E0317]
+        );
+        assert_eq!(
+            codes("let f = |mut x: int| x; let mut y = 1; f(y); f(1);"),
+            [codes::E0317]
+        );
+    }
+
+    #[test]
+    fn test_spans() {
+        let (_, errors) = check_source("let x: int = \"a\";");
+        assert_eq!(errors[0].span(), Some(FileId::TEST.span(13..16)));
+        assert_eq!(
+            errors[0].labels[0].message.as_deref(),
+            Some("expected `i64`, found `string`")
+        );
+    }
+}
@@ -0,0 +1,329 @@
+//! Checks that the arms of a `match` cover every value it could be given, and that each arm can
+//! match something the arms before it don't.
+//!
+//! Patterns are lowered to constructors applied to the patterns of their fields, and whether a
+//! pattern matches a value that none of the rows before it do is decided with the usefulness
+//! algorithm from "Warnings for pattern matching" (Maranget, 2007). Int and char patterns are
+//! ranges, which are split at the bounds of the other ranges in their column, so that every part
+//! is either inside of a pattern's range or outside of it.
+
+use std::{collections::HashMap, fmt::Write};
+
+use crate::parser::ast::NodeId;
+
+use super::{EnumInfo, Payload, Type};
+
+/// A pattern as far as exhaustiveness is concerned, bindings are wildcards or the pattern after
+/// their `@`.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Pat {
+    Wild,
+    Ctor(Ctor, Vec<Pat>),
+    Or(Vec<Pat>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Ctor {
+    /// A variant of an enum, by its index. Its fields are the patterns of its payload.
+    Variant(usize),
+    Bool(bool),
+    /// The ints or chars from the first to the second, inclusive. Chars are their code points.
+    Range(i128, i128),
+    /// A value of a type that has too many to list, like a float or a string, written out.
+    Opaque(String),
+}
+
+/// The values of a type, by the constructors that make them.
+enum Signature {
+    Finite(Vec<Ctor>),
+    /// Ints and chars, made of the disjoint ranges.
+    Ranges(Vec<(i128, i128)>),
+    /// There are always values that no list of constructors covers.
+    Infinite,
+}
+
+/// The chars, which skip the surrogate code points.
+const CHARS: [(i128, i128); 2] = [(0, 0xD7FF), (0xE000, 0x10FFFF)];
+
+type Row = Vec<Pat>;
+
+/// Finds the useful and missing patterns, the enums tell it what their variants are.
+pub(super) struct Usefulness<'a> {
+    enums: &'a HashMap<NodeId, EnumInfo>,
+}
+
+impl<'a> Usefulness<'a> {
+    pub(super) fn new(enums: &'a HashMap<NodeId, EnumInfo>) -> Self {
+        Self { enums }
+    }
+
+    fn signature(&self, ty: &Type) -> Signature {
+        match ty {
+            Type::Bool => Signature::Finite(vec![Ctor::Bool(false), Ctor::Bool(true)]),
+            Type::Enum { id, .. } => {
+                let variants = self.enums[id].variants.len();
+                Signature::Finite((0..variants).map(Ctor::Variant).collect())
+            }
+            // A value of `!` is never made, so there's nothing to cover
+            Type::Never => Signature::Finite(Vec::new()),
+            Type::Int(ty) => Signature::Ranges(vec![(ty.min(), ty.max())]),
+            Type::Char => Signature::Ranges(CHARS.to_vec()),
+            _ => Signature::Infinite,
+        }
+    }
+
+    /// The types of the fields of a constructor of `ty`.
+    fn fields(&self, ty: &Type, ctor: &Ctor) -> Vec<Type> {
+        match (ty, ctor) {
+            (Type::Enum { id, .. }, Ctor::Variant(index)) => {
+                self.enums[id].variants[*index].fields.clone()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Whether there's a value that `row` matches and none of `rows` do. All of them match the
+    /// values of `tys`.
+    pub(super) fn is_useful(&self, rows: &[Row], row: &[Pat], tys: &[Type]) -> bool {
+        let Some(head) = row.first() else {
+            return rows.is_empty();
+        };
+        let rows = expand_or(rows);
+        let ty = &tys[0];
+
+        match head {
+            Pat::Or(alternatives) => alternatives.iter().any(|alternative| {
+                let mut row = row.to_vec();
+                row[0] = alternative.clone();
+                self.is_useful(&rows, &row, tys)
+            }),
+            Pat::Ctor(Ctor::Range(lo, hi), _) => {
+                // Each part of the range is either covered by a row's range or by none of them
+                split(&[(*lo, *hi)], heads(&rows))
+                    .into_iter()
+                    .any(|(lo, hi)| self.is_useful_ctor(&rows, &Ctor::Range(lo, hi), row, tys))
+            }
+            Pat::Ctor(ctor, _) => self.is_useful_ctor(&rows, ctor, row, tys),
+            Pat::Wild => match self.split_signature(ty, &rows) {
+                Some(ctors) if ctors.iter().all(|ctor| covered(ctor, &rows)) => ctors
+                    .iter()
+                    .any(|ctor| self.is_useful_ctor(&rows, ctor, row, tys)),
+                // The wildcard matches the missing constructors, so only the rows that also do
+                // matter
+                _ => self.is_useful(&default(&rows), &row[1..], &tys[1..]),
+            },
+        }
+    }
+
+    fn is_useful_ctor(&self, rows: &[Row], ctor: &Ctor, row: &[Pat], tys: &[Type]) -> bool {
+        let fields = self.fields(&tys[0], ctor);
+        let rows = specialize(rows, ctor, fields.len());
+        let row = specialize_row(row, ctor, fields.len()).expect("the row has the constructor");
+        self.is_useful(&rows, &row, &[fields, tys[1..].to_vec()].concat())
+    }
+
+    /// The values of `tys` that none of the rows match, as patterns.
+    pub(super) fn missing(&self, rows: &[Row], tys: &[Type]) -> Vec<Row> {
+        let Some(ty) = tys.first() else {
+            return match rows.is_empty() {
+                true => vec![Vec::new()],
+                false => Vec::new(),
+            };
+        };
+        let rows = expand_or(rows);
+
+        let Some(ctors) = self.split_signature(ty, &rows) else {
+            return self
+                .missing(&default(&rows), &tys[1..])
+                .into_iter()
+                .map(|witness| [vec![Pat::Wild], witness].concat())
+                .collect();
+        };
+
+        let (present, absent) = ctors
+            .into_iter()
+            .partition::<Vec<_>, _>(|ctor| covered(ctor, &rows));
+        if absent.is_empty() {
+            let mut witnesses = Vec::new();
+            for ctor in present {
+                let fields = self.fields(ty, &ctor);
+                let rows = specialize(&rows, &ctor, fields.len());
+                let tys = [fields.clone(), tys[1..].to_vec()].concat();
+                for mut witness in self.missing(&rows, &tys) {
+                    let rest = witness.split_off(fields.len());
+                    witnesses.push([vec![Pat::Ctor(ctor.clone(), witness)], rest].concat());
+                }
+            }
+            return witnesses;
+        }
+
+        // Every missing constructor is its own witness, with anything for its fields
+        let mut witnesses = Vec::new();
+        for witness in self.missing(&default(&rows), &tys[1..]) {
+            for ctor in &absent {
+                let fields = vec![Pat::Wild; self.fields(ty, ctor).len()];
+                let head = Pat::Ctor(ctor.clone(), fields);
+                witnesses.push([vec![head], witness.clone()].concat());
+            }
+        }
+        witnesses
+    }
+
+    /// The constructors of a type, with its ranges split at the bounds of the rows' ranges.
+    /// `None` if the type has too many values to list.
+    fn split_signature(&self, ty: &Type, rows: &[Row]) -> Option<Vec<Ctor>> {
+        match self.signature(ty) {
+            Signature::Finite(ctors) => Some(ctors),
+            Signature::Ranges(ranges) => Some(
+                split(&ranges, heads(rows))
+                    .into_iter()
+                    .map(|(lo, hi)| Ctor::Range(lo, hi))
+                    .collect(),
+            ),
+            Signature::Infinite => None,
+        }
+    }
+
+    /// Writes a pattern of type `ty` like it would be written in the program.
+    pub(super) fn display(&self, pat: &Pat, ty: &Type) -> String {
+        match (pat, ty) {
+            (Pat::Ctor(Ctor::Variant(index), fields), Type::Enum { id, name }) => {
+                let variant = &self.enums[id].variants[*index];
+                let mut out = format!("{}::{}", name, variant.name);
+                let shown = fields
+                    .iter()
+                    .zip(&variant.fields)
+                    .map(|(field, ty)| self.display(field, ty));
+                match &variant.payload {
+                    Payload::Unit => {}
+                    Payload::Tuple => {
+                        let _ = write!(out, "({})", shown.collect::<Vec<_>>().join(", "));
+                    }
+                    Payload::Struct(names) => {
+                        let mut parts = names
+                            .iter()
+                            .zip(shown)
+                            .filter(|(_, shown)| shown != "_")
+                            .map(|(name, shown)| format!("{}: {}", name, shown))
+                            .collect::<Vec<_>>();
+                        if parts.len() < names.len() {
+                            parts.push("..".into());
+                        }
+                        let _ = write!(out, " {{ {} }}", parts.join(", "));
+                    }
+                }
+                out
+            }
+            (Pat::Ctor(Ctor::Bool(value), _), _) => value.to_string(),
+            (Pat::Ctor(Ctor::Range(lo, hi), _), Type::Char) => {
+                let char = |code| format!("{:?}", char::from_u32(code as u32).unwrap_or('?'));
+                match lo == hi {
+                    true => char(*lo),
+                    false => format!("{}..={}", char(*lo), char(*hi)),
+                }
+            }
+            (Pat::Ctor(Ctor::Range(lo, hi), _), ty) => match ty {
+                Type::Int(int) if *lo == int.min() && *hi == int.max() => "_".into(),
+                _ if lo == hi => lo.to_string(),
+                _ => format!("{}..={}", lo, hi),
+            },
+            (Pat::Ctor(Ctor::Opaque(value), _), _) => value.clone(),
+            (Pat::Or(alternatives), _) => alternatives
+                .iter()
+                .map(|alternative| self.display(alternative, ty))
+                .collect::<Vec<_>>()
+                .join(" | "),
+            _ => "_".into(),
+        }
+    }
+}
+
+/// Replaces the rows that start with an or-pattern with a row for each alternative.
+fn expand_or(rows: &[Row]) -> Vec<Row> {
+    let mut expanded = Vec::new();
+    for row in rows {
+        match row.first() {
+            Some(Pat::Or(alternatives)) => {
+                let alternatives = alternatives
+                    .iter()
+                    .map(|alternative| [vec![alternative.clone()], row[1..].to_vec()].concat())
+                    .collect::<Vec<_>>();
+                expanded.extend(expand_or(&alternatives));
+            }
+            _ => expanded.push(row.clone()),
+        }
+    }
+    expanded
+}
+
+/// The ranges at the start of the rows.
+fn heads(rows: &[Row]) -> impl Iterator<Item = (i128, i128)> + '_ {
+    rows.iter().filter_map(|row| match row.first() {
+        Some(Pat::Ctor(Ctor::Range(lo, hi), _)) => Some((*lo, *hi)),
+        _ => None,
+    })
+}
+
+/// Splits the ranges at the bounds of the other ranges, so that each part is either inside or
+/// outside of every one of them.
+fn split(ranges: &[(i128, i128)], others: impl Iterator<Item = (i128, i128)>) -> Vec<(i128, i128)> {
+    // The first value of each part
+    let mut starts = others.flat_map(|(lo, hi)| [lo, hi + 1]).collect::<Vec<_>>();
+    starts.sort_unstable();
+    starts.dedup();
+
+    let mut parts = Vec::new();
+    for &(lo, hi) in ranges {
+        let mut start = lo;
+        for &next in &starts {
+            if next > start && next <= hi {
+                parts.push((start, next - 1));
+                start = next;
+            }
+        }
+        parts.push((start, hi));
+    }
+    parts
+}
+
+/// Whether a constructor is covered by one of the constructors at the start of the rows. Ranges
+/// have already been split, so they're either inside or outside of the rows' ranges.
+fn covered(ctor: &Ctor, rows: &[Row]) -> bool {
+    rows.iter().any(|row| match row.first() {
+        Some(Pat::Ctor(head, _)) => covers(head, ctor),
+        _ => false,
+    })
+}
+
+/// Whether the values of `ctor` are values of `outer`.
+fn covers(outer: &Ctor, ctor: &Ctor) -> bool {
+    match (outer, ctor) {
+        (Ctor::Range(lo, hi), Ctor::Range(start, end)) => lo <= start && end <= hi,
+        _ => outer == ctor,
+    }
+}
+
+/// The rows for the values made by a constructor, with the fields of the constructor in place
+/// of their first pattern. Rows that can't match those values are left out.
+fn specialize(rows: &[Row], ctor: &Ctor, arity: usize) -> Vec<Row> {
+    rows.iter()
+        .filter_map(|row| specialize_row(row, ctor, arity))
+        .collect()
+}
+
+fn specialize_row(row: &[Pat], ctor: &Ctor, arity: usize) -> Option<Row> {
+    let fields = match &row[0] {
+        Pat::Wild => vec![Pat::Wild; arity],
+        Pat::Ctor(head, fields) if covers(head, ctor) => fields.clone(),
+        _ => return None,
+    };
+    Some([fields, row[1..].to_vec()].concat())
+}
+
+/// The rows that match any value of the first column, without it.
+fn default(rows: &[Row]) -> Vec<Row> {
+    rows.iter()
+        .filter(|row| row[0] == Pat::Wild)
+        .map(|row| row[1..].to_vec())
+        .collect()
+}
@@ -0,0 +1,319 @@
+use std::{collections::HashMap, fmt};
+
+use smol_str::SmolStr;
+
+use crate::{parser::ast::NodeId, token::NumberSuffix};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Int(IntTy),
+    Float(FloatTy),
+    Bool,
+    Char,
+    String,
+    /// `()`
+    Unit,
+    /// `[T]`
+    Array(Box<Type>),
+    /// What `range(start, end)` returns, iterating over it gives ints.
+    Range,
+    Fn(FnType),
+    /// A struct declared in the program. Structs are told apart by their item, since they can
+    /// have the same name in different scopes.
+    Struct {
+        id: NodeId,
+        name: SmolStr,
+    },
+    /// An enum declared in the program, told apart by its item like structs.
+    Enum {
+        id: NodeId,
+        name: SmolStr,
+    },
+    /// `dyn Trait`, a value of any struct that implements the trait. Its methods are found when
+    /// they're called.
+    Dyn {
+        id: NodeId,
+        name: SmolStr,
+    },
+    /// A type parameter of a generic function. Inside the function, all that's known about it is
+    /// that it implements the traits of its bounds.
+    Param {
+        id: NodeId,
+        name: SmolStr,
+    },
+    /// A type that hasn't been inferred yet.
+    Var(TypeVar),
+    /// The type of expressions that never finish, like `return` or `break`. It fits anywhere.
+    Never,
+    /// The type of something that already had an error reported, it fits anywhere so that one
+    /// mistake doesn't cause a chain of errors.
+    Error,
+}
+
+/// The sizes of ints. `int` is another name for `i64`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IntTy {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+}
+
+/// The sizes of floats. `float` is another name for `f64`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FloatTy {
+    F32,
+    F64,
+}
+
+/// An unknown type that gets filled in by inference, see [`InferenceTable`].
+///
+/// [`InferenceTable`]: super::infer::InferenceTable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TypeVar {
+    pub(super) index: u32,
+    pub(super) kind: VarKind,
+}
+
+/// What a type variable can turn out to be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VarKind {
+    Any,
+    /// The type of an int literal without a suffix, which is `int` unless it's used as another
+    /// kind of int.
+    Int,
+    /// Like [`VarKind::Int`], for float literals and `float`.
+    Float,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FnType {
+    pub params: Vec<FnParam>,
+    pub ret: Box<Type>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FnParam {
+    pub ty: Type,
+    /// `mut` parameters change the value they're passed, so they need a mutable place.
+    pub mutable: bool,
+}
+
+impl FnParam {
+    pub fn new(ty: Type) -> Self {
+        Self { ty, mutable: false }
+    }
+}
+
+impl IntTy {
+    pub fn name(self) -> &'static str {
+        match self {
+            IntTy::I8 => "i8",
+            IntTy::I16 => "i16",
+            IntTy::I32 => "i32",
+            IntTy::I64 => "i64",
+            IntTy::U8 => "u8",
+            IntTy::U16 => "u16",
+            IntTy::U32 => "u32",
+            IntTy::U64 => "u64",
+        }
+    }
+
+    pub fn is_signed(self) -> bool {
+        matches!(self, IntTy::I8 | IntTy::I16 | IntTy::I32 | IntTy::I64)
+    }
+
+    pub fn bits(self) -> u32 {
+        match self {
+            IntTy::I8 | IntTy::U8 => 8,
+            IntTy::I16 | IntTy::U16 => 16,
+            IntTy::I32 | IntTy::U32 => 32,
+            IntTy::I64 | IntTy::U64 => 64,
+        }
+    }
+
+    pub fn min(self) -> i128 {
+        if self.is_signed() {
+            -(1 << (self.bits() - 1))
+        } else {
+            0
+        }
+    }
+
+    pub fn max(self) -> i128 {
+        if self.is_signed() {
+            (1 << (self.bits() - 1)) - 1
+        } else {
+            (1 << self.bits()) - 1
+        }
+    }
+}
+
+impl FloatTy {
+    pub fn name(self) -> &'static str {
+        match self {
+            FloatTy::F32 => "f32",
+            FloatTy::F64 => "f64",
+        }
+    }
+}
+
+impl Type {
+    /// `int`, which is `i64`.
+    pub const INT: Type = Type::Int(IntTy::I64);
+    /// `float`, which is `f64`.
+    pub const FLOAT: Type = Type::Float(FloatTy::F64);
+
+    /// The types that are always in scope, by name.
+    pub fn primitive(name: &str) -> Option<Type> {
+        Some(match name {
+            "int" | "i64" => Type::INT,
+            "i8" => Type::Int(IntTy::I8),
+            "i16" => Type::Int(IntTy::I16),
+            "i32" => Type::Int(IntTy::I32),
+            "u8" => Type::Int(IntTy::U8),
+            "u16" => Type::Int(IntTy::U16),
+            "u32" => Type::Int(IntTy::U32),
+            "u64" => Type::Int(IntTy::U64),
+            "float" | "f64" => Type::FLOAT,
+            "f32" => Type::Float(FloatTy::F32),
+            "bool" => Type::Bool,
+            "char" => Type::Char,
+            "string" | "str" => Type::String,
+            _ => return None,
+        })
+    }
+
+    /// Whether the type is an int, or an int literal whose size isn't known yet.
+    pub fn is_int(&self) -> bool {
+        matches!(self, Type::Int(_)) || matches!(self, Type::Var(var) if var.kind == VarKind::Int)
+    }
+
+    /// Whether the type is a number, or a number literal whose size isn't known yet.
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, Type::Int(_) | Type::Float(_))
+            || matches!(self, Type::Var(var) if var.kind != VarKind::Any)
+    }
+
+    /// A type variable that could be anything, which needs more information to be inferred.
+    pub fn is_unknown(&self) -> bool {
+        matches!(self, Type::Var(var) if var.kind == VarKind::Any)
+    }
+
+    pub fn is_error(&self) -> bool {
+        matches!(self, Type::Error)
+    }
+
+    /// The type parameters in the type, each one once, in the order they first appear.
+    pub fn type_params(&self) -> Vec<(NodeId, SmolStr)> {
+        fn collect(ty: &Type, params: &mut Vec<(NodeId, SmolStr)>) {
+            match ty {
+                Type::Param { id, name } if !params.iter().any(|(param, _)| param == id) => {
+                    params.push((*id, name.clone()));
+                }
+                Type::Array(element) => collect(element, params),
+                Type::Fn(FnType {
+                    params: fn_params,
+                    ret,
+                }) => {
+                    for param in fn_params {
+                        collect(&param.ty, params);
+                    }
+                    collect(ret, params);
+                }
+                _ => {}
+            }
+        }
+
+        let mut params = Vec::new();
+        collect(self, &mut params);
+        params
+    }
+
+    /// Replaces the type parameters in the type with the types they're mapped to.
+    pub fn substitute(&self, types: &HashMap<NodeId, Type>) -> Type {
+        match self {
+            Type::Param { id, .. } => types.get(id).cloned().unwrap_or_else(|| self.clone()),
+            Type::Array(element) => Type::Array(Box::new(element.substitute(types))),
+            Type::Fn(ty) => Type::Fn(ty.substitute(types)),
+            _ => self.clone(),
+        }
+    }
+}
+
+impl FnType {
+    /// Like [`Type::substitute`], for the types of the parameters and the return type.
+    pub fn substitute(&self, types: &HashMap<NodeId, Type>) -> FnType {
+        FnType {
+            params: self
+                .params
+                .iter()
+                .map(|param| FnParam {
+                    ty: param.ty.substitute(types),
+                    mutable: param.mutable,
+                })
+                .collect(),
+            ret: Box::new(self.ret.substitute(types)),
+        }
+    }
+}
+
+impl From<NumberSuffix> for Type {
+    fn from(suffix: NumberSuffix) -> Self {
+        match suffix {
+            NumberSuffix::I8 => Type::Int(IntTy::I8),
+            NumberSuffix::I16 => Type::Int(IntTy::I16),
+            NumberSuffix::I32 => Type::Int(IntTy::I32),
+            NumberSuffix::I64 => Type::Int(IntTy::I64),
+            NumberSuffix::U8 => Type::Int(IntTy::U8),
+            NumberSuffix::U16 => Type::Int(IntTy::U16),
+            NumberSuffix::U32 => Type::Int(IntTy::U32),
+            NumberSuffix::U64 => Type::Int(IntTy::U64),
+            NumberSuffix::F32 => Type::Float(FloatTy::F32),
+            NumberSuffix::F64 => Type::Float(FloatTy::F64),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Int(ty) => write!(f, "{}", ty.name()),
+            Type::Float(ty) => write!(f, "{}", ty.name()),
+            Type::Bool => write!(f, "bool"),
+            Type::Char => write!(f, "char"),
+            Type::String => write!(f, "string"),
+            Type::Unit => write!(f, "()"),
+            Type::Array(element) => write!(f, "[{}]", element),
+            Type::Range => write!(f, "range"),
+            Type::Fn(FnType { params, ret }) => {
+                write!(f, "fn(")?;
+                for (i, param) in params.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    if param.mutable {
+                        write!(f, "mut ")?;
+                    }
+                    write!(f, "{}", param.ty)?;
+                }
+                write!(f, ") -> {}", ret)
+            }
+            Type::Struct { name, .. } | Type::Enum { name, .. } | Type::Param { name, .. } => {
+                write!(f, "{}", name)
+            }
+            Type::Dyn { name, .. } => write!(f, "dyn {}", name),
+            Type::Var(var) => match var.kind {
+                VarKind::Any => write!(f, "_"),
+                VarKind::Int => write!(f, "{{integer}}"),
+                VarKind::Float => write!(f, "{{float}}"),
+            },
+            Type::Never => write!(f, "!"),
+            Type::Error => write!(f, "{{unknown}}"),
+        }
+    }
+}
@@ -0,0 +1,1945 @@
+//! A lossless concrete syntax tree, for tooling like formatters and refactoring tools that have to
+//! keep the source as it was written.
+//!
+//! The tree is split in two like rowan's. Green nodes are immutable, don't know their position and
+//! are shared between versions of a tree, so an edit only rebuilds the path from the edited node to
+//! the root. Red nodes, [`SyntaxNode`] and [`SyntaxToken`], are created on demand on top of them and
+//! know their parent and where they are in the source.
+//!
+//! The nodes follow the grammar of the [parser], so items, statements and
+//! expressions can be found by their [`SyntaxKind`], and every piece of the source is kept,
+//! whitespace and comments included, so printing a tree reproduces its source byte for byte.
+//! Source that doesn't fit the grammar is kept in [`SyntaxKind::Error`] nodes and an empty one is
+//! left where the grammar needs something that is missing, so a tree without errors is one that
+//! the parser accepts. Reporting the errors is up to the parser.
+
+use std::{fmt, mem, rc::Rc};
+
+use smol_str::SmolStr;
+
+use crate::{
+    lexer::{self, LexError, LosslessToken, Span, Trivia},
+    parser,
+    source::FileId,
+    token::{Delimiter, Keyword, Operator, Punctuation, Token},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SyntaxKind {
+    Whitespace,
+    Comment,
+    /// Characters that the lexer couldn't make sense of.
+    Skipped,
+    /// Trivia as well, the doc comments before an item, method, field or variant are part of it.
+    DocComment,
+    Identifier,
+    Keyword,
+    Literal,
+    Operator,
+    Punctuation,
+    Delimiter,
+
+    /// The whole source.
+    Root,
+    /// Tokens that don't fit the grammar.
+    Error,
+
+    // Items, their outer doc comments and the `;` that can follow them are part of the node
+    /// `fn name<T: Bound>(params) -> Type { .. }`, the body is left out in traits.
+    FnItem,
+    /// `const NAME: Type = value;`
+    ConstItem,
+    /// `struct Name { members }`
+    StructItem,
+    /// `impl Type { methods }` or `impl Trait for Type { methods }`
+    ImplItem,
+    /// `trait Name { methods }`
+    TraitItem,
+    /// `enum Name { variants }`
+    EnumItem,
+
+    /// `<T: A + B, U>`
+    GenericList,
+    Generic,
+    /// The parameters of a function or constructor, with the parentheses around them.
+    ParamList,
+    /// `mut name: Type`
+    Param,
+    /// `self` or `mut self`
+    Receiver,
+    /// `()`, `Name`, `dyn Trait` or `[Type]`, which has another type inside of it.
+    Type,
+    /// The fields, constructors and destructor of a struct, with the braces around them.
+    MemberList,
+    /// `name: Type`, in a struct or a struct variant.
+    StructField,
+    /// `Name(params) { .. }`
+    Constructor,
+    /// `~Name() { .. }`
+    Destructor,
+    /// The functions of an `impl` or `trait`, with the braces around them.
+    MethodList,
+    /// The variants of an enum, with the braces around them.
+    VariantList,
+    Variant,
+    /// `(Type, Type)` after a variant.
+    TupleFields,
+    /// `{ name: Type }` after a variant.
+    StructFields,
+
+    /// `let mut name: Type = value;`
+    LetStmt,
+    /// An expression in a block that isn't its value, with its `;`.
+    ExprStmt,
+    /// `{ statements value }`
+    Block,
+
+    LiteralExpr,
+    IdentExpr,
+    /// `Type::name`
+    PathExpr,
+    /// `(expr)`
+    ParenExpr,
+    ArrayExpr,
+    UnaryExpr,
+    BinaryExpr,
+    /// `target = value`, or with an operator like `+=`.
+    AssignExpr,
+    /// `expr++` or `expr--`
+    StepExpr,
+    /// `expr as Type`
+    CastExpr,
+    CallExpr,
+    /// `name!(args)`
+    MacroExpr,
+    /// The arguments of a call, with the parentheses around them.
+    ArgList,
+    /// `expr[index]`
+    IndexExpr,
+    /// `expr.field`
+    FieldExpr,
+    /// `expr.method(args)`
+    MethodCallExpr,
+    /// `|params| body` or `|params| -> Type { .. }`
+    ClosureExpr,
+    /// The parameters of a closure, with the `|` around them, or `||`.
+    ClosureParamList,
+    /// `mut name: Type`, the type can be left out.
+    ClosureParam,
+    IfExpr,
+    LoopExpr,
+    WhileExpr,
+    ForExpr,
+    MatchExpr,
+    /// The arms of a `match`, with the braces around them.
+    MatchArmList,
+    /// `pattern if guard => body`
+    MatchArm,
+    ReturnExpr,
+    BreakExpr,
+    ContinueExpr,
+
+    /// `_`
+    WildcardPattern,
+    /// A literal, which can be negative.
+    LiteralPattern,
+    /// `start..end` or `start..=end`
+    RangePattern,
+    /// `name` or `name @ pattern`
+    BindingPattern,
+    /// `pattern | pattern`
+    OrPattern,
+    /// `Type::Variant` with an optional payload.
+    VariantPattern,
+    /// `(pattern)`
+    ParenPattern,
+    /// `(patterns)` after a variant.
+    TuplePayload,
+    /// `{ fields, .. }` after a variant.
+    StructPayload,
+    /// `name: pattern`, or `name` on its own to bind the field to a variable of the same name.
+    FieldPattern,
+}
+
+impl SyntaxKind {
+    pub fn is_trivia(self) -> bool {
+        matches!(
+            self,
+            SyntaxKind::Whitespace | SyntaxKind::Comment | SyntaxKind::Skipped
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GreenToken {
+    kind: SyntaxKind,
+    text: SmolStr,
+}
+
+impl GreenToken {
+    pub fn new(kind: SyntaxKind, text: &str) -> Self {
+        Self {
+            kind,
+            text: text.into(),
+        }
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GreenElement {
+    Node(Rc<GreenNode>),
+    Token(Rc<GreenToken>),
+}
+
+impl GreenElement {
+    pub fn kind(&self) -> SyntaxKind {
+        match self {
+            GreenElement::Node(node) => node.kind,
+            GreenElement::Token(token) => token.kind,
+        }
+    }
+
+    pub fn text_len(&self) -> usize {
+        match self {
+            GreenElement::Node(node) => node.text_len,
+            GreenElement::Token(token) => token.text.len(),
+        }
+    }
+}
+
+impl From<GreenNode> for GreenElement {
+    fn from(node: GreenNode) -> Self {
+        GreenElement::Node(Rc::new(node))
+    }
+}
+
+impl From<GreenToken> for GreenElement {
+    fn from(token: GreenToken) -> Self {
+        GreenElement::Token(Rc::new(token))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GreenNode {
+    kind: SyntaxKind,
+    text_len: usize,
+    children: Vec<GreenElement>,
+}
+
+impl GreenNode {
+    pub fn new(kind: SyntaxKind, children: Vec<GreenElement>) -> Self {
+        Self {
+            kind,
+            text_len: children.iter().map(GreenElement::text_len).sum(),
+            children,
+        }
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    pub fn text_len(&self) -> usize {
+        self.text_len
+    }
+
+    pub fn children(&self) -> &[GreenElement] {
+        &self.children
+    }
+
+    /// A copy of this node with one child replaced, the other children are shared.
+    pub fn replace_child(&self, index: usize, child: GreenElement) -> GreenNode {
+        let mut children = self.children.clone();
+        children[index] = child;
+
+        GreenNode::new(self.kind, children)
+    }
+}
+
+impl fmt::Display for GreenNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for child in &self.children {
+            match child {
+                GreenElement::Node(node) => write!(f, "{}", node)?,
+                GreenElement::Token(token) => write!(f, "{}", token.text)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SyntaxNode(Rc<NodeData>);
+
+#[derive(Debug)]
+struct NodeData {
+    green: Rc<GreenNode>,
+    parent: Option<SyntaxNode>,
+    /// The index of this node in its parent's children.
+    index: usize,
+    /// The file that the tree was parsed from, which the spans are in.
+    file: FileId,
+    offset: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct SyntaxToken {
+    green: Rc<GreenToken>,
+    parent: SyntaxNode,
+    /// The index of this token in its parent's children.
+    index: usize,
+    offset: usize,
+}
+
+#[derive(Debug, Clone)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxNode {
+    /// The root of a tree of the source of a file.
+    pub fn new_root(green: GreenNode, file: FileId) -> Self {
+        SyntaxNode(Rc::new(NodeData {
+            green: Rc::new(green),
+            parent: None,
+            index: 0,
+            file,
+            offset: 0,
+        }))
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.0.green.kind
+    }
+
+    pub fn green(&self) -> &GreenNode {
+        &self.0.green
+    }
+
+    pub fn parent(&self) -> Option<&SyntaxNode> {
+        self.0.parent.as_ref()
+    }
+
+    pub fn span(&self) -> Span {
+        self.0
+            .file
+            .span(self.0.offset..self.0.offset + self.0.green.text_len)
+    }
+
+    pub fn children_with_tokens(&self) -> impl Iterator<Item = SyntaxElement> + '_ {
+        let mut offset = self.0.offset;
+
+        self.0
+            .green
+            .children
+            .iter()
+            .enumerate()
+            .map(move |(index, child)| {
+                let child_offset = offset;
+                offset += child.text_len();
+
+                match child {
+                    GreenElement::Node(green) => {
+                        SyntaxElement::Node(SyntaxNode(Rc::new(NodeData {
+                            green: green.clone(),
+                            parent: Some(self.clone()),
+                            index,
+                            file: self.0.file,
+                            offset: child_offset,
+                        })))
+                    }
+                    GreenElement::Token(green) => SyntaxElement::Token(SyntaxToken {
+                        green: green.clone(),
+                        parent: self.clone(),
+                        index,
+                        offset: child_offset,
+                    }),
+                }
+            })
+    }
+
+    pub fn children(&self) -> impl Iterator<Item = SyntaxNode> + '_ {
+        self.children_with_tokens().filter_map(|child| match child {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+
+    /// This node and every node below it, in source order.
+    pub fn descendants(&self) -> Vec<SyntaxNode> {
+        let mut nodes = vec![self.clone()];
+        for child in self.children() {
+            nodes.extend(child.descendants());
+        }
+
+        nodes
+    }
+
+    /// Every token in this node and its descendants, in source order.
+    pub fn tokens(&self) -> Vec<SyntaxToken> {
+        let mut tokens = Vec::new();
+
+        for child in self.children_with_tokens() {
+            match child {
+                SyntaxElement::Node(node) => tokens.extend(node.tokens()),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+
+        tokens
+    }
+
+    /// The token that contains the byte at `offset`.
+    pub fn token_at_offset(&self, offset: usize) -> Option<SyntaxToken> {
+        self.tokens()
+            .into_iter()
+            .find(|token| token.span().start <= offset && offset < token.span().end)
+    }
+
+    /// Replaces this node, returning the root of the new tree. This tree isn't changed.
+    pub fn replace_with(&self, green: GreenNode) -> SyntaxNode {
+        match &self.0.parent {
+            Some(parent) => {
+                parent.replace_with(parent.green().replace_child(self.0.index, green.into()))
+            }
+            None => SyntaxNode::new_root(green, self.0.file),
+        }
+    }
+}
+
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.green)
+    }
+}
+
+impl SyntaxToken {
+    pub fn kind(&self) -> SyntaxKind {
+        self.green.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.green.text
+    }
+
+    pub fn green(&self) -> &GreenToken {
+        &self.green
+    }
+
+    pub fn parent(&self) -> &SyntaxNode {
+        &self.parent
+    }
+
+    pub fn span(&self) -> Span {
+        self.parent
+            .0
+            .file
+            .span(self.offset..self.offset + self.green.text.len())
+    }
+
+    /// Replaces this token, returning the root of the new tree. This tree isn't changed.
+    pub fn replace_with(&self, green: GreenToken) -> SyntaxNode {
+        self.parent
+            .replace_with(self.parent.green().replace_child(self.index, green.into()))
+    }
+}
+
+impl fmt::Display for SyntaxToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.green.text)
+    }
+}
+
+/// Builds the syntax tree of the source of a file, the lexer errors are returned alongside it as
+/// the tree keeps the source that caused them.
+pub fn parse(source: &str, file: FileId) -> (SyntaxNode, Vec<LexError>) {
+    let (tokens, errors) = lexer::lex_lossless(source, file);
+
+    let tokens = tokens
+        .into_iter()
+        .map(|(token, span)| {
+            let text = &source[span.start..span.end];
+
+            match token {
+                LosslessToken::Trivia(trivia) => {
+                    let kind = match trivia {
+                        Trivia::Whitespace => SyntaxKind::Whitespace,
+                        Trivia::Comment => SyntaxKind::Comment,
+                        Trivia::Skipped => SyntaxKind::Skipped,
+                    };
+                    (kind, text, None)
+                }
+                // Like in the parser, doc comments don't change how the tokens around them parse
+                LosslessToken::Token(Token::DocComment(..)) => (SyntaxKind::DocComment, text, None),
+                LosslessToken::Token(token) => {
+                    let kind = match token {
+                        Token::Identifier(_) => SyntaxKind::Identifier,
+                        Token::Literal(_) => SyntaxKind::Literal,
+                        Token::Operator(_) => SyntaxKind::Operator,
+                        Token::Keyword(_) => SyntaxKind::Keyword,
+                        Token::Punctuation(_) => SyntaxKind::Punctuation,
+                        Token::Delimiter(_) => SyntaxKind::Delimiter,
+                        Token::DocComment(..) => unreachable!("doc comments are trivia"),
+                    };
+                    (kind, text, Some(token))
+                }
+            }
+        })
+        .collect();
+
+    let mut parser = Parser::new(tokens);
+    parser.root();
+
+    (SyntaxNode::new_root(parser.finish_root(), file), errors)
+}
+
+fn kw(keyword: Keyword) -> Token<'static> {
+    Token::Keyword(keyword)
+}
+
+fn punct(punct: Punctuation) -> Token<'static> {
+    Token::Punctuation(punct)
+}
+
+fn op(op: Operator) -> Token<'static> {
+    Token::Operator(op)
+}
+
+fn delim(delim: Delimiter) -> Token<'static> {
+    Token::Delimiter(delim)
+}
+
+/// The binary operators, from the lowest precedence to the highest.
+const BINARY_OPERATORS: &[&[Operator]] = &[
+    &[Operator::LogicalOr],
+    &[Operator::LogicalAnd],
+    &[
+        Operator::Equal,
+        Operator::NotEqual,
+        Operator::LessThanOrEqual,
+        Operator::GreaterThanOrEqual,
+        Operator::LessThan,
+        Operator::GreaterThan,
+    ],
+    &[Operator::BitwiseOr],
+    &[Operator::BitwiseXor],
+    &[Operator::BitwiseAnd],
+    &[Operator::ShiftLeft, Operator::ShiftRight],
+    &[Operator::Add, Operator::Subtract],
+    &[Operator::Multiply, Operator::Divide, Operator::Modulo],
+];
+
+fn is_closing(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Delimiter(Delimiter::CloseParen | Delimiter::CloseBrace | Delimiter::CloseBracket)
+    )
+}
+
+/// Expressions that end with a block, which end a statement without a semicolon.
+fn starts_block_like(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Delimiter(Delimiter::OpenBrace)
+            | Token::Keyword(
+                Keyword::If | Keyword::Loop | Keyword::While | Keyword::For | Keyword::Match
+            )
+    )
+}
+
+fn starts_expr(token: &Token) -> bool {
+    starts_block_like(token)
+        || matches!(
+            token,
+            Token::Literal(_)
+                | Token::Identifier(_)
+                | Token::Operator(
+                    Operator::Subtract
+                        | Operator::LogicalNot
+                        | Operator::BitwiseNot
+                        | Operator::BitwiseOr
+                        | Operator::LogicalOr
+                )
+                | Token::Delimiter(Delimiter::OpenParen | Delimiter::OpenBracket)
+                | Token::Keyword(Keyword::Return | Keyword::Break | Keyword::Continue)
+        )
+}
+
+/// Builds the tree while parsing the tokens, following the grammar of the parser in
+/// [`crate::parser`]. It never fails, a token that doesn't fit is put in an [`SyntaxKind::Error`]
+/// node and a missing one is marked by an empty one.
+///
+/// Trivia is added to the node that is being built when the token after it is, so nodes start at
+/// their first token and the trivia between nodes belongs to their parent.
+struct Parser<'a> {
+    tokens: Vec<(SyntaxKind, &'a str, Option<Token<'a>>)>,
+    /// The index of every token that isn't trivia.
+    significant: Vec<usize>,
+    /// The next token in `significant`.
+    pos: usize,
+    /// The next token in `tokens` that hasn't been added to the tree.
+    added: usize,
+    /// The nodes that are still being built, the root is at the bottom.
+    stack: Vec<(SyntaxKind, Vec<GreenElement>)>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(tokens: Vec<(SyntaxKind, &'a str, Option<Token<'a>>)>) -> Self {
+        let significant = tokens
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, _, token))| token.is_some())
+            .map(|(i, _)| i)
+            .collect();
+
+        Self {
+            tokens,
+            significant,
+            pos: 0,
+            added: 0,
+            stack: vec![(SyntaxKind::Root, Vec::new())],
+        }
+    }
+
+    fn finish_root(mut self) -> GreenNode {
+        self.add_tokens(self.tokens.len());
+        let (kind, children) = self.stack.pop().expect("the root is never finished early");
+        assert!(self.stack.is_empty(), "every node should be finished");
+
+        GreenNode::new(kind, children)
+    }
+
+    /// The nth token after the current one, skipping trivia.
+    fn nth(&self, n: usize) -> Option<&Token<'a>> {
+        let &index = self.significant.get(self.pos + n)?;
+        self.tokens[index].2.as_ref()
+    }
+
+    fn peek(&self) -> Option<&Token<'a>> {
+        self.nth(0)
+    }
+
+    fn at(&self, token: Token) -> bool {
+        self.peek() == Some(&token)
+    }
+
+    fn at_ident(&self) -> bool {
+        matches!(self.peek(), Some(Token::Identifier(_)))
+    }
+
+    /// Adds the tokens before `end` that haven't been added to the current node.
+    fn add_tokens(&mut self, end: usize) {
+        let (_, children) = self
+            .stack
+            .last_mut()
+            .expect("the root is never finished early");
+        for (kind, text, _) in &self.tokens[self.added..end] {
+            children.push(GreenToken::new(*kind, text).into());
+        }
+        self.added = end;
+    }
+
+    fn add_trivia(&mut self) {
+        let end = self
+            .significant
+            .get(self.pos)
+            .copied()
+            .unwrap_or(self.tokens.len());
+        self.add_tokens(end);
+    }
+
+    /// Adds the current token to the current node.
+    fn bump(&mut self) {
+        if let Some(&index) = self.significant.get(self.pos) {
+            self.add_tokens(index + 1);
+            self.pos += 1;
+        }
+    }
+
+    fn eat(&mut self, token: Token) -> bool {
+        let at = self.at(token);
+        if at {
+            self.bump();
+        }
+        at
+    }
+
+    /// Marks where a token or node that the grammar needs is missing with an empty error node.
+    fn missing(&mut self) {
+        self.start(SyntaxKind::Error);
+        self.finish();
+    }
+
+    fn expect(&mut self, token: Token) {
+        if !self.eat(token) {
+            self.missing();
+        }
+    }
+
+    fn expect_ident(&mut self) {
+        if self.at_ident() {
+            self.bump();
+        } else {
+            self.missing();
+        }
+    }
+
+    fn start(&mut self, kind: SyntaxKind) {
+        self.add_trivia();
+        self.stack.push((kind, Vec::new()));
+    }
+
+    /// Where a node can be started later with [`Parser::start_at`], when it is only known what it
+    /// is after its first child, like the operands of binary operators.
+    fn checkpoint(&mut self) -> usize {
+        self.add_trivia();
+        self.stack.last().map_or(0, |(_, children)| children.len())
+    }
+
+    /// Like [`Parser::checkpoint`], but the doc comments before the next token are left for the
+    /// node that is started at it.
+    fn doc_checkpoint(&mut self) -> usize {
+        let end = self
+            .significant
+            .get(self.pos)
+            .copied()
+            .unwrap_or(self.tokens.len());
+        let mut start = end;
+        while start > self.added && self.tokens[start - 1].2.is_none() {
+            start -= 1;
+        }
+        while start < end && self.tokens[start].0 != SyntaxKind::DocComment {
+            start += 1;
+        }
+
+        self.add_tokens(start);
+        self.stack.last().map_or(0, |(_, children)| children.len())
+    }
+
+    fn start_at(&mut self, checkpoint: usize, kind: SyntaxKind) {
+        let (_, children) = self
+            .stack
+            .last_mut()
+            .expect("the root is never finished early");
+        let children = children.split_off(checkpoint);
+        self.stack.push((kind, children));
+    }
+
+    fn finish(&mut self) {
+        let (kind, children) = self.stack.pop().expect("a node was started");
+        let node = GreenNode::new(kind, children);
+        let (_, parent) = self
+            .stack
+            .last_mut()
+            .expect("the root is never finished early");
+        parent.push(node.into());
+    }
+
+    fn error(&mut self) {
+        self.start(SyntaxKind::Error);
+        self.bump();
+        self.finish();
+    }
+
+    /// Whether the elements of a group up to `close` end at the current token. A closing delimiter
+    /// of another group or the start of a statement ends it early, they're left to the nodes
+    /// around it.
+    fn at_end(&self, close: &Token) -> bool {
+        self.peek().is_none_or(|token| {
+            token == close || is_closing(token) || parser::starts_statement(token)
+        })
+    }
+
+    /// Parses elements separated by commas up to the `close` token.
+    fn list(&mut self, close: Token, mut element: impl FnMut(&mut Self)) {
+        while !self.at_end(&close) {
+            let pos = self.pos;
+            element(self);
+            if self.pos == pos {
+                self.error();
+            }
+            if !self.eat(punct(Punctuation::Comma)) && !self.at_end(&close) {
+                self.missing();
+            }
+        }
+
+        self.expect(close);
+    }
+
+    fn root(&mut self) {
+        while let Some(token) = self.peek() {
+            if is_closing(token) {
+                self.error();
+            } else {
+                self.block_item();
+            }
+        }
+    }
+
+    fn block(&mut self) {
+        if !self.at(delim(Delimiter::OpenBrace)) {
+            return self.missing();
+        }
+        self.start(SyntaxKind::Block);
+        self.bump();
+
+        while let Some(token) = self.peek() {
+            if *token == delim(Delimiter::CloseBrace) {
+                break;
+            } else if is_closing(token) {
+                self.error();
+            } else {
+                self.block_item();
+            }
+        }
+
+        self.expect(delim(Delimiter::CloseBrace));
+        self.finish();
+    }
+
+    fn block_item(&mut self) {
+        let checkpoint = self.doc_checkpoint();
+
+        let kind = match self.peek() {
+            Some(Token::Keyword(Keyword::Fn)) => SyntaxKind::FnItem,
+            Some(Token::Keyword(Keyword::Const)) => SyntaxKind::ConstItem,
+            Some(Token::Keyword(Keyword::Struct)) => SyntaxKind::StructItem,
+            Some(Token::Keyword(Keyword::Impl)) => SyntaxKind::ImplItem,
+            Some(Token::Keyword(Keyword::Trait)) => SyntaxKind::TraitItem,
+            Some(Token::Keyword(Keyword::Enum)) => SyntaxKind::EnumItem,
+            Some(Token::Keyword(Keyword::Let)) => SyntaxKind::LetStmt,
+            Some(token) if starts_block_like(token) => {
+                // Like in the parser, operators after a block-like expression start a new one
+                self.block_like();
+                return self.expr_stmt(checkpoint, true);
+            }
+            Some(token) if starts_expr(token) => {
+                self.expr();
+                return self.expr_stmt(checkpoint, false);
+            }
+            token => {
+                let closing = token.is_none_or(is_closing);
+                self.start_at(checkpoint, SyntaxKind::Error);
+                if !closing {
+                    self.bump();
+                }
+                return self.finish();
+            }
+        };
+
+        self.start_at(checkpoint, kind);
+        match kind {
+            SyntaxKind::FnItem => self.function_rest(true),
+            SyntaxKind::ConstItem => self.const_rest(),
+            SyntaxKind::StructItem => self.struct_rest(),
+            SyntaxKind::ImplItem => self.impl_rest(),
+            SyntaxKind::TraitItem => self.trait_rest(),
+            SyntaxKind::EnumItem => self.enum_rest(),
+            _ => self.let_rest(),
+        }
+        self.finish();
+    }
+
+    /// Makes the expression of a block item a statement, unless it is the value of the block. Only
+    /// a block-like expression can be followed by another item without a `;`.
+    fn expr_stmt(&mut self, checkpoint: usize, block_like: bool) {
+        let value = matches!(
+            self.peek(),
+            None | Some(Token::Delimiter(Delimiter::CloseBrace))
+        );
+        if self.at(punct(Punctuation::Semicolon)) || !value {
+            self.start_at(checkpoint, SyntaxKind::ExprStmt);
+            if !self.eat(punct(Punctuation::Semicolon)) && !block_like {
+                self.missing();
+            }
+            self.finish();
+        }
+    }
+
+    /// The rest of a function after its doc comments, `body` is whether it needs one, otherwise
+    /// it can be left out with a `;`.
+    fn function_rest(&mut self, body: bool) {
+        self.bump();
+        self.expect_ident();
+        if self.at(op(Operator::LessThan)) {
+            self.generics();
+        }
+        self.params(true);
+        if self.eat(punct(Punctuation::Arrow)) {
+            self.ty();
+        }
+        self.member_body(body);
+    }
+
+    fn generics(&mut self) {
+        self.start(SyntaxKind::GenericList);
+        self.bump();
+        self.list(op(Operator::GreaterThan), |parser| {
+            if !parser.at_ident() {
+                return;
+            }
+            parser.start(SyntaxKind::Generic);
+            parser.bump();
+            if parser.eat(punct(Punctuation::Colon)) {
+                parser.ty();
+                while parser.eat(op(Operator::Add)) {
+                    parser.ty();
+                }
+            }
+            parser.finish();
+        });
+        self.finish();
+    }
+
+    /// The parameters of a function, or of a constructor which can't take `self`.
+    fn params(&mut self, receiver: bool) {
+        if !self.at(delim(Delimiter::OpenParen)) {
+            return self.missing();
+        }
+        self.start(SyntaxKind::ParamList);
+        self.bump();
+
+        // Only the first parameter can be `self`
+        let mut first = receiver;
+        self.list(delim(Delimiter::CloseParen), |parser| {
+            let receiver = match parser.peek() {
+                Some(Token::Keyword(Keyword::Mut)) => {
+                    parser.nth(1) == Some(&Token::Identifier("self"))
+                }
+                token => token == Some(&Token::Identifier("self")),
+            };
+            if mem::take(&mut first) && receiver {
+                parser.start(SyntaxKind::Receiver);
+                parser.eat(kw(Keyword::Mut));
+                parser.bump();
+                parser.finish();
+            } else {
+                parser.param();
+            }
+        });
+        self.finish();
+    }
+
+    fn param(&mut self) {
+        if !matches!(
+            self.peek(),
+            Some(Token::Identifier(_) | Token::Keyword(Keyword::Mut))
+        ) {
+            return;
+        }
+        self.start(SyntaxKind::Param);
+        self.eat(kw(Keyword::Mut));
+        self.expect_ident();
+        self.expect(punct(Punctuation::Colon));
+        self.ty();
+        self.finish();
+    }
+
+    fn ty(&mut self) {
+        match self.peek() {
+            Some(Token::Delimiter(Delimiter::OpenParen)) => {
+                self.start(SyntaxKind::Type);
+                self.bump();
+                self.expect(delim(Delimiter::CloseParen));
+            }
+            Some(Token::Delimiter(Delimiter::OpenBracket)) => {
+                self.start(SyntaxKind::Type);
+                self.bump();
+                self.ty();
+                self.expect(delim(Delimiter::CloseBracket));
+            }
+            Some(Token::Keyword(Keyword::Dyn)) => {
+                self.start(SyntaxKind::Type);
+                self.bump();
+                self.expect_ident();
+            }
+            Some(Token::Identifier(_)) => {
+                self.start(SyntaxKind::Type);
+                self.bump();
+            }
+            _ => return self.missing(),
+        }
+        self.finish();
+    }
+
+    fn const_rest(&mut self) {
+        self.bump();
+        self.expect_ident();
+        if self.eat(punct(Punctuation::Colon)) {
+            self.ty();
+        }
+        self.expect(op(Operator::Assign));
+        self.expr();
+        self.expect(punct(Punctuation::Semicolon));
+    }
+
+    fn let_rest(&mut self) {
+        self.bump();
+        self.eat(kw(Keyword::Mut));
+        self.expect_ident();
+        if self.eat(punct(Punctuation::Colon)) {
+            self.ty();
+        }
+        if self.eat(op(Operator::Assign)) {
+            self.expr();
+        }
+        self.expect(punct(Punctuation::Semicolon));
+    }
+
+    fn struct_rest(&mut self) {
+        self.bump();
+        self.expect_ident();
+
+        if self.at(delim(Delimiter::OpenBrace)) {
+            self.start(SyntaxKind::MemberList);
+            self.bump();
+
+            let close = delim(Delimiter::CloseBrace);
+            while !self.at_end(&close) {
+                let pos = self.pos;
+                match (self.peek(), self.nth(1)) {
+                    (Some(Token::Identifier(_)), Some(Token::Delimiter(Delimiter::OpenParen))) => {
+                        self.start(SyntaxKind::Constructor);
+                        self.bump();
+                        self.params(false);
+                        self.member_body(false);
+                        self.finish();
+                    }
+                    (Some(Token::Operator(Operator::BitwiseNot)), _) => {
+                        self.start(SyntaxKind::Destructor);
+                        self.bump();
+                        self.expect_ident();
+                        self.expect(delim(Delimiter::OpenParen));
+                        self.expect(delim(Delimiter::CloseParen));
+                        self.member_body(false);
+                        self.finish();
+                    }
+                    // Fields are separated by commas, the other members end with their body
+                    _ => {
+                        self.struct_field();
+                        if !self.eat(punct(Punctuation::Comma)) && !self.at_end(&close) {
+                            self.missing();
+                        }
+                    }
+                }
+                if self.pos == pos {
+                    self.error();
+                }
+            }
+
+            self.expect(close);
+            self.finish();
+        } else {
+            self.missing();
+        }
+        self.eat(punct(Punctuation::Semicolon));
+    }
+
+    /// The body of a function, constructor or destructor, which is left out with a `;` unless
+    /// `required`.
+    fn member_body(&mut self, required: bool) {
+        if required || self.at(delim(Delimiter::OpenBrace)) {
+            self.block();
+            self.eat(punct(Punctuation::Semicolon));
+        } else {
+            self.expect(punct(Punctuation::Semicolon));
+        }
+    }
+
+    fn struct_field(&mut self) {
+        if !self.at_ident() {
+            return;
+        }
+        let checkpoint = self.doc_checkpoint();
+        self.start_at(checkpoint, SyntaxKind::StructField);
+        self.bump();
+        self.expect(punct(Punctuation::Colon));
+        self.ty();
+        self.finish();
+    }
+
+    fn impl_rest(&mut self) {
+        self.bump();
+        self.ty();
+        if self.eat(kw(Keyword::For)) {
+            self.ty();
+        }
+        self.methods(true);
+        self.eat(punct(Punctuation::Semicolon));
+    }
+
+    fn trait_rest(&mut self) {
+        self.bump();
+        self.expect_ident();
+        self.methods(false);
+        self.eat(punct(Punctuation::Semicolon));
+    }
+
+    /// The methods of an `impl`, or of a `trait` when they don't need a `body`.
+    fn methods(&mut self, body: bool) {
+        if !self.at(delim(Delimiter::OpenBrace)) {
+            return self.missing();
+        }
+        self.start(SyntaxKind::MethodList);
+        self.bump();
+
+        loop {
+            let checkpoint = self.doc_checkpoint();
+            match self.peek() {
+                Some(Token::Keyword(Keyword::Fn)) => {
+                    self.start_at(checkpoint, SyntaxKind::FnItem);
+                    self.function_rest(body);
+                    self.finish();
+                }
+                Some(token) if !is_closing(token) && !parser::starts_statement(token) => {
+                    self.start_at(checkpoint, SyntaxKind::Error);
+                    self.bump();
+                    self.finish();
+                }
+                _ => break,
+            }
+        }
+
+        self.expect(delim(Delimiter::CloseBrace));
+        self.finish();
+    }
+
+    fn enum_rest(&mut self) {
+        self.bump();
+        self.expect_ident();
+
+        if self.at(delim(Delimiter::OpenBrace)) {
+            self.start(SyntaxKind::VariantList);
+            self.bump();
+            self.list(delim(Delimiter::CloseBrace), |parser| {
+                if !parser.at_ident() {
+                    return;
+                }
+                let checkpoint = parser.doc_checkpoint();
+                parser.start_at(checkpoint, SyntaxKind::Variant);
+                parser.bump();
+                match parser.peek() {
+                    Some(Token::Delimiter(Delimiter::OpenParen)) => {
+                        parser.start(SyntaxKind::TupleFields);
+                        parser.bump();
+                        parser.list(delim(Delimiter::CloseParen), Self::ty);
+                        parser.finish();
+                    }
+                    Some(Token::Delimiter(Delimiter::OpenBrace)) => {
+                        parser.start(SyntaxKind::StructFields);
+                        parser.bump();
+                        parser.list(delim(Delimiter::CloseBrace), Self::struct_field);
+                        parser.finish();
+                    }
+                    _ => {}
+                }
+                parser.finish();
+            });
+            self.finish();
+        } else {
+            self.missing();
+        }
+        self.eat(punct(Punctuation::Semicolon));
+    }
+
+    fn expr(&mut self) {
+        let checkpoint = self.checkpoint();
+        self.binary(0);
+
+        // Assignment has the lowest precedence and is right associative
+        let assign = matches!(
+            self.peek(),
+            Some(Token::Operator(
+                Operator::Assign
+                    | Operator::AddAssign
+                    | Operator::SubAssign
+                    | Operator::MulAssign
+                    | Operator::DivAssign
+                    | Operator::ModAssign
+                    | Operator::AndAssign
+                    | Operator::OrAssign
+                    | Operator::XorAssign
+                    | Operator::ShlAssign
+                    | Operator::ShrAssign
+            ))
+        );
+        if assign {
+            self.start_at(checkpoint, SyntaxKind::AssignExpr);
+            self.bump();
+            self.expr();
+            self.finish();
+        }
+    }
+
+    fn binary(&mut self, level: usize) {
+        let Some(operators) = BINARY_OPERATORS.get(level) else {
+            return self.cast();
+        };
+
+        let checkpoint = self.checkpoint();
+        self.binary(level + 1);
+        while matches!(self.peek(), Some(Token::Operator(op)) if operators.contains(op)) {
+            self.start_at(checkpoint, SyntaxKind::BinaryExpr);
+            self.bump();
+            self.binary(level + 1);
+            self.finish();
+        }
+    }
+
+    fn cast(&mut self) {
+        let checkpoint = self.checkpoint();
+        self.unary();
+        while self.at(kw(Keyword::As)) {
+            self.start_at(checkpoint, SyntaxKind::CastExpr);
+            self.bump();
+            self.ty();
+            self.finish();
+        }
+    }
+
+    fn unary(&mut self) {
+        if matches!(
+            self.peek(),
+            Some(Token::Operator(
+                Operator::Subtract | Operator::LogicalNot | Operator::BitwiseNot
+            ))
+        ) {
+            self.start(SyntaxKind::UnaryExpr);
+            self.bump();
+            self.unary();
+            self.finish();
+        } else {
+            self.postfix();
+        }
+    }
+
+    fn postfix(&mut self) {
+        let checkpoint = self.checkpoint();
+        if !self.atom() {
+            return self.missing();
+        }
+
+        loop {
+            match self.peek() {
+                Some(Token::Delimiter(Delimiter::OpenParen)) => {
+                    self.start_at(checkpoint, SyntaxKind::CallExpr);
+                    self.args();
+                }
+                Some(Token::Delimiter(Delimiter::OpenBracket)) => {
+                    self.start_at(checkpoint, SyntaxKind::IndexExpr);
+                    self.bump();
+                    self.expr();
+                    self.expect(delim(Delimiter::CloseBracket));
+                }
+                Some(Token::Punctuation(Punctuation::Dot)) => {
+                    if self.nth(2) == Some(&delim(Delimiter::OpenParen)) {
+                        self.start_at(checkpoint, SyntaxKind::MethodCallExpr);
+                        self.bump();
+                        self.expect_ident();
+                        self.args();
+                    } else {
+                        self.start_at(checkpoint, SyntaxKind::FieldExpr);
+                        self.bump();
+                        self.expect_ident();
+                    }
+                }
+                Some(Token::Operator(Operator::Increment | Operator::Decrement)) => {
+                    self.start_at(checkpoint, SyntaxKind::StepExpr);
+                    self.bump();
+                }
+                _ => return,
+            }
+            self.finish();
+        }
+    }
+
+    fn args(&mut self) {
+        self.start(SyntaxKind::ArgList);
+        self.bump();
+        self.list(delim(Delimiter::CloseParen), Self::expr);
+        self.finish();
+    }
+
+    /// Parses an expression that can't be split up by operators, returning whether there was one.
+    fn atom(&mut self) -> bool {
+        let Some(token) = self.peek() else {
+            return false;
+        };
+
+        let kind = match token {
+            token if starts_block_like(token) => {
+                self.block_like();
+                return true;
+            }
+            Token::Operator(Operator::BitwiseOr | Operator::LogicalOr) => {
+                self.closure();
+                return true;
+            }
+            Token::Literal(_) => SyntaxKind::LiteralExpr,
+            Token::Identifier(_) => match self.nth(1) {
+                Some(Token::Operator(Operator::LogicalNot))
+                    if self.nth(2) == Some(&delim(Delimiter::OpenParen)) =>
+                {
+                    self.start(SyntaxKind::MacroExpr);
+                    self.bump();
+                    self.bump();
+                    self.args();
+                    self.finish();
+                    return true;
+                }
+                Some(Token::Punctuation(Punctuation::DoubleColon)) => {
+                    self.start(SyntaxKind::PathExpr);
+                    self.bump();
+                    self.bump();
+                    self.expect_ident();
+                    self.finish();
+                    return true;
+                }
+                _ => SyntaxKind::IdentExpr,
+            },
+            Token::Keyword(Keyword::Continue) => SyntaxKind::ContinueExpr,
+            Token::Keyword(Keyword::Return | Keyword::Break) => {
+                let kind = match token {
+                    Token::Keyword(Keyword::Return) => SyntaxKind::ReturnExpr,
+                    _ => SyntaxKind::BreakExpr,
+                };
+                self.start(kind);
+                self.bump();
+                if self.peek().is_some_and(starts_expr) {
+                    self.expr();
+                }
+                self.finish();
+                return true;
+            }
+            Token::Delimiter(Delimiter::OpenParen) => {
+                // `()` is the unit literal
+                if self.nth(1) == Some(&delim(Delimiter::CloseParen)) {
+                    self.start(SyntaxKind::LiteralExpr);
+                    self.bump();
+                } else {
+                    self.start(SyntaxKind::ParenExpr);
+                    self.bump();
+                    self.expr();
+                }
+                self.expect(delim(Delimiter::CloseParen));
+                self.finish();
+                return true;
+            }
+            Token::Delimiter(Delimiter::OpenBracket) => {
+                self.start(SyntaxKind::ArrayExpr);
+                self.bump();
+                self.list(delim(Delimiter::CloseBracket), Self::expr);
+                self.finish();
+                return true;
+            }
+            _ => return false,
+        };
+
+        // A single token
+        self.start(kind);
+        self.bump();
+        self.finish();
+        true
+    }
+
+    fn closure(&mut self) {
+        self.start(SyntaxKind::ClosureExpr);
+
+        self.start(SyntaxKind::ClosureParamList);
+        if !self.eat(op(Operator::LogicalOr)) {
+            self.bump();
+            self.list(op(Operator::BitwiseOr), |parser| {
+                if !matches!(
+                    parser.peek(),
+                    Some(Token::Identifier(_) | Token::Keyword(Keyword::Mut))
+                ) {
+                    return;
+                }
+                parser.start(SyntaxKind::ClosureParam);
+                parser.eat(kw(Keyword::Mut));
+                parser.expect_ident();
+                if parser.eat(punct(Punctuation::Colon)) {
+                    parser.ty();
+                }
+                parser.finish();
+            });
+        }
+        self.finish();
+
+        if self.eat(punct(Punctuation::Arrow)) {
+            self.ty();
+            self.block();
+        } else {
+            self.expr();
+        }
+
+        self.finish();
+    }
+
+    fn block_like(&mut self) {
+        let kind = match self.peek() {
+            Some(Token::Delimiter(Delimiter::OpenBrace)) => return self.block(),
+            Some(Token::Keyword(Keyword::If)) => return self.if_(),
+            Some(Token::Keyword(Keyword::Loop)) => SyntaxKind::LoopExpr,
+            Some(Token::Keyword(Keyword::While)) => SyntaxKind::WhileExpr,
+            Some(Token::Keyword(Keyword::For)) => SyntaxKind::ForExpr,
+            Some(Token::Keyword(Keyword::Match)) => SyntaxKind::MatchExpr,
+            _ => return,
+        };
+
+        self.start(kind);
+        self.bump();
+        match kind {
+            SyntaxKind::LoopExpr => {}
+            SyntaxKind::WhileExpr => self.expr(),
+            SyntaxKind::ForExpr => {
+                self.expect_ident();
+                self.expect(kw(Keyword::In));
+                self.expr();
+            }
+            _ => {
+                self.expr();
+                self.match_arms();
+            }
+        }
+        if kind != SyntaxKind::MatchExpr {
+            self.block();
+        }
+        self.finish();
+    }
+
+    fn if_(&mut self) {
+        self.start(SyntaxKind::IfExpr);
+        self.bump();
+        self.expr();
+        self.block();
+        if self.eat(kw(Keyword::Else)) {
+            if self.at(kw(Keyword::If)) {
+                self.if_();
+            } else {
+                self.block();
+            }
+        }
+        self.finish();
+    }
+
+    fn match_arms(&mut self) {
+        if !self.at(delim(Delimiter::OpenBrace)) {
+            return self.missing();
+        }
+        self.start(SyntaxKind::MatchArmList);
+        self.bump();
+
+        let close = delim(Delimiter::CloseBrace);
+        while !self.at_end(&close) {
+            let pos = self.pos;
+            let checkpoint = self.checkpoint();
+            self.pattern();
+            self.start_at(checkpoint, SyntaxKind::MatchArm);
+            if self.eat(kw(Keyword::If)) {
+                self.expr();
+            }
+            self.expect(punct(Punctuation::FatArrow));
+            // Like in the parser, an `if` or block body isn't followed by operators, and it doesn't
+            // need a comma after it
+            let block_like = match self.peek() {
+                Some(Token::Keyword(Keyword::If)) => {
+                    self.if_();
+                    true
+                }
+                Some(Token::Delimiter(Delimiter::OpenBrace)) => {
+                    self.block();
+                    true
+                }
+                _ => {
+                    self.expr();
+                    false
+                }
+            };
+            self.finish();
+
+            if !self.eat(punct(Punctuation::Comma)) && !block_like && !self.at_end(&close) {
+                self.missing();
+            }
+            if self.pos == pos {
+                self.error();
+            }
+        }
+
+        self.expect(close);
+        self.finish();
+    }
+
+    fn pattern(&mut self) {
+        let checkpoint = self.checkpoint();
+        self.atom_pattern();
+
+        if self.at(op(Operator::BitwiseOr)) {
+            self.start_at(checkpoint, SyntaxKind::OrPattern);
+            while self.eat(op(Operator::BitwiseOr)) {
+                self.atom_pattern();
+            }
+            self.finish();
+        }
+    }
+
+    fn atom_pattern(&mut self) {
+        match (self.peek(), self.nth(1)) {
+            (Some(Token::Identifier("_")), _) => {
+                self.start(SyntaxKind::WildcardPattern);
+                self.bump();
+                self.finish();
+            }
+            (Some(Token::Literal(_) | Token::Operator(Operator::Subtract)), _) => {
+                let checkpoint = self.checkpoint();
+                self.literal_pattern();
+                if self.eat(punct(Punctuation::DotDot)) || self.eat(punct(Punctuation::DotDotEq)) {
+                    self.start_at(checkpoint, SyntaxKind::RangePattern);
+                    self.literal_pattern();
+                    self.finish();
+                }
+            }
+            (Some(Token::Identifier(_)), Some(Token::Punctuation(Punctuation::DoubleColon))) => {
+                self.start(SyntaxKind::VariantPattern);
+                self.bump();
+                self.bump();
+                self.expect_ident();
+                self.payload_pattern();
+                self.finish();
+            }
+            (Some(Token::Identifier(_)), _) => {
+                self.start(SyntaxKind::BindingPattern);
+                self.bump();
+                if self.eat(punct(Punctuation::At)) {
+                    self.atom_pattern();
+                }
+                self.finish();
+            }
+            (Some(Token::Delimiter(Delimiter::OpenParen)), _) => {
+                self.start(SyntaxKind::ParenPattern);
+                self.bump();
+                self.pattern();
+                self.expect(delim(Delimiter::CloseParen));
+                self.finish();
+            }
+            _ => self.missing(),
+        }
+    }
+
+    fn literal_pattern(&mut self) {
+        self.start(SyntaxKind::LiteralPattern);
+        self.eat(op(Operator::Subtract));
+        if matches!(self.peek(), Some(Token::Literal(_))) {
+            self.bump();
+        } else {
+            self.missing();
+        }
+        self.finish();
+    }
+
+    fn payload_pattern(&mut self) {
+        match self.peek() {
+            Some(Token::Delimiter(Delimiter::OpenParen)) => {
+                self.start(SyntaxKind::TuplePayload);
+                self.bump();
+                self.list(delim(Delimiter::CloseParen), Self::pattern);
+            }
+            Some(Token::Delimiter(Delimiter::OpenBrace)) => {
+                self.start(SyntaxKind::StructPayload);
+                self.bump();
+                // `..` ends the fields, a comma before it can be left out
+                let close = delim(Delimiter::CloseBrace);
+                while !self.at_end(&close) && !self.eat(punct(Punctuation::DotDot)) {
+                    if self.at_ident() {
+                        self.start(SyntaxKind::FieldPattern);
+                        self.bump();
+                        if self.eat(punct(Punctuation::Colon)) {
+                            self.pattern();
+                        }
+                        self.finish();
+                    } else {
+                        self.error();
+                    }
+                    if !self.eat(punct(Punctuation::Comma))
+                        && !self.at_end(&close)
+                        && !self.at(punct(Punctuation::DotDot))
+                    {
+                        self.missing();
+                    }
+                }
+                self.expect(close);
+            }
+            _ => return,
+        }
+        self.finish();
+    }
+}
+
+/// Checks that the tree of a program has errors exactly when the parser rejects it. The tests that
+/// parse programs run every one of them through this, so that the two grammars can't drift apart.
+#[cfg(test)]
+pub(crate) fn assert_agrees(source: &str) {
+    use crate::diagnostic::codes;
+
+    let (tokens, _) = lexer::lex(source, FileId::TEST);
+    let (_, errors) = parser::parse(&tokens, FileId::TEST);
+    let code = |error: &parser::ParseError| match error {
+        parser::SyntaxError::Custom { code, .. } => Some(*code),
+        parser::SyntaxError::Unexpected(_) => None,
+    };
+    // The tree isn't limited in how deep it nests
+    if errors.iter().any(|error| code(error) == Some(codes::E0206)) {
+        return;
+    }
+    // Literals that are out of range, invalid constructors and `self` outside of methods are
+    // checked after the grammar
+    let rejected = errors.iter().any(|error| {
+        !matches!(code(error), Some(code) if [codes::E0203, codes::E0204, codes::E0205].contains(&code))
+    });
+
+    let (root, _) = parse(source, FileId::TEST);
+    let tree_errors = root
+        .descendants()
+        .into_iter()
+        .filter(|node| node.kind() == SyntaxKind::Error)
+        .map(|node| node.span().start..node.span().end)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        !tree_errors.is_empty(),
+        rejected,
+        "the tree and the parser disagree on {:?}\nparser: {:?}\ntree: {:?}",
+        source,
+        errors,
+        tree_errors
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{assert_agrees, parse, GreenNode, GreenToken, SyntaxKind, SyntaxNode};
+    use crate::{lexer, parser, source::FileId};
+
+    const SOURCE: &str = "
+    /// Doubles `y`
+    fn example(y: i32) -> i32 {
+        // a comment /* that isn't a block */
+        let z = y * 2; /* block /* nested */ */
+        return z; $
+    };
+    ";
+
+    /// The kinds of the nodes in a tree, like `FnItem(ParamList(Param(Type)))`.
+    fn shape(node: &SyntaxNode) -> String {
+        let children = node
+            .children()
+            .map(|child| shape(&child))
+            .collect::<Vec<_>>();
+
+        if children.is_empty() {
+            format!("{:?}", node.kind())
+        } else {
+            format!("{:?}({})", node.kind(), children.join(" "))
+        }
+    }
+
+    fn find(root: &SyntaxNode, kind: SyntaxKind) -> SyntaxNode {
+        root.descendants()
+            .into_iter()
+            .find(|node| node.kind() == kind)
+            .unwrap_or_else(|| panic!("no {:?} in `{}`", kind, root))
+    }
+
+    #[test]
+    fn lossless() {
+        let (root, errors) = parse(SOURCE, FileId::TEST);
+
+        assert_eq!(root.to_string(), SOURCE);
+        assert_eq!(root.span().start..root.span().end, 0..SOURCE.len());
+        // The unknown `$` is reported but still kept in the tree
+        assert_eq!(errors.len(), 1);
+
+        for src in [
+            "",
+            "  ",
+            "(((",
+            ")]}",
+            "a /* unterminated",
+            "\"unterminated",
+            "'😀' é",
+            "fn (",
+            "let = ;",
+            "struct { ~ ( }",
+            "impl for { x }",
+            "match x { 1 | => , }",
+            "|||",
+            "a.b.(",
+            "enum E { A(, B { c: } }",
+        ] {
+            assert_eq!(parse(src, FileId::TEST).0.to_string(), src);
+        }
+    }
+
+    #[test]
+    fn structure() {
+        let (root, _) = parse(SOURCE, FileId::TEST);
+
+        assert_eq!(
+            shape(&root),
+            "Root(FnItem(ParamList(Param(Type)) Type Block(\
+                LetStmt(BinaryExpr(IdentExpr LiteralExpr)) \
+                ExprStmt(ReturnExpr(IdentExpr)))))"
+        );
+
+        let params = find(&root, SyntaxKind::ParamList);
+        assert_eq!(params.to_string(), "(y: i32)");
+        assert_eq!(&SOURCE[params.span().start..params.span().end], "(y: i32)");
+
+        // Precedence and associativity are the parser's
+        let (root, _) = parse("a = b = c + d * -e as i8 || f;", FileId::TEST);
+        assert_eq!(
+            shape(&root),
+            "Root(ExprStmt(AssignExpr(IdentExpr AssignExpr(IdentExpr BinaryExpr(\
+                BinaryExpr(IdentExpr BinaryExpr(IdentExpr CastExpr(UnaryExpr(IdentExpr) Type))) \
+                IdentExpr)))))"
+        );
+
+        // Operators after a block-like statement start a new expression, like in the parser
+        let (root, _) = parse("if a { b } else { c } -1", FileId::TEST);
+        assert_eq!(
+            shape(&root),
+            "Root(ExprStmt(IfExpr(IdentExpr Block(IdentExpr) Block(IdentExpr))) \
+                UnaryExpr(LiteralExpr))"
+        );
+    }
+
+    #[test]
+    fn grammar() {
+        // Every part of the language, which the parser accepts as well
+        let source = "
+        //! A module doc
+        const LIMIT: int = 10;
+        struct Point { x: int, y: int, Point(); ~Point() { println!(\"dropped\"); } }
+        impl Point { fn sum(self) -> int { self.x + self.y } }
+        trait Shape { fn area(self) -> float; fn name(self) -> string { \"shape\" } }
+        impl Shape for Point { fn area(self) -> float { 0.0 } }
+        enum Token { Number(int), Word { text: string }, End }
+        fn largest<T: Shape + Named>(shapes: [T], mut n: int) -> dyn Shape { shapes[0] }
+        let mut total: int;
+        let add = |x: int, mut y| x + y;
+        let run = || -> () { total = 0; };
+        for i in range(0, LIMIT) { total += i; i++; }
+        while total > 0 { total -= 1; if total == 5 { break; } else if total < 0 { continue; } }
+        let v = loop { break 1; };
+        let p = Point::origin().sum() as i64;
+        match Token::End {
+            Token::Number(n @ 1..=9) | Token::Number(-1) if n > 0 => fmt(\"{}\", n),
+            Token::Word { text: t, .. } => { t }
+            Token::Word { text } => text,
+            (_) => \"\",
+        }
+        [1, 2, 3][0] + (4) - ~5 >> !6 as int
+        ";
+        let (root, errors) = parse(source, FileId::TEST);
+        assert!(errors.is_empty());
+        assert_eq!(root.to_string(), source);
+        assert!(!root
+            .descendants()
+            .iter()
+            .any(|node| node.kind() == SyntaxKind::Error));
+
+        let (tokens, errors) = lexer::lex(source, FileId::TEST);
+        assert!(errors.is_empty());
+        let (_, errors) = parser::parse(&tokens, FileId::TEST);
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let items = root
+            .children()
+            .map(|node| node.kind())
+            .take(8)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            items,
+            [
+                SyntaxKind::ConstItem,
+                SyntaxKind::StructItem,
+                SyntaxKind::ImplItem,
+                SyntaxKind::TraitItem,
+                SyntaxKind::ImplItem,
+                SyntaxKind::EnumItem,
+                SyntaxKind::FnItem,
+                SyntaxKind::LetStmt,
+            ]
+        );
+        assert_eq!(
+            shape(&find(&root, SyntaxKind::MatchArm)),
+            "MatchArm(OrPattern(\
+                VariantPattern(TuplePayload(BindingPattern(RangePattern(LiteralPattern LiteralPattern)))) \
+                VariantPattern(TuplePayload(LiteralPattern))) \
+                BinaryExpr(IdentExpr LiteralExpr) CallExpr(IdentExpr ArgList(LiteralExpr IdentExpr)))"
+        );
+    }
+
+    #[test]
+    fn recovery() {
+        // An unclosed parenthesis ends at the next statement, and what's missing is marked by empty
+        // errors
+        let (root, _) = parse("let a = (1 + ;\nlet b = 2;\n) fn f(", FileId::TEST);
+        assert_eq!(
+            shape(&root),
+            "Root(LetStmt(ParenExpr(BinaryExpr(LiteralExpr Error) Error)) LetStmt(LiteralExpr) \
+                Error FnItem(ParamList(Error) Error))"
+        );
+        let (root, _) = parse("let a = [1 2] fn", FileId::TEST);
+        assert_eq!(
+            shape(&root),
+            "Root(LetStmt(ArrayExpr(LiteralExpr Error LiteralExpr) Error) FnItem(Error Error Error))"
+        );
+
+        let (root, _) = parse("fn f() { let x = 1; ] x }", FileId::TEST);
+        assert_eq!(
+            shape(&root),
+            "Root(FnItem(ParamList Block(LetStmt(LiteralExpr) Error IdentExpr)))"
+        );
+    }
+
+    /// The code blocks of a markdown file.
+    fn code_blocks(markdown: &str) -> Vec<&str> {
+        markdown
+            .split("```")
+            .skip(1)
+            .step_by(2)
+            .map(|block| block.split_once('\n').map_or(block, |(_, code)| code))
+            .collect()
+    }
+
+    #[test]
+    fn agrees_with_parser() {
+        let examples = code_blocks(include_str!("../syntax.md"))
+            .into_iter()
+            .chain(code_blocks(include_str!("../readme.md")));
+
+        for example in examples {
+            assert_agrees(example);
+
+            // And with each token left out, which breaks most of them
+            let (root, _) = parse(example, FileId::TEST);
+            for token in root.tokens() {
+                if matches!(
+                    token.kind(),
+                    SyntaxKind::Whitespace
+                        | SyntaxKind::Comment
+                        | SyntaxKind::Skipped
+                        | SyntaxKind::DocComment
+                ) {
+                    continue;
+                }
+                let span = token.span();
+                assert_agrees(&format!(
+                    "{}{}",
+                    &example[..span.start],
+                    &example[span.end..]
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn items() {
+        let (root, _) = parse(SOURCE, FileId::TEST);
+
+        // The doc comment and the `;` after the function are part of it
+        let function = find(&root, SyntaxKind::FnItem);
+        assert!(function
+            .to_string()
+            .starts_with("/// Doubles `y`\n    fn example"));
+        assert!(function.to_string().ends_with("return z; $\n    };"));
+        assert_eq!(function.parent().unwrap().kind(), SyntaxKind::Root);
+
+        let let_ = find(&root, SyntaxKind::LetStmt);
+        assert_eq!(let_.to_string(), "let z = y * 2;");
+        assert_eq!(
+            &SOURCE[let_.span().start..let_.span().end],
+            "let z = y * 2;"
+        );
+
+        // Replace the statement with one parsed from new source
+        let (new, _) = parse("let z = y + y;", FileId::TEST);
+        let new_let = find(&new, SyntaxKind::LetStmt);
+        let root = let_.replace_with(new_let.green().clone());
+
+        let edited = SOURCE.replace("let z = y * 2;", "let z = y + y;");
+        assert_eq!(root.to_string(), edited);
+        assert_eq!(shape(&root), shape(&parse(&edited, FileId::TEST).0));
+    }
+
+    #[test]
+    fn tokens() {
+        let (root, _) = parse(SOURCE, FileId::TEST);
+
+        let token = root
+            .token_at_offset(SOURCE.find("let").unwrap() + 1)
+            .unwrap();
+        assert_eq!(token.kind(), SyntaxKind::Keyword);
+        assert_eq!(token.text(), "let");
+        assert_eq!(token.parent().kind(), SyntaxKind::LetStmt);
+        assert_eq!(token.parent().parent().unwrap().kind(), SyntaxKind::Block);
+
+        let doc = root
+            .tokens()
+            .into_iter()
+            .find(|token| token.kind() == SyntaxKind::DocComment)
+            .unwrap();
+        assert_eq!(doc.text(), "/// Doubles `y`");
+        assert_eq!(doc.parent().kind(), SyntaxKind::FnItem);
+
+        let comments = root
+            .tokens()
+            .into_iter()
+            .filter(|token| token.kind() == SyntaxKind::Comment)
+            .count();
+        assert_eq!(comments, 2);
+    }
+
+    #[test]
+    fn edits() {
+        let (root, _) = parse(SOURCE, FileId::TEST);
+
+        // Rename `z` to `doubled`, keeping the comments around it
+        let mut root = root;
+        while let Some(token) = root
+            .tokens()
+            .into_iter()
+            .find(|token| token.kind() == SyntaxKind::Identifier && token.text() == "z")
+        {
+            root = token.replace_with(GreenToken::new(SyntaxKind::Identifier, "doubled"));
+        }
+
+        assert_eq!(root.to_string(), SOURCE.replace(" z", " doubled"));
+
+        // Replace the parameter list
+        let params = find(&root, SyntaxKind::ParamList);
+        let new_params = GreenNode::new(
+            SyntaxKind::ParamList,
+            vec![
+                GreenToken::new(SyntaxKind::Delimiter, "(").into(),
+                GreenToken::new(SyntaxKind::Delimiter, ")").into(),
+            ],
+        );
+        let root = params.replace_with(new_params);
+
+        assert_eq!(
+            root.to_string(),
+            SOURCE.replace(" z", " doubled").replace("(y: i32)", "()")
+        );
+    }
+}
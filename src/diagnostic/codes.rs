@@ -0,0 +1,1243 @@
+//! Every diagnostic has a stable code that can be looked up with `ahlang explain <code>`. Codes are
+//! grouped by the stage that reports them, `E01xx` for the lexer, `E02xx` for the parser, `E03xx`
+//! for the type checker, `E04xx` for names and `E05xx` for errors while the program runs. Codes
+//! are never reused, even if the error they were for is removed.
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ErrorCode {
+    pub code: &'static str,
+    /// A short description of the error.
+    pub summary: &'static str,
+    /// A long form explanation with an example of the wrong code and how to correct it.
+    pub explanation: &'static str,
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.code)
+    }
+}
+
+/// Finds the error with a code, ignoring case.
+pub fn lookup(code: &str) -> Option<&'static ErrorCode> {
+    CODES
+        .iter()
+        .find(|error| error.code.eq_ignore_ascii_case(code.trim()))
+}
+
+pub const CODES: &[ErrorCode] = &[
+    E0101, E0102, E0103, E0104, E0105, E0106, E0107, E0108, E0201, E0202, E0203, E0204, E0205,
+    E0206, E0301, E0302, E0303, E0304, E0305, E0306, E0307, E0308, E0309, E0310, E0311, E0312,
+    E0313, E0314, E0315, E0316, E0317, E0318, E0319, E0320, E0321, E0322, E0323, E0324, E0325,
+    E0326, E0327, E0328, E0329, E0330, E0331, E0401, E0402, E0403, E0501, E0502, E0503, E0504,
+    E0505, E0506, E0507, E0508, E0509,
+];
+
+pub const E0101: ErrorCode = ErrorCode {
+    code: "E0101",
+    summary: "invalid number literal",
+    explanation: "\
+A number literal is malformed.
+
+Number literals can have at most one decimal point, can't end with an underscore,
+can only use the digits of their base and can only end with a known suffix
+(`i8` to `i64`, `u8` to `u64`, `f32` or `f64`). Float suffixes can't be used on
+integers written in another base, and integer suffixes can't be used on floats.
+
+Erroneous code example:
+
+    let x = 1.2.3;
+    let y = 0b102;
+    let z = 1.5i32;
+
+Corrected:
+
+    let x = 1.23;
+    let y = 0b101;
+    let z = 1.5f32;
+",
+};
+
+pub const E0102: ErrorCode = ErrorCode {
+    code: "E0102",
+    summary: "invalid escape sequence",
+    explanation: "\
+A string or character literal contains an unknown escape sequence.
+
+The supported escapes are `\\n`, `\\r`, `\\t`, `\\\\`, `\\\"`, `\\'`, `\\0` and unicode
+escapes like `\\u{1F600}`, which must be a valid unicode scalar value of at most
+six hex digits. A backslash at the end of a line skips the line break and the
+whitespace at the start of the next line.
+
+Erroneous code example:
+
+    let path = \"C:\\files\";
+
+Corrected, either escape the backslash or use a raw string:
+
+    let path = \"C:\\\\files\";
+    let path = r\"C:\\files\";
+",
+};
+
+pub const E0103: ErrorCode = ErrorCode {
+    code: "E0103",
+    summary: "unterminated string literal",
+    explanation: "\
+A string literal is missing its closing quote, so it goes on until the end of the
+file. Raw strings need a closing quote followed by as many `#`s as they started
+with.
+
+Erroneous code example:
+
+    let greeting = \"hello;
+    let raw = r#\"say \"hi\"\";
+
+Corrected:
+
+    let greeting = \"hello\";
+    let raw = r#\"say \"hi\"\"#;
+",
+};
+
+pub const E0104: ErrorCode = ErrorCode {
+    code: "E0104",
+    summary: "unterminated character literal",
+    explanation: "\
+A character literal is missing its closing quote.
+
+Erroneous code example:
+
+    let c = 'a;
+
+Corrected:
+
+    let c = 'a';
+",
+};
+
+pub const E0105: ErrorCode = ErrorCode {
+    code: "E0105",
+    summary: "character literal with more or less than one character",
+    explanation: "\
+Character literals hold exactly one character. Text with any other length is a
+string, which is written with double quotes.
+
+Erroneous code example:
+
+    let name = 'ahlang';
+    let empty = '';
+
+Corrected:
+
+    let name = \"ahlang\";
+    let empty = \"\";
+",
+};
+
+pub const E0106: ErrorCode = ErrorCode {
+    code: "E0106",
+    summary: "unterminated block comment",
+    explanation: "\
+A block comment is missing its closing `*/`. Block comments nest, so every `/*`
+inside of a comment needs its own `*/` too.
+
+Erroneous code example:
+
+    /* outer /* inner */
+    let x = 1;
+
+Corrected:
+
+    /* outer /* inner */ */
+    let x = 1;
+",
+};
+
+pub const E0107: ErrorCode = ErrorCode {
+    code: "E0107",
+    summary: "unknown character",
+    explanation: "\
+The source contains a character that isn't part of the language, outside of a
+string, character literal or comment.
+
+Erroneous code example:
+
+    let price = $5;
+
+Corrected:
+
+    let price = 5;
+",
+};
+
+pub const E0108: ErrorCode = ErrorCode {
+    code: "E0108",
+    summary: "unexpected end of input while lexing",
+    explanation: "\
+The source ended in the middle of a token.
+
+Erroneous code example:
+
+    let c = '
+
+Corrected:
+
+    let c = 'c';
+",
+};
+
+pub const E0201: ErrorCode = ErrorCode {
+    code: "E0201",
+    summary: "unexpected token",
+    explanation: "\
+The parser found a token where it doesn't fit the grammar. The error lists the
+tokens that would have been valid there.
+
+Erroneous code example:
+
+    let x = 1 + ;
+    fn add(a int) -> int { a }
+
+Corrected:
+
+    let x = 1 + 2;
+    fn add(a: int) -> int { a }
+",
+};
+
+pub const E0202: ErrorCode = ErrorCode {
+    code: "E0202",
+    summary: "missing semicolon after a statement",
+    explanation: "\
+Expression statements, `let` and `const` need to end with a semicolon. Only
+block-like expressions such as `if`, `match`, `loop`, `while`, `for` and blocks
+can leave it out, and the last expression of a block leaves it out to be the value
+of the block.
+
+Erroneous code example:
+
+    let x = 1
+    let y = 2;
+    x + y
+    println!(\"{}\", x);
+
+Corrected:
+
+    let x = 1;
+    let y = 2;
+    x + y;
+    println!(\"{}\", x);
+",
+};
+
+pub const E0203: ErrorCode = ErrorCode {
+    code: "E0203",
+    summary: "number literal out of range",
+    explanation: "\
+A number literal is too large to be represented. Integer literals have to fit in
+64 bits.
+
+Erroneous code example:
+
+    let big = 18446744073709551616;
+
+Corrected:
+
+    let big = 18446744073709551615;
+",
+};
+
+pub const E0204: ErrorCode = ErrorCode {
+    code: "E0204",
+    summary: "invalid constructor or destructor",
+    explanation: "\
+A struct's constructor and destructor are named after the struct, the destructor
+with a `~` in front. A struct can have at most one of each. A constructor without
+a body, like `Point();`, takes the values of the fields in the order they're
+declared, so it can't have parameters.
+
+Erroneous code example:
+
+    struct Point {
+        x: int,
+        y: int,
+
+        Point(x: int, y: int);
+        ~Pointer();
+    }
+
+Corrected:
+
+    struct Point {
+        x: int,
+        y: int,
+
+        Point();
+        ~Point();
+    }
+",
+};
+
+pub const E0205: ErrorCode = ErrorCode {
+    code: "E0205",
+    summary: "`self` parameter outside of an `impl`",
+    explanation: "\
+A function that isn't in an `impl` block takes `self`. Only methods have a value
+they're called on, so move the function into an `impl` of the type, or give the
+parameter a name and a type.
+
+Erroneous code example:
+
+    fn area(self) -> int { self.width * self.height }
+
+Corrected:
+
+    impl Rect {
+        fn area(self) -> int { self.width * self.height }
+    }
+",
+};
+
+pub const E0206: ErrorCode = ErrorCode {
+    code: "E0206",
+    summary: "nested too deeply",
+    explanation: "\
+Expressions, blocks, types or patterns are nested more than 256 levels deep. A
+long chain of operators like `1 + 1 + 1 + ...` nests too, as every operator
+contains the ones before it. Programs this deep would run out of stack while they
+are checked or run, so split the code up with variables or functions.
+
+Erroneous code example:
+
+    let total = 1 + 1 + 1 + 1 + ... + 1; // more than 256 operators
+
+Corrected:
+
+    let mut total = 0;
+    for i in range(0, 300) {
+        total += 1;
+    }
+",
+};
+
+pub const E0301: ErrorCode = ErrorCode {
+    code: "E0301",
+    summary: "mismatched types",
+    explanation: "\
+A value has a different type than the one that is expected where it's used. This
+happens when a value doesn't match the type annotation of a variable, the type of
+a function parameter or the return type of a function, when a condition isn't a
+`bool`, or when the branches of an `if` or `match` have different types.
+
+Erroneous code example:
+
+    let x: int = \"one\";
+    if x { }
+
+Corrected:
+
+    let x: int = 1;
+    if x > 0 { }
+",
+};
+
+pub const E0302: ErrorCode = ErrorCode {
+    code: "E0302",
+    summary: "unknown type",
+    explanation: "\
+A type annotation names a type that doesn't exist. The built in types are the
+ints `i8` to `i64` and `u8` to `u64`, the floats `f32` and `f64`, `bool`, `char`,
+`string`, `()` and arrays like `[int]`. `int` is another name for `i64`, and
+`float` for `f64`.
+
+Erroneous code example:
+
+    let x: integer = 1;
+
+Corrected:
+
+    let x: int = 1;
+",
+};
+
+pub const E0303: ErrorCode = ErrorCode {
+    code: "E0303",
+    summary: "binary operator applied to the wrong types",
+    explanation: "\
+A binary operator was used with operands it doesn't support. Arithmetic needs two
+numbers of the same type, `+` also joins two strings, `&&` and `||` need bools,
+and `==` and `!=` need two values of the same type. Numbers are never converted
+implicitly, use `as` to convert them.
+
+Erroneous code example:
+
+    let x = 1 + 2.5;
+
+Corrected:
+
+    let x = 1 as float + 2.5;
+",
+};
+
+pub const E0304: ErrorCode = ErrorCode {
+    code: "E0304",
+    summary: "unary operator applied to the wrong type",
+    explanation: "\
+A unary operator was used with an operand it doesn't support. `-` needs a signed
+int or a float, `!` needs a bool and `~` needs an int. `++` and `--` need a
+number.
+
+Erroneous code example:
+
+    let x = !1;
+
+Corrected:
+
+    let x = ~1;
+    let y = !true;
+",
+};
+
+pub const E0305: ErrorCode = ErrorCode {
+    code: "E0305",
+    summary: "invalid cast",
+    explanation: "\
+An `as` cast converts between types that can't be converted. Only numbers, bools
+and chars can be cast, and only to numbers.
+
+Erroneous code example:
+
+    let x = \"5\" as int;
+
+Corrected:
+
+    let x = 5 as float;
+",
+};
+
+pub const E0306: ErrorCode = ErrorCode {
+    code: "E0306",
+    summary: "wrong number of arguments",
+    explanation: "\
+A function was called with more or fewer arguments than it has parameters.
+
+Erroneous code example:
+
+    fn add(a: int, b: int) -> int { a + b }
+    add(1);
+
+Corrected:
+
+    fn add(a: int, b: int) -> int { a + b }
+    add(1, 2);
+",
+};
+
+pub const E0307: ErrorCode = ErrorCode {
+    code: "E0307",
+    summary: "called something that isn't a function",
+    explanation: "\
+A value that isn't a function was called.
+
+Erroneous code example:
+
+    let x = 1;
+    x();
+
+Corrected:
+
+    fn x() -> int { 1 }
+    x();
+",
+};
+
+pub const E0308: ErrorCode = ErrorCode {
+    code: "E0308",
+    summary: "indexed into something that isn't an array",
+    explanation: "\
+Only arrays can be indexed, and only with an `int`.
+
+Erroneous code example:
+
+    let x = 5;
+    x[0];
+
+Corrected:
+
+    let x = [5];
+    x[0];
+",
+};
+
+pub const E0309: ErrorCode = ErrorCode {
+    code: "E0309",
+    summary: "no such field",
+    explanation: "\
+A field was accessed on a type that doesn't have a field with that name. Only
+structs have fields.
+
+Erroneous code example:
+
+    struct Point { x: int, y: int, Point(); }
+    let p = Point(1, 2);
+    p.z;
+
+Corrected:
+
+    struct Point { x: int, y: int, Point(); }
+    let p = Point(1, 2);
+    p.x;
+",
+};
+
+pub const E0310: ErrorCode = ErrorCode {
+    code: "E0310",
+    summary: "invalid assignment target",
+    explanation: "\
+The left-hand side of an assignment has to be a place that can hold a value: a
+variable, an element of an array or a field.
+
+Erroneous code example:
+
+    1 = x;
+
+Corrected:
+
+    x = 1;
+",
+};
+
+pub const E0311: ErrorCode = ErrorCode {
+    code: "E0311",
+    summary: "value can't be iterated over",
+    explanation: "\
+A `for` loop can only iterate over arrays and ranges.
+
+Erroneous code example:
+
+    for i in 10 { }
+
+Corrected:
+
+    for i in range(0, 10) { }
+",
+};
+
+pub const E0312: ErrorCode = ErrorCode {
+    code: "E0312",
+    summary: "`break` or `continue` outside of a loop",
+    explanation: "\
+`break` and `continue` can only be used inside of a loop. A function declared
+inside of a loop can't break out of it.
+
+Erroneous code example:
+
+    fn stop() { break; }
+
+Corrected:
+
+    fn stop() { return; }
+",
+};
+
+pub const E0313: ErrorCode = ErrorCode {
+    code: "E0313",
+    summary: "`break` with a value outside of `loop`",
+    explanation: "\
+Only `loop` can break with a value, because `while` and `for` loops can also end
+without a `break` and then wouldn't have a value.
+
+Erroneous code example:
+
+    let found = while true { break 1; };
+
+Corrected:
+
+    let found = loop { break 1; };
+",
+};
+
+pub const E0314: ErrorCode = ErrorCode {
+    code: "E0314",
+    summary: "unknown macro",
+    explanation: "\
+A macro that doesn't exist was used. The macros are `println!`, `print!` and
+`format!`.
+
+Erroneous code example:
+
+    printline!(\"hi\");
+
+Corrected:
+
+    println!(\"hi\");
+",
+};
+
+pub const E0315: ErrorCode = ErrorCode {
+    code: "E0315",
+    summary: "type annotations needed",
+    explanation: "\
+The type of something couldn't be inferred from how it's used. Variables without
+a type annotation, closure parameters and empty arrays get their types from the
+values assigned to them and the ways they're used, but if nothing tells what the
+type is, or the type is needed before anything tells what it is, it has to be
+written.
+
+Erroneous code example:
+
+    let values = [];
+    let double = |x| x + x;
+
+Corrected:
+
+    let values: [int] = [];
+    let double = |x: int| x + x;
+",
+};
+
+pub const E0316: ErrorCode = ErrorCode {
+    code: "E0316",
+    summary: "cannot assign to an immutable value",
+    explanation: "\
+Something that can't be changed was assigned to, changed with a compound
+assignment like `+=`, or incremented or decremented. Variables and parameters
+are immutable unless they're declared with `mut`, and constants and functions
+can never be changed. An immutable `let` without a value can be assigned once,
+outside of any loop it isn't declared in.
+
+Erroneous code example:
+
+    let count = 0;
+    count += 1;
+
+Corrected:
+
+    let mut count = 0;
+    count += 1;
+",
+};
+
+pub const E0317: ErrorCode = ErrorCode {
+    code: "E0317",
+    summary: "immutable value passed to a `mut` parameter",
+    explanation: "\
+A function can change the values passed to its `mut` parameters, so they have to
+be given a mutable variable, or an element or field of one, and not an immutable
+variable or a temporary value.
+
+Erroneous code example:
+
+    fn reset(mut values: [int]) { values[0] = 0; }
+    let values = [1, 2];
+    reset(values);
+
+Corrected:
+
+    fn reset(mut values: [int]) { values[0] = 0; }
+    let mut values = [1, 2];
+    reset(values);
+",
+};
+
+pub const E0318: ErrorCode = ErrorCode {
+    code: "E0318",
+    summary: "builtin function used as a value",
+    explanation: "\
+The builtin functions, like `range`, `len`, `fmt`, `print` and `wrapping_add`, can
+only be called, they can't be stored in a variable or passed to another function.
+Wrap them in a closure to use them as a value.
+
+Erroneous code example:
+
+    let length = len;
+
+Corrected:
+
+    let length = |values: [int]| len(values);
+",
+};
+
+pub const E0319: ErrorCode = ErrorCode {
+    code: "E0319",
+    summary: "literal out of range",
+    explanation: "\
+An int literal doesn't fit in the type it's used as. Literals without a suffix
+are `int` unless they're used as another kind of int, like an argument for a
+`u8` parameter.
+
+Erroneous code example:
+
+    let x: u8 = 256;
+    let y = 200i8;
+
+Corrected:
+
+    let x: u16 = 256;
+    let y = 200u8;
+",
+};
+
+pub const E0320: ErrorCode = ErrorCode {
+    code: "E0320",
+    summary: "struct without a constructor",
+    explanation: "\
+A struct was created, but it doesn't declare a constructor. Declare one named
+after the struct, either with a body that assigns every field of `self`, or as
+`Name();` to take the values of the fields in order.
+
+Erroneous code example:
+
+    struct Point { x: int, y: int }
+    let p = Point(1, 2);
+
+Corrected:
+
+    struct Point {
+        x: int,
+        y: int,
+
+        Point();
+    }
+    let p = Point(1, 2);
+",
+};
+
+pub const E0321: ErrorCode = ErrorCode {
+    code: "E0321",
+    summary: "methods for a type that isn't a struct",
+    explanation: "\
+An `impl` block is for a type that isn't a struct. Only structs can have methods,
+for other types use a function that takes the value as a parameter.
+
+Erroneous code example:
+
+    impl int {
+        fn double(self) -> int { self * 2 }
+    }
+
+Corrected:
+
+    fn double(value: int) -> int { value * 2 }
+",
+};
+
+pub const E0322: ErrorCode = ErrorCode {
+    code: "E0322",
+    summary: "no such method or variant",
+    explanation: "\
+A method or associated function was used that the type doesn't have, or a variant
+that the enum doesn't have. Methods take `self` and are called on a value, like
+`p.length()`. Associated functions don't take `self` and are called on the type,
+like `Point::origin()`.
+
+Erroneous code example:
+
+    impl Point {
+        fn origin() -> Point { Point(0, 0) }
+        fn length(self) -> int { self.x + self.y }
+    }
+    let p = Point::new();
+    p.origin();
+
+Corrected:
+
+    let p = Point::origin();
+    p.length();
+",
+};
+
+pub const E0323: ErrorCode = ErrorCode {
+    code: "E0323",
+    summary: "trait used as a type, or a type used as a trait",
+    explanation: "\
+A trait was used where a type or a value is expected, or something that isn't a
+trait was used where a trait is expected, like after `impl ... for`, after `dyn`
+or in the bounds of a type parameter. A value of any type that implements a
+trait has the type `dyn Trait`.
+
+Erroneous code example:
+
+    trait Shape { fn area(self) -> float; }
+    impl Circle for Shape {}
+    let shapes: [Shape] = [];
+
+Corrected:
+
+    impl Shape for Circle { fn area(self) -> float { 3.14 * self.r * self.r } }
+    let shapes: [dyn Shape] = [];
+",
+};
+
+pub const E0324: ErrorCode = ErrorCode {
+    code: "E0324",
+    summary: "impl doesn't match its trait",
+    explanation: "\
+An `impl` of a trait has to define every method of the trait that doesn't have a
+default body, with the same parameters, return type and `self` as the trait
+declares. It can't have methods that aren't in the trait, those go in an `impl`
+block of their own.
+
+Erroneous code example:
+
+    trait Shape {
+        fn area(self) -> float;
+        fn name(self) -> string { \"shape\" }
+    }
+    impl Shape for Circle {
+        fn area(self) -> int { 3 }
+        fn radius(self) -> float { self.r }
+    }
+
+Corrected:
+
+    impl Shape for Circle {
+        fn area(self) -> float { 3.14 * self.r * self.r }
+    }
+    impl Circle {
+        fn radius(self) -> float { self.r }
+    }
+",
+};
+
+pub const E0325: ErrorCode = ErrorCode {
+    code: "E0325",
+    summary: "trait not implemented",
+    explanation: "\
+A value was used as a `dyn Trait`, or given to a type parameter with the trait
+as a bound, but its type doesn't implement the trait.
+
+Erroneous code example:
+
+    trait Shape { fn area(self) -> float; }
+    fn total<T: Shape>(shapes: [T]) -> float { ... }
+    total([1, 2]);
+    let shape: dyn Shape = Square(2.0);
+
+Corrected:
+
+    impl Shape for Square { fn area(self) -> float { self.side * self.side } }
+    total([Square(1.0), Square(2.0)]);
+    let shape: dyn Shape = Square(2.0);
+",
+};
+
+pub const E0326: ErrorCode = ErrorCode {
+    code: "E0326",
+    summary: "invalid range pattern",
+    explanation: "\
+A range pattern is for a type that isn't a number or a char, or it can't match
+anything because its start is after its end. `a..b` doesn't include `b`, so it's
+also empty if `a` and `b` are the same.
+
+Erroneous code example:
+
+    match x {
+        10..=1 => println!(\"small\"),
+        _ => println!(\"big\"),
+    }
+
+Corrected:
+
+    match x {
+        1..=10 => println!(\"small\"),
+        _ => println!(\"big\"),
+    }
+",
+};
+
+pub const E0327: ErrorCode = ErrorCode {
+    code: "E0327",
+    summary: "invalid variant pattern",
+    explanation: "\
+A variant pattern doesn't match how the variant is declared. The payload of a
+tuple variant is matched with one pattern for each value, like `Shape::Circle(r)`,
+and the fields of a struct variant by name, like `Shape::Rect { w, h }`. Fields
+can only be left out if the pattern ends with `..`. A variant pattern can only
+match a value of its enum.
+
+Erroneous code example:
+
+    enum Shape { Circle(float), Rect { w: float, h: float } }
+    match shape {
+        Shape::Circle => 0.0,
+        Shape::Rect { w } => w,
+    }
+
+Corrected:
+
+    match shape {
+        Shape::Circle(_) => 0.0,
+        Shape::Rect { w, .. } => w,
+    }
+",
+};
+
+pub const E0328: ErrorCode = ErrorCode {
+    code: "E0328",
+    summary: "non-exhaustive match",
+    explanation: "\
+The arms of a `match` don't cover every value it could be given. The error lists
+the values that no arm matches, add arms for them or a `_` arm for everything
+else. Arms with an `if` guard don't count, since the guard could be false.
+
+Erroneous code example:
+
+    enum Direction { Up, Down, Left, Right }
+    match direction {
+        Direction::Up => 1,
+        Direction::Down => 2,
+    }
+
+Corrected:
+
+    match direction {
+        Direction::Up => 1,
+        Direction::Down => 2,
+        Direction::Left | Direction::Right => 0,
+    }
+",
+};
+
+pub const E0329: ErrorCode = ErrorCode {
+    code: "E0329",
+    summary: "unreachable match arm",
+    explanation: "\
+An arm of a `match`, or an alternative of a `|` pattern, can never match,
+because the arms before it already match every value it does. Arms are tried in
+order, so more specific arms have to go before more general ones.
+
+Erroneous code example:
+
+    match x {
+        _ => println!(\"anything\"),
+        0 => println!(\"zero\"),
+    }
+
+Corrected:
+
+    match x {
+        0 => println!(\"zero\"),
+        _ => println!(\"anything\"),
+    }
+",
+};
+
+pub const E0330: ErrorCode = ErrorCode {
+    code: "E0330",
+    summary: "enum used as a value",
+    explanation: "\
+The name of an enum was used as a value. Values of an enum are created with one
+of its variants.
+
+Erroneous code example:
+
+    enum Direction { Up, Down }
+    let d = Direction;
+
+Corrected:
+
+    let d = Direction::Up;
+",
+};
+
+pub const E0331: ErrorCode = ErrorCode {
+    code: "E0331",
+    summary: "wrong number of format arguments",
+    explanation: "\
+Every `{}` in a format string is replaced with an argument, so there has to be
+exactly one argument for each of them. Write `{{` and `}}` for literal braces.
+
+Erroneous code example:
+
+    println!(\"{} + {}\", 1);
+
+Corrected:
+
+    println!(\"{} + {}\", 1, 2);
+",
+};
+
+pub const E0401: ErrorCode = ErrorCode {
+    code: "E0401",
+    summary: "cannot find value",
+    explanation: "\
+A name was used that isn't declared in any enclosing scope. Functions can use the
+functions and constants around them, but not the variables.
+
+Erroneous code example:
+
+    let x = 1;
+    println!(\"{}\", y);
+
+Corrected:
+
+    let y = 1;
+    println!(\"{}\", y);
+",
+};
+
+pub const E0402: ErrorCode = ErrorCode {
+    code: "E0402",
+    summary: "name defined multiple times",
+    explanation: "\
+Functions, constants, structs, traits and enums can only be declared once in the
+same block, and the parameters of a function or closure, the fields of a struct
+and the variants of an enum need different names, as do the variables bound by a
+pattern. A struct can only have one method or associated function with a name,
+even across `impl` blocks and the traits it implements, and it can only implement
+a trait once. Variables declared with `let` can be shadowed by declaring them
+again.
+
+Erroneous code example:
+
+    fn area(width: int, width: int) -> int { width * width }
+    fn area() {}
+
+Corrected:
+
+    fn area(width: int, height: int) -> int { width * height }
+    fn print_area() {}
+",
+};
+
+pub const E0403: ErrorCode = ErrorCode {
+    code: "E0403",
+    summary: "variable not bound in all alternatives",
+    explanation: "\
+The alternatives of a `|` pattern have to bind the same variables, since the arm
+can use them whichever alternative matched.
+
+Erroneous code example:
+
+    match shape {
+        Shape::Circle(r) | Shape::Square(s) => r,
+    }
+
+Corrected:
+
+    match shape {
+        Shape::Circle(size) | Shape::Square(size) => size,
+    }
+",
+};
+
+pub const E0501: ErrorCode = ErrorCode {
+    code: "E0501",
+    summary: "division by zero",
+    explanation: "\
+An int was divided by zero, or the remainder of dividing by zero was taken. This
+is only known when the program runs. Dividing floats by zero gives infinity or
+NaN instead.
+
+Erroneous code example:
+
+    let count = 0;
+    let average = 10 / count;
+
+Corrected:
+
+    let count = 0;
+    let average = if count == 0 { 0 } else { 10 / count };
+",
+};
+
+pub const E0502: ErrorCode = ErrorCode {
+    code: "E0502",
+    summary: "index out of bounds",
+    explanation: "\
+An array was indexed with a negative index, or one that isn't less than its
+length.
+
+Erroneous code example:
+
+    let values = [1, 2, 3];
+    let last = values[3];
+
+Corrected:
+
+    let values = [1, 2, 3];
+    let last = values[len(values) - 1];
+",
+};
+
+pub const E0503: ErrorCode = ErrorCode {
+    code: "E0503",
+    summary: "arithmetic overflow",
+    explanation: "\
+The result of an int operation doesn't fit in its type, like `255u8 + 1`.
+Shifting by a negative amount, or by at least the number of bits in the type,
+also overflows. The `wrapping_add`, `wrapping_sub` and `wrapping_mul` builtins
+wrap around at the bounds of the type instead, and `saturating_add`,
+`saturating_sub` and `saturating_mul` stop at them.
+
+Erroneous code example:
+
+    let big: u8 = 200;
+    let bigger = big + 100;
+
+Corrected:
+
+    let big: u8 = 200;
+    let bigger = big as int + 100;
+    let wrapped = wrapping_add(big, 100);
+",
+};
+
+pub const E0504: ErrorCode = ErrorCode {
+    code: "E0504",
+    summary: "stack overflow",
+    explanation: "\
+Function calls were nested too deeply, which usually means that a recursive
+function never stops calling itself.
+
+Erroneous code example:
+
+    fn count(n: int) -> int { count(n + 1) }
+    count(0);
+
+Corrected:
+
+    fn count(n: int) -> int { if n == 10 { n } else { count(n + 1) } }
+    count(0);
+",
+};
+
+pub const E0505: ErrorCode = ErrorCode {
+    code: "E0505",
+    summary: "use of uninitialized variable",
+    explanation: "\
+A variable declared without a value was used before anything was assigned to it.
+
+Erroneous code example:
+
+    let mut total: int;
+    total += 1;
+
+Corrected:
+
+    let mut total = 0;
+    total += 1;
+",
+};
+
+pub const E0506: ErrorCode = ErrorCode {
+    code: "E0506",
+    summary: "no match arm matched",
+    explanation: "\
+None of the arms of a `match` matched the value. Matches are now checked to cover
+every value before the program runs, which reports this as E0328 instead.
+
+Erroneous code example:
+
+    match 3 {
+        1 => println!(\"one\"),
+        2 => println!(\"two\"),
+    }
+
+Corrected:
+
+    match 3 {
+        1 => println!(\"one\"),
+        2 => println!(\"two\"),
+        _ => println!(\"many\"),
+    }
+",
+};
+
+pub const E0507: ErrorCode = ErrorCode {
+    code: "E0507",
+    summary: "wrong number of format arguments",
+    explanation: "\
+Every `{}` in a format string is replaced with an argument, so there has to be
+exactly one argument for each of them. Write `{{` and `}}` for literal braces.
+Literal format strings are checked before the program runs (see E0331), this
+error is for format strings that are only known while it runs.
+
+Erroneous code example:
+
+    let format = \"{} + {}\";
+    println!(format, 1);
+
+Corrected:
+
+    let format = \"{} + {}\";
+    println!(format, 1, 2);
+",
+};
+
+pub const E0508: ErrorCode = ErrorCode {
+    code: "E0508",
+    summary: "failed to write the output",
+    explanation: "\
+Printing failed because the output couldn't be written to, for example because
+the program's output was piped into something that stopped reading it, or the
+writer given to the interpreter returned an error.
+
+Erroneous code example:
+
+    // Run as `ahlang count.ah | head -n 1`
+    for i in range(0, 100000) { println!(\"{}\", i); }
+
+Corrected:
+
+    // Run as `ahlang count.ah > count.txt`
+    for i in range(0, 100000) { println!(\"{}\", i); }
+",
+};
+
+pub const E0509: ErrorCode = ErrorCode {
+    code: "E0509",
+    summary: "unassigned field",
+    explanation: "\
+A constructor didn't assign every field of `self`, or it used a field before
+assigning it. A struct can only be created once all of its fields have a value.
+
+Erroneous code example:
+
+    struct Point {
+        x: int,
+        y: int,
+
+        Point(x: int) {
+            self.x = x;
+        }
+    }
+
+Corrected:
+
+    struct Point {
+        x: int,
+        y: int,
+
+        Point(x: int) {
+            self.x = x;
+            self.y = 0;
+        }
+    }
+",
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes() {
+        for (i, error) in CODES.iter().enumerate() {
+            assert!(
+                CODES[i + 1..].iter().all(|other| other.code != error.code),
+                "{} is used twice",
+                error.code
+            );
+            assert!(error.explanation.contains("Erroneous code example:"));
+            assert!(error.explanation.contains("Corrected"));
+        }
+
+        assert_eq!(lookup("e0101"), Some(&E0101));
+        assert_eq!(lookup("E9999"), None);
+    }
+}
@@ -0,0 +1,551 @@
+pub mod codes;
+
+use std::{fmt, io};
+
+use ariadne::{Color, Config, IndexType, Report, ReportKind};
+use chumsky::{
+    error::{RichPattern, RichReason},
+    span::Span as _,
+};
+
+use crate::{
+    interpreter::RuntimeError,
+    lexer::{LexError, Span},
+    parser::{ParseError, SyntaxError},
+    source::{FileId, SourceMap},
+};
+
+pub use codes::ErrorCode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A span of source code with a message explaining what's wrong with it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: Option<String>,
+    /// The primary label is where the problem is, secondary labels give context.
+    pub primary: bool,
+}
+
+/// An error or warning from any stage of running a program, which can be rendered with the source
+/// code it points at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Option<ErrorCode>,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            code: None,
+            message: message.into(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: None,
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    pub fn with_code(mut self, code: ErrorCode) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// Adds the primary label, pointing at where the problem is.
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: Some(message.into()),
+            primary: true,
+        });
+        self
+    }
+
+    /// Adds a primary label without a message, for when the message says it all.
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.labels.push(Label {
+            span,
+            message: None,
+            primary: true,
+        });
+        self
+    }
+
+    /// Adds a label pointing at something related to the problem.
+    pub fn with_secondary_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: Some(message.into()),
+            primary: false,
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// The span of the primary label, or of the first label if none are primary.
+    pub fn span(&self) -> Option<Span> {
+        self.labels
+            .iter()
+            .find(|label| label.primary)
+            .or(self.labels.first())
+            .map(|label| label.span)
+    }
+
+    /// The file that the primary label points into.
+    pub fn file(&self) -> Option<FileId> {
+        self.span().map(|span| span.context())
+    }
+
+    /// Writes the diagnostic with snippets of the source it points at. The labels that point into
+    /// files that aren't in the source map are left out.
+    pub fn render(
+        &self,
+        sources: &SourceMap,
+        color: bool,
+        writer: impl io::Write,
+    ) -> io::Result<()> {
+        let file = self.file().and_then(|file| sources.get(file));
+        let name = file.map_or("<unknown>".to_string(), |file| file.name().to_string());
+
+        let (kind, primary_color) = match self.severity {
+            Severity::Error => (ReportKind::Error, Color::Red),
+            Severity::Warning => (ReportKind::Warning, Color::Yellow),
+        };
+        let offset = self.span().map_or(0, |span| span.start);
+
+        let mut report = Report::build(kind, name.clone(), offset)
+            .with_config(
+                Config::default()
+                    .with_color(color)
+                    .with_index_type(IndexType::Byte),
+            )
+            .with_message(&self.message);
+
+        if let Some(code) = &self.code {
+            report = report.with_code(code);
+        }
+
+        // The snippets of every file that a label points into
+        let mut files = vec![(name, file.map_or("", |file| file.source()))];
+
+        for label in &self.labels {
+            let Some(file) = sources.get(label.span.context()) else {
+                continue;
+            };
+            let name = file.name().to_string();
+            if !files.iter().any(|(other, _)| *other == name) {
+                files.push((name.clone(), file.source()));
+            }
+
+            let mut report_label = ariadne::Label::new((name, label.span.start..label.span.end))
+                .with_color(if label.primary {
+                    primary_color
+                } else {
+                    Color::Blue
+                })
+                .with_priority(label.primary as i32);
+
+            if let Some(message) = &label.message {
+                report_label = report_label.with_message(message);
+            }
+
+            report = report.with_label(report_label);
+        }
+
+        // Ariadne only shows one note
+        if !self.notes.is_empty() {
+            report = report.with_note(self.notes.join("\n"));
+        }
+
+        if let Some(help) = &self.help {
+            report = report.with_help(help);
+        }
+
+        report.finish().write(ariadne::sources(files), writer)
+    }
+
+    /// Renders the diagnostic into a string without colors.
+    pub fn render_to_string(&self, sources: &SourceMap) -> String {
+        let mut out = Vec::new();
+        self.render(sources, false, &mut out)
+            .expect("writing to a Vec can't fail");
+        String::from_utf8_lossy(&out).into_owned()
+    }
+
+    /// Writes the diagnostic as a JSON object on a single line, for tools to read. Lines and
+    /// columns start at 1, columns count characters, and are left out of the spans when their file
+    /// isn't in the source map. `span` is the span of the primary label.
+    pub fn to_json(&self, sources: &SourceMap) -> String {
+        let file = self.file().and_then(|file| sources.get(file));
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        let code = self
+            .code
+            .map_or("null".to_string(), |code| json_string(code.code));
+        let span = self
+            .span()
+            .map_or("null".to_string(), |span| json_span(sources, span));
+        let labels = self
+            .labels
+            .iter()
+            .map(|label| {
+                format!(
+                    r#"{{"span":{},"message":{},"primary":{}}}"#,
+                    json_span(sources, label.span),
+                    label
+                        .message
+                        .as_deref()
+                        .map_or("null".to_string(), json_string),
+                    label.primary
+                )
+            })
+            .collect::<Vec<_>>();
+        let notes = self
+            .notes
+            .iter()
+            .map(|note| json_string(note))
+            .collect::<Vec<_>>();
+
+        format!(
+            r#"{{"file":{},"severity":"{}","code":{},"message":{},"span":{},"labels":[{}],"notes":[{}],"help":{}}}"#,
+            file.map_or("null".to_string(), |file| json_string(
+                &file.name().to_string()
+            )),
+            severity,
+            code,
+            json_string(&self.message),
+            span,
+            labels.join(","),
+            notes.join(","),
+            self.help.as_deref().map_or("null".to_string(), json_string)
+        )
+    }
+}
+
+fn json_span(sources: &SourceMap, span: Span) -> String {
+    match sources.get(span.context()) {
+        Some(file) => {
+            let (line, column) = file.line_column(span.start);
+            let (end_line, end_column) = file.line_column(span.end);
+            format!(
+                r#"{{"start":{},"end":{},"line":{},"column":{},"end_line":{},"end_column":{}}}"#,
+                span.start, span.end, line, column, end_line, end_column
+            )
+        }
+        None => format!(r#"{{"start":{},"end":{}}}"#, span.start, span.end),
+    }
+}
+
+fn json_string(string: &str) -> String {
+    let mut out = String::with_capacity(string.len() + 2);
+    out.push('"');
+    for char in string.chars() {
+        match char {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            char if char.is_control() => out.push_str(&format!("\\u{:04x}", char as u32)),
+            char => out.push(char),
+        }
+    }
+    out.push('"');
+    out
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.code {
+            Some(code) => write!(f, "[{}] {}", code, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl From<LexError> for Diagnostic {
+    fn from(error: LexError) -> Self {
+        let span = error.span();
+        let code = match error {
+            LexError::InvalidNumber { .. } => codes::E0101,
+            LexError::InvalidEscape { .. } => codes::E0102,
+            LexError::UnterminatedString { .. } => codes::E0103,
+            LexError::UnterminatedChar { .. } => codes::E0104,
+            LexError::InvalidChar { .. } => codes::E0105,
+            LexError::UnterminatedComment { .. } => codes::E0106,
+            LexError::UnknownCharacter { .. } => codes::E0107,
+            LexError::UnexpectedEnd { .. } => codes::E0108,
+        };
+        let diagnostic = Diagnostic::error(error.to_string()).with_code(code);
+
+        match error {
+            LexError::UnterminatedString { .. } => diagnostic
+                .with_label(span, "this string is never closed")
+                .with_help("add a `\"` at the end of the string"),
+            LexError::UnterminatedChar { .. } => diagnostic
+                .with_label(span, "this character is never closed")
+                .with_help("add a `'` after the character"),
+            LexError::UnterminatedComment { .. } => diagnostic
+                .with_label(span, "this comment is never closed")
+                .with_note("block comments can be nested, each `/*` needs its own `*/`"),
+            LexError::InvalidChar { .. } => diagnostic
+                .with_span(span)
+                .with_help("use double quotes for strings"),
+            LexError::InvalidEscape { reason, .. } | LexError::InvalidNumber { reason, .. } => {
+                diagnostic.with_label(span, reason)
+            }
+            LexError::UnknownCharacter { .. } => {
+                diagnostic.with_label(span, "this character isn't part of the language")
+            }
+            LexError::UnexpectedEnd { .. } => diagnostic.with_span(span),
+        }
+    }
+}
+
+impl From<RuntimeError> for Diagnostic {
+    fn from(error: RuntimeError) -> Self {
+        let span = error.span();
+        let code = match error {
+            RuntimeError::DivisionByZero { .. } => codes::E0501,
+            RuntimeError::IndexOutOfBounds { .. } => codes::E0502,
+            RuntimeError::Overflow { .. } => codes::E0503,
+            RuntimeError::StackOverflow { .. } => codes::E0504,
+            RuntimeError::Uninitialized { .. } => codes::E0505,
+            RuntimeError::FormatArgs { .. } => codes::E0507,
+            RuntimeError::Output { .. } => codes::E0508,
+            RuntimeError::UnassignedField { .. } => codes::E0509,
+        };
+        let diagnostic = Diagnostic::error(error.to_string()).with_code(code);
+
+        match error {
+            RuntimeError::DivisionByZero { .. } => {
+                diagnostic.with_label(span, "the divisor is zero")
+            }
+            RuntimeError::IndexOutOfBounds { .. } => {
+                diagnostic.with_label(span, "this index is out of bounds")
+            }
+            RuntimeError::Overflow { ty, .. } => diagnostic
+                .with_label(span, format!("the result doesn't fit in `{}`", ty.name()))
+                .with_note(format!(
+                    "`{}` goes from {} to {}",
+                    ty.name(),
+                    ty.min(),
+                    ty.max()
+                )),
+            RuntimeError::StackOverflow { .. } => diagnostic
+                .with_label(span, "this call is too deep")
+                .with_help("check that the recursion stops"),
+            RuntimeError::Uninitialized { .. } => {
+                diagnostic.with_label(span, "this variable hasn't been assigned yet")
+            }
+            RuntimeError::FormatArgs { .. } => diagnostic.with_span(span),
+            RuntimeError::Output { .. } => diagnostic.with_label(span, "while printing this"),
+            RuntimeError::UnassignedField { ty, field, .. } => diagnostic
+                .with_label(span, format!("`{}` hasn't been assigned yet", field))
+                .with_help(format!(
+                    "the constructor of `{}` has to assign every field, like `self.{} = ...`",
+                    ty, field
+                )),
+        }
+    }
+}
+
+impl From<ParseError> for Diagnostic {
+    fn from(error: ParseError) -> Self {
+        let error = match error {
+            SyntaxError::Custom {
+                span,
+                code,
+                message,
+            } => return Diagnostic::error(message).with_code(code).with_span(span),
+            SyntaxError::Unexpected(error) => error,
+        };
+        let span = *error.span();
+
+        match error.reason() {
+            RichReason::Custom(message) => Diagnostic::error(message)
+                .with_code(codes::E0201)
+                .with_span(span),
+            RichReason::ExpectedFound { expected, found } => {
+                let found = match found {
+                    Some(token) => format!("`{}`", &**token),
+                    None => "end of input".to_string(),
+                };
+
+                let mut expected = expected.iter().filter_map(pattern).collect::<Vec<_>>();
+                expected.dedup();
+                let label = match expected.as_slice() {
+                    [] => "unexpected here".to_string(),
+                    [expected] => format!("expected {}", expected),
+                    [expected @ .., last] => {
+                        format!("expected one of {} or {}", expected.join(", "), last)
+                    }
+                };
+
+                Diagnostic::error(format!("Unexpected {}", found))
+                    .with_code(codes::E0201)
+                    .with_label(span, label)
+            }
+        }
+    }
+}
+
+/// Describes what the parser expected, `None` for a pattern that doesn't say anything.
+fn pattern(pattern: &RichPattern<String>) -> Option<String> {
+    Some(match pattern {
+        RichPattern::Token(token) => format!("`{}`", &**token),
+        RichPattern::Label(label) => label.to_string(),
+        RichPattern::Identifier(ident) => format!("`{}`", ident),
+        RichPattern::Any => "any token".to_string(),
+        RichPattern::SomethingElse => return None,
+        RichPattern::EndOfInput => "end of input".to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        lexer, parser,
+        source::{SourceMap, SourceName},
+    };
+
+    #[test]
+    fn test_render() {
+        let mut sources = SourceMap::new();
+        let file = sources.add(
+            SourceName::Host("test.ah".into()),
+            "let x = 1;\nlet y = x +;\n",
+        );
+        let other = sources.add(SourceName::Host("other.ah".into()), "fn f() {}");
+        let diagnostic = Diagnostic::error("Something is wrong")
+            .with_code(codes::E0201)
+            .with_label(file.span(21..22), "here")
+            .with_secondary_label(file.span(4..5), "declared here")
+            .with_secondary_label(other.span(3..4), "in another file")
+            .with_note("a note")
+            .with_help("some help");
+        assert_eq!(diagnostic.file(), Some(file));
+
+        let rendered = diagnostic.render_to_string(&sources);
+
+        assert!(rendered.contains("[E0201] Error: Something is wrong"));
+        assert!(rendered.contains("test.ah:2:11"));
+        assert!(rendered.contains("here"));
+        assert!(rendered.contains("declared here"));
+        assert!(rendered.contains("other.ah:1:4"));
+        assert!(rendered.contains("in another file"));
+        assert!(rendered.contains("Note: a note"));
+        assert!(rendered.contains("Help: some help"));
+
+        // Without the file only the message is left
+        let rendered = diagnostic.render_to_string(&SourceMap::new());
+        assert!(rendered.contains("Something is wrong"));
+        assert!(!rendered.contains("declared here"));
+    }
+
+    #[test]
+    fn test_from_errors() {
+        let (_, errors) = lexer::lex("let s = \"abc", FileId::TEST);
+        let diagnostic = Diagnostic::from(errors[0].clone());
+        assert_eq!(diagnostic.span(), Some(FileId::TEST.span(8..12)));
+        assert!(diagnostic.help.is_some());
+
+        let (tokens, _) = lexer::lex("let x = 1 + ;", FileId::TEST);
+        let (_, errors) = parser::parse(&tokens, FileId::TEST);
+        let diagnostic = Diagnostic::from(errors[0].clone());
+        assert_eq!(diagnostic.message, "Unexpected `;`");
+        assert_eq!(diagnostic.code, Some(codes::E0201));
+        assert_eq!(diagnostic.span(), Some(FileId::TEST.span(12..13)));
+        assert!(diagnostic.labels[0]
+            .message
+            .as_ref()
+            .unwrap()
+            .starts_with("expected one of"));
+
+        let (tokens, _) = lexer::lex("let x = 1 2;", FileId::TEST);
+        let (_, errors) = parser::parse(&tokens, FileId::TEST);
+        let diagnostic = Diagnostic::from(errors[0].clone());
+        assert_eq!(diagnostic.message, "Unexpected `2`");
+        assert_eq!(
+            diagnostic.labels[0].message.as_deref(),
+            Some("expected one of `(`, `[`, `.`, operator, `as` or `;`")
+        );
+
+        let (tokens, _) = lexer::lex("let x = 1;\nx + 1 x", FileId::TEST);
+        let (_, errors) = parser::parse(&tokens, FileId::TEST);
+        let codes = errors
+            .into_iter()
+            .map(|error| Diagnostic::from(error).code)
+            .collect::<Vec<_>>();
+        assert_eq!(codes, [Some(codes::E0202)]);
+    }
+
+    #[test]
+    fn test_json() {
+        let mut sources = SourceMap::new();
+        let file = sources.add(
+            SourceName::Path("dir\\test.ah".into()),
+            "let a = 1;\nlet b = \"x\" é;",
+        );
+        let diagnostic = Diagnostic::error("Unexpected `\"é\"`")
+            .with_code(codes::E0101)
+            .with_label(file.span(23..25), "here")
+            .with_secondary_label(file.span(15..19), "after \"this\"")
+            .with_note("a note")
+            .with_note("another")
+            .with_help("remove it");
+
+        assert_eq!(
+            diagnostic.to_json(&sources),
+            concat!(
+                r#"{"file":"dir\\test.ah","severity":"error","code":"E0101","message":"Unexpected `\"é\"`","#,
+                r#""span":{"start":23,"end":25,"line":2,"column":13,"end_line":2,"end_column":14},"#,
+                r#""labels":["#,
+                r#"{"span":{"start":23,"end":25,"line":2,"column":13,"end_line":2,"end_column":14},"message":"here","primary":true},"#,
+                r#"{"span":{"start":15,"end":19,"line":2,"column":5,"end_line":2,"end_column":9},"message":"after \"this\"","primary":false}"#,
+                r#"],"notes":["a note","another"],"help":"remove it"}"#
+            )
+        );
+        assert_eq!(
+            Diagnostic::warning("a\tb").to_json(&sources),
+            r#"{"file":null,"severity":"warning","code":null,"message":"a\tb","span":null,"labels":[],"notes":[],"help":null}"#
+        );
+        // Without the file the spans only have their offsets
+        assert_eq!(
+            Diagnostic::error("x")
+                .with_span(file.span(1..2))
+                .to_json(&SourceMap::new()),
+            r#"{"file":null,"severity":"error","code":null,"message":"x","span":{"start":1,"end":2},"labels":[{"span":{"start":1,"end":2},"message":null,"primary":true}],"notes":[],"help":null}"#
+        );
+    }
+}
@@ -1,23 +1,2219 @@
+//! Runs programs by walking their AST. Programs are resolved and type checked before they're run,
+//! so the interpreter trusts that names exist and that operators get the types they expect, and
+//! only reports the errors that depend on the values, like dividing by zero.
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    fmt,
+    io::{self, Write},
+    rc::{Rc, Weak},
+};
+
+use smol_str::SmolStr;
+use thiserror::Error;
+
+use crate::{
+    checker::{Callee, FloatTy, IntTy, Type, TypeInfo},
+    lexer::Span,
+    parser::ast::{
+        Ast, BinaryOp, Block, Enum, Expr, ExprKind, Function, Ident, ItemKind, Literal, MatchArm,
+        NodeId, Pattern, PatternKind, PayloadPattern, StepOp, Stmt, StmtKind, Struct, TypeExpr,
+        TypeExprKind, UnaryOp, VariantKind,
+    },
+    resolver::{Builtin, Res, Resolutions},
+};
+
+/// How many calls can be nested before the program is stopped, so that infinite recursion is
+/// reported instead of overflowing the interpreter's own stack.
+pub const MAX_CALL_DEPTH: usize = 512;
+
+/// When less than this much stack is left, evaluating an expression first grows the stack by
+/// [`STACK_GROWTH`], so nested calls don't depend on how large the thread's stack is.
+const STACK_RED_ZONE: usize = 128 << 10;
+const STACK_GROWTH: usize = 2 << 20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    /// `i64`, which is also `int`.
+    I64(i64),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    F32(f32),
+    /// `f64`, which is also `float`.
+    F64(f64),
+    Bool(bool),
+    Char(char),
+    String(String),
+    /// `()`
+    Unit,
+    Array(Vec<Value>),
+    /// `range(start, end)`, the end is excluded.
+    Range(i64, i64),
+    Fn(Rc<Function>),
+    Closure(Rc<Closure>),
+    /// The name of a struct, with its item. Calling it runs the constructor.
+    Constructor(NodeId, Rc<Struct>),
+    Struct(Rc<RefCell<Instance>>),
+    /// A variant of an enum with a payload, by its index. Calling it creates the enum.
+    Variant(Rc<Enum>, usize),
+    Enum(Rc<EnumValue>),
+}
+
+/// A value of an enum, unlike structs they're never changed so they don't need to be shared.
+#[derive(Debug)]
+pub struct EnumValue {
+    def: Rc<Enum>,
+    /// The index of the variant.
+    variant: usize,
+    /// The values of its payload, in the order the variant declares them.
+    fields: Vec<Value>,
+}
+
+impl PartialEq for EnumValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.variant == other.variant && self.fields == other.fields
+    }
+}
+
+/// A closure together with the variables it uses from around it.
+#[derive(Debug, PartialEq)]
+pub struct Closure {
+    /// The closure expression.
+    expr: Rc<Expr>,
+    /// The captured variables, which share their values with the variables they were captured
+    /// from.
+    captures: Vec<Variable>,
+}
+
+/// An instance of a struct. Struct values are shared, copying one copies a reference to the same
+/// instance, and its destructor runs once the last reference to it is dropped.
+#[derive(Debug)]
+pub struct Instance {
+    /// The item of the struct, which its trait methods are found by.
+    ty: NodeId,
+    def: Rc<Struct>,
+    /// `None` for the fields the constructor hasn't assigned yet.
+    fields: Vec<Option<Value>>,
+    /// Where the instance goes when it's dropped, to have its destructor run. It's only set once
+    /// the constructor has finished, and only for structs with a destructor.
+    drops: Weak<DropQueue>,
+}
+
+/// The instances that were dropped and are waiting for their destructor to run.
+type DropQueue = RefCell<VecDeque<Instance>>;
+
+impl Instance {
+    fn new(ty: NodeId, def: Rc<Struct>, fields: Vec<Option<Value>>) -> Rc<RefCell<Instance>> {
+        Rc::new(RefCell::new(Instance {
+            ty,
+            def,
+            fields,
+            drops: Weak::new(),
+        }))
+    }
+
+    fn index(&self, field: &str) -> usize {
+        self.def
+            .fields
+            .iter()
+            .position(|declared| declared.name.name == field)
+            .expect("the checker only allows fields that the struct has")
+    }
+
+    /// The value of a field, an error if the constructor hasn't assigned it yet.
+    fn field(&self, field: &str, span: Span) -> Result<&Value, RuntimeError> {
+        self.fields[self.index(field)]
+            .as_ref()
+            .ok_or_else(|| RuntimeError::UnassignedField {
+                span,
+                ty: self.def.name.name.clone(),
+                field: field.into(),
+            })
+    }
+}
+
+impl Drop for Instance {
+    fn drop(&mut self) {
+        // The destructor can't run from here, the interpreter runs it after the statement that
+        // dropped the instance
+        if let Some(drops) = self.drops.upgrade() {
+            drops.borrow_mut().push_back(Instance {
+                ty: self.ty,
+                def: self.def.clone(),
+                fields: std::mem::take(&mut self.fields),
+                drops: Weak::new(),
+            });
+        }
+
+        // Fields are dropped in the reverse order they're declared, like variables
+        while self.fields.pop().is_some() {}
+    }
+}
+
+impl PartialEq for Instance {
+    fn eq(&self, other: &Self) -> bool {
+        self.fields == other.fields
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum RuntimeError {
+    #[error("Attempt to divide by zero")]
+    DivisionByZero { span: Span },
+    #[error("Index out of bounds, the length is {len} but the index is {index}")]
+    IndexOutOfBounds { span: Span, index: i64, len: usize },
+    #[error("Attempt to {op} with overflow")]
+    Overflow {
+        span: Span,
+        op: &'static str,
+        /// The type of the result that didn't fit.
+        ty: IntTy,
+    },
+    #[error("Stack overflow, calls were nested more than {MAX_CALL_DEPTH} times")]
+    StackOverflow { span: Span },
+    #[error("Use of uninitialized variable `{name}`")]
+    Uninitialized { span: Span, name: SmolStr },
+    #[error(
+        "The format string has {placeholders} `{{}}` but {args} argument{} {} given",
+        if *.args == 1 { "" } else { "s" },
+        if *.args == 1 { "was" } else { "were" }
+    )]
+    FormatArgs {
+        span: Span,
+        placeholders: usize,
+        args: usize,
+    },
+    #[error("Failed to write the output, {reason}")]
+    Output { span: Span, reason: String },
+    #[error("Field `{field}` of `{ty}` hasn't been assigned")]
+    UnassignedField {
+        span: Span,
+        ty: SmolStr,
+        field: SmolStr,
+    },
+}
+
+impl RuntimeError {
+    pub fn span(&self) -> Span {
+        match self {
+            RuntimeError::DivisionByZero { span }
+            | RuntimeError::IndexOutOfBounds { span, .. }
+            | RuntimeError::Overflow { span, .. }
+            | RuntimeError::StackOverflow { span }
+            | RuntimeError::Uninitialized { span, .. }
+            | RuntimeError::FormatArgs { span, .. }
+            | RuntimeError::Output { span, .. }
+            | RuntimeError::UnassignedField { span, .. } => *span,
+        }
+    }
+}
 
 /// Scope represents an environment in which variables and functions can be declared and used.
 /// Every time a new scope is entered, the interpreter will add it to the stack of scopes.
 /// When a scope is exited, it will be removed from the stack.
-struct Scope<'a> {
-    pub(crate) variables: Vec<Variable>,
-    pub(crate) functions: Vec<()>,
-    _phantom: std::marker::PhantomData<&'a ()>,
+///
+/// Functions and constants are kept by the interpreter instead, closures can still call them
+/// after the scope that declared them is gone.
+#[derive(Debug, Default)]
+struct Scope {
+    variables: Vec<Variable>,
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        // The variables declared last are dropped first, so their destructors run first
+        while self.variables.pop().is_some() {}
+    }
 }
 
+#[derive(Debug, Clone, PartialEq)]
 struct Variable {
-    name: String,
-    value: VariableValue,
+    name: SmolStr,
+    /// The node that declared the variable, which is what names are resolved to.
+    id: NodeId,
+    value: Slot,
     mutable: bool,
 }
 
-enum VariableValue {
-    I32(i32),
-    F32(f32),
-    String(String),
-    Char(char),
-    Boolean(bool),
-}
\ No newline at end of file
+/// Where the value of a variable is kept, `None` until a variable declared without a value is
+/// assigned. Closures capture the slots of variables, so that assignments inside and outside of
+/// the closure change the same variable.
+type Slot = Rc<RefCell<Option<Value>>>;
+
+fn slot(value: Option<Value>) -> Slot {
+    Rc::new(RefCell::new(value))
+}
+
+/// A step from a variable to the part of it that's assigned.
+enum Step {
+    Index(i64, Span),
+    Field(SmolStr, Span),
+}
+
+/// What a place starts from. Places in temporary values, like `f().x`, can still be assigned to
+/// when the value is a struct that's shared with something else.
+enum Root<'e> {
+    Variable(&'e Expr),
+    Temporary(Value),
+}
+
+/// Why an expression stopped before producing its value.
+enum Unwind {
+    Break(Value),
+    Continue,
+    Return(Value),
+    Error(RuntimeError),
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(error: RuntimeError) -> Self {
+        Unwind::Error(error)
+    }
+}
+
+type Eval<T = Value> = Result<T, Unwind>;
+
+pub struct Interpreter<'out> {
+    names: Resolutions,
+    /// The types of the expressions, which is how literals know what size of number they are.
+    types: TypeInfo,
+    /// Where `print` and the printing macros write to.
+    output: Box<dyn Write + 'out>,
+    scopes: Vec<Scope>,
+    /// The index of the first scope of the function being run. The scopes below it belong to
+    /// the callers, and their variables can't be used.
+    frame: usize,
+    depth: usize,
+    /// Functions and constants, by their item.
+    items: HashMap<NodeId, Value>,
+    /// The closure expressions that have been evaluated, so they're only copied out of the AST
+    /// once.
+    closures: HashMap<NodeId, Rc<Expr>>,
+    /// The instances that were dropped, whose destructors run once the statement that dropped
+    /// them is done.
+    drops: Rc<DropQueue>,
+}
+
+impl Default for Interpreter<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'out> Interpreter<'out> {
+    /// An interpreter that prints to stdout.
+    pub fn new() -> Self {
+        Self::with_output(io::stdout())
+    }
+
+    /// An interpreter that prints to a writer, like a `Vec<u8>` to capture the output.
+    pub fn with_output(output: impl Write + 'out) -> Self {
+        Self {
+            names: Resolutions::default(),
+            types: TypeInfo::default(),
+            output: Box::new(output),
+            // The global scope, which the top level of the program runs in
+            scopes: vec![Scope::default()],
+            frame: 0,
+            depth: 0,
+            items: HashMap::new(),
+            closures: HashMap::new(),
+            drops: Rc::default(),
+        }
+    }
+
+    /// Runs a program that has been resolved and checked without errors, returning the value of
+    /// its last expression or what it returned. If it fails, the globals it declared are dropped
+    /// again, so the REPL's next input starts from the globals before it.
+    pub fn run(
+        &mut self,
+        ast: &Ast,
+        names: Resolutions,
+        types: TypeInfo,
+    ) -> Result<Value, RuntimeError> {
+        self.names.extend(names);
+        self.types.extend(types);
+
+        // Instances dropped since the last program, like its value after it was shown
+        self.run_destructors()?;
+
+        let globals = self.scopes[0].variables.len();
+        let result = match self.eval_stmts(&ast.body) {
+            Ok(value) | Err(Unwind::Return(value)) => Ok(value),
+            Err(Unwind::Error(error)) => Err(error),
+            Err(Unwind::Break(_) | Unwind::Continue) => {
+                unreachable!("the checker rejects `break` and `continue` outside of loops")
+            }
+        };
+        if result.is_err() {
+            // Last declared first, like at the end of a block
+            while self.scopes[0].variables.len() > globals {
+                self.scopes[0].variables.pop();
+            }
+        }
+        let result = match self.run_destructors() {
+            Err(error) if result.is_ok() => Err(error),
+            _ => result,
+        };
+
+        self.flush(ast.body.span)?;
+        result
+    }
+
+    /// Drops the variables of the global scope, which runs the destructors of the instances that
+    /// only they kept alive. Scripts end with this, while the REPL keeps its globals for the next
+    /// input. The span is of the program, for errors while writing the output.
+    pub fn drop_globals(&mut self, span: Span) -> Result<(), RuntimeError> {
+        self.scopes[0] = Scope::default();
+        let result = self.run_destructors();
+        self.flush(span)?;
+        result
+    }
+
+    fn flush(&mut self, span: Span) -> Result<(), RuntimeError> {
+        self.output.flush().map_err(|error| RuntimeError::Output {
+            span,
+            reason: error.to_string(),
+        })
+    }
+
+    /// The value of a variable declared at the top level of a program that was run, `None` if it
+    /// hasn't been assigned or the program stopped before declaring it.
+    pub fn global(&self, id: NodeId) -> Option<Value> {
+        self.scopes[0]
+            .variables
+            .iter()
+            .rev()
+            .find(|variable| variable.id == id)
+            .and_then(|variable| variable.value.borrow().clone())
+    }
+
+    /// Writes text to the output, the span is of what printed it.
+    fn write(&mut self, text: &str, span: Span) -> Result<(), RuntimeError> {
+        self.output
+            .write_all(text.as_bytes())
+            .map_err(|error| RuntimeError::Output {
+                span,
+                reason: error.to_string(),
+            })
+    }
+
+    fn scope(&mut self) -> &mut Scope {
+        self.scopes.last_mut().expect("there is always a scope")
+    }
+
+    fn declare(&mut self, variable: Variable) {
+        self.scope().variables.push(variable);
+    }
+
+    fn variable(&self, id: NodeId) -> &Variable {
+        self.scopes[self.frame..]
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.variables.iter().rev())
+            .find(|variable| variable.id == id)
+            .expect("the resolver only resolves names to variables in scope")
+    }
+
+    /// Evaluates a block in a scope of its own.
+    fn eval_block(&mut self, block: &Block) -> Eval {
+        self.scopes.push(Scope::default());
+        let value = self.eval_stmts(block);
+        self.scopes.pop();
+        self.with_destructors(value)
+    }
+
+    /// Evaluates the statements of a block in the current scope.
+    fn eval_stmts(&mut self, block: &Block) -> Eval {
+        // Functions, structs, methods and variants can be used before they're declared
+        for stmt in &block.stmts {
+            let StmtKind::Item(item) = &stmt.kind else {
+                continue;
+            };
+            match &item.kind {
+                ItemKind::Fn(function) => {
+                    self.items
+                        .entry(item.id)
+                        .or_insert_with(|| Value::Fn(Rc::new(function.clone())));
+                }
+                ItemKind::Struct(def) => {
+                    self.items
+                        .entry(item.id)
+                        .or_insert_with(|| Value::Constructor(item.id, Rc::from(def.clone())));
+                }
+                ItemKind::Impl(def) => {
+                    for method in &def.methods {
+                        self.items
+                            .entry(method.id)
+                            .or_insert_with(|| Value::Fn(Rc::new(method.function.clone())));
+                    }
+                }
+                // The default methods are called for the structs that don't define them
+                ItemKind::Trait(def) => {
+                    for method in &def.methods {
+                        if method.function.body.is_some() {
+                            self.items
+                                .entry(method.id)
+                                .or_insert_with(|| Value::Fn(Rc::new(method.function.clone())));
+                        }
+                    }
+                }
+                // Unit variants are values, the others create them
+                ItemKind::Enum(def) => {
+                    let def = Rc::new((**def).clone());
+                    for (index, variant) in def.variants.iter().enumerate() {
+                        let value = match variant.kind {
+                            VariantKind::Unit => Value::Enum(Rc::new(EnumValue {
+                                def: def.clone(),
+                                variant: index,
+                                fields: Vec::new(),
+                            })),
+                            _ => Value::Variant(def.clone(), index),
+                        };
+                        self.items.entry(variant.id).or_insert(value);
+                    }
+                }
+                ItemKind::Const { .. } => {}
+            }
+        }
+
+        for stmt in &block.stmts {
+            self.eval_stmt(stmt)?;
+            self.run_destructors()?;
+        }
+
+        match &block.tail {
+            Some(tail) => self.eval_expr(tail),
+            None => Ok(Value::Unit),
+        }
+    }
+
+    fn eval_stmt(&mut self, stmt: &Stmt) -> Eval<()> {
+        match &stmt.kind {
+            StmtKind::Let {
+                name,
+                mutable,
+                value,
+                ..
+            } => {
+                let value = match value {
+                    Some(value) => Some(self.eval_expr(value)?),
+                    None => None,
+                };
+                self.declare(Variable {
+                    name: name.name.clone(),
+                    id: stmt.id,
+                    value: slot(value),
+                    mutable: *mutable,
+                });
+            }
+            StmtKind::Item(item) => match &item.kind {
+                ItemKind::Fn(_)
+                | ItemKind::Struct(_)
+                | ItemKind::Impl(_)
+                | ItemKind::Trait(_)
+                | ItemKind::Enum(_) => {}
+                ItemKind::Const { value, .. } => {
+                    let value = self.eval_expr(value)?;
+                    self.items.insert(item.id, value);
+                }
+            },
+            StmtKind::Expr(expr) | StmtKind::Semi(expr) => {
+                self.eval_expr(expr)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn eval_expr(&mut self, expr: &Expr) -> Eval {
+        // Every call in the program recurses through here, so it's where the stack is grown
+        stacker::maybe_grow(STACK_RED_ZONE, STACK_GROWTH, || self.eval_kind(expr))
+    }
+
+    fn eval_kind(&mut self, expr: &Expr) -> Eval {
+        // Each kind of expression is evaluated in a function of its own, which keeps this one's
+        // stack frame small since every call in the program recurses through it
+        match &expr.kind {
+            ExprKind::Error => unreachable!("programs with errors aren't run"),
+            ExprKind::Literal(literal) => Ok(self.eval_literal(expr, literal, false)),
+            ExprKind::Ident(_) => self.eval_ident(expr),
+            ExprKind::Array(elements) => Ok(Value::Array(self.eval_args(elements)?)),
+            ExprKind::Unary(op, operand) => self.eval_unary(expr, *op, operand),
+            ExprKind::Binary(lhs, op, rhs) => self.eval_binary(expr, lhs, *op, rhs),
+            ExprKind::Assign { target, op, value } => self.eval_assign(expr, target, *op, value),
+            ExprKind::Step(target, op) => self.eval_step(expr, target, *op),
+            ExprKind::Cast(value, ty) => {
+                let value = self.eval_expr(value)?;
+                Ok(cast(value, ty))
+            }
+            ExprKind::Call { func, args } => self.eval_call(expr, func, args),
+            ExprKind::MethodCall { receiver, args, .. } => {
+                self.eval_method_call(expr, receiver, args)
+            }
+            ExprKind::Path(..) => Ok(self.method(expr).clone()),
+            ExprKind::Closure { .. } => Ok(self.eval_closure(expr)),
+            ExprKind::Macro { name, args } => self.eval_macro(expr, &name.name, args),
+            ExprKind::Index(value, index) => self.eval_index(expr, value, index),
+            ExprKind::Field(value, field) => self.eval_field(expr, value, field),
+            ExprKind::Block(block) => self.eval_block(block),
+            ExprKind::If {
+                cond,
+                then,
+                otherwise,
+            } => {
+                if self.eval_bool(cond)? {
+                    self.eval_block(then)
+                } else if let Some(otherwise) = otherwise {
+                    self.eval_expr(otherwise)
+                } else {
+                    Ok(Value::Unit)
+                }
+            }
+            ExprKind::Loop(body) => self.eval_loop(body),
+            ExprKind::While { cond, body } => self.eval_while(cond, body),
+            ExprKind::For {
+                binding,
+                iter,
+                body,
+            } => self.eval_for(expr, &binding.name, iter, body),
+            ExprKind::Match { scrutinee, arms } => self.eval_match(scrutinee, arms),
+            ExprKind::Return(value) => Err(Unwind::Return(self.eval_optional(value)?)),
+            ExprKind::Break(value) => Err(Unwind::Break(self.eval_optional(value)?)),
+            ExprKind::Continue => Err(Unwind::Continue),
+        }
+    }
+
+    /// The value of a literal, which is negated for a `-` in front of it so that the smallest
+    /// ints, like `-128i8`, can be written.
+    fn eval_literal(&self, expr: &Expr, literal: &Literal, negated: bool) -> Value {
+        let sign = if negated { -1 } else { 1 };
+        match literal {
+            Literal::Int { value, .. } => match self.types.expr(expr.id) {
+                Some(Type::Float(ty)) => Value::float(sign as f64 * *value as f64, *ty),
+                Some(Type::Int(ty)) => Value::int(sign * i128::from(*value), *ty)
+                    .expect("the checker only allows literals that fit in their type"),
+                _ => unreachable!("the checker gives literals a number type"),
+            },
+            Literal::Float { value, .. } => match self.types.expr(expr.id) {
+                Some(Type::Float(ty)) => Value::float(sign as f64 * value, *ty),
+                _ => unreachable!("the checker gives float literals a float type"),
+            },
+            Literal::String(value) => Value::String(value.clone()),
+            Literal::Char(value) => Value::Char(*value),
+            Literal::Bool(value) => Value::Bool(*value),
+            Literal::Unit => Value::Unit,
+        }
+    }
+
+    fn eval_ident(&mut self, expr: &Expr) -> Eval {
+        match self.names.get(expr.id) {
+            Some(Res::Local(id)) => {
+                let variable = self.variable(id);
+                let value = variable.value.borrow().clone();
+                value.ok_or_else(|| {
+                    RuntimeError::Uninitialized {
+                        span: expr.span,
+                        name: variable.name.clone(),
+                    }
+                    .into()
+                })
+            }
+            Some(Res::Item(id)) => Ok(self.items[&id].clone()),
+            Some(Res::Builtin(_) | Res::TypeParam(_)) | None => {
+                unreachable!("builtins can only be called and every name is resolved to a value")
+            }
+        }
+    }
+
+    fn eval_binary(&mut self, expr: &Expr, lhs: &Expr, op: BinaryOp, rhs: &Expr) -> Eval {
+        let lhs = self.eval_expr(lhs)?;
+
+        // The right hand side of `&&` and `||` is only evaluated if it's needed
+        match (op, &lhs) {
+            (BinaryOp::And, Value::Bool(false)) => return Ok(Value::Bool(false)),
+            (BinaryOp::Or, Value::Bool(true)) => return Ok(Value::Bool(true)),
+            (BinaryOp::And | BinaryOp::Or, _) => return self.eval_expr(rhs),
+            _ => {}
+        }
+
+        let rhs = self.eval_expr(rhs)?;
+        Ok(binary(op, lhs, rhs, expr.span)?)
+    }
+
+    fn eval_assign(
+        &mut self,
+        expr: &Expr,
+        target: &Expr,
+        op: Option<BinaryOp>,
+        value: &Expr,
+    ) -> Eval {
+        let mut value = self.eval_expr(value)?;
+        if let Some(op) = op {
+            let current = self.eval_expr(target)?;
+            value = binary(op, current, value, expr.span)?;
+        }
+        self.assign(target, value)?;
+        Ok(Value::Unit)
+    }
+
+    fn eval_step(&mut self, expr: &Expr, target: &Expr, op: StepOp) -> Eval {
+        let op = match op {
+            StepOp::Increment => BinaryOp::Add,
+            StepOp::Decrement => BinaryOp::Sub,
+        };
+        let value = self.eval_expr(target)?;
+        let one = match (value.as_int(), value.as_float()) {
+            (Some((_, ty)), _) => Value::int(1, ty).expect("every int type has 1"),
+            (_, Some((_, ty))) => Value::float(1.0, ty),
+            _ => unreachable!("the checker only allows stepping numbers"),
+        };
+        let value = binary(op, value, one, expr.span)?;
+        self.assign(target, value)?;
+        Ok(Value::Unit)
+    }
+
+    fn eval_closure(&mut self, expr: &Expr) -> Value {
+        let captures = self
+            .names
+            .captures(expr.id)
+            .iter()
+            .map(|id| self.variable(*id).clone())
+            .collect();
+        let expr = self
+            .closures
+            .entry(expr.id)
+            .or_insert_with(|| Rc::new(expr.clone()))
+            .clone();
+        Value::Closure(Rc::new(Closure { expr, captures }))
+    }
+
+    fn eval_macro(&mut self, expr: &Expr, name: &str, args: &[Expr]) -> Eval {
+        let args = self.eval_args(args)?;
+        let mut text = format(args, expr.span)?;
+        match name {
+            "println" => text.push('\n'),
+            "print" => {}
+            "format" => return Ok(Value::String(text)),
+            _ => unreachable!("the checker rejects unknown macros"),
+        }
+        self.write(&text, expr.span)?;
+        Ok(Value::Unit)
+    }
+
+    fn eval_index(&mut self, expr: &Expr, value: &Expr, index: &Expr) -> Eval {
+        let value = self.eval_expr(value)?;
+        let index = self.eval_expr(index)?;
+        let (Value::Array(mut elements), Value::I64(index)) = (value, index) else {
+            unreachable!("the checker only allows indexing arrays with ints")
+        };
+
+        let len = elements.len();
+        match usize::try_from(index) {
+            Ok(i) if i < len => Ok(elements.swap_remove(i)),
+            _ => Err(RuntimeError::IndexOutOfBounds {
+                span: expr.span,
+                index,
+                len,
+            }
+            .into()),
+        }
+    }
+
+    fn eval_field(&mut self, expr: &Expr, value: &Expr, field: &Ident) -> Eval {
+        let Value::Struct(instance) = self.eval_expr(value)? else {
+            unreachable!("the checker only allows fields of structs")
+        };
+        let value = instance.borrow().field(&field.name, expr.span)?.clone();
+        Ok(value)
+    }
+
+    fn eval_loop(&mut self, body: &Block) -> Eval {
+        loop {
+            match self.eval_block(body) {
+                Ok(_) | Err(Unwind::Continue) => {}
+                Err(Unwind::Break(value)) => return Ok(value),
+                Err(unwind) => return Err(unwind),
+            }
+        }
+    }
+
+    fn eval_while(&mut self, cond: &Expr, body: &Block) -> Eval {
+        while self.eval_bool(cond)? {
+            match self.eval_block(body) {
+                Ok(_) | Err(Unwind::Continue) => {}
+                Err(Unwind::Break(_)) => break,
+                Err(unwind) => return Err(unwind),
+            }
+        }
+        Ok(Value::Unit)
+    }
+
+    fn eval_for(&mut self, expr: &Expr, binding: &SmolStr, iter: &Expr, body: &Block) -> Eval {
+        let values: Box<dyn Iterator<Item = Value>> = match self.eval_expr(iter)? {
+            Value::Array(elements) => Box::new(elements.into_iter()),
+            Value::Range(start, end) => Box::new((start..end).map(Value::I64)),
+            _ => unreachable!("the checker only allows iterating over arrays and ranges"),
+        };
+
+        for value in values {
+            // The loop variable gets a scope around the body's, so every iteration has its own
+            self.scopes.push(Scope::default());
+            self.declare(Variable {
+                name: binding.clone(),
+                id: expr.id,
+                value: slot(Some(value)),
+                mutable: false,
+            });
+            let result = self.eval_block(body);
+            self.scopes.pop();
+
+            match self.with_destructors(result) {
+                Ok(_) | Err(Unwind::Continue) => {}
+                Err(Unwind::Break(_)) => break,
+                Err(unwind) => return Err(unwind),
+            }
+        }
+        Ok(Value::Unit)
+    }
+
+    fn eval_match(&mut self, scrutinee: &Expr, arms: &[MatchArm]) -> Eval {
+        let value = self.eval_expr(scrutinee)?;
+        for arm in arms {
+            self.scopes.push(Scope::default());
+            let result = match self.eval_arm(arm, &value) {
+                Ok(true) => Some(self.eval_expr(&arm.body)),
+                Ok(false) => None,
+                Err(unwind) => Some(Err(unwind)),
+            };
+            self.scopes.pop();
+
+            if let Some(result) = result {
+                return self.with_destructors(result);
+            }
+        }
+
+        unreachable!("the checker only allows matches that cover every value")
+    }
+
+    /// Whether an arm's pattern matches the value and its guard is true, with the pattern's
+    /// bindings declared in the current scope.
+    fn eval_arm(&mut self, arm: &MatchArm, value: &Value) -> Eval<bool> {
+        if !self.eval_pattern(&arm.pattern, value)? {
+            return Ok(false);
+        }
+        match &arm.guard {
+            Some(guard) => self.eval_bool(guard),
+            None => Ok(true),
+        }
+    }
+
+    fn eval_optional(&mut self, expr: &Option<Box<Expr>>) -> Eval {
+        match expr {
+            Some(expr) => self.eval_expr(expr),
+            None => Ok(Value::Unit),
+        }
+    }
+
+    fn eval_bool(&mut self, expr: &Expr) -> Eval<bool> {
+        match self.eval_expr(expr)? {
+            Value::Bool(value) => Ok(value),
+            _ => unreachable!("the checker only allows bools as conditions"),
+        }
+    }
+
+    fn eval_args(&mut self, args: &[Expr]) -> Eval<Vec<Value>> {
+        args.iter().map(|arg| self.eval_expr(arg)).collect()
+    }
+
+    fn eval_unary(&mut self, expr: &Expr, op: UnaryOp, operand: &Expr) -> Eval {
+        // The smallest ints can only be written negated, since they have no positive counterpart
+        if let (UnaryOp::Neg, ExprKind::Literal(literal @ Literal::Int { .. })) =
+            (op, &operand.kind)
+        {
+            return Ok(self.eval_literal(operand, literal, true));
+        }
+
+        let value = self.eval_expr(operand)?;
+        Ok(match (op, value.as_int(), value.as_float()) {
+            (UnaryOp::Neg, Some((value, ty)), _) => {
+                Value::int(-value, ty).ok_or(RuntimeError::Overflow {
+                    span: expr.span,
+                    op: "negate",
+                    ty,
+                })?
+            }
+            (UnaryOp::Neg, _, Some((value, ty))) => Value::float(-value, ty),
+            (UnaryOp::BitNot, Some((value, ty)), _) => Value::wrapping(!value, ty),
+            (UnaryOp::Not, ..) => match value {
+                Value::Bool(value) => Value::Bool(!value),
+                _ => unreachable!("the checker only allows `!` on bools"),
+            },
+            _ => unreachable!("the checker only allows unary operators on matching types"),
+        })
+    }
+
+    /// Binds a pattern in the current scope if it matches the value. An alternative of a `|`
+    /// pattern that doesn't match can leave some of its bindings behind, the alternative that
+    /// matches declares them again.
+    fn eval_pattern(&mut self, pattern: &Pattern, value: &Value) -> Eval<bool> {
+        match &pattern.kind {
+            PatternKind::Wildcard => Ok(true),
+            PatternKind::Literal(literal) => Ok(self.eval_expr(literal)? == *value),
+            PatternKind::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                let start = self.eval_expr(start)?;
+                let end = self.eval_expr(end)?;
+                let op = if *inclusive {
+                    BinaryOp::Le
+                } else {
+                    BinaryOp::Lt
+                };
+                let (above, below) = (
+                    binary(BinaryOp::Le, start, value.clone(), pattern.span)?,
+                    binary(op, value.clone(), end, pattern.span)?,
+                );
+                Ok(above == Value::Bool(true) && below == Value::Bool(true))
+            }
+            PatternKind::Binding(name, subpattern) => {
+                if let Some(subpattern) = subpattern {
+                    if !self.eval_pattern(subpattern, value)? {
+                        return Ok(false);
+                    }
+                }
+                // The later alternatives of `|` patterns declare the first one's variables
+                let id = match self.names.get(pattern.id) {
+                    Some(Res::Local(id)) => id,
+                    _ => pattern.id,
+                };
+                self.declare(Variable {
+                    name: name.clone(),
+                    id,
+                    value: slot(Some(value.clone())),
+                    mutable: false,
+                });
+                Ok(true)
+            }
+            PatternKind::Or(alternatives) => {
+                for alternative in alternatives {
+                    if self.eval_pattern(alternative, value)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            PatternKind::Variant { name, payload, .. } => {
+                let Value::Enum(value) = value else {
+                    unreachable!("the checker only allows variant patterns for enums")
+                };
+                let variant = &value.def.variants[value.variant];
+                if variant.name.name != name.name {
+                    return Ok(false);
+                }
+
+                match payload {
+                    PayloadPattern::Unit => Ok(true),
+                    PayloadPattern::Tuple(patterns) => {
+                        for (pattern, field) in patterns.iter().zip(&value.fields) {
+                            if !self.eval_pattern(pattern, field)? {
+                                return Ok(false);
+                            }
+                        }
+                        Ok(true)
+                    }
+                    PayloadPattern::Struct { fields, .. } => {
+                        let VariantKind::Struct(declared) = &variant.kind else {
+                            unreachable!(
+                                "the checker only allows field patterns for struct variants"
+                            )
+                        };
+                        for field in fields {
+                            let index = declared
+                                .iter()
+                                .position(|declared| declared.name.name == field.name.name)
+                                .expect("the checker only allows fields that the variant has");
+                            if !self.eval_pattern(&field.pattern, &value.fields[index])? {
+                                return Ok(false);
+                            }
+                        }
+                        Ok(true)
+                    }
+                }
+            }
+        }
+    }
+
+    fn eval_call(&mut self, expr: &Expr, func: &Expr, args: &[Expr]) -> Eval {
+        if let Some(Res::Builtin(builtin)) = self.names.get(func.id) {
+            let args = self.eval_args(args)?;
+            return Ok(self.call_builtin(builtin, args, expr.span)?);
+        }
+
+        let callee = self.eval_expr(func)?;
+        let arg_values = self.eval_args(args)?;
+        let (value, params) = self.call(callee, arg_values, expr.span)?;
+        self.write_back(args, params)?;
+        Ok(value)
+    }
+
+    /// Calls the method the checker found for a method call, with the receiver as `self`. Calls
+    /// of trait methods on trait objects and type parameters call the method of the receiver's
+    /// struct.
+    fn eval_method_call(&mut self, expr: &Expr, receiver: &Expr, args: &[Expr]) -> Eval {
+        let this = self.eval_expr(receiver)?;
+        let method = match self.types.method(expr.id) {
+            Some(Callee::Dynamic(name)) => {
+                let Value::Struct(instance) = &this else {
+                    unreachable!("only structs implement traits")
+                };
+                let id = self
+                    .types
+                    .trait_method(instance.borrow().ty, name)
+                    .expect("the checker only allows trait objects of structs with an `impl`");
+                self.items[&id].clone()
+            }
+            _ => self.method(expr).clone(),
+        };
+        let mut arg_values = vec![this];
+        arg_values.extend(self.eval_args(args)?);
+        let (value, mut params) = self.call(method, arg_values, expr.span)?;
+
+        if let Some(value) = params.remove(0) {
+            self.assign(receiver, value)?;
+        }
+        self.write_back(args, params)?;
+        Ok(value)
+    }
+
+    /// The function a method call or path refers to, when it's known before the call.
+    fn method(&self, expr: &Expr) -> &Value {
+        let Some(Callee::Static(id)) = self.types.method(expr.id) else {
+            unreachable!("the checker resolves every method")
+        };
+        &self.items[id]
+    }
+
+    /// Changes the places given to `mut` parameters to the final values of the parameters.
+    fn write_back(&mut self, args: &[Expr], params: Vec<Option<Value>>) -> Eval<()> {
+        for (arg, param) in args.iter().zip(params) {
+            if let Some(value) = param {
+                self.assign(arg, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn call_builtin(
+        &mut self,
+        builtin: Builtin,
+        args: Vec<Value>,
+        span: Span,
+    ) -> Result<Value, RuntimeError> {
+        Ok(match builtin {
+            Builtin::Range => match args.as_slice() {
+                [Value::I64(start), Value::I64(end)] => Value::Range(*start, *end),
+                _ => unreachable!("the checker only allows `range` with two ints"),
+            },
+            Builtin::Len => match args.as_slice() {
+                [Value::Array(elements)] => Value::I64(elements.len() as i64),
+                [Value::String(string)] => Value::I64(string.chars().count() as i64),
+                _ => unreachable!("the checker only allows `len` on arrays and strings"),
+            },
+            Builtin::Fmt => Value::String(format(args, span)?),
+            Builtin::Print => {
+                let mut text = format(args, span)?;
+                text.push('\n');
+                self.write(&text, span)?;
+                Value::Unit
+            }
+            Builtin::WrappingAdd
+            | Builtin::WrappingSub
+            | Builtin::WrappingMul
+            | Builtin::SaturatingAdd
+            | Builtin::SaturatingSub
+            | Builtin::SaturatingMul => {
+                let [(a, ty), (b, _)] = [&args[0], &args[1]].map(|arg| {
+                    arg.as_int()
+                        .expect("the checker only allows these builtins on ints")
+                });
+                overflowing(builtin, a, b, ty)
+            }
+        })
+    }
+
+    /// Calls a function, closure, constructor or variant, returning its result and the final values of
+    /// its `mut` parameters.
+    fn call(
+        &mut self,
+        callee: Value,
+        args: Vec<Value>,
+        span: Span,
+    ) -> Result<(Value, Vec<Option<Value>>), RuntimeError> {
+        // Creating an enum doesn't run any code, so it doesn't need a frame
+        if let Value::Variant(def, variant) = callee {
+            let value = EnumValue {
+                def,
+                variant,
+                fields: args,
+            };
+            return Ok((Value::Enum(Rc::new(value)), Vec::new()));
+        }
+
+        // The parameters are always the first scope of the call
+        self.in_frame(span, |interpreter| match &callee {
+            Value::Fn(function) => {
+                // Methods take `self` before the other parameters
+                let this = SmolStr::new_static("self");
+                let receiver = function
+                    .receiver
+                    .iter()
+                    .map(|receiver| (receiver.id, &this, receiver.mutable));
+                let params = receiver.chain(
+                    function
+                        .params
+                        .iter()
+                        .map(|param| (param.id, &param.name.name, param.mutable)),
+                );
+                interpreter.scopes.push(Scope {
+                    variables: parameters(params, args),
+                });
+                let body = function
+                    .body
+                    .as_ref()
+                    .expect("only required trait methods have no body, which aren't values");
+                interpreter.eval_block(body)
+            }
+            Value::Closure(closure) => {
+                let ExprKind::Closure { params, body, .. } = &closure.expr.kind else {
+                    unreachable!("closures are made from closure expressions")
+                };
+                let params = params
+                    .iter()
+                    .map(|param| (param.id, &param.name.name, param.mutable));
+                interpreter.scopes.push(Scope {
+                    variables: parameters(params, args),
+                });
+                interpreter.scopes.push(Scope {
+                    variables: closure.captures.clone(),
+                });
+                interpreter.eval_expr(body)
+            }
+            Value::Constructor(id, def) => interpreter.construct(*id, def, args, span),
+            _ => unreachable!("the checker only allows calling functions"),
+        })
+    }
+
+    /// Runs code in a frame of its own, like the body of a function, returning its value and the
+    /// final values of the `mut` parameters in its first scope.
+    fn in_frame(
+        &mut self,
+        span: Span,
+        run: impl FnOnce(&mut Self) -> Eval,
+    ) -> Result<(Value, Vec<Option<Value>>), RuntimeError> {
+        if self.depth == MAX_CALL_DEPTH {
+            return Err(RuntimeError::StackOverflow { span });
+        }
+
+        let caller_frame = std::mem::replace(&mut self.frame, self.scopes.len());
+        self.depth += 1;
+
+        let result = run(self);
+
+        let params = self.scopes[self.frame]
+            .variables
+            .iter()
+            .map(|param| {
+                param
+                    .mutable
+                    .then(|| param.value.borrow().clone())
+                    .flatten()
+            })
+            .collect();
+        while self.scopes.len() > self.frame {
+            self.scopes.pop();
+        }
+        self.frame = caller_frame;
+        self.depth -= 1;
+
+        let value = match result {
+            Ok(value) | Err(Unwind::Return(value)) => value,
+            Err(Unwind::Error(error)) => return Err(error),
+            Err(Unwind::Break(_) | Unwind::Continue) => {
+                unreachable!("`break` and `continue` can't leave a function")
+            }
+        };
+        self.run_destructors()?;
+        Ok((value, params))
+    }
+
+    /// Creates an instance of a struct. A constructor with a body runs with the instance as
+    /// `self`, in a scope after the parameters.
+    fn construct(&mut self, id: NodeId, def: &Rc<Struct>, args: Vec<Value>, span: Span) -> Eval {
+        let constructor = def
+            .constructor
+            .as_ref()
+            .expect("the checker only allows creating structs with a constructor");
+
+        let Some(body) = &constructor.body else {
+            // Without a body, the arguments are the fields in order
+            self.scopes.push(Scope::default());
+            let instance = Instance::new(id, def.clone(), args.into_iter().map(Some).collect());
+            self.track(&instance);
+            return Ok(Value::Struct(instance));
+        };
+
+        let params = constructor
+            .params
+            .iter()
+            .map(|param| (param.id, &param.name.name, param.mutable));
+        self.scopes.push(Scope {
+            variables: parameters(params, args),
+        });
+        let instance = Instance::new(id, def.clone(), vec![None; def.fields.len()]);
+        self.scopes.push(Scope {
+            variables: vec![Variable {
+                name: "self".into(),
+                id: constructor.id,
+                value: slot(Some(Value::Struct(instance.clone()))),
+                mutable: true,
+            }],
+        });
+        match self.eval_block(body) {
+            Ok(_) | Err(Unwind::Return(_)) => {}
+            Err(unwind) => return Err(unwind),
+        }
+
+        let unassigned = instance.borrow().fields.iter().position(Option::is_none);
+        if let Some(i) = unassigned {
+            return Err(RuntimeError::UnassignedField {
+                span,
+                ty: def.name.name.clone(),
+                field: def.fields[i].name.name.clone(),
+            }
+            .into());
+        }
+        self.track(&instance);
+        Ok(Value::Struct(instance))
+    }
+
+    /// Makes the destructor of an instance run when it's dropped. Instances whose constructor
+    /// didn't finish aren't destroyed, since they were never created.
+    fn track(&self, instance: &Rc<RefCell<Instance>>) {
+        let mut instance = instance.borrow_mut();
+        let has_destructor = instance
+            .def
+            .destructor
+            .as_ref()
+            .is_some_and(|destructor| destructor.body.is_some());
+        if has_destructor {
+            instance.drops = Rc::downgrade(&self.drops);
+        }
+    }
+
+    /// Runs the destructors of the instances that were dropped, including the ones that are
+    /// dropped by the destructors.
+    fn run_destructors(&mut self) -> Result<(), RuntimeError> {
+        loop {
+            let Some(instance) = self.drops.borrow_mut().pop_front() else {
+                return Ok(());
+            };
+            let def = instance.def.clone();
+            let Some(destructor) = &def.destructor else {
+                unreachable!("only instances with a destructor are dropped into the queue")
+            };
+            let Some(body) = &destructor.body else {
+                unreachable!("only destructors with a body are run")
+            };
+
+            let this = Variable {
+                name: "self".into(),
+                id: destructor.id,
+                value: slot(Some(Value::Struct(Rc::new(RefCell::new(instance))))),
+                mutable: true,
+            };
+            self.in_frame(destructor.span, |interpreter| {
+                interpreter.scopes.push(Scope {
+                    variables: vec![this],
+                });
+                interpreter.eval_block(body)
+            })?;
+        }
+    }
+
+    /// Runs the destructors of the instances dropped by the code that gave the result. An error
+    /// from that code is kept over one from a destructor.
+    fn with_destructors<T>(&mut self, result: Eval<T>) -> Eval<T> {
+        match self.run_destructors() {
+            Err(error) if !matches!(result, Err(Unwind::Error(_))) => Err(error.into()),
+            _ => result,
+        }
+    }
+
+    /// Assigns to a variable or a part of it, like an element of an array or a field.
+    fn assign(&mut self, place: &Expr, value: Value) -> Eval<()> {
+        let (root, path) = self.place(place)?;
+        let root = match root {
+            Root::Variable(root) => root,
+            Root::Temporary(mut target) => return Ok(assign_path(&mut target, &path, value)?),
+        };
+        let Some(Res::Local(id)) = self.names.get(root.id) else {
+            unreachable!("the checker only allows assigning to variables")
+        };
+
+        let variable = self.variable(id);
+        let mut slot = variable.value.borrow_mut();
+        if path.is_empty() {
+            *slot = Some(value);
+            return Ok(());
+        }
+
+        let Some(target) = slot.as_mut() else {
+            return Err(RuntimeError::Uninitialized {
+                span: root.span,
+                name: variable.name.clone(),
+            }
+            .into());
+        };
+        Ok(assign_path(target, &path, value)?)
+    }
+
+    /// What a place starts from, and the steps from it to the place.
+    fn place<'e>(&mut self, place: &'e Expr) -> Eval<(Root<'e>, Vec<Step>)> {
+        match &place.kind {
+            ExprKind::Ident(_) => Ok((Root::Variable(place), Vec::new())),
+            ExprKind::Index(value, index) => {
+                let (root, mut path) = self.place(value)?;
+                let Value::I64(index) = self.eval_expr(index)? else {
+                    unreachable!("the checker only allows indexing with ints")
+                };
+                path.push(Step::Index(index, place.span));
+                Ok((root, path))
+            }
+            ExprKind::Field(value, field) => {
+                let (root, mut path) = self.place(value)?;
+                path.push(Step::Field(field.name.clone(), place.span));
+                Ok((root, path))
+            }
+            _ => Ok((Root::Temporary(self.eval_expr(place)?), Vec::new())),
+        }
+    }
+}
+
+/// Assigns to the part of a value at the end of a path.
+fn assign_path(target: &mut Value, path: &[Step], value: Value) -> Result<(), RuntimeError> {
+    let Some((step, rest)) = path.split_first() else {
+        *target = value;
+        return Ok(());
+    };
+
+    match (step, target) {
+        (Step::Index(index, span), Value::Array(elements)) => {
+            let len = elements.len();
+            let element = usize::try_from(*index)
+                .ok()
+                .and_then(|i| elements.get_mut(i))
+                .ok_or(RuntimeError::IndexOutOfBounds {
+                    span: *span,
+                    index: *index,
+                    len,
+                })?;
+            assign_path(element, rest, value)
+        }
+        (Step::Field(field, span), Value::Struct(instance)) => {
+            let mut instance = instance.borrow_mut();
+            let i = instance.index(field);
+            if rest.is_empty() {
+                instance.fields[i] = Some(value);
+                return Ok(());
+            }
+            let def = instance.def.clone();
+            match &mut instance.fields[i] {
+                Some(target) => assign_path(target, rest, value),
+                None => Err(RuntimeError::UnassignedField {
+                    span: *span,
+                    ty: def.name.name.clone(),
+                    field: field.clone(),
+                }),
+            }
+        }
+        _ => unreachable!("the checker only allows indexing arrays and fields of structs"),
+    }
+}
+
+/// The variables for the parameters of a call.
+fn parameters<'p>(
+    params: impl Iterator<Item = (NodeId, &'p SmolStr, bool)>,
+    args: Vec<Value>,
+) -> Vec<Variable> {
+    params
+        .zip(args)
+        .map(|((id, name, mutable), value)| Variable {
+            name: name.clone(),
+            id,
+            value: slot(Some(value)),
+            mutable,
+        })
+        .collect()
+}
+
+/// Replaces each `{}` in the format string, which is the first argument, with the next argument.
+/// `{{` and `}}` are a literal brace.
+fn format(args: Vec<Value>, span: Span) -> Result<String, RuntimeError> {
+    let mut args = args.into_iter();
+    let Some(Value::String(format)) = args.next() else {
+        unreachable!("the checker requires a format string")
+    };
+    let arg_count = args.len();
+
+    let mut text = String::new();
+    let mut placeholders = 0;
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                text.push(c);
+            }
+            ('{', Some('}')) => {
+                chars.next();
+                placeholders += 1;
+                if let Some(arg) = args.next() {
+                    text.push_str(&arg.to_string());
+                }
+            }
+            _ => text.push(c),
+        }
+    }
+
+    if placeholders != arg_count {
+        return Err(RuntimeError::FormatArgs {
+            span,
+            placeholders,
+            args: arg_count,
+        });
+    }
+    Ok(text)
+}
+
+fn binary(op: BinaryOp, lhs: Value, rhs: Value, span: Span) -> Result<Value, RuntimeError> {
+    use BinaryOp::*;
+
+    if let (Some((a, ty)), Some((b, _))) = (lhs.as_int(), rhs.as_int()) {
+        return int_binary(op, a, b, ty, span);
+    }
+    if let (Some((a, ty)), Some((b, _))) = (lhs.as_float(), rhs.as_float()) {
+        return Ok(match op {
+            Add => Value::float(a + b, ty),
+            Sub => Value::float(a - b, ty),
+            Mul => Value::float(a * b, ty),
+            Div => Value::float(a / b, ty),
+            Rem => Value::float(a % b, ty),
+            // NaN isn't ordered, so every comparison with it is false
+            Lt => Value::Bool(a < b),
+            Gt => Value::Bool(a > b),
+            Le => Value::Bool(a <= b),
+            Ge => Value::Bool(a >= b),
+            Eq => Value::Bool(a == b),
+            Ne => Value::Bool(a != b),
+            _ => unreachable!("the checker only allows arithmetic and comparisons on floats"),
+        });
+    }
+
+    Ok(match (op, lhs, rhs) {
+        (Add, Value::String(a), Value::String(b)) => Value::String(a + &b),
+        (BitAnd, Value::Bool(a), Value::Bool(b)) => Value::Bool(a & b),
+        (BitOr, Value::Bool(a), Value::Bool(b)) => Value::Bool(a | b),
+        (BitXor, Value::Bool(a), Value::Bool(b)) => Value::Bool(a ^ b),
+        (Eq, a, b) => Value::Bool(a == b),
+        (Ne, a, b) => Value::Bool(a != b),
+        (Lt | Gt | Le | Ge, a, b) => {
+            let ordering = match (a, b) {
+                (Value::Char(a), Value::Char(b)) => a.cmp(&b),
+                (Value::String(a), Value::String(b)) => a.cmp(&b),
+                _ => unreachable!("the checker only allows comparing numbers, chars and strings"),
+            };
+            Value::Bool(match op {
+                Lt => ordering.is_lt(),
+                Gt => ordering.is_gt(),
+                Le => ordering.is_le(),
+                _ => ordering.is_ge(),
+            })
+        }
+        _ => unreachable!("the checker only allows binary operators on matching types"),
+    })
+}
+
+/// Applies a binary operator to two ints of type `ty`. The result is worked out with more bits
+/// than any int has, and is an error if it doesn't fit in `ty`.
+fn int_binary(
+    op: BinaryOp,
+    a: i128,
+    b: i128,
+    ty: IntTy,
+    span: Span,
+) -> Result<Value, RuntimeError> {
+    use BinaryOp::*;
+
+    let overflow = |op| RuntimeError::Overflow { span, op, ty };
+    let fit = |value: Option<i128>, op| {
+        value
+            .and_then(|value| Value::int(value, ty))
+            .ok_or(overflow(op))
+    };
+
+    Ok(match op {
+        Add => fit(a.checked_add(b), "add")?,
+        Sub => fit(a.checked_sub(b), "subtract")?,
+        Mul => fit(a.checked_mul(b), "multiply")?,
+        Div | Rem if b == 0 => return Err(RuntimeError::DivisionByZero { span }),
+        Div => fit(Some(a / b), "divide")?,
+        // The remainder always fits, but `int::MIN % -1` overflows in the division it comes from
+        Rem => {
+            fit(Some(a / b), "calculate the remainder")?;
+            fit(Some(a % b), "calculate the remainder")?
+        }
+        BitAnd => Value::wrapping(a & b, ty),
+        BitOr => Value::wrapping(a | b, ty),
+        BitXor => Value::wrapping(a ^ b, ty),
+        // The amount has to be less than the number of bits, the bits shifted out are dropped
+        Shl | Shr => {
+            let amount = u32::try_from(b)
+                .ok()
+                .filter(|amount| *amount < ty.bits())
+                .ok_or(overflow(if op == Shl {
+                    "shift left"
+                } else {
+                    "shift right"
+                }))?;
+            match op {
+                Shl => Value::wrapping(a << amount, ty),
+                _ => Value::wrapping(a >> amount, ty),
+            }
+        }
+        Lt => Value::Bool(a < b),
+        Gt => Value::Bool(a > b),
+        Le => Value::Bool(a <= b),
+        Ge => Value::Bool(a >= b),
+        Eq => Value::Bool(a == b),
+        Ne => Value::Bool(a != b),
+        And | Or => unreachable!("the checker only allows `&&` and `||` on bools"),
+    })
+}
+
+/// The `wrapping_*` and `saturating_*` builtins, which can't overflow.
+fn overflowing(builtin: Builtin, a: i128, b: i128, ty: IntTy) -> Value {
+    let saturate = |value: Option<i128>| {
+        // Only a product of two 64 bit ints can overflow an `i128`, its sign is known from theirs
+        let value = value.unwrap_or(if (a < 0) == (b < 0) {
+            i128::MAX
+        } else {
+            i128::MIN
+        });
+        Value::int(value.clamp(ty.min(), ty.max()), ty).expect("the value was clamped")
+    };
+
+    match builtin {
+        Builtin::WrappingAdd => Value::wrapping(a + b, ty),
+        Builtin::WrappingSub => Value::wrapping(a - b, ty),
+        Builtin::WrappingMul => Value::wrapping(a.wrapping_mul(b), ty),
+        Builtin::SaturatingAdd => saturate(a.checked_add(b)),
+        Builtin::SaturatingSub => saturate(a.checked_sub(b)),
+        Builtin::SaturatingMul => saturate(a.checked_mul(b)),
+        _ => unreachable!("only called for the overflowing builtins"),
+    }
+}
+
+/// Converts a value to the type of a cast, like Rust's `as`. Ints are cast to smaller ints by
+/// dropping the high bits, and floats are cast to ints by rounding towards zero, saturating at
+/// the limits, with NaN becoming 0.
+fn cast(value: Value, ty: &TypeExpr) -> Value {
+    let TypeExprKind::Named(name) = &ty.kind else {
+        return value;
+    };
+
+    let int = match &value {
+        Value::Bool(value) => Some(i128::from(*value)),
+        Value::Char(value) => Some(i128::from(u32::from(*value))),
+        value => value.as_int().map(|(value, _)| value),
+    };
+    match (Type::primitive(name), int, value.as_float()) {
+        (Some(Type::Int(ty)), Some(int), _) => Value::wrapping(int, ty),
+        (Some(Type::Int(ty)), _, Some((float, _))) => {
+            // `as` from a float to an int saturates, and NaN is 0
+            let int = (float as i128).clamp(ty.min(), ty.max());
+            Value::int(int, ty).expect("the value was clamped")
+        }
+        (Some(Type::Float(ty)), Some(int), _) => Value::float(int as f64, ty),
+        (Some(Type::Float(ty)), _, Some((float, _))) => Value::float(float, ty),
+        _ => value,
+    }
+}
+
+impl Value {
+    /// An int of type `ty`, `None` if the value doesn't fit in it.
+    pub fn int(value: i128, ty: IntTy) -> Option<Value> {
+        Some(match ty {
+            IntTy::I8 => Value::I8(value.try_into().ok()?),
+            IntTy::I16 => Value::I16(value.try_into().ok()?),
+            IntTy::I32 => Value::I32(value.try_into().ok()?),
+            IntTy::I64 => Value::I64(value.try_into().ok()?),
+            IntTy::U8 => Value::U8(value.try_into().ok()?),
+            IntTy::U16 => Value::U16(value.try_into().ok()?),
+            IntTy::U32 => Value::U32(value.try_into().ok()?),
+            IntTy::U64 => Value::U64(value.try_into().ok()?),
+        })
+    }
+
+    /// An int of type `ty` that wraps around at its bounds, keeping the low bits of the value.
+    pub fn wrapping(value: i128, ty: IntTy) -> Value {
+        match ty {
+            IntTy::I8 => Value::I8(value as i8),
+            IntTy::I16 => Value::I16(value as i16),
+            IntTy::I32 => Value::I32(value as i32),
+            IntTy::I64 => Value::I64(value as i64),
+            IntTy::U8 => Value::U8(value as u8),
+            IntTy::U16 => Value::U16(value as u16),
+            IntTy::U32 => Value::U32(value as u32),
+            IntTy::U64 => Value::U64(value as u64),
+        }
+    }
+
+    /// A float of type `ty`, rounded to the nearest `f32` for those.
+    pub fn float(value: f64, ty: FloatTy) -> Value {
+        match ty {
+            FloatTy::F32 => Value::F32(value as f32),
+            FloatTy::F64 => Value::F64(value),
+        }
+    }
+
+    /// The value and type of an int, big enough to hold every int.
+    pub fn as_int(&self) -> Option<(i128, IntTy)> {
+        Some(match *self {
+            Value::I8(value) => (value.into(), IntTy::I8),
+            Value::I16(value) => (value.into(), IntTy::I16),
+            Value::I32(value) => (value.into(), IntTy::I32),
+            Value::I64(value) => (value.into(), IntTy::I64),
+            Value::U8(value) => (value.into(), IntTy::U8),
+            Value::U16(value) => (value.into(), IntTy::U16),
+            Value::U32(value) => (value.into(), IntTy::U32),
+            Value::U64(value) => (value.into(), IntTy::U64),
+            _ => return None,
+        })
+    }
+
+    /// The value and type of a float. Every `f32` is exactly an `f64`.
+    pub fn as_float(&self) -> Option<(f64, FloatTy)> {
+        match *self {
+            Value::F32(value) => Some((value.into(), FloatTy::F32)),
+            Value::F64(value) => Some((value, FloatTy::F64)),
+            _ => None,
+        }
+    }
+
+    /// Writes the value like a literal, which is how values inside of arrays are shown.
+    fn fmt_literal(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::String(value) => write!(f, "{:?}", value),
+            Value::Char(value) => write!(f, "{:?}", value),
+            value => write!(f, "{}", value),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::I8(value) => write!(f, "{}", value),
+            Value::I16(value) => write!(f, "{}", value),
+            Value::I32(value) => write!(f, "{}", value),
+            Value::I64(value) => write!(f, "{}", value),
+            Value::U8(value) => write!(f, "{}", value),
+            Value::U16(value) => write!(f, "{}", value),
+            Value::U32(value) => write!(f, "{}", value),
+            Value::U64(value) => write!(f, "{}", value),
+            // Debug keeps the `.0` of whole numbers, so floats don't look like ints
+            Value::F32(value) => write!(f, "{:?}", value),
+            Value::F64(value) => write!(f, "{:?}", value),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Char(value) => write!(f, "{}", value),
+            Value::String(value) => write!(f, "{}", value),
+            Value::Unit => write!(f, "()"),
+            Value::Array(elements) => {
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    element.fmt_literal(f)?;
+                }
+                write!(f, "]")
+            }
+            Value::Range(start, end) => write!(f, "range({}, {})", start, end),
+            Value::Fn(function) => write!(f, "fn {}", function.name.name),
+            Value::Closure(_) => write!(f, "closure"),
+            Value::Constructor(_, def) => write!(f, "fn {}", def.name.name),
+            Value::Variant(def, variant) => {
+                write!(
+                    f,
+                    "fn {}::{}",
+                    def.name.name, def.variants[*variant].name.name
+                )
+            }
+            Value::Enum(value) => {
+                let variant = &value.def.variants[value.variant];
+                write!(f, "{}::{}", value.def.name.name, variant.name.name)?;
+                match &variant.kind {
+                    VariantKind::Unit => Ok(()),
+                    VariantKind::Tuple(_) => {
+                        write!(f, "(")?;
+                        for (i, field) in value.fields.iter().enumerate() {
+                            if i > 0 {
+                                write!(f, ", ")?;
+                            }
+                            field.fmt_literal(f)?;
+                        }
+                        write!(f, ")")
+                    }
+                    VariantKind::Struct(declared) => {
+                        write!(f, " {{")?;
+                        for (i, (field, value)) in declared.iter().zip(&value.fields).enumerate() {
+                            write!(f, "{} {}: ", if i > 0 { "," } else { "" }, field.name.name)?;
+                            value.fmt_literal(f)?;
+                        }
+                        write!(f, " }}")
+                    }
+                }
+            }
+            Value::Struct(instance) => {
+                let instance = instance.borrow();
+                write!(f, "{} {{", instance.def.name.name)?;
+                for (i, (field, value)) in
+                    instance.def.fields.iter().zip(&instance.fields).enumerate()
+                {
+                    write!(f, "{} {}: ", if i > 0 { "," } else { "" }, field.name.name)?;
+                    match value {
+                        Some(value) => value.fmt_literal(f)?,
+                        None => write!(f, "_")?,
+                    }
+                }
+                if instance.fields.is_empty() {
+                    write!(f, "}}")
+                } else {
+                    write!(f, " }}")
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{checker, cst, lexer, parser, resolver, source::FileId};
+
+    fn eval(source: &str) -> Result<Value, RuntimeError> {
+        cst::assert_agrees(source);
+        let (tokens, errors) = lexer::lex(source, FileId::TEST);
+        assert!(errors.is_empty(), "{:?}", errors);
+        let (ast, errors) = parser::parse(&tokens, FileId::TEST);
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let ast = ast.unwrap();
+        let (names, errors) = resolver::resolve(&ast);
+        assert!(errors.is_empty(), "{:?}", errors);
+        let (types, errors) = checker::check(&ast, &names);
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        Interpreter::new().run(&ast, names, types)
+    }
+
+    fn int(source: &str) -> i64 {
+        match eval(source) {
+            Ok(Value::I64(value)) => value,
+            result => panic!("expected an int, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_expressions() {
+        assert_eq!(int("1 + 2 * 3"), 7);
+        assert_eq!(int("(1 + 2) * 3 - 10 / 3 % 2"), 8);
+        assert_eq!(int("-(2 << 3) | 1"), -15);
+        assert_eq!(int("7 as float as int + 'a' as int + true as int"), 105);
+        assert_eq!(int("-9223372036854775808 + 1"), i64::MIN + 1);
+        assert_eq!(eval("2.5 * 2.0"), Ok(Value::F64(5.0)));
+        assert_eq!(eval("\"a\" + \"b\""), Ok(Value::String("ab".into())));
+        assert_eq!(
+            eval("1 < 2 && 'b' >= 'a' && \"x\" != \"y\""),
+            Ok(Value::Bool(true))
+        );
+        assert_eq!(eval("[1, 2] == [1, 2]"), Ok(Value::Bool(true)));
+        assert_eq!(
+            eval("fmt(\"{} {{}} {}\", [\"a\"], 1.0)"),
+            Ok(Value::String("[\"a\"] {} 1.0".into()))
+        );
+    }
+
+    #[test]
+    fn test_short_circuit() {
+        // The right side would divide by zero if it were evaluated
+        assert_eq!(
+            eval("let x = 0; x != 0 && 10 / x > 1"),
+            Ok(Value::Bool(false))
+        );
+        assert_eq!(
+            eval("let x = 0; x == 0 || 10 / x > 1"),
+            Ok(Value::Bool(true))
+        );
+    }
+
+    #[test]
+    fn test_variables() {
+        assert_eq!(int("let mut x = 1; x += 2; x *= 3; x++; x"), 10);
+        assert_eq!(int("let x = 1; let x = x + 1; x"), 2);
+        assert_eq!(int("let x = 1; { let x = 2; } x"), 1);
+        assert_eq!(int("let x; x = 5; x"), 5);
+        assert_eq!(
+            int("let mut a = [[1, 2], [3, 4]]; a[1][0] = 5; a[1][0] + a[0][1]"),
+            7
+        );
+        assert_eq!(int("const N = 4; fn f() -> int { N * 2 } f()"), 8);
+    }
+
+    #[test]
+    fn test_control_flow() {
+        assert_eq!(int("if 1 > 2 { 1 } else if 2 > 1 { 2 } else { 3 }"), 2);
+        assert_eq!(
+            int("let mut i = 0; loop { i++; if i == 5 { break; } } i"),
+            5
+        );
+        assert_eq!(
+            int("let mut i = 0; loop { i++; if i == 3 { break i * 2; } }"),
+            6
+        );
+        assert_eq!(
+            int("let mut i = 0; let mut sum = 0; while i < 10 { i++; if i % 2 == 0 { continue; } sum += i; } sum"),
+            25
+        );
+        assert_eq!(
+            int("let mut sum = 0; for i in range(0, 5) { sum += i; } sum"),
+            10
+        );
+        assert_eq!(
+            int("let mut sum = 0; for x in [3, 4, 5] { if x == 5 { break; } sum += x; } sum"),
+            7
+        );
+        assert_eq!(int("match 2 { 1 => 10, 2 => 20, _ => 30, }"), 20);
+        assert_eq!(int("match 7 { 1 => 10, n => n * 2, }"), 14);
+        assert_eq!(
+            int("fn f() -> int { for i in range(0, 10) { if i == 3 { return i; } } 0 } f()"),
+            3
+        );
+    }
+
+    #[test]
+    fn test_functions() {
+        assert_eq!(
+            int(
+                "fn fib(n: int) -> int { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } } fib(15)"
+            ),
+            610
+        );
+        // Functions can't see the variables of their caller, even with the same name
+        assert_eq!(
+            int("fn f(x: int) -> int { x + 1 } let x = 10; f(1) + x"),
+            12
+        );
+        assert_eq!(
+            int("fn inc(mut x: int) { x += 1; } let mut a = [1, 2]; inc(a[1]); a[1]"),
+            3
+        );
+        assert_eq!(int("let y = 2; let f = |x| x * y; f(4)"), 8);
+        // Closures share the variables they capture with the scope around them
+        assert_eq!(int("let mut y = 2; let f = |x| x * y; y = 10; f(4)"), 40);
+        assert_eq!(int("let mut c = 0; let f = || { c += 1; }; f(); f(); c"), 2);
+        assert_eq!(
+            int("let mut total = 0; let add = |x: int| { total += x; }; for i in range(1, 4) { add(i); } total"),
+            6
+        );
+        // Every iteration of a loop has its own variable
+        assert_eq!(
+            int("let mut fs = [|| 0, || 0, || 0]; for i in range(0, 3) { fs[i] = || i; } fs[1]()"),
+            1
+        );
+        assert_eq!(
+            int("let add = |x: int| x + 3; let twice = |x| add(add(x)); twice(1)"),
+            7
+        );
+    }
+
+    #[test]
+    fn test_numbers() {
+        assert_eq!(eval("let x: u8 = 250; x + 5"), Ok(Value::U8(255)));
+        assert_eq!(eval("-128i8 / 2"), Ok(Value::I8(-64)));
+        assert_eq!(eval("~0u16"), Ok(Value::U16(u16::MAX)));
+        assert_eq!(eval("(1u8 << 7) | -8i8 as u8 >> 1"), Ok(Value::U8(252)));
+        assert_eq!(eval("let mut x: u32 = 1; x++; x * 3"), Ok(Value::U32(6)));
+        assert_eq!(eval("1.5f32 * 2.0"), Ok(Value::F32(3.0)));
+        assert_eq!(eval("3 < 2u64"), Ok(Value::Bool(false)));
+        assert_eq!(
+            eval("fmt(\"{} {}\", 1.5f32, 18446744073709551615u64)"),
+            Ok(Value::String("1.5 18446744073709551615".into()))
+        );
+
+        // Casts keep the low bits of ints and saturate floats, like Rust's `as`
+        assert_eq!(eval("300 as u8"), Ok(Value::U8(44)));
+        assert_eq!(eval("255u8 as i8"), Ok(Value::I8(-1)));
+        assert_eq!(eval("-1 as u32"), Ok(Value::U32(u32::MAX)));
+        assert_eq!(eval("-1.5 as u8"), Ok(Value::U8(0)));
+        assert_eq!(eval("10000000000.0 as i32"), Ok(Value::I32(i32::MAX)));
+        assert_eq!(eval("'a' as u8 as f32"), Ok(Value::F32(97.0)));
+
+        assert_eq!(eval("wrapping_add(250u8, 10)"), Ok(Value::U8(4)));
+        assert_eq!(eval("wrapping_sub(0u8, 1)"), Ok(Value::U8(255)));
+        assert_eq!(eval("wrapping_mul(100i8, 3)"), Ok(Value::I8(44)));
+        assert_eq!(eval("saturating_add(250u8, 10)"), Ok(Value::U8(255)));
+        assert_eq!(eval("saturating_sub(-100i8, 100)"), Ok(Value::I8(-128)));
+        assert_eq!(
+            eval("let max = 18446744073709551615u64; saturating_mul(max, max)"),
+            Ok(Value::U64(u64::MAX))
+        );
+        assert_eq!(int("saturating_mul(-9223372036854775807, 2)"), i64::MIN);
+
+        let overflow = |source| match eval(source) {
+            Err(RuntimeError::Overflow { op, ty, .. }) => (op, ty),
+            result => panic!("expected an overflow, got {:?}", result),
+        };
+        assert_eq!(overflow("let x: u8 = 200; x + 100"), ("add", IntTy::U8));
+        assert_eq!(overflow("0u32 - 1"), ("subtract", IntTy::U32));
+        assert_eq!(overflow("let x: i8 = -128; -x"), ("negate", IntTy::I8));
+        assert_eq!(overflow("-128i8 / -1"), ("divide", IntTy::I8));
+        assert_eq!(
+            overflow("-9223372036854775808 % -1"),
+            ("calculate the remainder", IntTy::I64)
+        );
+        assert_eq!(overflow("1u8 << 8"), ("shift left", IntTy::U8));
+        assert_eq!(overflow("let mut x = 127i8; x++;"), ("add", IntTy::I8));
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        assert!(matches!(
+            eval("let x = 0; 1 / x"),
+            Err(RuntimeError::DivisionByZero { span }) if span == FileId::TEST.span(11..16)
+        ));
+        assert!(matches!(
+            eval("let x: u8 = 0; 7 % x"),
+            Err(RuntimeError::DivisionByZero { span }) if span == FileId::TEST.span(15..20)
+        ));
+        assert!(matches!(
+            eval("let a = [1]; a[1]"),
+            Err(RuntimeError::IndexOutOfBounds {
+                index: 1,
+                len: 1,
+                ..
+            })
+        ));
+        assert!(matches!(
+            eval("let mut a = [1]; a[-1] = 2;"),
+            Err(RuntimeError::IndexOutOfBounds { index: -1, .. })
+        ));
+        assert!(matches!(
+            eval("9223372036854775807 + 1"),
+            Err(RuntimeError::Overflow { op: "add", .. })
+        ));
+        assert!(matches!(
+            eval("let mut x: int; x + 1"),
+            Err(RuntimeError::Uninitialized { .. })
+        ));
+        let error = eval("let format = \"{} {}\"; fmt(format, 1)").unwrap_err();
+        assert!(matches!(
+            error,
+            RuntimeError::FormatArgs {
+                placeholders: 2,
+                args: 1,
+                ..
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "The format string has 2 `{}` but 1 argument was given"
+        );
+
+        // The interpreter can run again after an error
+        let mut interpreter = Interpreter::new();
+        let (tokens, _) = lexer::lex("fn f(n: int) -> int { { 10 / n } } f(0)", FileId::TEST);
+        let ast = parser::parse(&tokens, FileId::TEST).0.unwrap();
+        let (names, _) = resolver::resolve(&ast);
+        let (types, _) = checker::check(&ast, &names);
+        assert!(interpreter.run(&ast, names, types).is_err());
+        assert_eq!(interpreter.scopes.len(), 1);
+        assert_eq!((interpreter.frame, interpreter.depth), (0, 0));
+    }
+
+    #[test]
+    fn test_structs() {
+        let point = "struct Point { x: int, y: int, Point(); } ";
+        assert_eq!(
+            int(&format!("{point} let p = Point(1, 2); p.x * 10 + p.y")),
+            12
+        );
+        assert_eq!(
+            int(&format!(
+                "{point} let mut p = Point(1, 2); p.x = 5; p.y += 1; p.x * 10 + p.y"
+            )),
+            53
+        );
+        assert_eq!(
+            int("struct Counter { count: int, Counter(start: int) { self.count = start * 2; } } Counter(4).count"),
+            8
+        );
+        // Struct values are shared, like the references they are
+        assert_eq!(
+            int(&format!(
+                "{point} let a = Point(1, 2); let mut b = a; b.x = 7; a.x"
+            )),
+            7
+        );
+        assert_eq!(
+            int("struct Line { points: [int], Line(); } let mut l = Line([1, 2]); l.points[1] = 5; l.points[1]"),
+            5
+        );
+        assert_eq!(
+            eval(&format!("{point} fmt(\"{{}}\", Point(1, -2))")),
+            Ok(Value::String("Point { x: 1, y: -2 }".into()))
+        );
+
+        assert!(matches!(
+            eval("struct P { x: int, y: int, P() { self.x = 1; } } P()"),
+            Err(RuntimeError::UnassignedField { ty, field, .. }) if ty == "P" && field == "y"
+        ));
+        assert!(matches!(
+            eval("struct P { x: int, P() { let y = self.x; self.x = y; } } P()"),
+            Err(RuntimeError::UnassignedField { .. })
+        ));
+    }
+
+    #[test]
+    fn test_methods() {
+        let point = "
+            struct Point { x: int, y: int, Point(); }
+            impl Point {
+                fn origin() -> Point { Point(0, 0) }
+                fn sum(self) -> int { self.x + self.y }
+                fn shift(mut self, by: int) { self.x += by; self.y += by * 2; }
+                fn moved(self, by: int) -> Point { Point(self.x + by, self.y + by) }
+            }
+        ";
+        let int = |source: &str| int(&format!("{}{}", point, source));
+
+        assert_eq!(int("Point(1, 2).sum()"), 3);
+        assert_eq!(int("let mut p = Point::origin(); p.shift(2); p.sum()"), 6);
+        assert_eq!(int("Point(1, 2).moved(1).moved(2).sum()"), 9);
+        assert_eq!(int("let f = Point::sum; f(Point(3, 4))"), 7);
+        assert_eq!(int("let p = Point(5, 0); Point::moved(p, 1).x"), 6);
+
+        // A `mut self` that's replaced changes the variable the method was called on
+        assert_eq!(
+            int("impl Point { fn reset(mut self) { self = Point::origin(); } } let a = Point(1, 1); let mut b = a; b.reset(); a.sum() * 10 + b.sum()"),
+            20
+        );
+        assert_eq!(
+            int("fn f() -> int { impl Point { fn double(self) -> int { self.sum() * 2 } } Point(1, 2).double() } f() + f()"),
+            12
+        );
+    }
+
+    #[test]
+    fn test_traits() {
+        let shapes = "
+            trait Shape {
+                fn area(self) -> int;
+                fn double(self) -> int { self.area() * 2 }
+            }
+            struct Rect { w: int, h: int, Rect(); }
+            struct Square { side: int, Square(); }
+            impl Shape for Rect { fn area(self) -> int { self.w * self.h } }
+            impl Shape for Square {
+                fn area(self) -> int { self.side * self.side }
+                fn double(self) -> int { 100 }
+            }
+        ";
+        let int = |source: &str| int(&format!("{}{}", shapes, source));
+
+        // Each value in the array calls the methods of its own struct
+        assert_eq!(
+            int("let shapes: [dyn Shape] = [Rect(2, 3), Square(2)]; let mut sum = 0; for s in shapes { sum += s.area(); } sum"),
+            10
+        );
+        assert_eq!(
+            int("let shapes: [dyn Shape] = [Rect(2, 3), Square(2)]; shapes[0].double() + shapes[1].double()"),
+            112
+        );
+        assert_eq!(int("Rect(1, 2).double() + Square(3).area()"), 13);
+        assert_eq!(
+            int("fn bigger<T: Shape>(a: T, b: T) -> T { if a.area() > b.area() { a } else { b } } bigger(Square(2), Square(3)).side"),
+            3
+        );
+        assert_eq!(
+            int("fn f(s: dyn Shape) -> int { s.double() } f(Rect(1, 5)) + f(Square(1))"),
+            110
+        );
+    }
+
+    #[test]
+    fn test_enums() {
+        let shapes = "
+            enum Shape { Circle(float), Rect { w: float, h: float }, Empty }
+            fn area(s: Shape) -> float {
+                match s {
+                    Shape::Circle(r) => 3.0 * r * r,
+                    Shape::Rect { w, h } => w * h,
+                    Shape::Empty => 0.0,
+                }
+            }
+        ";
+        let eval = |source: &str| eval(&format!("{}{}", shapes, source)).unwrap();
+
+        assert_eq!(
+            eval("area(Shape::Circle(1.0)) + area(Shape::Rect(2.0, 3.0)) + area(Shape::Empty)"),
+            Value::F64(9.0)
+        );
+        assert_eq!(
+            eval("Shape::Rect(1.0, 2.0) == Shape::Rect(1.0, 2.0)"),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            eval("Shape::Circle(1.0) == Shape::Circle(2.0)"),
+            Value::Bool(false)
+        );
+        assert_eq!(
+            eval("[Shape::Circle(1.0), Shape::Rect(1.0, 2.0), Shape::Empty]").to_string(),
+            "[Shape::Circle(1.0), Shape::Rect { w: 1.0, h: 2.0 }, Shape::Empty]"
+        );
+
+        // Guards, or-patterns, ranges and bindings
+        let classify = "
+            fn classify(n: int) -> string {
+                match n {
+                    0 => \"zero\",
+                    x if x < 0 => \"negative\",
+                    1 | 2 | 3 => \"small\",
+                    4..10 => \"medium\",
+                    big @ 10..=99 if big % 2 == 0 => \"even\",
+                    10..=99 => \"odd\",
+                    _ => \"large\",
+                }
+            }
+        ";
+        let classify = |n: i64| eval(&format!("{} classify({})", classify, n)).to_string();
+        assert_eq!(classify(0), "zero");
+        assert_eq!(classify(-5), "negative");
+        assert_eq!(classify(2), "small");
+        assert_eq!(classify(9), "medium");
+        assert_eq!(classify(10), "even");
+        assert_eq!(classify(11), "odd");
+        assert_eq!(classify(100), "large");
+
+        assert_eq!(
+            eval("match Shape::Rect(2.0, 5.0) { Shape::Circle(x) | Shape::Rect { h: x, .. } => x, Shape::Empty => 0.0, }"),
+            Value::F64(5.0)
+        );
+        assert_eq!(
+            eval("match 'q' { 'a'..='m' => 1, 'n'..='z' => 2, _ => 3, }"),
+            Value::I64(2)
+        );
+        assert_eq!(
+            eval("enum List { Cons(int, List), Nil } fn sum(l: List) -> int { match l { List::Cons(x, rest) => x + sum(rest), List::Nil => 0, } } sum(List::Cons(1, List::Cons(2, List::Nil)))"),
+            Value::I64(3)
+        );
+    }
+
+    #[test]
+    fn test_destructors() {
+        let output = |source: &str| {
+            let mut output = Vec::new();
+            let mut interpreter = Interpreter::with_output(&mut output);
+            cst::assert_agrees(source);
+            let (tokens, _) = lexer::lex(source, FileId::TEST);
+            let ast = parser::parse(&tokens, FileId::TEST).0.unwrap();
+            let (names, errors) = resolver::resolve(&ast);
+            assert!(errors.is_empty(), "{:?}", errors);
+            let (types, errors) = checker::check(&ast, &names);
+            assert!(errors.is_empty(), "{:?}", errors);
+            let result = interpreter.run(&ast, names, types);
+            let dropped = interpreter.drop_globals(ast.body.span);
+            drop(interpreter);
+            (String::from_utf8(output).unwrap(), result.and(dropped))
+        };
+        let handle =
+            "struct Handle { id: int, Handle(); ~Handle() { print!(\"{} \", self.id); } } ";
+
+        // Destructors run when the last owner goes away, variables are dropped in reverse
+        assert_eq!(
+            output(&format!("{handle} let a = Handle(1); {{ let b = Handle(2); let c = Handle(3); }} let d = Handle(4);")).0,
+            "3 2 4 1 "
+        );
+        assert_eq!(
+            output(&format!(
+                "{handle} let a = Handle(1); let b = a; {{ let c = b; }} print!(\"end \");"
+            ))
+            .0,
+            "end 1 "
+        );
+        // Temporaries are dropped after their statement, and reassigning drops the old value
+        assert_eq!(
+            output(&format!(
+                "{handle} Handle(1); let mut h = Handle(2); h = Handle(3); print!(\"end \");"
+            ))
+            .0,
+            "1 2 end 3 "
+        );
+        assert_eq!(
+            output(&format!(
+                "{handle} fn f() -> int {{ let h = Handle(1); h.id + 1 }} print!(\"{{}} \", f());"
+            ))
+            .0,
+            "1 2 "
+        );
+        // Fields are dropped after the instance that owns them
+        assert_eq!(
+            output(&format!("{handle} struct Pair {{ a: Handle, b: Handle, Pair(); ~Pair() {{ print!(\"pair \"); }} }} let p = Pair(Handle(1), Handle(2));")).0,
+            "pair 2 1 "
+        );
+        // An instance whose constructor failed was never created, so it isn't destroyed
+        let (printed, result) = output(
+            "struct H { x: int, H(x: int) { self.x = 10 / x; } ~H() { print!(\"drop \"); } } let a = H(1); let b = H(0);",
+        );
+        assert_eq!(printed, "drop ");
+        assert!(matches!(result, Err(RuntimeError::DivisionByZero { .. })));
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        let result = eval("fn f(n: int) -> int { f(n + 1) } f(0)");
+        assert!(matches!(result, Err(RuntimeError::StackOverflow { .. })));
+
+        // The limit is reached before the stack runs out, even with deeply nested calls
+        let source = "
+        fn f(n: int) -> int {
+            if n > 0 {
+                let a = { match n { 0 => 0, _ => { if true { f(n - 1) } else { 0 } } } };
+                a + 1
+            } else {
+                0
+            }
+        }";
+        assert_eq!(int(&format!("{} f(300)", source)), 300);
+        let result = eval(&format!("{} f(1000)", source));
+        assert!(matches!(result, Err(RuntimeError::StackOverflow { .. })));
+    }
+}
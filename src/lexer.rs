@@ -1,85 +1,809 @@
-use chumsky::prelude::*;
-
-use crate::token::{Literal, Token};
-
-pub type Span = SimpleSpan<usize>;
-
-fn lexer<'a>() -> impl Parser<'a, &'a str, Vec<(Token<'a>, Span)>, extra::Err<Rich<'a, char, Span>>>
-{
-    let ignore_underscores = just('_').repeated().then(text::digits(10)).or_not();
-
-    let num = text::digits(10)
-        // .ignore_then(just('_'))
-        // .or_not()
-        .then(ignore_underscores)
-        .then(
-            just('.')
-                .then(text::digits(10))
-                .then(ignore_underscores)
-                .or_not(),
-        )
+use std::borrow::Cow;
+
+use chumsky::{
+    error::{Error, LabelError},
+    input::{Input as _, WithContext},
+    prelude::*,
+    util::MaybeRef,
+};
+
+pub use crate::source::Span;
+use crate::{
+    source::FileId,
+    token::{Delimiter, DocStyle, Keyword, Literal, NumberSuffix, Operator, Punctuation, Token},
+};
+
+/// The source code being lexed, its spans are in the file that it's from.
+type Input<'a> = WithContext<Span, &'a str>;
+
+/// The output of the lexer, every token paired with the span it was lexed from.
+pub type Tokens<'a> = Vec<(Token<'a>, Span)>;
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum LexError {
+    #[error("Unterminated string literal")]
+    UnterminatedString { span: Span },
+    #[error("Unterminated character literal")]
+    UnterminatedChar { span: Span },
+    #[error("Unterminated block comment")]
+    UnterminatedComment { span: Span },
+    #[error("Character literals must contain exactly one character")]
+    InvalidChar { span: Span },
+    #[error("Invalid escape sequence, {reason}")]
+    InvalidEscape { span: Span, reason: String },
+    #[error("Invalid number literal, {reason}")]
+    InvalidNumber { span: Span, reason: String },
+    #[error("Unknown character `{found}`")]
+    UnknownCharacter { span: Span, found: char },
+    #[error("Unexpected end of input")]
+    UnexpectedEnd { span: Span },
+}
+
+impl LexError {
+    pub fn span(&self) -> Span {
+        match self {
+            LexError::UnterminatedString { span }
+            | LexError::UnterminatedChar { span }
+            | LexError::UnterminatedComment { span }
+            | LexError::InvalidChar { span }
+            | LexError::InvalidEscape { span, .. }
+            | LexError::InvalidNumber { span, .. }
+            | LexError::UnknownCharacter { span, .. }
+            | LexError::UnexpectedEnd { span } => *span,
+        }
+    }
+}
+
+impl<'a> Error<'a, Input<'a>> for LexError {}
+
+impl<'a, L> LabelError<'a, Input<'a>, L> for LexError {
+    fn expected_found<E: IntoIterator<Item = L>>(
+        _expected: E,
+        found: Option<MaybeRef<'a, char>>,
+        span: Span,
+    ) -> Self {
+        // Every token is tried at each position, so what the lexer expected isn't useful,
+        // only the character it couldn't make sense of is.
+        match found {
+            Some(found) => LexError::UnknownCharacter {
+                span,
+                found: *found,
+            },
+            None => LexError::UnexpectedEnd { span },
+        }
+    }
+}
+
+/// Lexes the whole input of a file, recovering from errors so that every error in the input is
+/// reported.
+pub fn lex(input: &str, file: FileId) -> (Tokens<'_>, Vec<LexError>) {
+    let (tokens, errors) = lexer().parse(input.with_context(file)).into_output_errors();
+
+    (tokens.unwrap_or_default(), errors)
+}
+
+/// Source text that the lexer skips over.
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+pub enum Trivia {
+    Whitespace,
+    /// A line or block comment, doc comments are tokens instead.
+    Comment,
+    /// Characters that couldn't be lexed, there is a [`LexError`] for each of them.
+    Skipped,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum LosslessToken<'a> {
+    Token(Token<'a>),
+    Trivia(Trivia),
+}
+
+/// Lexes the whole input like [`lex`], but also keeps the trivia between tokens, so that
+/// concatenating the source of every span reproduces the input exactly.
+pub fn lex_lossless(input: &str, file: FileId) -> (Vec<(LosslessToken<'_>, Span)>, Vec<LexError>) {
+    let (tokens, errors) = lex(input, file);
+    let mut lossless = Vec::with_capacity(tokens.len() * 2);
+    let mut end = 0;
+
+    for (token, span) in tokens {
+        trivia(input, file, end..span.start, &mut lossless);
+        lossless.push((LosslessToken::Token(token), span));
+        end = span.end;
+    }
+
+    trivia(input, file, end..input.len(), &mut lossless);
+
+    (lossless, errors)
+}
+
+/// Splits the gap between two tokens into whitespace, comments and skipped characters.
+fn trivia(
+    input: &str,
+    file: FileId,
+    gap: std::ops::Range<usize>,
+    out: &mut Vec<(LosslessToken, Span)>,
+) {
+    let mut start = gap.start;
+
+    while start < gap.end {
+        let rest = &input[start..gap.end];
+
+        let (kind, len) = if rest.starts_with("//") {
+            (Trivia::Comment, rest.find('\n').unwrap_or(rest.len()))
+        } else if rest.starts_with("/*") {
+            // Find the end of the outermost comment, it runs to the end if it isn't closed
+            let mut depth = 0;
+            let mut i = 0;
+
+            while i < rest.len() {
+                if rest[i..].starts_with("/*") {
+                    depth += 1;
+                    i += 2;
+                } else if rest[i..].starts_with("*/") {
+                    depth -= 1;
+                    i += 2;
+
+                    if depth == 0 {
+                        break;
+                    }
+                } else {
+                    i += rest[i..].chars().next().map_or(1, char::len_utf8);
+                }
+            }
+
+            (Trivia::Comment, i.min(rest.len()))
+        } else {
+            let is_whitespace = rest.starts_with(char::is_whitespace);
+            // Runs until the kind of trivia changes or a comment starts
+            let len = rest
+                .char_indices()
+                .skip(1)
+                .find(|(i, c)| {
+                    c.is_whitespace() != is_whitespace
+                        || rest[*i..].starts_with("//")
+                        || rest[*i..].starts_with("/*")
+                })
+                .map_or(rest.len(), |(i, _)| i);
+
+            if is_whitespace {
+                (Trivia::Whitespace, len)
+            } else {
+                (Trivia::Skipped, len)
+            }
+        };
+
+        out.push((LosslessToken::Trivia(kind), file.span(start..start + len)));
+        start += len;
+    }
+}
+
+/// A reusable lexer, mostly a convenience for callers that want to hold on to the tokens.
+#[derive(Debug)]
+pub struct Lexer<'a> {
+    input: &'a str,
+    file: FileId,
+    tokens: Tokens<'a>,
+}
+
+impl<'a> Lexer<'a> {
+    /// A lexer for the inputs of a file.
+    pub fn new(file: FileId) -> Self {
+        Self {
+            input: "",
+            file,
+            tokens: Vec::new(),
+        }
+    }
+
+    pub fn set_input(&mut self, input: &'a str) {
+        self.input = input;
+        self.tokens.clear();
+    }
+
+    /// Tokenizes the current input, returning every error found if there were any.
+    pub fn tokenize(&mut self) -> Result<&Tokens<'a>, Vec<LexError>> {
+        let (tokens, errors) = lex(self.input, self.file);
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        self.tokens = tokens;
+
+        Ok(&self.tokens)
+    }
+}
+
+fn lexer<'a>() -> impl Parser<'a, Input<'a>, Tokens<'a>, extra::Err<LexError>> {
+    let digit = any().filter(|c: &char| c.is_ascii_digit());
+
+    // Anything that starts with a digit is lexed as one number, and then checked, so that
+    // a malformed number is reported as a whole instead of being split into several tokens.
+    // The radix prefixed numbers are lexed separately as their digits can contain `e`.
+    let radix_num = just('0').then(one_of("xob")).then(
+        any()
+            .filter(|c: &char| c.is_ascii_alphanumeric() || *c == '_')
+            .repeated(),
+    );
+
+    let decimal_num = digit.then(
+        one_of("eE")
+            .then(one_of("+-"))
+            .then(digit)
+            .ignored()
+            .or(any()
+                .filter(|c: &char| c.is_ascii_alphanumeric() || *c == '_')
+                .ignored())
+            .or(just('.').then(digit).ignored())
+            .repeated(),
+    );
+
+    let num = radix_num
+        .ignored()
+        .or(decimal_num.ignored())
         .to_slice()
-        // .from_str()
-        // .unwrapped()
-        .map(|x| Token::Literal(Literal::Integer(x)));
+        .validate(|x: &str, e, emitter| {
+            let literal = number(x).unwrap_or_else(|reason| {
+                emitter.emit(LexError::InvalidNumber {
+                    span: e.span(),
+                    reason,
+                });
+
+                // Keep lexing with a placeholder, the error stops the program from running anyway
+                Literal::Integer {
+                    digits: "0",
+                    radix: 10,
+                    suffix: None,
+                }
+            });
+
+            Token::Literal(literal)
+        });
 
-    // // A parser for strings
+    // The body of a string or char literal, escape sequences are processed after it is lexed
+    let body = |excluded: &'static str| {
+        just('\\')
+            .then(any())
+            .ignored()
+            .or(none_of(excluded).ignored())
+            .repeated()
+            .to_slice()
+    };
+
+    // A parser for strings, they can span multiple lines
     let string = just('"')
-        .ignore_then(none_of('"').repeated())
-        .then_ignore(just('"'))
-        .to_slice()
-        .map(|x| Token::Literal(Literal::String(x)));
-
-    // let char = just("'")
-    //     .ignore_then(none_of('"').repeated())
-    //     .then_ignore(just('"'))
-    //     .to_slice()
-    //     .map(|x| Token::Literal(Literal::Char(x)));
-
-    // // A parser for operators
-    // let op = one_of("+*-/!=")
-    //     .repeated()
-    //     .at_least(1)
-    //     .to_slice()
-    //     .map(Token::Op);
-
-    // // A parser for control characters (delimiters, semicolons, etc.)
-    // let ctrl = one_of("()[]{};,").map(Token::Ctrl);
-
-    // A parser for identifiers and keywords
-    // let ident = text::ascii::ident().map(|ident: &str| match ident {
-    //     "fn" => Token::Fn,
-    //     "let" => Token::Let,
-    //     "print" => Token::Print,
-    //     "if" => Token::If,
-    //     "else" => Token::Else,
-    //     "true" => Token::Bool(true),
-    //     "false" => Token::Bool(false),
-    //     "null" => Token::Null,
-    //     _ => Token::Ident(ident),
-    // });
-
-    // A single token can be one of the above
-    let token = num.or(string);
-    // .or(str_).or(op).or(ctrl).or(ident);
-
-    let comment = just("//")
-        .then(any().and_is(just('\n').not()).repeated())
-        .padded();
-
-    token
-        .map_with(|tok, e| (tok, e.span()))
-        .padded_by(comment.repeated())
-        .padded()
-        // If we encounter an error, skip and attempt to lex the next character as a token instead
-        .recover_with(skip_then_retry_until(any().ignored(), end()))
-        .repeated()
-        .collect()
+        .ignore_then(body("\"\\"))
+        .then(just('"').or_not())
+        .validate(|(x, close): (&str, _), e, emitter| {
+            let span: Span = e.span();
+
+            if close.is_none() {
+                emitter.emit(LexError::UnterminatedString { span });
+            }
+
+            let (cooked, errors) = unescape(x, span.context(), span.start + 1);
+
+            for error in errors {
+                emitter.emit(error);
+            }
+
+            Token::Literal(Literal::String(cooked))
+        });
+
+    // Raw strings are delimited by `r"` and `"`, or by the same number of hashes on both sides like
+    // `r#"` and `"#`, which is easier to count by hand than with combinators
+    let raw_string = custom(|inp| {
+        let start = inp.cursor();
+        let mut hashes = 0;
+
+        let prefix = inp.next();
+        while prefix == Some('r') && inp.peek() == Some('#') {
+            inp.next();
+            hashes += 1;
+        }
+
+        let quote = inp.next();
+        if prefix != Some('r') || quote != Some('"') {
+            let span = inp.span_since(&start);
+            return Err(match quote.or(prefix) {
+                Some(found) => LexError::UnknownCharacter { span, found },
+                None => LexError::UnexpectedEnd { span },
+            });
+        }
+
+        let body_start = inp.cursor();
+
+        loop {
+            let body_end = inp.cursor();
+
+            match inp.next() {
+                Some('"') => {
+                    let mut closing = 0;
+                    while closing < hashes && inp.peek() == Some('#') {
+                        inp.next();
+                        closing += 1;
+                    }
+
+                    if closing == hashes {
+                        return Ok((inp.slice(&body_start..&body_end), true));
+                    }
+                }
+                Some(_) => {}
+                None => return Ok((inp.slice_from(&body_start..), false)),
+            }
+        }
+    })
+    .validate(|(x, closed): (&str, bool), e, emitter| {
+        if !closed {
+            emitter.emit(LexError::UnterminatedString { span: e.span() });
+        }
+
+        Token::Literal(Literal::String(Cow::Borrowed(x)))
+    });
+
+    // A parser for chars, they can't span multiple lines
+    let char = just('\'')
+        .ignore_then(body("'\\\n"))
+        .then(just('\'').or_not())
+        .validate(|(x, close): (&str, _), e, emitter| {
+            let span: Span = e.span();
+
+            if close.is_none() {
+                emitter.emit(LexError::UnterminatedChar { span });
+                return Token::Literal(Literal::Char('\0'));
+            }
+
+            let (cooked, errors) = unescape(x, span.context(), span.start + 1);
+
+            if errors.is_empty() && cooked.chars().count() != 1 {
+                emitter.emit(LexError::InvalidChar { span });
+            }
+
+            for error in errors {
+                emitter.emit(error);
+            }
+
+            Token::Literal(Literal::Char(cooked.chars().next().unwrap_or_default()))
+        });
+
+    // A parser for operators, longer operators have to come first so `>>=` isn't lexed as `>` `>=`
+    let op = choice((
+        choice((
+            just("<<=").to(Operator::ShlAssign),
+            just(">>=").to(Operator::ShrAssign),
+        )),
+        choice((
+            just("&&").to(Operator::LogicalAnd),
+            just("||").to(Operator::LogicalOr),
+            just("<<").to(Operator::ShiftLeft),
+            just(">>").to(Operator::ShiftRight),
+            just("==").to(Operator::Equal),
+            just("!=").to(Operator::NotEqual),
+            just("<=").to(Operator::LessThanOrEqual),
+            just(">=").to(Operator::GreaterThanOrEqual),
+            just("+=").to(Operator::AddAssign),
+            just("-=").to(Operator::SubAssign),
+            just("*=").to(Operator::MulAssign),
+            just("/=").to(Operator::DivAssign),
+            just("%=").to(Operator::ModAssign),
+            just("&=").to(Operator::AndAssign),
+            just("|=").to(Operator::OrAssign),
+            just("^=").to(Operator::XorAssign),
+            just("++").to(Operator::Increment),
+            just("--").to(Operator::Decrement),
+        )),
+        choice((
+            just('+').to(Operator::Add),
+            just('-').to(Operator::Subtract),
+            just('*').to(Operator::Multiply),
+            just('/').to(Operator::Divide),
+            just('%').to(Operator::Modulo),
+            just('!').to(Operator::LogicalNot),
+            just('&').to(Operator::BitwiseAnd),
+            just('|').to(Operator::BitwiseOr),
+            just('^').to(Operator::BitwiseXor),
+            just('~').to(Operator::BitwiseNot),
+            just('<').to(Operator::LessThan),
+            just('>').to(Operator::GreaterThan),
+            just('=').to(Operator::Assign),
+        )),
+    ))
+    .map(Token::Operator);
+
+    // Arrows share their first character with operators so they are tried before them
+    let arrow = choice((
+        just("->").to(Punctuation::Arrow),
+        just("=>").to(Punctuation::FatArrow),
+    ))
+    .map(Token::Punctuation);
+
+    let punct = choice((
+        just(',').to(Punctuation::Comma),
+        just("..=").to(Punctuation::DotDotEq),
+        just("..").to(Punctuation::DotDot),
+        just('.').to(Punctuation::Dot),
+        just("::").to(Punctuation::DoubleColon),
+        just(':').to(Punctuation::Colon),
+        just(';').to(Punctuation::Semicolon),
+        just('@').to(Punctuation::At),
+    ))
+    .map(Token::Punctuation);
+
+    let delim = choice((
+        just('(').to(Delimiter::OpenParen),
+        just(')').to(Delimiter::CloseParen),
+        just('{').to(Delimiter::OpenBrace),
+        just('}').to(Delimiter::CloseBrace),
+        just('[').to(Delimiter::OpenBracket),
+        just(']').to(Delimiter::CloseBracket),
+    ))
+    .map(Token::Delimiter);
+
+    // A parser for identifiers, keywords and boolean literals.
+    // `text::ascii::ident` doesn't accept digits after the first character, so `i32` would be split.
+    let ident = text::ident().map(|ident: &str| match ident {
+        "fn" => Token::Keyword(Keyword::Fn),
+        "let" => Token::Keyword(Keyword::Let),
+        "if" => Token::Keyword(Keyword::If),
+        "else" => Token::Keyword(Keyword::Else),
+        "while" => Token::Keyword(Keyword::While),
+        "for" => Token::Keyword(Keyword::For),
+        "return" => Token::Keyword(Keyword::Return),
+        "break" => Token::Keyword(Keyword::Break),
+        "continue" => Token::Keyword(Keyword::Continue),
+        "match" => Token::Keyword(Keyword::Match),
+        "case" => Token::Keyword(Keyword::Case),
+        "struct" => Token::Keyword(Keyword::Struct),
+        "impl" => Token::Keyword(Keyword::Impl),
+        "trait" => Token::Keyword(Keyword::Trait),
+        "dyn" => Token::Keyword(Keyword::Dyn),
+        "enum" => Token::Keyword(Keyword::Enum),
+        "type" => Token::Keyword(Keyword::Type),
+        "use" => Token::Keyword(Keyword::Use),
+        "mod" => Token::Keyword(Keyword::Mod),
+        "extern" => Token::Keyword(Keyword::Extern),
+        "static" => Token::Keyword(Keyword::Static),
+        "mut" => Token::Keyword(Keyword::Mut),
+        "const" => Token::Keyword(Keyword::Const),
+        "loop" => Token::Keyword(Keyword::Loop),
+        "in" => Token::Keyword(Keyword::In),
+        "as" => Token::Keyword(Keyword::As),
+        "true" | "false" => Token::Literal(Literal::Boolean(ident)),
+        _ => Token::Identifier(ident),
+    });
+
+    let line_comment_body = any().and_is(just('\n').not()).repeated().to_slice();
+
+    // `///` documents the item that follows it and `//!` the item it is in, `////` is a normal comment
+    let line_doc = choice((
+        just("///").and_is(just("////").not()).to(DocStyle::Outer),
+        just("//!").to(DocStyle::Inner),
+    ))
+    .then(line_comment_body)
+    .map(|(style, text)| Token::DocComment(style, text));
+
+    let line_comment = just("//")
+        .and_is(line_doc.not())
+        .then(line_comment_body)
+        .ignored();
+
+    // Block comments can be nested, the output is the body of the outermost comment and whether it was closed
+    let block_comment = recursive(|block_comment| {
+        just("/*")
+            .ignore_then(
+                block_comment
+                    .ignored()
+                    .or(any().and_is(just("*/").not()).ignored())
+                    .repeated()
+                    .to_slice(),
+            )
+            .then(just("*/").or_not().map(|close| close.is_some()))
+    })
+    .validate(|(body, closed): (&str, bool), e, emitter| {
+        if !closed {
+            emitter.emit(LexError::UnterminatedComment { span: e.span() });
+        }
+
+        body
+    });
+
+    let block_doc = block_comment
+        .clone()
+        .filter(|body: &&str| doc_style(body).is_some())
+        .map(|body: &str| Token::DocComment(doc_style(body).unwrap(), &body[1..]));
+
+    let block_comment = block_comment
+        .filter(|body: &&str| doc_style(body).is_none())
+        .ignored();
+
+    // A single token can be one of the above, doc comments come before operators as they start with `/`
+    let token = choice((
+        num, string, raw_string, char, line_doc, block_doc, arrow, op, punct, delim, ident,
+    ));
+
+    // Whitespace and comments between tokens
+    let trivia = line_comment.or(block_comment).padded().repeated().padded();
+
+    trivia.clone().ignore_then(
+        token
+            .map_with(|tok, e| (tok, e.span()))
+            .then_ignore(trivia)
+            // If we encounter an error, skip and attempt to lex the next character as a token instead
+            .recover_with(skip_then_retry_until(any().ignored(), end()))
+            .repeated()
+            .collect(),
+    )
+}
+
+/// The style of a block comment's body, `/**` and `/*!` are doc comments but `/***` and `/**/` aren't.
+fn doc_style(body: &str) -> Option<DocStyle> {
+    if body.starts_with("**") || body == "*" {
+        None
+    } else if body.starts_with('*') {
+        Some(DocStyle::Outer)
+    } else if body.starts_with('!') {
+        Some(DocStyle::Inner)
+    } else {
+        None
+    }
+}
+
+/// Processes the escape sequences in the body of a string or char literal, `offset` is where the
+/// body starts in the source so that errors point at the exact escape sequence.
+/// The body is borrowed as is if it has no escape sequences.
+fn unescape(body: &str, file: FileId, offset: usize) -> (Cow<'_, str>, Vec<LexError>) {
+    let mut errors = Vec::new();
+
+    if !body.contains('\\') {
+        return (Cow::Borrowed(body), errors);
+    }
+
+    let mut cooked = String::with_capacity(body.len());
+    let mut chars = body.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        if c != '\\' {
+            cooked.push(c);
+            continue;
+        }
+
+        let escaped = match chars.next() {
+            Some((_, 'n')) => '\n',
+            Some((_, 'r')) => '\r',
+            Some((_, 't')) => '\t',
+            Some((_, '\\')) => '\\',
+            Some((_, '"')) => '"',
+            Some((_, '\'')) => '\'',
+            Some((_, '0')) => '\0',
+            // A backslash at the end of a line continues the string on the next line,
+            // without the newline and the indentation
+            Some((_, '\n')) => {
+                while chars.peek().is_some_and(|(_, c)| c.is_whitespace()) {
+                    chars.next();
+                }
+                continue;
+            }
+            Some((_, 'u')) => {
+                let mut end = i + 2;
+                let mut digits = String::new();
+                let mut closed = false;
+
+                if let Some(&(j, '{')) = chars.peek() {
+                    chars.next();
+                    end = j + 1;
+
+                    while let Some(&(j, c)) = chars.peek() {
+                        if c == '}' {
+                            chars.next();
+                            end = j + 1;
+                            closed = true;
+                            break;
+                        }
+
+                        if !c.is_ascii_hexdigit() {
+                            break;
+                        }
+
+                        chars.next();
+                        end = j + 1;
+                        digits.push(c);
+                    }
+                }
+
+                let span = file.span(offset + i..offset + end);
+
+                if !closed || digits.is_empty() || digits.len() > 6 {
+                    errors.push(LexError::InvalidEscape {
+                        span,
+                        reason: "unicode escapes must be 1 to 6 hex digits in braces, like `\\u{1F600}`."
+                            .to_string(),
+                    });
+                    continue;
+                }
+
+                match u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                {
+                    Some(c) => c,
+                    None => {
+                        errors.push(LexError::InvalidEscape {
+                            span,
+                            reason: format!("`{}` is not a valid unicode character.", digits),
+                        });
+                        continue;
+                    }
+                }
+            }
+            Some((j, c)) => {
+                errors.push(LexError::InvalidEscape {
+                    span: file.span(offset + i..offset + j + c.len_utf8()),
+                    reason: format!("unknown escape `\\{}`.", c),
+                });
+                continue;
+            }
+            // The lexer never ends a body on a lone backslash
+            None => break,
+        };
+
+        cooked.push(escaped);
+    }
+
+    (Cow::Owned(cooked), errors)
+}
+
+/// Splits a lexed number into its digits, radix and suffix, checking that it is well formed.
+fn number(x: &str) -> Result<Literal<'_>, String> {
+    fn check_digits(digits: &str, radix: u32) -> Result<(), String> {
+        if digits.is_empty() {
+            return Err("missing digits.".to_string());
+        }
+
+        if digits.ends_with('_') {
+            return Err("trailing underscore.".to_string());
+        }
+
+        match digits.chars().find(|c| *c != '_' && !c.is_digit(radix)) {
+            Some(c) => Err(format!(
+                "invalid digit `{}` for a base {} literal.",
+                c, radix
+            )),
+            None => Ok(()),
+        }
+    }
+
+    let radix = match x.get(..2) {
+        Some("0x") => 16,
+        Some("0o") => 8,
+        Some("0b") => 2,
+        _ => 10,
+    };
+
+    if radix != 10 {
+        let rest = &x[2..];
+        // The suffix starts at the first letter that can't be a digit of this radix
+        let suffix_start = rest
+            .find(|c: char| c.is_ascii_alphabetic() && !c.is_digit(radix.max(10)))
+            .unwrap_or(rest.len());
+        let (digits, suffix) = rest.split_at(suffix_start);
+
+        check_digits(digits, radix)?;
+
+        let suffix = match suffix {
+            "" => None,
+            suffix => match NumberSuffix::parse(suffix) {
+                Some(suffix) if !suffix.is_float() => Some(suffix),
+                Some(_) => {
+                    return Err(format!(
+                        "float suffix `{}` on a base {} literal.",
+                        suffix, radix
+                    ))
+                }
+                None if radix == 16 => {
+                    return Err(format!(
+                        "invalid digit `{}` for a base 16 literal.",
+                        &suffix[..1]
+                    ))
+                }
+                None => return Err(format!("invalid suffix `{}`.", suffix)),
+            },
+        };
+
+        return Ok(Literal::Integer {
+            digits,
+            radix,
+            suffix,
+        });
+    }
+
+    if x.matches('.').count() > 1 {
+        return Err("two decimals found.".to_string());
+    }
+
+    // Integer part, then the optional fraction and exponent, anything left over is the suffix
+    let int_end = x
+        .find(|c: char| !c.is_ascii_digit() && c != '_')
+        .unwrap_or(x.len());
+    let mut end = int_end;
+    let mut is_float = false;
+
+    check_digits(&x[..int_end], 10)?;
+
+    if x[end..].starts_with('.') {
+        let frac_end = x[end + 1..]
+            .find(|c: char| !c.is_ascii_digit() && c != '_')
+            .map_or(x.len(), |i| i + end + 1);
+
+        check_digits(&x[end + 1..frac_end], 10)?;
+
+        end = frac_end;
+        is_float = true;
+    }
+
+    if x[end..].starts_with(['e', 'E']) {
+        let sign = x[end + 1..].starts_with(['+', '-']) as usize;
+        let exp_start = end + 1 + sign;
+        let exp_end = x[exp_start..]
+            .find(|c: char| !c.is_ascii_digit() && c != '_')
+            .map_or(x.len(), |i| i + exp_start);
+
+        // `1e` or `1else` aren't exponents, leave them to be reported as a suffix
+        if exp_end > exp_start || sign == 1 {
+            check_digits(&x[exp_start..exp_end], 10)?;
+
+            end = exp_end;
+            is_float = true;
+        }
+    }
+
+    let (digits, suffix) = x.split_at(end);
+
+    let suffix = match suffix {
+        "" => None,
+        suffix => match NumberSuffix::parse(suffix) {
+            Some(suffix) if is_float && !suffix.is_float() => {
+                return Err(format!("integer suffix `{}` on a float literal.", suffix))
+            }
+            Some(suffix) => Some(suffix),
+            None => return Err(format!("invalid suffix `{}`.", suffix)),
+        },
+    };
+
+    if is_float || suffix.is_some_and(|s| s.is_float()) {
+        Ok(Literal::Float { digits, suffix })
+    } else {
+        Ok(Literal::Integer {
+            digits,
+            radix,
+            suffix,
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use chumsky::Parser;
+    use chumsky::{input::Input, Parser};
+
+    use std::borrow::Cow;
+
+    use crate::token::{
+        Delimiter, DocStyle, Keyword, Literal, NumberSuffix, Operator, Punctuation, Token,
+    };
+
+    use super::LexError;
+    use crate::source::FileId;
+
+    fn lex(src: &str) -> Vec<Token<'_>> {
+        let (tokens, errs) = super::lex(src, FileId::TEST);
+
+        assert_eq!(errs.len(), 0, "Errors: {:?}", errs);
+
+        tokens.into_iter().map(|(tok, _)| tok).collect()
+    }
+
+    fn errors(src: &str) -> Vec<LexError> {
+        super::lex(src, FileId::TEST).1
+    }
 
     #[test]
     fn numbers() {
@@ -87,12 +811,14 @@ mod tests {
         // a weird number
         120_05.03_2";
 
-        let (tokens, errs) = super::lexer().parse(src).into_output_errors();
+        let (tokens, errs) = super::lexer()
+            .parse(src.with_context(FileId::TEST))
+            .into_output_errors();
 
         println!("Errors: {:?}", errs);
         println!("Tokens: {:?}", tokens);
 
-        assert_eq!(errs.len() ,0);
+        assert_eq!(errs.len(), 0);
     }
 
     #[test]
@@ -101,11 +827,365 @@ mod tests {
         \"Hello there!\"
         ";
 
-        let (tokens, errs) = super::lexer().parse(src).into_output_errors();
+        let (tokens, errs) = super::lexer()
+            .parse(src.with_context(FileId::TEST))
+            .into_output_errors();
 
         println!("Errors: {:?}", errs);
         println!("Tokens: {:?}", tokens);
 
         assert_eq!(errs.len(), 0);
     }
+
+    #[test]
+    fn declaration() {
+        assert_eq!(
+            lex("let a = 5;"),
+            vec![
+                Token::Keyword(Keyword::Let),
+                Token::Identifier("a"),
+                Token::Operator(Operator::Assign),
+                Token::Literal(Literal::Integer {
+                    digits: "5",
+                    radix: 10,
+                    suffix: None
+                }),
+                Token::Punctuation(Punctuation::Semicolon),
+            ]
+        );
+    }
+
+    #[test]
+    fn multichar_operators() {
+        assert_eq!(
+            lex("a += >>= <<= ++ -- => -> == >> > = :: : ..= .. @"),
+            vec![
+                Token::Identifier("a"),
+                Token::Operator(Operator::AddAssign),
+                Token::Operator(Operator::ShrAssign),
+                Token::Operator(Operator::ShlAssign),
+                Token::Operator(Operator::Increment),
+                Token::Operator(Operator::Decrement),
+                Token::Punctuation(Punctuation::FatArrow),
+                Token::Punctuation(Punctuation::Arrow),
+                Token::Operator(Operator::Equal),
+                Token::Operator(Operator::ShiftRight),
+                Token::Operator(Operator::GreaterThan),
+                Token::Operator(Operator::Assign),
+                Token::Punctuation(Punctuation::DoubleColon),
+                Token::Punctuation(Punctuation::Colon),
+                Token::Punctuation(Punctuation::DotDotEq),
+                Token::Punctuation(Punctuation::DotDot),
+                Token::Punctuation(Punctuation::At),
+            ]
+        );
+    }
+
+    #[test]
+    fn chars_and_booleans() {
+        assert_eq!(
+            lex("'a' true false truthy"),
+            vec![
+                Token::Literal(Literal::Char('a')),
+                Token::Literal(Literal::Boolean("true")),
+                Token::Literal(Literal::Boolean("false")),
+                Token::Identifier("truthy"),
+            ]
+        );
+    }
+
+    #[test]
+    fn spans() {
+        let (tokens, _) = super::lexer()
+            .parse("fn  x>>=1".with_context(FileId::TEST))
+            .into_output_errors();
+        let spans = tokens
+            .unwrap()
+            .into_iter()
+            .map(|(_, span)| span.start..span.end)
+            .collect::<Vec<_>>();
+
+        assert_eq!(spans, vec![0..2, 4..5, 5..8, 8..9]);
+    }
+
+    #[test]
+    fn function() {
+        assert_eq!(
+            lex("fn example(y: i32) -> str { return y[0]; }"),
+            vec![
+                Token::Keyword(Keyword::Fn),
+                Token::Identifier("example"),
+                Token::Delimiter(Delimiter::OpenParen),
+                Token::Identifier("y"),
+                Token::Punctuation(Punctuation::Colon),
+                Token::Identifier("i32"),
+                Token::Delimiter(Delimiter::CloseParen),
+                Token::Punctuation(Punctuation::Arrow),
+                Token::Identifier("str"),
+                Token::Delimiter(Delimiter::OpenBrace),
+                Token::Keyword(Keyword::Return),
+                Token::Identifier("y"),
+                Token::Delimiter(Delimiter::OpenBracket),
+                Token::Literal(Literal::Integer {
+                    digits: "0",
+                    radix: 10,
+                    suffix: None
+                }),
+                Token::Delimiter(Delimiter::CloseBracket),
+                Token::Punctuation(Punctuation::Semicolon),
+                Token::Delimiter(Delimiter::CloseBrace),
+            ]
+        );
+    }
+
+    #[test]
+    fn only_trivia() {
+        assert_eq!(lex(""), vec![]);
+        assert_eq!(lex("  // just a comment"), vec![]);
+        assert_eq!(
+            lex("// one\n// two\nx // three"),
+            vec![Token::Identifier("x")]
+        );
+    }
+
+    #[test]
+    fn unterminated_string() {
+        assert_eq!(
+            errors("let a = \"hello;"),
+            vec![LexError::UnterminatedString {
+                span: FileId::TEST.span(8..15)
+            }]
+        );
+    }
+
+    #[test]
+    fn floats() {
+        assert_eq!(
+            lex("120_05.03_2 1.5e-3 2E10 2.0f32 3f64"),
+            vec![
+                Token::Literal(Literal::Float {
+                    digits: "120_05.03_2",
+                    suffix: None
+                }),
+                Token::Literal(Literal::Float {
+                    digits: "1.5e-3",
+                    suffix: None
+                }),
+                Token::Literal(Literal::Float {
+                    digits: "2E10",
+                    suffix: None
+                }),
+                Token::Literal(Literal::Float {
+                    digits: "2.0",
+                    suffix: Some(NumberSuffix::F32)
+                }),
+                Token::Literal(Literal::Float {
+                    digits: "3",
+                    suffix: Some(NumberSuffix::F64)
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn radix_and_suffixes() {
+        let integer = |digits, radix, suffix| {
+            Token::Literal(Literal::Integer {
+                digits,
+                radix,
+                suffix,
+            })
+        };
+
+        assert_eq!(
+            lex("0xFF 0o17 0b1010 10i64 0xffu8 0x1e 1..2"),
+            vec![
+                integer("FF", 16, None),
+                integer("17", 8, None),
+                integer("1010", 2, None),
+                integer("10", 10, Some(NumberSuffix::I64)),
+                integer("ff", 16, Some(NumberSuffix::U8)),
+                integer("1e", 16, None),
+                integer("1", 10, None),
+                Token::Punctuation(Punctuation::DotDot),
+                integer("2", 10, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_numbers() {
+        let reason = |src| match &errors(src)[..] {
+            [LexError::InvalidNumber { reason, span }] => {
+                assert_eq!(span.start..span.end, 0..src.len());
+                reason.clone()
+            }
+            errs => panic!("expected one invalid number error, got {:?}", errs),
+        };
+
+        assert_eq!(reason("5.5.5"), "two decimals found.");
+        assert_eq!(reason("12ab"), "invalid suffix `ab`.");
+        assert_eq!(reason("100_"), "trailing underscore.");
+        assert_eq!(reason("1_.5"), "trailing underscore.");
+        assert_eq!(reason("0b102"), "invalid digit `2` for a base 2 literal.");
+        assert_eq!(reason("0o8"), "invalid digit `8` for a base 8 literal.");
+        assert_eq!(reason("0xFG"), "invalid digit `G` for a base 16 literal.");
+        assert_eq!(reason("0x"), "missing digits.");
+        assert_eq!(reason("1e"), "invalid suffix `e`.");
+        assert_eq!(reason("1.5i32"), "integer suffix `i32` on a float literal.");
+        assert_eq!(reason("0b1f32"), "float suffix `f32` on a base 2 literal.");
+    }
+
+    #[test]
+    fn unknown_character() {
+        let (tokens, errs) = super::lex("a $ b", FileId::TEST);
+
+        assert_eq!(
+            errs,
+            vec![LexError::UnknownCharacter {
+                span: FileId::TEST.span(2..3),
+                found: '$'
+            }]
+        );
+        // Lexing carries on after the error
+        assert_eq!(tokens.len(), 2);
+    }
+
+    #[test]
+    fn tokenize() {
+        let mut lexer = super::Lexer::new(FileId::TEST);
+
+        lexer.set_input("let x = 'a';");
+        assert_eq!(lexer.tokenize().unwrap().len(), 5);
+
+        lexer.set_input("'ab'");
+        assert_eq!(
+            lexer.tokenize().unwrap_err(),
+            vec![LexError::InvalidChar {
+                span: FileId::TEST.span(0..4)
+            }]
+        );
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(
+            lex(r#""a\n\t\\\"\'\0b" '\'' '\n' '\u{1F600}' "\u{48}i""#),
+            vec![
+                Token::Literal(Literal::String(Cow::Owned("a\n\t\\\"'\0b".to_string()))),
+                Token::Literal(Literal::Char('\'')),
+                Token::Literal(Literal::Char('\n')),
+                Token::Literal(Literal::Char('😀')),
+                Token::Literal(Literal::String(Cow::Owned("Hi".to_string()))),
+            ]
+        );
+
+        // Strings without escapes borrow from the source
+        assert!(matches!(
+            &lex("\"plain\"")[..],
+            [Token::Literal(Literal::String(Cow::Borrowed("plain")))]
+        ));
+    }
+
+    #[test]
+    fn multiline_strings() {
+        assert_eq!(
+            lex("\"one\ntwo\" \"three \\\n        four\""),
+            vec![
+                Token::Literal(Literal::String(Cow::Borrowed("one\ntwo"))),
+                Token::Literal(Literal::String(Cow::Owned("three four".to_string()))),
+            ]
+        );
+    }
+
+    #[test]
+    fn raw_strings() {
+        assert_eq!(
+            lex(r###"r"C:\path" r#"say "hi""# r##"a "# b"## r"###),
+            vec![
+                Token::Literal(Literal::String(Cow::Borrowed(r"C:\path"))),
+                Token::Literal(Literal::String(Cow::Borrowed(r#"say "hi""#))),
+                Token::Literal(Literal::String(Cow::Borrowed(r##"a "# b"##))),
+                Token::Identifier("r"),
+            ]
+        );
+
+        assert_eq!(
+            errors(r##"r#"never closed""##),
+            vec![LexError::UnterminatedString {
+                span: FileId::TEST.span(0..16)
+            }]
+        );
+    }
+
+    #[test]
+    fn invalid_escapes() {
+        let spans = |src| {
+            errors(src)
+                .into_iter()
+                .map(|e| match e {
+                    LexError::InvalidEscape { span, .. } => span.start..span.end,
+                    e => panic!("expected an invalid escape, got {:?}", e),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(spans(r#""ab\qc \w""#), vec![3..5, 7..9]);
+        assert_eq!(
+            spans(r#""\u{110000}" '\u{zz}' "\u1""#),
+            vec![1..11, 14..17, 23..25]
+        );
+    }
+
+    #[test]
+    fn invalid_chars() {
+        assert_eq!(
+            errors("'' 'ab' 'a"),
+            vec![
+                LexError::InvalidChar {
+                    span: FileId::TEST.span(0..2)
+                },
+                LexError::InvalidChar {
+                    span: FileId::TEST.span(3..7)
+                },
+                LexError::UnterminatedChar {
+                    span: FileId::TEST.span(8..10)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn block_comments() {
+        assert_eq!(
+            lex("a /* one /* nested */ still a comment */ b /**/ c /***/ d"),
+            vec![
+                Token::Identifier("a"),
+                Token::Identifier("b"),
+                Token::Identifier("c"),
+                Token::Identifier("d"),
+            ]
+        );
+
+        assert_eq!(
+            errors("a /* one /* two */"),
+            vec![LexError::UnterminatedComment {
+                span: FileId::TEST.span(2..18)
+            }]
+        );
+    }
+
+    #[test]
+    fn doc_comments() {
+        assert_eq!(
+            lex("//! A module\n/// Adds one\n//// not a doc\nfn /** block */ /*! inner */"),
+            vec![
+                Token::DocComment(DocStyle::Inner, " A module"),
+                Token::DocComment(DocStyle::Outer, " Adds one"),
+                Token::Keyword(Keyword::Fn),
+                Token::DocComment(DocStyle::Outer, " block "),
+                Token::DocComment(DocStyle::Inner, " inner "),
+            ]
+        );
+    }
 }
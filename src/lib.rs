@@ -1,35 +1,283 @@
-pub(crate) mod token;
+pub mod token;
 
+pub mod checker;
+pub mod cst;
+pub mod diagnostic;
 pub mod interpreter;
 pub mod lexer;
 pub mod parser;
+pub mod repl;
+pub mod resolver;
+pub mod source;
 
-pub fn run(source: &str) -> Result<(), String> {
-    // let tokens = lexer::Lexer::lex(source)?;
-    // let mut parser = parser::Parser::new(&tokens);
-    // let ast = parser.parse()?;
+use std::{fmt, io};
 
-    // println!("{}", ast);
+use diagnostic::Diagnostic;
+use interpreter::{Interpreter, RuntimeError, Value};
+use lexer::LexError;
+use parser::{
+    ast::{Ast, NodeId},
+    ParseError,
+};
+use source::{FileId, SourceMap, SourceName};
 
-    Ok(())
+pub use repl::ReplEngine;
+
+/// Why a program couldn't be run to the end. Each stage stops the program before the next one
+/// runs, so only the errors of one of them are returned. Lexing and parsing are one stage, the
+/// parser runs on the tokens recovered from lex errors so that both are reported together.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    Syntax {
+        lex: Vec<LexError>,
+        parse: Vec<ParseError>,
+    },
+    /// Names that can't be found and type errors, which are reported before the program runs.
+    Type(Vec<Diagnostic>),
+    Runtime(RuntimeError),
 }
 
-#[test]
-fn test_run() {
-    let source = "
-    let x = 10;
-    let y = 20;
-    ";
-    // let z = x + y;
+impl Error {
+    /// The diagnostics for the errors, in the order they're in the source.
+    pub fn into_diagnostics(self) -> Vec<Diagnostic> {
+        match self {
+            Error::Syntax { lex, parse } => {
+                let mut diagnostics = lex
+                    .into_iter()
+                    .map(Diagnostic::from)
+                    .chain(parse.into_iter().map(Diagnostic::from))
+                    .collect::<Vec<_>>();
+                diagnostics.sort_by_key(|diagnostic| diagnostic.span().map(|span| span.start));
+                diagnostics
+            }
+            Error::Type(diagnostics) => diagnostics,
+            Error::Runtime(error) => vec![Diagnostic::from(error)],
+        }
+    }
+}
 
-    let res = run(source);
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The first error, and how many more there are
+        let (first, count) = match self {
+            Error::Syntax { .. } => {
+                let diagnostics = self.clone().into_diagnostics();
+                (diagnostics[0].message.clone(), diagnostics.len())
+            }
+            Error::Type(diagnostics) => (diagnostics[0].message.clone(), diagnostics.len()),
+            Error::Runtime(error) => (error.to_string(), 1),
+        };
 
-    println!("{:?}", res);
+        match count {
+            1 => write!(f, "{}", first),
+            2 => write!(f, "{} (and 1 more error)", first),
+            _ => write!(f, "{} (and {} more errors)", first, count - 1),
+        }
+    }
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub(crate) struct SourceLocation {
-    line: usize,
-    column: usize,
-    file: smol_str::SmolStr,
+impl std::error::Error for Error {}
+
+/// Runs the source code, printing to stdout, and returns the value of its last expression.
+pub fn run(source: &str) -> Result<Value, Error> {
+    run_with_output(source, io::stdout())
+}
+
+/// Like [`run`], but what the program prints is written to `output`. The spans of the errors are
+/// in a file of their own, use [`run_file`] to render them.
+///
+/// ```
+/// let mut output = Vec::new();
+/// let value = ahlang::run_with_output("println!(\"{}\", 1 + 2); 4", &mut output).unwrap();
+///
+/// assert_eq!(output, b"3\n");
+/// assert_eq!(value, ahlang::interpreter::Value::I64(4));
+/// ```
+pub fn run_with_output(source: &str, output: impl io::Write) -> Result<Value, Error> {
+    let file = SourceMap::new().add(SourceName::Host("<input>".into()), source);
+    run_in(&mut Interpreter::with_output(output), source, file)
+}
+
+/// Runs a file from the source map, returning the diagnostics for the errors in it.
+pub fn run_file(sources: &SourceMap, file: FileId) -> Result<Value, Vec<Diagnostic>> {
+    let source = sources
+        .get(file)
+        .expect("the file should be in the source map")
+        .source();
+
+    run_in(&mut Interpreter::new(), source, file).map_err(Error::into_diagnostics)
+}
+
+/// Checks the source code of a file and runs it in an interpreter.
+fn run_in(interpreter: &mut Interpreter, source: &str, file: FileId) -> Result<Value, Error> {
+    let ast = parse_source(source, file, &mut NodeId(0))?;
+    let (names, name_errors) = resolver::resolve(&ast);
+    let (types, type_errors) = checker::check(&ast, &names);
+    check_errors(name_errors, type_errors)?;
+
+    let result = interpreter.run(&ast, names, types);
+    // The end of a script drops its globals, running the destructors that are left
+    let dropped = interpreter.drop_globals(ast.body.span);
+    result
+        .and_then(|value| dropped.map(|()| value))
+        .map_err(Error::Runtime)
+}
+
+/// Lexes and parses the source code of a file, with node ids starting at `next_id`. The parser runs
+/// even if there are lex errors, on the tokens that could be lexed.
+fn parse_source(source: &str, file: FileId, next_id: &mut NodeId) -> Result<Ast, Error> {
+    // Every program that the tests run is checked against the grammar of the syntax tree too
+    #[cfg(test)]
+    cst::assert_agrees(source);
+
+    let (tokens, lex) = lexer::lex(source, file);
+    let (ast, mut parse) = parser::parse_continuing(&tokens, file, next_id);
+    // Text that couldn't be lexed is missing from the tokens, an unterminated string takes the rest
+    // of the file with it, so parse errors there only repeat the lex error
+    parse.retain(|error| {
+        let start = error.span().start;
+        !lex.iter()
+            .any(|lex| (lex.span().start..=lex.span().end).contains(&start))
+    });
+
+    if !lex.is_empty() || !parse.is_empty() {
+        return Err(Error::Syntax { lex, parse });
+    }
+    // Only parsed cleanly, the recovered parts would cause confusing errors in the later stages
+    Ok(ast.expect("the parser produces an ast when there are no errors"))
+}
+
+/// Combines the errors of the resolver and the checker, in the order they're in the source.
+fn check_errors(name_errors: Vec<Diagnostic>, type_errors: Vec<Diagnostic>) -> Result<(), Error> {
+    let mut diagnostics = name_errors
+        .into_iter()
+        .chain(type_errors)
+        .collect::<Vec<_>>();
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span().map(|span| span.start));
+
+    if diagnostics.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Type(diagnostics))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::codes;
+
+    #[test]
+    fn test_run() {
+        let source = "
+        let x = 10;
+        let y = 20;
+        let z = x + y;
+        ";
+
+        let res = run(source);
+
+        assert_eq!(res, Ok(Value::Unit));
+    }
+
+    #[test]
+    fn test_output() {
+        let mut output = Vec::new();
+        let value = run_with_output(
+            "fn square(x: int) -> int { x * x } print(\"{}\", square(3)); print!(\"done\"); 5",
+            &mut output,
+        );
+
+        assert_eq!(value, Ok(Value::I64(5)));
+        assert_eq!(String::from_utf8(output).unwrap(), "9\ndone");
+    }
+
+    #[test]
+    fn test_destructors() {
+        let mut output = Vec::new();
+        let source = "
+        struct Handle { name: string, Handle(); ~Handle() { println!(\"closed {}\", self.name); } }
+        let a = Handle(\"a\");
+        let b = Handle(\"b\");
+        println!(\"done\");
+        ";
+        let value = run_with_output(source, &mut output);
+
+        // The globals are dropped at the end of the script
+        assert_eq!(value, Ok(Value::Unit));
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "done\nclosed b\nclosed a\n"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let run = |source| run_with_output(source, io::sink()).unwrap_err();
+
+        assert!(matches!(
+            run("let = 1;"),
+            Error::Syntax { lex, parse } if lex.is_empty() && parse.len() == 1
+        ));
+        assert!(matches!(run("let x: int = true; y;"), Error::Type(errors) if errors.len() == 2));
+
+        let error = run("let a = [1, 2]; a[5]");
+        assert_eq!(
+            error.to_string(),
+            "Index out of bounds, the length is 2 but the index is 5"
+        );
+        let Error::Runtime(error) = error else {
+            panic!("expected a runtime error");
+        };
+        let span = error.span();
+        assert_eq!(span.start..span.end, 16..20);
+
+        assert_eq!(
+            run("let x: int = true; y;").to_string(),
+            "Mismatched types (and 1 more error)"
+        );
+    }
+
+    #[test]
+    fn test_run_file() {
+        let mut sources = SourceMap::new();
+        let files = ["let a = \"\\q\";", "let b: int = true;", "let c = 1 / 0;"]
+            .map(|source| sources.add(SourceName::Host("test.ah".into()), source));
+
+        // The errors of every stage point into the file they were found in
+        for (file, code) in files
+            .into_iter()
+            .zip([codes::E0102, codes::E0301, codes::E0501])
+        {
+            let diagnostics = run_file(&sources, file).unwrap_err();
+            assert_eq!(diagnostics[0].code, Some(code));
+            assert_eq!(diagnostics[0].file(), Some(file));
+        }
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        let run = |source| run_with_output(source, io::sink()).unwrap_err();
+
+        // A lex error doesn't stop the parser, both errors are reported in source order
+        let error = run("let a = \"\\q\";\nlet = 3;");
+        let Error::Syntax { lex, parse } = &error else {
+            panic!("expected syntax errors, found {:?}", error);
+        };
+        assert_eq!(lex.len(), 1);
+        assert_eq!(parse.len(), 1);
+
+        let diagnostics = error.into_diagnostics();
+        assert_eq!(
+            diagnostics.iter().map(|d| d.code).collect::<Vec<_>>(),
+            [Some(codes::E0102), Some(codes::E0201)]
+        );
+        assert!(diagnostics[0].span().unwrap().start < diagnostics[1].span().unwrap().start);
+
+        // The end of the file isn't reported again after a string that is never closed
+        assert!(matches!(
+            run("let s = \"abc;"),
+            Error::Syntax { lex, parse } if lex.len() == 1 && parse.is_empty()
+        ));
+    }
 }
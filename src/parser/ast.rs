@@ -1,12 +1,449 @@
-use crate::SourceLocation;
+use smol_str::SmolStr;
 
+use crate::{lexer::Span, token::NumberSuffix};
+
+/// Identifies a node in the AST, so that later passes can keep information about nodes in side
+/// tables instead of having to change the tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(pub u32);
+
+/// A name and where it was written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ident {
+    pub name: SmolStr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Ast {
-    root: Node,
+    /// The `//!` doc comments at the start of the source.
+    pub doc: Option<String>,
+    pub body: Block,
+}
+
+/// A sequence of statements, optionally ending with an expression that is the block's value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub id: NodeId,
+    pub stmts: Vec<Stmt>,
+    pub tail: Option<Box<Expr>>,
+    pub span: Span,
+}
+
+/// A declaration that can be used before it is written, like a function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    pub id: NodeId,
+    /// The `///` doc comments before the item.
+    pub doc: Option<String>,
+    pub kind: ItemKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ItemKind {
+    Fn(Function),
+    Const {
+        name: Ident,
+        ty: Option<TypeExpr>,
+        value: Expr,
+    },
+    Struct(Box<Struct>),
+    Impl(Box<Impl>),
+    Trait(Box<Trait>),
+    Enum(Box<Enum>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: Ident,
+    /// `<T: Trait, U>`, the type parameters of a generic function.
+    pub generics: Vec<Generic>,
+    /// `self` or `mut self`, which only methods have.
+    pub receiver: Option<Receiver>,
+    pub params: Vec<Param>,
+    /// `None` if the function returns `()`.
+    pub ret: Option<TypeExpr>,
+    /// `None` for the required methods of a trait, which end with `;` instead.
+    pub body: Option<Block>,
+}
+
+/// A type parameter, `T` or `T: Trait + Other`. The type is chosen where the function is used,
+/// and has to implement the traits of its bounds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Generic {
+    /// Declares the type in the function.
+    pub id: NodeId,
+    pub name: Ident,
+    pub bounds: Vec<TypeExpr>,
+    pub span: Span,
+}
+
+/// The `self` parameter of a method, the value it's called on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Receiver {
+    /// Declares `self` in the body.
+    pub id: NodeId,
+    pub mutable: bool,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    pub id: NodeId,
+    pub name: Ident,
+    pub mutable: bool,
+    pub ty: TypeExpr,
+    pub span: Span,
+}
+
+/// `struct Name { fields, constructor, destructor }`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Struct {
+    pub name: Ident,
+    pub fields: Vec<StructField>,
+    /// Structs without a constructor can't be created.
+    pub constructor: Option<Constructor>,
+    pub destructor: Option<Destructor>,
+}
+
+/// `enum Name { variants }`, a type whose values are one of its variants.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Enum {
+    pub name: Ident,
+    pub variants: Vec<Variant>,
+}
+
+/// A variant of an enum, `Name`, `Name(T, U)` or `Name { field: T }`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    pub id: NodeId,
+    /// The `///` doc comments before the variant.
+    pub doc: Option<String>,
+    pub name: Ident,
+    pub kind: VariantKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VariantKind {
+    /// A variant without a payload, which is a value of the enum by itself.
+    Unit,
+    /// Values in order, like the arguments of a function.
+    Tuple(Vec<TypeExpr>),
+    /// Named fields, which are given in order when the variant is created, like the fields of a
+    /// struct with a constructor without a body.
+    Struct(Vec<StructField>),
+}
+
+/// `name: T`
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructField {
+    /// The `///` doc comments before the field.
+    pub doc: Option<String>,
+    pub name: Ident,
+    pub ty: TypeExpr,
+    pub span: Span,
+}
+
+/// `Name(params) { ... }`, which assigns the fields of `self`, or `Name();` which takes the
+/// values of the fields in the order they're declared.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constructor {
+    /// Declares `self` in the body.
+    pub id: NodeId,
+    pub name: Ident,
+    pub params: Vec<Param>,
+    pub body: Option<Block>,
+    pub span: Span,
+}
+
+/// `~Name() { ... }`, which runs when the last reference to an instance is dropped. `~Name();`
+/// does nothing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Destructor {
+    /// Declares `self` in the body.
+    pub id: NodeId,
+    pub name: Ident,
+    pub body: Option<Block>,
+    pub span: Span,
+}
+
+/// `impl Name { methods }`, or `impl Trait for Name { methods }` which implements the methods
+/// of a trait.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Impl {
+    pub trait_: Option<TypeExpr>,
+    pub ty: TypeExpr,
+    pub methods: Vec<Method>,
 }
 
-pub struct Node {
-    children: Vec<Node>,
-    location: SourceLocation,
+/// `trait Name { methods }`, the methods that the types implementing it have. Methods without a
+/// body are required, the others are defaults that can be replaced.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trait {
+    pub name: Ident,
+    pub methods: Vec<Method>,
 }
 
-pub enum NodeType {}
+/// A function in an `impl` or `trait` block. Methods take `self` and are called as `value.name()`, the
+/// others are associated functions called as `Type::name()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Method {
+    pub id: NodeId,
+    /// The `///` doc comments before the method.
+    pub doc: Option<String>,
+    pub function: Function,
+    pub span: Span,
+}
+
+/// A parameter of a closure, its type is inferred when it isn't written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClosureParam {
+    pub id: NodeId,
+    pub name: Ident,
+    pub mutable: bool,
+    pub ty: Option<TypeExpr>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stmt {
+    pub id: NodeId,
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StmtKind {
+    Let {
+        name: Ident,
+        mutable: bool,
+        ty: Option<TypeExpr>,
+        value: Option<Expr>,
+    },
+    Item(Item),
+    /// A block-like expression, such as an `if` or a `loop`, without a semicolon after it.
+    Expr(Expr),
+    /// An expression followed by a semicolon.
+    Semi(Expr),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeExpr {
+    pub id: NodeId,
+    pub kind: TypeExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeExprKind {
+    /// `()`
+    Unit,
+    /// A type referred to by name, like `int` or `i32`.
+    Named(SmolStr),
+    /// `[T]`
+    Array(Box<TypeExpr>),
+    /// `dyn Trait`, a value of any type that implements the trait.
+    Dyn(SmolStr),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    pub id: NodeId,
+    pub kind: PatternKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatternKind {
+    /// `_`
+    Wildcard,
+    /// A literal, or a negated number literal, stored as an expression.
+    Literal(Box<Expr>),
+    /// `start..end` or `start..=end`, whose bounds are literals like [`PatternKind::Literal`]'s.
+    Range {
+        start: Box<Expr>,
+        end: Box<Expr>,
+        inclusive: bool,
+    },
+    /// Matches anything and binds it to a name, or with `name @ pattern` only what the pattern
+    /// matches.
+    Binding(SmolStr, Option<Box<Pattern>>),
+    /// `a | b`, which matches if any of the alternatives does. They all bind the same names.
+    Or(Vec<Pattern>),
+    /// `Enum::Variant`, `Enum::Variant(patterns)` or `Enum::Variant { field: pattern, .. }`.
+    Variant {
+        ty: TypeExpr,
+        name: Ident,
+        payload: PayloadPattern,
+    },
+}
+
+/// The patterns for the payload of a variant.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PayloadPattern {
+    Unit,
+    Tuple(Vec<Pattern>),
+    /// The fields that are left out are ignored if there's a `..` at the end.
+    Struct {
+        fields: Vec<FieldPattern>,
+        rest: bool,
+    },
+}
+
+/// `field: pattern`, or just `field` which binds it to a variable with the same name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldPattern {
+    pub name: Ident,
+    pub pattern: Pattern,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    /// Integers are always positive, negative numbers are negated literals.
+    Int {
+        value: u64,
+        suffix: Option<NumberSuffix>,
+    },
+    Float {
+        value: f64,
+        suffix: Option<NumberSuffix>,
+    },
+    String(String),
+    Char(char),
+    Bool(bool),
+    /// `()`
+    Unit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnaryOp {
+    /// `-`
+    Neg,
+    /// `!`
+    Not,
+    /// `~`
+    BitNot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    /// `&&`, short-circuiting
+    And,
+    /// `||`, short-circuiting
+    Or,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StepOp {
+    /// `++`
+    Increment,
+    /// `--`
+    Decrement,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub id: NodeId,
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    /// Stands in for an expression that failed to parse.
+    Error,
+    Literal(Literal),
+    Ident(SmolStr),
+    /// `[a, b, c]`
+    Array(Vec<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(Box<Expr>, BinaryOp, Box<Expr>),
+    /// `a = b`, or a compound assignment like `a += b` if there is an operator.
+    Assign {
+        target: Box<Expr>,
+        op: Option<BinaryOp>,
+        value: Box<Expr>,
+    },
+    /// `a++` or `a--`
+    Step(Box<Expr>, StepOp),
+    /// `a as T`
+    Cast(Box<Expr>, TypeExpr),
+    Call {
+        func: Box<Expr>,
+        args: Vec<Expr>,
+    },
+    /// `|a, b| a + b` or `|a: int| -> int { a }`
+    Closure {
+        params: Vec<ClosureParam>,
+        /// Only written when the body is a block.
+        ret: Option<TypeExpr>,
+        body: Box<Expr>,
+    },
+    /// A macro call like `println!("{}", x)`.
+    Macro {
+        name: Ident,
+        args: Vec<Expr>,
+    },
+    /// `a[i]`
+    Index(Box<Expr>, Box<Expr>),
+    /// `a.b`
+    Field(Box<Expr>, Ident),
+    /// `a.b(args)`
+    MethodCall {
+        receiver: Box<Expr>,
+        method: Ident,
+        args: Vec<Expr>,
+    },
+    /// `T::name`, a function of a type.
+    Path(TypeExpr, Ident),
+    Block(Block),
+    If {
+        cond: Box<Expr>,
+        then: Block,
+        /// Either a block or another `if`.
+        otherwise: Option<Box<Expr>>,
+    },
+    Loop(Block),
+    While {
+        cond: Box<Expr>,
+        body: Block,
+    },
+    For {
+        binding: Ident,
+        iter: Box<Expr>,
+        body: Block,
+    },
+    Match {
+        scrutinee: Box<Expr>,
+        arms: Vec<MatchArm>,
+    },
+    Return(Option<Box<Expr>>),
+    Break(Option<Box<Expr>>),
+    Continue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    /// `pattern if condition => body`, the arm is only taken if the condition is true.
+    pub guard: Option<Expr>,
+    pub body: Expr,
+}
@@ -0,0 +1,170 @@
+//! The errors of the parser.
+
+use std::fmt;
+
+use chumsky::{
+    error::{Error, LabelError, Rich, RichPattern},
+    input::Input,
+    util::MaybeRef,
+};
+
+use crate::{diagnostic::ErrorCode, lexer::Span};
+
+/// An error found while parsing, generic over the tokens that it mentions.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyntaxError<'a, T> {
+    /// A token that the grammar doesn't allow where it was found.
+    Unexpected(Rich<'a, T, Span>),
+    /// A mistake that the parser recognizes, like a missing `;`, with the code it's reported with.
+    Custom {
+        span: Span,
+        code: ErrorCode,
+        message: String,
+    },
+}
+
+impl<'a, T> SyntaxError<'a, T> {
+    pub fn custom(span: Span, code: ErrorCode, message: impl Into<String>) -> Self {
+        SyntaxError::Custom {
+            span,
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn span(&self) -> &Span {
+        match self {
+            SyntaxError::Unexpected(error) => error.span(),
+            SyntaxError::Custom { span, .. } => span,
+        }
+    }
+
+    pub fn map_token<U>(self, f: impl FnMut(T) -> U) -> SyntaxError<'a, U>
+    where
+        T: Clone,
+    {
+        match self {
+            SyntaxError::Unexpected(error) => SyntaxError::Unexpected(error.map_token(f)),
+            SyntaxError::Custom {
+                span,
+                code,
+                message,
+            } => SyntaxError::Custom {
+                span,
+                code,
+                message,
+            },
+        }
+    }
+
+    /// Copies the tokens that the error borrows.
+    pub fn into_owned<'b>(self) -> SyntaxError<'b, T>
+    where
+        T: Clone,
+    {
+        match self {
+            SyntaxError::Unexpected(error) => SyntaxError::Unexpected(error.into_owned()),
+            SyntaxError::Custom {
+                span,
+                code,
+                message,
+            } => SyntaxError::Custom {
+                span,
+                code,
+                message,
+            },
+        }
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for SyntaxError<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyntaxError::Unexpected(error) => write!(f, "{}", error),
+            SyntaxError::Custom { message, .. } => write!(f, "{}", message),
+        }
+    }
+}
+
+// Unexpected tokens are kept the way `Rich` keeps them, custom errors are kept over them and over
+// labels as they say more about what's wrong.
+
+impl<'a, I> Error<'a, I> for SyntaxError<'a, I::Token>
+where
+    I: Input<'a, Span = Span>,
+    I::Token: PartialEq,
+{
+    fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (SyntaxError::Unexpected(a), SyntaxError::Unexpected(b)) => {
+                SyntaxError::Unexpected(<Rich<_, _> as Error<'a, I>>::merge(a, b))
+            }
+            (custom @ SyntaxError::Custom { .. }, _) | (_, custom) => custom,
+        }
+    }
+}
+
+impl<'a, I, L> LabelError<'a, I, L> for SyntaxError<'a, I::Token>
+where
+    I: Input<'a, Span = Span>,
+    I::Token: PartialEq,
+    L: Into<RichPattern<'a, I::Token>>,
+{
+    fn expected_found<E: IntoIterator<Item = L>>(
+        expected: E,
+        found: Option<MaybeRef<'a, I::Token>>,
+        span: Span,
+    ) -> Self {
+        SyntaxError::Unexpected(<Rich<_, _> as LabelError<'a, I, L>>::expected_found(
+            expected, found, span,
+        ))
+    }
+
+    fn merge_expected_found<E: IntoIterator<Item = L>>(
+        self,
+        expected: E,
+        found: Option<MaybeRef<'a, I::Token>>,
+        span: Span,
+    ) -> Self {
+        match self {
+            SyntaxError::Unexpected(error) => {
+                SyntaxError::Unexpected(<Rich<_, _> as LabelError<'a, I, L>>::merge_expected_found(
+                    error, expected, found, span,
+                ))
+            }
+            custom => custom,
+        }
+    }
+
+    fn replace_expected_found<E: IntoIterator<Item = L>>(
+        self,
+        expected: E,
+        found: Option<MaybeRef<'a, I::Token>>,
+        span: Span,
+    ) -> Self {
+        match self {
+            SyntaxError::Unexpected(error) => {
+                SyntaxError::Unexpected(
+                    <Rich<_, _> as LabelError<'a, I, L>>::replace_expected_found(
+                        error, expected, found, span,
+                    ),
+                )
+            }
+            SyntaxError::Custom { .. } => {
+                <Self as LabelError<'a, I, L>>::expected_found(expected, found, span)
+            }
+        }
+    }
+
+    fn label_with(&mut self, label: L) {
+        if let SyntaxError::Unexpected(error) = self {
+            <Rich<_, _> as LabelError<'a, I, L>>::label_with(error, label)
+        }
+    }
+
+    fn in_context(&mut self, label: L, span: Span) {
+        if let SyntaxError::Unexpected(error) = self {
+            <Rich<_, _> as LabelError<'a, I, L>>::in_context(error, label, span)
+        }
+    }
+}
@@ -1,5 +1,2183 @@
 pub mod ast;
+mod error;
 
-pub fn parse(code: &str) {
-    todo!()
+use std::{collections::HashMap, mem};
+
+use chumsky::{
+    input::{Checkpoint, Cursor, InputRef, ValueInput},
+    inspector::Inspector,
+    prelude::*,
+};
+use smol_str::SmolStr;
+
+use crate::{
+    diagnostic::codes,
+    lexer::Span,
+    source::FileId,
+    token::{self, Delimiter, DocStyle, Keyword, Operator, Punctuation, Token},
+};
+
+use ast::{
+    Ast, BinaryOp, Block, ClosureParam, Constructor, Destructor, Enum, Expr, ExprKind,
+    FieldPattern, Function, Generic, Ident, Impl, Item, ItemKind, Literal, MatchArm, Method,
+    NodeId, Param, Pattern, PatternKind, PayloadPattern, Receiver, StepOp, Stmt, StmtKind, Struct,
+    StructField, Trait, TypeExpr, TypeExprKind, UnaryOp, Variant, VariantKind,
+};
+
+pub use error::SyntaxError;
+
+/// A parse error, the tokens in it are turned into their source text so it doesn't borrow them.
+pub type ParseError = SyntaxError<'static, String>;
+
+/// How deeply expressions, blocks, types and patterns can be nested. The parser and the stages
+/// after it recurse into nested nodes, so deeper programs are an error instead of running out
+/// of stack.
+pub const MAX_NESTING: usize = 256;
+
+/// The size of the stack that the parser runs on. It's enough for [`MAX_NESTING`] levels even
+/// in a debug build, where every level takes a couple hundred kilobytes of the parser's stack
+/// frames. Only the part that's used is actually allocated.
+const STACK_SIZE: usize = 128 << 20;
+
+type Extra<'a> = extra::Full<SyntaxError<'a, Token<'a>>, State, ()>;
+
+/// The parser state is the id of the next node, and the doc comments by the start of the token
+/// after them. Doc comments aren't part of the grammar, a node that can be documented looks up
+/// the one before it.
+struct State {
+    next_id: u32,
+    docs: HashMap<usize, String>,
+    /// The levels of nodes that can contain themselves, a new entry is pushed whenever a level
+    /// changes so backtracking only has to drop the entries after it. The last entry is the
+    /// innermost level that is being parsed, the first one is the top level.
+    nesting: Vec<Nesting>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Nesting {
+    /// How many levels are open, this one included.
+    depth: usize,
+    /// The entry of the level that this one is inside of.
+    outer: usize,
+    /// The height of the highest node inside of it that was parsed so far.
+    inner: usize,
+    /// Operators, casts and postfixes that were parsed at this level, each of which contains the
+    /// ones before it.
+    chained: usize,
+    /// Whether it was reported as too deep, or a level inside of it was.
+    too_deep: bool,
+}
+
+impl Nesting {
+    fn height(self) -> usize {
+        self.inner + self.chained + 1
+    }
+}
+
+// The checkpoint is a single number on purpose, chumsky copies it into every position that it
+// saves and a larger one makes the parser's stack frames a lot larger.
+impl<'a, I: Input<'a>> Inspector<'a, I> for State {
+    type Checkpoint = usize;
+
+    fn on_token(&mut self, _: &I::Token) {}
+
+    fn on_save<'parse>(&self, _: &Cursor<'a, 'parse, I>) -> Self::Checkpoint {
+        self.nesting.len()
+    }
+
+    fn on_rewind<'parse>(&mut self, marker: &Checkpoint<'a, 'parse, I, Self::Checkpoint>) {
+        self.nesting.truncate(*marker.inspector());
+    }
+}
+
+type Emitter<'a> = chumsky::input::Emitter<SyntaxError<'a, Token<'a>>>;
+
+/// Parses the tokens that the lexer produced for a file into an AST.
+pub fn parse<'a>(tokens: &'a [(Token<'a>, Span)], file: FileId) -> (Option<Ast>, Vec<ParseError>) {
+    parse_continuing(tokens, file, &mut NodeId(0))
+}
+
+/// Like [`parse`], but the node ids start at `next_id`, which is moved past the ids that were
+/// used. Programs that are parsed one after another, like the inputs of the REPL, can then share
+/// side tables keyed by [`NodeId`].
+pub fn parse_continuing<'a>(
+    tokens: &'a [(Token<'a>, Span)],
+    file: FileId,
+    next_id: &mut NodeId,
+) -> (Option<Ast>, Vec<ParseError>) {
+    let eoi = tokens.last().map_or(0, |(_, span)| span.end);
+    let eoi = file.span(eoi..eoi);
+
+    // `//!` documents the file when it's at the start, other doc comments that don't come right
+    // before something that can be documented are ignored
+    let mut inner = Vec::new();
+    let mut outer = Vec::new();
+    let mut docs = HashMap::new();
+    let mut start = true;
+    let tokens = tokens
+        .iter()
+        .filter_map(|(token, span)| match token {
+            Token::DocComment(DocStyle::Inner, text) => {
+                if start && outer.is_empty() {
+                    inner.push(*text);
+                }
+                None
+            }
+            Token::DocComment(DocStyle::Outer, text) => {
+                outer.push(*text);
+                None
+            }
+            _ => {
+                start = false;
+                if let Some(doc) = doc(mem::take(&mut outer)) {
+                    docs.insert(span.start, doc);
+                }
+                Some((token.clone(), *span))
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut state = State {
+        next_id: next_id.0,
+        docs,
+        nesting: vec![Nesting::default()],
+    };
+    let (ast, errors) = stacker::grow(STACK_SIZE, || {
+        let (ast, errors) = parser()
+            .parse_with_state(tokens.map(eoi, |(token, span)| (token, span)), &mut state)
+            .into_output_errors();
+        // An AST that's nested too deeply would run out of stack wherever it's dropped
+        let too_deep = errors.iter().any(is_too_deep);
+        (ast.filter(|_| !too_deep), errors)
+    });
+    *next_id = NodeId(state.next_id);
+    let ast = ast.map(|ast| Ast {
+        doc: doc(inner),
+        ..ast
+    });
+
+    // Alternatives that fail at the same token can report the same error more than once
+    let mut errors = errors
+        .into_iter()
+        .map(|error| error.map_token(|token| token.to_string()).into_owned())
+        .collect::<Vec<ParseError>>();
+    errors.sort_by_key(|error| (error.span().start, error.span().end));
+    errors.dedup_by(|a, b| a.span() == b.span() && a.to_string() == b.to_string());
+    // Every level around nesting that's too deep is too deep as well, it's reported once
+    let mut too_deep = false;
+    errors.retain(|error| !is_too_deep(error) || !mem::replace(&mut too_deep, true));
+
+    (ast, errors)
+}
+
+fn next_id(state: &mut State) -> NodeId {
+    let id = NodeId(state.next_id);
+    state.next_id += 1;
+    id
+}
+
+/// The doc comment right before the node, which starts at `span`.
+fn doc_at(span: Span, state: &State) -> Option<String> {
+    state.docs.get(&span.start).cloned()
+}
+
+/// A member of a struct, before they're sorted into the parts of [`Struct`].
+#[derive(Clone)]
+enum Member {
+    Field(StructField),
+    Constructor(Constructor),
+    Destructor(Destructor),
+}
+
+/// A statement in a block, or an expression that might be the value of the block if it is last.
+enum BlockItem {
+    Stmt(Stmt),
+    Expr {
+        /// The id for the statement if the expression doesn't end the block.
+        id: NodeId,
+        expr: Expr,
+        semicolon: bool,
+        block_like: bool,
+    },
+}
+
+fn parser<'a, I>() -> impl Parser<'a, I, Ast, Extra<'a>>
+where
+    I: ValueInput<'a, Token = Token<'a>, Span = Span>,
+{
+    let punct = |punct| just(Token::Punctuation(punct));
+    let delim = |delim| just(Token::Delimiter(delim));
+    let op = |op| just(Token::Operator(op));
+    let kw = |keyword| just(Token::Keyword(keyword));
+
+    let ident = select! { Token::Identifier(ident) => SmolStr::from(ident) }.labelled("identifier");
+    let spanned_ident = ident.map_with(|name, e| Ident {
+        name,
+        span: e.span(),
+    });
+
+    let ty = recursive(|ty| {
+        nested(
+            choice((
+                delim(Delimiter::OpenParen)
+                    .then(delim(Delimiter::CloseParen))
+                    .to(TypeExprKind::Unit),
+                ty.delimited_by(
+                    delim(Delimiter::OpenBracket),
+                    delim(Delimiter::CloseBracket),
+                )
+                .map(|ty| TypeExprKind::Array(Box::new(ty))),
+                kw(Keyword::Dyn).ignore_then(ident).map(TypeExprKind::Dyn),
+                ident.map(TypeExprKind::Named),
+            ))
+            .map_with(|kind, e| TypeExpr {
+                id: next_id(e.state()),
+                kind,
+                span: e.span(),
+            }),
+        )
+    })
+    .labelled("type");
+
+    let literal = select! { Token::Literal(literal) => literal }
+        .validate(|token, e, emitter| {
+            literal(token).unwrap_or_else(|message| {
+                emitter.emit(SyntaxError::custom(e.span(), codes::E0203, message));
+                Literal::Int {
+                    value: 0,
+                    suffix: None,
+                }
+            })
+        })
+        .labelled("literal");
+
+    // Literal patterns are stored as expressions so that negative numbers can be matched
+    let literal_pattern = op(Operator::Subtract)
+        .or_not()
+        .then(literal.map_with(|literal, e| Expr {
+            id: next_id(e.state()),
+            kind: ExprKind::Literal(literal),
+            span: e.span(),
+        }))
+        .map_with(|(negate, literal), e| match negate {
+            Some(_) => Expr {
+                id: next_id(e.state()),
+                kind: ExprKind::Unary(UnaryOp::Neg, Box::new(literal)),
+                span: e.span(),
+            },
+            None => literal,
+        });
+
+    // `|` separates the alternatives of a whole pattern, the rest of the patterns are atoms
+    let mut pattern = Recursive::declare();
+    let mut atom_pattern = Recursive::declare();
+
+    let field_pattern = spanned_ident
+        .then(
+            punct(Punctuation::Colon)
+                .ignore_then(pattern.clone())
+                .or_not(),
+        )
+        .map_with(|(name, pattern): (Ident, Option<Pattern>), e| {
+            // `field` is short for `field: field`
+            let pattern = pattern.unwrap_or_else(|| Pattern {
+                id: next_id(e.state()),
+                kind: PatternKind::Binding(name.name.clone(), None),
+                span: name.span,
+            });
+            FieldPattern { name, pattern }
+        });
+    let payload_pattern = choice((
+        pattern
+            .clone()
+            .separated_by(punct(Punctuation::Comma))
+            .allow_trailing()
+            .collect::<Vec<_>>()
+            .delimited_by(delim(Delimiter::OpenParen), delim(Delimiter::CloseParen))
+            .map(PayloadPattern::Tuple),
+        field_pattern
+            .separated_by(punct(Punctuation::Comma))
+            .allow_trailing()
+            .collect::<Vec<_>>()
+            .then(punct(Punctuation::DotDot).or_not())
+            .delimited_by(delim(Delimiter::OpenBrace), delim(Delimiter::CloseBrace))
+            .map(|(fields, rest)| PayloadPattern::Struct {
+                fields,
+                rest: rest.is_some(),
+            }),
+    ))
+    .or_not()
+    .map(|payload| payload.unwrap_or(PayloadPattern::Unit));
+
+    atom_pattern.define(nested(
+        choice((
+            select! { Token::Identifier("_") => PatternKind::Wildcard },
+            literal_pattern
+                .clone()
+                .then(
+                    choice((
+                        punct(Punctuation::DotDotEq).to(true),
+                        punct(Punctuation::DotDot).to(false),
+                    ))
+                    .then(literal_pattern)
+                    .or_not(),
+                )
+                .map(|(start, end)| match end {
+                    Some((inclusive, end)) => PatternKind::Range {
+                        start: Box::new(start),
+                        end: Box::new(end),
+                        inclusive,
+                    },
+                    None => PatternKind::Literal(Box::new(start)),
+                }),
+            ident
+                .map_with(|name, e| TypeExpr {
+                    id: next_id(e.state()),
+                    kind: TypeExprKind::Named(name),
+                    span: e.span(),
+                })
+                .then_ignore(punct(Punctuation::DoubleColon))
+                .then(spanned_ident)
+                .then(payload_pattern)
+                .map(|((ty, name), payload)| PatternKind::Variant { ty, name, payload }),
+            ident
+                .then(
+                    punct(Punctuation::At)
+                        .ignore_then(atom_pattern.clone())
+                        .or_not(),
+                )
+                .map(|(name, pattern)| PatternKind::Binding(name, pattern.map(Box::new))),
+            pattern
+                .clone()
+                .delimited_by(delim(Delimiter::OpenParen), delim(Delimiter::CloseParen))
+                .map(|pattern: Pattern| pattern.kind),
+        ))
+        .map_with(|kind, e| Pattern {
+            id: next_id(e.state()),
+            kind,
+            span: e.span(),
+        }),
+    ));
+
+    pattern.define(
+        atom_pattern
+            .separated_by(op(Operator::BitwiseOr))
+            .at_least(1)
+            .collect::<Vec<_>>()
+            .map_with(|mut alternatives, e| match alternatives.len() {
+                1 => alternatives.pop().expect("there is one alternative"),
+                _ => Pattern {
+                    id: next_id(e.state()),
+                    kind: PatternKind::Or(alternatives),
+                    span: e.span(),
+                },
+            })
+            .labelled("pattern"),
+    );
+
+    let mut expr = Recursive::declare();
+    let mut block = Recursive::declare();
+
+    let block_expr = block.clone().map_with(|block, e| Expr {
+        id: next_id(e.state()),
+        kind: ExprKind::Block(block),
+        span: e.span(),
+    });
+
+    let args = expr
+        .clone()
+        .separated_by(punct(Punctuation::Comma))
+        .allow_trailing()
+        .collect::<Vec<_>>()
+        .delimited_by(delim(Delimiter::OpenParen), delim(Delimiter::CloseParen))
+        .recover_with(via_parser(nested_delimiters(
+            Token::Delimiter(Delimiter::OpenParen),
+            Token::Delimiter(Delimiter::CloseParen),
+            [
+                (
+                    Token::Delimiter(Delimiter::OpenBracket),
+                    Token::Delimiter(Delimiter::CloseBracket),
+                ),
+                (
+                    Token::Delimiter(Delimiter::OpenBrace),
+                    Token::Delimiter(Delimiter::CloseBrace),
+                ),
+            ],
+            |_| Vec::new(),
+        )));
+
+    // Expressions that end with a block, they end a statement without needing a semicolon
+    let block_like = {
+        let if_ = recursive(|if_| {
+            nested(
+                kw(Keyword::If)
+                    .ignore_then(expr.clone())
+                    .then(block.clone())
+                    .then(
+                        kw(Keyword::Else)
+                            .ignore_then(block_expr.clone().or(if_))
+                            .or_not(),
+                    )
+                    .map_with(|((cond, then), otherwise), e| Expr {
+                        id: next_id(e.state()),
+                        kind: ExprKind::If {
+                            cond: Box::new(cond),
+                            then,
+                            otherwise: otherwise.map(Box::new),
+                        },
+                        span: e.span(),
+                    }),
+            )
+        });
+
+        let loop_ = kw(Keyword::Loop)
+            .ignore_then(block.clone())
+            .map(ExprKind::Loop);
+
+        let while_ = kw(Keyword::While)
+            .ignore_then(expr.clone())
+            .then(block.clone())
+            .map(|(cond, body)| ExprKind::While {
+                cond: Box::new(cond),
+                body,
+            });
+
+        let for_ = kw(Keyword::For)
+            .ignore_then(spanned_ident)
+            .then_ignore(kw(Keyword::In))
+            .then(expr.clone())
+            .then(block.clone())
+            .map(|((binding, iter), body)| ExprKind::For {
+                binding,
+                iter: Box::new(iter),
+                body,
+            });
+
+        let arm = pattern
+            .clone()
+            .then(kw(Keyword::If).ignore_then(expr.clone()).or_not())
+            .then_ignore(punct(Punctuation::FatArrow))
+            .then(choice((
+                if_.clone()
+                    .or(block_expr.clone())
+                    .then_ignore(punct(Punctuation::Comma).or_not()),
+                expr.clone().then_ignore(
+                    punct(Punctuation::Comma)
+                        .ignored()
+                        .or(delim(Delimiter::CloseBrace).rewind().ignored()),
+                ),
+            )))
+            .map(|((pattern, guard), body)| MatchArm {
+                pattern,
+                guard,
+                body,
+            });
+
+        let match_ = kw(Keyword::Match)
+            .ignore_then(expr.clone())
+            .then(
+                arm.repeated()
+                    .collect::<Vec<_>>()
+                    .delimited_by(delim(Delimiter::OpenBrace), delim(Delimiter::CloseBrace)),
+            )
+            .map(|(scrutinee, arms)| ExprKind::Match {
+                scrutinee: Box::new(scrutinee),
+                arms,
+            });
+
+        choice((loop_, while_, for_, match_))
+            .map_with(|kind, e| Expr {
+                id: next_id(e.state()),
+                kind,
+                span: e.span(),
+            })
+            .or(block_expr.clone())
+            .or(if_)
+            .boxed()
+    };
+
+    let closure_param = kw(Keyword::Mut)
+        .or_not()
+        .then(spanned_ident)
+        .then(punct(Punctuation::Colon).ignore_then(ty.clone()).or_not())
+        .map_with(|((mutable, name), ty), e| ClosureParam {
+            id: next_id(e.state()),
+            name,
+            mutable: mutable.is_some(),
+            ty,
+            span: e.span(),
+        });
+
+    let closure = choice((
+        op(Operator::LogicalOr).to(Vec::new()),
+        closure_param
+            .separated_by(punct(Punctuation::Comma))
+            .allow_trailing()
+            .collect::<Vec<_>>()
+            .delimited_by(op(Operator::BitwiseOr), op(Operator::BitwiseOr)),
+    ))
+    .then(choice((
+        punct(Punctuation::Arrow)
+            .ignore_then(ty.clone())
+            .then(block_expr.clone())
+            .map(|(ret, body)| (Some(ret), body)),
+        expr.clone().map(|body| (None, body)),
+    )))
+    .map(|(params, (ret, body))| ExprKind::Closure {
+        params,
+        ret,
+        body: Box::new(body),
+    });
+
+    // Expressions that can't be split up by operators
+    let atom = choice((
+        closure,
+        literal.map(ExprKind::Literal),
+        spanned_ident
+            .then_ignore(op(Operator::LogicalNot))
+            .then(args.clone())
+            .map(|(name, args)| ExprKind::Macro { name, args }),
+        ident
+            .map_with(|name, e| TypeExpr {
+                id: next_id(e.state()),
+                kind: TypeExprKind::Named(name),
+                span: e.span(),
+            })
+            .then_ignore(punct(Punctuation::DoubleColon))
+            .then(spanned_ident)
+            .map(|(ty, name)| ExprKind::Path(ty, name)),
+        ident.map(ExprKind::Ident),
+        delim(Delimiter::OpenParen)
+            .then(delim(Delimiter::CloseParen))
+            .to(ExprKind::Literal(Literal::Unit)),
+        expr.clone()
+            .delimited_by(delim(Delimiter::OpenParen), delim(Delimiter::CloseParen))
+            .map(|expr: Expr| expr.kind)
+            .recover_with(via_parser(nested_delimiters(
+                Token::Delimiter(Delimiter::OpenParen),
+                Token::Delimiter(Delimiter::CloseParen),
+                [
+                    (
+                        Token::Delimiter(Delimiter::OpenBracket),
+                        Token::Delimiter(Delimiter::CloseBracket),
+                    ),
+                    (
+                        Token::Delimiter(Delimiter::OpenBrace),
+                        Token::Delimiter(Delimiter::CloseBrace),
+                    ),
+                ],
+                |_| ExprKind::Error,
+            ))),
+        expr.clone()
+            .separated_by(punct(Punctuation::Comma))
+            .allow_trailing()
+            .collect::<Vec<_>>()
+            .delimited_by(
+                delim(Delimiter::OpenBracket),
+                delim(Delimiter::CloseBracket),
+            )
+            .map(ExprKind::Array)
+            .recover_with(via_parser(nested_delimiters(
+                Token::Delimiter(Delimiter::OpenBracket),
+                Token::Delimiter(Delimiter::CloseBracket),
+                [
+                    (
+                        Token::Delimiter(Delimiter::OpenParen),
+                        Token::Delimiter(Delimiter::CloseParen),
+                    ),
+                    (
+                        Token::Delimiter(Delimiter::OpenBrace),
+                        Token::Delimiter(Delimiter::CloseBrace),
+                    ),
+                ],
+                |_| ExprKind::Error,
+            ))),
+        kw(Keyword::Return)
+            .ignore_then(expr.clone().or_not())
+            .map(|value| ExprKind::Return(value.map(Box::new))),
+        kw(Keyword::Break)
+            .ignore_then(expr.clone().or_not())
+            .map(|value| ExprKind::Break(value.map(Box::new))),
+        kw(Keyword::Continue).to(ExprKind::Continue),
+    ))
+    .map_with(|kind, e| Expr {
+        id: next_id(e.state()),
+        kind,
+        span: e.span(),
+    })
+    .or(block_like.clone());
+
+    #[derive(Clone)]
+    enum Postfix {
+        Call(Vec<Expr>),
+        Index(Expr),
+        Method(Ident, Vec<Expr>),
+        Field(Ident),
+        Step(StepOp),
+    }
+
+    let postfix = atom
+        .foldl_with(
+            chained(choice((
+                args.clone().map(Postfix::Call),
+                expr.clone()
+                    .delimited_by(
+                        delim(Delimiter::OpenBracket),
+                        delim(Delimiter::CloseBracket),
+                    )
+                    .map(Postfix::Index),
+                punct(Punctuation::Dot)
+                    .ignore_then(spanned_ident)
+                    .then(args.clone())
+                    .map(|(method, args)| Postfix::Method(method, args)),
+                punct(Punctuation::Dot)
+                    .ignore_then(spanned_ident)
+                    .map(Postfix::Field),
+                choice((
+                    op(Operator::Increment).to(Postfix::Step(StepOp::Increment)),
+                    op(Operator::Decrement).to(Postfix::Step(StepOp::Decrement)),
+                ))
+                .labelled("operator"),
+            )))
+            .repeated(),
+            |lhs, postfix, e| {
+                let lhs = Box::new(lhs);
+                let kind = match postfix {
+                    Postfix::Call(args) => ExprKind::Call { func: lhs, args },
+                    Postfix::Index(index) => ExprKind::Index(lhs, Box::new(index)),
+                    Postfix::Method(method, args) => ExprKind::MethodCall {
+                        receiver: lhs,
+                        method,
+                        args,
+                    },
+                    Postfix::Field(field) => ExprKind::Field(lhs, field),
+                    Postfix::Step(op) => ExprKind::Step(lhs, op),
+                };
+                Expr {
+                    id: next_id(e.state()),
+                    kind,
+                    span: e.span(),
+                }
+            },
+        )
+        .boxed();
+
+    let unary = chained(
+        choice((
+            op(Operator::Subtract).to(UnaryOp::Neg),
+            op(Operator::LogicalNot).to(UnaryOp::Not),
+            op(Operator::BitwiseNot).to(UnaryOp::BitNot),
+        ))
+        .map_with(|op, e| (op, e.span())),
+    )
+    .repeated()
+    .foldr_with(postfix, |(op, span): (UnaryOp, Span), rhs: Expr, e| Expr {
+        id: next_id(e.state()),
+        span: span.union(rhs.span),
+        kind: ExprKind::Unary(op, Box::new(rhs)),
+    });
+
+    let cast = unary
+        .foldl_with(
+            chained(kw(Keyword::As).ignore_then(ty.clone())).repeated(),
+            |expr, ty, e| Expr {
+                id: next_id(e.state()),
+                kind: ExprKind::Cast(Box::new(expr), ty),
+                span: e.span(),
+            },
+        )
+        .boxed();
+
+    // Binary operators, from the highest precedence to the lowest
+    let product = binary(
+        cast,
+        choice((
+            op(Operator::Multiply).to(BinaryOp::Mul),
+            op(Operator::Divide).to(BinaryOp::Div),
+            op(Operator::Modulo).to(BinaryOp::Rem),
+        )),
+    );
+    let sum = binary(
+        product,
+        choice((
+            op(Operator::Add).to(BinaryOp::Add),
+            op(Operator::Subtract).to(BinaryOp::Sub),
+        )),
+    );
+    let shift = binary(
+        sum,
+        choice((
+            op(Operator::ShiftLeft).to(BinaryOp::Shl),
+            op(Operator::ShiftRight).to(BinaryOp::Shr),
+        )),
+    )
+    .boxed();
+    let bit_and = binary(shift, op(Operator::BitwiseAnd).to(BinaryOp::BitAnd));
+    let bit_xor = binary(bit_and, op(Operator::BitwiseXor).to(BinaryOp::BitXor));
+    let bit_or = binary(bit_xor, op(Operator::BitwiseOr).to(BinaryOp::BitOr));
+    let comparison = binary(
+        bit_or,
+        choice((
+            op(Operator::Equal).to(BinaryOp::Eq),
+            op(Operator::NotEqual).to(BinaryOp::Ne),
+            op(Operator::LessThanOrEqual).to(BinaryOp::Le),
+            op(Operator::GreaterThanOrEqual).to(BinaryOp::Ge),
+            op(Operator::LessThan).to(BinaryOp::Lt),
+            op(Operator::GreaterThan).to(BinaryOp::Gt),
+        )),
+    )
+    .boxed();
+    let and = binary(comparison, op(Operator::LogicalAnd).to(BinaryOp::And));
+    let or = binary(and, op(Operator::LogicalOr).to(BinaryOp::Or)).boxed();
+
+    // Assignment has the lowest precedence and is right associative
+    let assign_op = choice((
+        op(Operator::Assign).to(None),
+        op(Operator::AddAssign).to(Some(BinaryOp::Add)),
+        op(Operator::SubAssign).to(Some(BinaryOp::Sub)),
+        op(Operator::MulAssign).to(Some(BinaryOp::Mul)),
+        op(Operator::DivAssign).to(Some(BinaryOp::Div)),
+        op(Operator::ModAssign).to(Some(BinaryOp::Rem)),
+        op(Operator::AndAssign).to(Some(BinaryOp::BitAnd)),
+        op(Operator::OrAssign).to(Some(BinaryOp::BitOr)),
+        op(Operator::XorAssign).to(Some(BinaryOp::BitXor)),
+        op(Operator::ShlAssign).to(Some(BinaryOp::Shl)),
+        op(Operator::ShrAssign).to(Some(BinaryOp::Shr)),
+    ))
+    .labelled("operator");
+
+    expr.define(nested(
+        or.then(assign_op.then(expr.clone()).or_not())
+            .map_with(|(target, value), e| match value {
+                Some((op, value)) => Expr {
+                    id: next_id(e.state()),
+                    kind: ExprKind::Assign {
+                        target: Box::new(target),
+                        op,
+                        value: Box::new(value),
+                    },
+                    span: e.span(),
+                },
+                None => target,
+            })
+            .labelled("expression"),
+    ));
+
+    let semicolon = punct(Punctuation::Semicolon);
+
+    // Unlike `filter`, `select!` reports a token that doesn't match at the token itself
+    let statement_start = select! { token if starts_statement(&token) => token };
+    // A statement that runs into the next one is parsed as if the semicolon was there
+    let terminator = semicolon
+        .clone()
+        .to(true)
+        .or(statement_start.rewind().to(false));
+
+    let let_ = kw(Keyword::Let)
+        .ignore_then(kw(Keyword::Mut).or_not())
+        .then(spanned_ident)
+        .then(punct(Punctuation::Colon).ignore_then(ty.clone()).or_not())
+        .then(op(Operator::Assign).ignore_then(expr.clone()).or_not())
+        .then(terminator.clone())
+        .validate(|((((mutable, name), ty), value), semicolon), e, emitter| {
+            check_semicolon(semicolon, e.span(), emitter);
+            StmtKind::Let {
+                name,
+                mutable: mutable.is_some(),
+                ty,
+                value,
+            }
+        });
+
+    let const_ = kw(Keyword::Const)
+        .ignore_then(spanned_ident)
+        .then(punct(Punctuation::Colon).ignore_then(ty.clone()).or_not())
+        .then_ignore(op(Operator::Assign))
+        .then(expr.clone())
+        .then(terminator)
+        .validate(|(((name, ty), value), semicolon), e, emitter| {
+            check_semicolon(semicolon, e.span(), emitter);
+            ItemKind::Const { name, ty, value }
+        });
+
+    let param = kw(Keyword::Mut)
+        .or_not()
+        .then(spanned_ident)
+        .then_ignore(punct(Punctuation::Colon))
+        .then(ty.clone())
+        .map_with(|((mutable, name), ty), e| Param {
+            id: next_id(e.state()),
+            name,
+            mutable: mutable.is_some(),
+            ty,
+            span: e.span(),
+        });
+
+    let params = param
+        .clone()
+        .separated_by(punct(Punctuation::Comma))
+        .allow_trailing()
+        .collect::<Vec<_>>()
+        .delimited_by(delim(Delimiter::OpenParen), delim(Delimiter::CloseParen));
+
+    // `self` is an identifier, so that it can be used like any other variable in the body
+    let receiver = kw(Keyword::Mut)
+        .or_not()
+        .then_ignore(select! { Token::Identifier("self") => () })
+        .map_with(|mutable, e| Receiver {
+            id: next_id(e.state()),
+            mutable: mutable.is_some(),
+            span: e.span(),
+        })
+        .then_ignore(
+            punct(Punctuation::Comma)
+                .ignored()
+                .or(delim(Delimiter::CloseParen).rewind().ignored()),
+        );
+
+    let generics = spanned_ident
+        .then(
+            punct(Punctuation::Colon)
+                .ignore_then(
+                    ty.clone()
+                        .separated_by(op(Operator::Add))
+                        .at_least(1)
+                        .collect::<Vec<_>>(),
+                )
+                .or_not(),
+        )
+        .map_with(|(name, bounds), e| Generic {
+            id: next_id(e.state()),
+            name,
+            bounds: bounds.unwrap_or_default(),
+            span: e.span(),
+        })
+        .separated_by(punct(Punctuation::Comma))
+        .allow_trailing()
+        .collect::<Vec<_>>()
+        .delimited_by(op(Operator::LessThan), op(Operator::GreaterThan))
+        .or_not()
+        .map(Option::unwrap_or_default);
+
+    // Everything of a function but the body, which trait methods can leave out
+    let signature = kw(Keyword::Fn)
+        .ignore_then(spanned_ident)
+        .then(generics)
+        .then(
+            receiver
+                .or_not()
+                .then(
+                    param
+                        .separated_by(punct(Punctuation::Comma))
+                        .allow_trailing()
+                        .collect::<Vec<_>>(),
+                )
+                .delimited_by(delim(Delimiter::OpenParen), delim(Delimiter::CloseParen)),
+        )
+        .then(punct(Punctuation::Arrow).ignore_then(ty.clone()).or_not())
+        .map(|(((name, generics), (receiver, params)), ret)| Function {
+            name,
+            generics,
+            receiver,
+            params,
+            ret,
+            body: None,
+        });
+
+    let function = signature
+        .clone()
+        .then(block.clone())
+        .then_ignore(semicolon.clone().or_not())
+        .map(|(function, body)| Function {
+            body: Some(body),
+            ..function
+        });
+
+    let fn_ = function.clone().validate(|function, _, emitter| {
+        if let Some(receiver) = &function.receiver {
+            emitter.emit(SyntaxError::custom(
+                receiver.span,
+                codes::E0205,
+                "Only methods in an `impl` or `trait` block can take `self`",
+            ));
+        }
+        ItemKind::Fn(function)
+    });
+
+    let methods = |function: Boxed<'a, 'a, I, Function, Extra<'a>>| {
+        function
+            .map_with(|function, e| Method {
+                id: next_id(e.state()),
+                doc: doc_at(e.span(), e.state()),
+                function,
+                span: e.span(),
+            })
+            .repeated()
+            .collect::<Vec<_>>()
+            .delimited_by(delim(Delimiter::OpenBrace), delim(Delimiter::CloseBrace))
+    };
+
+    let impl_ = kw(Keyword::Impl)
+        .ignore_then(ty.clone())
+        .then(kw(Keyword::For).ignore_then(ty.clone()).or_not())
+        .then(methods(function.boxed()))
+        .then_ignore(semicolon.clone().or_not())
+        .map(|((ty, for_ty), methods)| {
+            // In `impl Trait for Type`, the first type is the trait
+            let (trait_, ty) = match for_ty {
+                Some(for_ty) => (Some(ty), for_ty),
+                None => (None, ty),
+            };
+            ItemKind::Impl(Box::new(Impl {
+                trait_,
+                ty,
+                methods,
+            }))
+        });
+
+    let trait_ = kw(Keyword::Trait)
+        .ignore_then(spanned_ident)
+        .then(methods(
+            signature
+                .then(
+                    block
+                        .clone()
+                        .then_ignore(semicolon.clone().or_not())
+                        .map(Some)
+                        .or(semicolon.clone().to(None)),
+                )
+                .map(|(function, body)| Function { body, ..function })
+                .boxed(),
+        ))
+        .then_ignore(semicolon.clone().or_not())
+        .map(|(name, methods)| ItemKind::Trait(Box::new(Trait { name, methods })));
+
+    let field = spanned_ident
+        .then_ignore(punct(Punctuation::Colon))
+        .then(ty.clone())
+        .map_with(|(name, ty), e| StructField {
+            doc: doc_at(e.span(), e.state()),
+            name,
+            ty,
+            span: e.span(),
+        });
+
+    // Fields are separated by commas, constructors and destructors end with their body or `;`
+    let member_body = block
+        .clone()
+        .then_ignore(semicolon.clone().or_not())
+        .map(Some)
+        .or(semicolon.clone().to(None));
+    let member = choice((
+        spanned_ident
+            .then(params)
+            .then(member_body.clone())
+            .map_with(|((name, params), body), e| {
+                Member::Constructor(Constructor {
+                    id: next_id(e.state()),
+                    name,
+                    params,
+                    body,
+                    span: e.span(),
+                })
+            }),
+        op(Operator::BitwiseNot)
+            .ignore_then(spanned_ident)
+            .then_ignore(delim(Delimiter::OpenParen))
+            .then_ignore(delim(Delimiter::CloseParen))
+            .then(member_body)
+            .map_with(|(name, body), e| {
+                Member::Destructor(Destructor {
+                    id: next_id(e.state()),
+                    name,
+                    body,
+                    span: e.span(),
+                })
+            }),
+        field.clone().map(Member::Field).then_ignore(
+            punct(Punctuation::Comma)
+                .ignored()
+                .or(delim(Delimiter::CloseBrace).rewind().ignored()),
+        ),
+    ));
+
+    let struct_ = kw(Keyword::Struct)
+        .ignore_then(spanned_ident)
+        .then(
+            member
+                .repeated()
+                .collect::<Vec<_>>()
+                .delimited_by(delim(Delimiter::OpenBrace), delim(Delimiter::CloseBrace)),
+        )
+        .then_ignore(semicolon.clone().or_not())
+        .validate(|(name, members), _, emitter| {
+            ItemKind::Struct(Box::new(make_struct(name, members, emitter)))
+        });
+
+    let variant = spanned_ident
+        .then(
+            choice((
+                ty.separated_by(punct(Punctuation::Comma))
+                    .allow_trailing()
+                    .collect::<Vec<_>>()
+                    .delimited_by(delim(Delimiter::OpenParen), delim(Delimiter::CloseParen))
+                    .map(VariantKind::Tuple),
+                field
+                    .separated_by(punct(Punctuation::Comma))
+                    .allow_trailing()
+                    .collect::<Vec<_>>()
+                    .delimited_by(delim(Delimiter::OpenBrace), delim(Delimiter::CloseBrace))
+                    .map(VariantKind::Struct),
+            ))
+            .or_not(),
+        )
+        .map_with(|(name, kind), e| Variant {
+            id: next_id(e.state()),
+            doc: doc_at(e.span(), e.state()),
+            name,
+            kind: kind.unwrap_or(VariantKind::Unit),
+            span: e.span(),
+        });
+
+    let enum_ = kw(Keyword::Enum)
+        .ignore_then(spanned_ident)
+        .then(
+            variant
+                .separated_by(punct(Punctuation::Comma))
+                .allow_trailing()
+                .collect::<Vec<_>>()
+                .delimited_by(delim(Delimiter::OpenBrace), delim(Delimiter::CloseBrace)),
+        )
+        .then_ignore(semicolon.clone().or_not())
+        .map(|(name, variants)| ItemKind::Enum(Box::new(Enum { name, variants })));
+
+    let item = choice((fn_, const_, struct_, impl_, trait_, enum_))
+        .map_with(|kind, e| Item {
+            id: next_id(e.state()),
+            doc: doc_at(e.span(), e.state()),
+            kind,
+            span: e.span(),
+        })
+        .map(StmtKind::Item);
+
+    let block_item = item
+        .or(let_)
+        .map_with(|kind, e| {
+            BlockItem::Stmt(Stmt {
+                id: next_id(e.state()),
+                kind,
+                span: e.span(),
+            })
+        })
+        .or(block_like
+            .map(|expr| (expr, true))
+            .or(expr.clone().map(|expr| (expr, false)))
+            .then(semicolon.clone().or_not())
+            .map_with(|((expr, block_like), semicolon), e| BlockItem::Expr {
+                id: next_id(e.state()),
+                expr,
+                semicolon: semicolon.is_some(),
+                block_like,
+            }))
+        .boxed();
+
+    // Skips the rest of a statement that failed to parse, up to and including the semicolon or
+    // up to where the next statement or item starts. Delimited groups are skipped as a whole so
+    // that a closing brace inside of them doesn't end the block.
+    let token_tree = recursive(|token_tree| {
+        // Parentheses and brackets can't contain statements, so when one is never closed it ends
+        // where the next statement starts
+        let group = |open, close| {
+            token_tree
+                .clone()
+                .and_is(any().filter(|token| !starts_statement(token)).ignored())
+                .repeated()
+                .delimited_by(delim(open), delim(close).ignored().or(empty()))
+        };
+
+        choice((
+            group(Delimiter::OpenParen, Delimiter::CloseParen),
+            token_tree
+                .clone()
+                .repeated()
+                .delimited_by(delim(Delimiter::OpenBrace), delim(Delimiter::CloseBrace)),
+            group(Delimiter::OpenBracket, Delimiter::CloseBracket),
+            any()
+                .filter(|token| !matches!(token, Token::Delimiter(_)))
+                .ignored(),
+        ))
+    });
+    let skip_statement = token_tree
+        .clone()
+        .or(any().and_is(delim(Delimiter::CloseBrace).not()).ignored())
+        .then(token_tree.and_is(statement_start.not()).repeated())
+        .then(semicolon.or_not())
+        .map_with(|_, e| {
+            let (span, state) = (e.span(), e.state());
+            let expr = Expr {
+                id: next_id(state),
+                kind: ExprKind::Error,
+                span,
+            };
+            BlockItem::Stmt(Stmt {
+                id: next_id(state),
+                kind: StmtKind::Semi(expr),
+                span,
+            })
+        });
+
+    let block_item = block_item.recover_with(via_parser(skip_statement));
+
+    block.define(nested(
+        block_item
+            .clone()
+            .repeated()
+            .collect::<Vec<_>>()
+            .delimited_by(delim(Delimiter::OpenBrace), delim(Delimiter::CloseBrace))
+            .validate(|items, e, emitter| make_block(items, e.span(), next_id(e.state()), emitter)),
+    ));
+
+    // Stray closing delimiters at the top level are skipped
+    block_item
+        .recover_with(skip_then_retry_until(any().ignored(), end()))
+        .repeated()
+        .collect::<Vec<_>>()
+        .validate(|items, e, emitter| make_block(items, e.span(), next_id(e.state()), emitter))
+        .map(|body| Ast { doc: None, body })
+}
+
+/// Whether a statement or item starts at the token, which is where skipping a statement that
+/// failed to parse stops.
+pub(crate) fn starts_statement(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Punctuation(Punctuation::Semicolon)
+            | Token::Keyword(
+                Keyword::Let
+                    | Keyword::Const
+                    | Keyword::Fn
+                    | Keyword::Struct
+                    | Keyword::Impl
+                    | Keyword::Trait
+                    | Keyword::Enum
+                    | Keyword::Type
+                    | Keyword::Use
+                    | Keyword::Mod
+                    | Keyword::Static
+                    | Keyword::Extern
+            )
+    )
+}
+
+/// Parses a node that can contain itself one level deeper, or fails when that's too deep.
+fn nested<'a, I, O, P>(parser: P) -> impl Parser<'a, I, O, Extra<'a>> + Clone
+where
+    I: ValueInput<'a, Token = Token<'a>, Span = Span>,
+    P: Parser<'a, I, O, Extra<'a>> + Clone,
+{
+    // Deeper levels aren't parsed at all, so the parser itself doesn't run out of stack
+    let enter = custom(|inp: &mut InputRef<'a, '_, I, Extra<'a>>| {
+        let nesting = &mut inp.state().nesting;
+        let outer = nesting.len() - 1;
+        let depth = nesting[outer].depth + 1;
+        if depth > MAX_NESTING {
+            let before = inp.cursor();
+            inp.next_maybe();
+            return Err(too_deep(inp.span_since(&before)));
+        }
+        nesting.push(Nesting {
+            depth,
+            outer,
+            ..Nesting::default()
+        });
+        Ok(())
+    });
+
+    enter.ignore_then(parser).validate(|out, e, emitter| {
+        let span = e.span();
+        let nesting = &mut e.state().nesting;
+        let mut inner = nesting[nesting.len() - 1];
+        check_height(&mut inner, span, emitter);
+        let mut outer = nesting[inner.outer];
+        outer.inner = outer.inner.max(inner.height());
+        outer.too_deep |= inner.too_deep;
+        nesting.push(outer);
+        out
+    })
+}
+
+/// Parses an operator, cast or postfix that contains what was parsed before it.
+fn chained<'a, I, O, P>(parser: P) -> impl Parser<'a, I, O, Extra<'a>> + Clone
+where
+    I: ValueInput<'a, Token = Token<'a>, Span = Span>,
+    P: Parser<'a, I, O, Extra<'a>> + Clone,
+{
+    parser.validate(|out, e, emitter| {
+        let span = e.span();
+        let nesting = &mut e.state().nesting;
+        let mut innermost = nesting[nesting.len() - 1];
+        innermost.chained += 1;
+        check_height(&mut innermost, span, emitter);
+        nesting.push(innermost);
+        out
+    })
+}
+
+/// Reports a level that has grown too high, unless it's already been reported inside of it. The
+/// nodes are still parsed as they don't make the parser recurse any deeper.
+fn check_height(nesting: &mut Nesting, span: Span, emitter: &mut Emitter) {
+    if nesting.height() > MAX_NESTING && !nesting.too_deep {
+        emitter.emit(too_deep(span));
+        nesting.too_deep = true;
+    }
+}
+
+fn is_too_deep<T>(error: &SyntaxError<T>) -> bool {
+    matches!(error, SyntaxError::Custom { code, .. } if *code == codes::E0206)
+}
+
+fn too_deep<'a>(span: Span) -> SyntaxError<'a, Token<'a>> {
+    SyntaxError::custom(
+        span,
+        codes::E0206,
+        format!("Nested more than {} levels deep", MAX_NESTING),
+    )
+}
+
+/// A level of left associative binary operators. Errors expect an operator rather than every
+/// one of them.
+fn binary<'a, I, P, O>(operand: P, op: O) -> impl Parser<'a, I, Expr, Extra<'a>> + Clone
+where
+    I: ValueInput<'a, Token = Token<'a>, Span = Span>,
+    P: Parser<'a, I, Expr, Extra<'a>> + Clone,
+    O: Parser<'a, I, BinaryOp, Extra<'a>> + Clone,
+{
+    operand.clone().foldl_with(
+        chained(op.labelled("operator").then(operand)).repeated(),
+        |lhs, (op, rhs), e| Expr {
+            id: next_id(e.state()),
+            kind: ExprKind::Binary(Box::new(lhs), op, Box::new(rhs)),
+            span: e.span(),
+        },
+    )
+}
+
+/// Turns the items of a block into statements. Only block-like expressions can leave out the
+/// semicolon after them, unless they're the last item which is then the value of the block.
+fn make_block(items: Vec<BlockItem>, span: Span, id: NodeId, emitter: &mut Emitter) -> Block {
+    let mut stmts = Vec::with_capacity(items.len());
+    let mut tail = None;
+    let count = items.len();
+    // Statements that failed to parse were skipped, and already have an error
+    let skipped = items
+        .iter()
+        .map(|item| {
+            matches!(
+                item,
+                BlockItem::Stmt(Stmt {
+                    kind: StmtKind::Semi(Expr {
+                        kind: ExprKind::Error,
+                        ..
+                    }),
+                    ..
+                })
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let stmt = |id, expr: Expr, semicolon| Stmt {
+        id,
+        span: expr.span,
+        kind: if semicolon {
+            StmtKind::Semi(expr)
+        } else {
+            StmtKind::Expr(expr)
+        },
+    };
+
+    for (i, item) in items.into_iter().enumerate() {
+        match item {
+            BlockItem::Stmt(s) => stmts.push(s),
+            BlockItem::Expr {
+                id,
+                expr,
+                semicolon: true,
+                ..
+            } => stmts.push(stmt(id, expr, true)),
+            BlockItem::Expr { expr, .. } if i + 1 == count => tail = Some(Box::new(expr)),
+            BlockItem::Expr {
+                id,
+                expr,
+                block_like: true,
+                ..
+            } => stmts.push(stmt(id, expr, false)),
+            BlockItem::Expr { id, expr, .. } => {
+                // The expression is the start of the statement after it that failed to parse
+                if !skipped[i + 1] {
+                    emitter.emit(SyntaxError::custom(
+                        expr.span.to_end(),
+                        codes::E0202,
+                        "Expected `;` after expression",
+                    ));
+                }
+                stmts.push(stmt(id, expr, true));
+            }
+        }
+    }
+
+    Block {
+        id,
+        stmts,
+        tail,
+        span,
+    }
+}
+
+/// Reports a `let` or `const` that ends without a semicolon, right after the end of its span.
+fn check_semicolon(semicolon: bool, span: Span, emitter: &mut Emitter) {
+    if !semicolon {
+        emitter.emit(SyntaxError::custom(
+            span.to_end(),
+            codes::E0202,
+            "Expected `;` after statement",
+        ));
+    }
+}
+
+/// Sorts the members of a struct into its fields, constructor and destructor.
+fn make_struct(name: Ident, members: Vec<Member>, emitter: &mut Emitter) -> Struct {
+    let mut fields = Vec::new();
+    let mut constructor: Option<Constructor> = None;
+    let mut destructor: Option<Destructor> = None;
+
+    for member in members {
+        let member_name = match &member {
+            Member::Field(_) => None,
+            Member::Constructor(c) => Some(&c.name),
+            Member::Destructor(d) => Some(&d.name),
+        };
+        if let Some(member_name) = member_name.filter(|member| member.name != name.name) {
+            emitter.emit(SyntaxError::custom(
+                member_name.span,
+                codes::E0204,
+                "Constructors and destructors have to be named after their struct",
+            ));
+        }
+
+        match member {
+            Member::Field(field) => fields.push(field),
+            Member::Constructor(c) if constructor.is_some() => {
+                emitter.emit(SyntaxError::custom(
+                    c.span,
+                    codes::E0204,
+                    "A struct can only have one constructor",
+                ));
+            }
+            Member::Constructor(c) => {
+                if c.body.is_none() && !c.params.is_empty() {
+                    emitter.emit(SyntaxError::custom(
+                        c.span,
+                        codes::E0204,
+                        "A constructor without a body takes the fields in order and can't have parameters",
+                    ));
+                }
+                constructor = Some(c);
+            }
+            Member::Destructor(d) if destructor.is_some() => {
+                emitter.emit(SyntaxError::custom(
+                    d.span,
+                    codes::E0204,
+                    "A struct can only have one destructor",
+                ));
+            }
+            Member::Destructor(d) => destructor = Some(d),
+        }
+    }
+
+    Struct {
+        name,
+        fields,
+        constructor,
+        destructor,
+    }
+}
+
+/// Joins the lines of doc comments, `None` if there weren't any.
+fn doc(lines: Vec<&str>) -> Option<String> {
+    if lines.is_empty() {
+        return None;
+    }
+
+    let lines = lines
+        .iter()
+        .map(|line| line.strip_prefix(' ').unwrap_or(line).trim_end())
+        .collect::<Vec<_>>();
+
+    Some(lines.join("\n").trim().to_string())
+}
+
+/// Converts a literal token into a literal value, or the message of why it doesn't fit.
+fn literal(token: token::Literal) -> Result<Literal, &'static str> {
+    Ok(match token {
+        token::Literal::Integer {
+            digits,
+            radix,
+            suffix,
+        } => Literal::Int {
+            value: u64::from_str_radix(&digits.replace('_', ""), radix)
+                .map_err(|_| "Integer literal is too large")?,
+            suffix,
+        },
+        token::Literal::Float { digits, suffix } => Literal::Float {
+            value: digits
+                .replace('_', "")
+                .parse()
+                .map_err(|_| "Invalid float literal")?,
+            suffix,
+        },
+        token::Literal::String(string) => Literal::String(string.into_owned()),
+        token::Literal::Char(char) => Literal::Char(char),
+        token::Literal::Boolean(boolean) => Literal::Bool(boolean == "true"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cst, diagnostic::Diagnostic, lexer};
+
+    fn parse_ok(source: &str) -> Ast {
+        cst::assert_agrees(source);
+        let (tokens, errors) = lexer::lex(source, FileId::TEST);
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let (ast, errors) = parse(&tokens, FileId::TEST);
+        assert!(errors.is_empty(), "{:?}", errors);
+        ast.unwrap()
+    }
+
+    fn parse_expr(source: &str) -> ExprKind {
+        let ast = parse_ok(source);
+        assert!(ast.body.stmts.is_empty());
+        ast.body.tail.unwrap().kind
+    }
+
+    fn errors(source: &str) -> Vec<ParseError> {
+        cst::assert_agrees(source);
+        let (tokens, _) = lexer::lex(source, FileId::TEST);
+        parse(&tokens, FileId::TEST).1
+    }
+
+    /// Prints an expression with parentheses around every operation, to check precedence.
+    fn show(expr: &ExprKind) -> String {
+        match expr {
+            ExprKind::Literal(Literal::Int { value, .. }) => value.to_string(),
+            ExprKind::Ident(ident) => ident.to_string(),
+            ExprKind::Unary(op, expr) => format!("({:?} {})", op, show(&expr.kind)),
+            ExprKind::Binary(lhs, op, rhs) => {
+                format!("({} {:?} {})", show(&lhs.kind), op, show(&rhs.kind))
+            }
+            ExprKind::Assign { target, op, value } => {
+                format!("({} {:?}= {})", show(&target.kind), op, show(&value.kind))
+            }
+            ExprKind::Cast(expr, ty) => format!("({} as {:?})", show(&expr.kind), ty.kind),
+            ExprKind::Call { func, args } => format!(
+                "{}({})",
+                show(&func.kind),
+                args.iter()
+                    .map(|arg| show(&arg.kind))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            ExprKind::Field(expr, field) => format!("{}.{}", show(&expr.kind), field.name),
+            ExprKind::MethodCall {
+                receiver,
+                method,
+                args,
+            } => format!(
+                "{}.{}({})",
+                show(&receiver.kind),
+                method.name,
+                args.iter()
+                    .map(|arg| show(&arg.kind))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            ExprKind::Path(ty, name) => format!("{:?}::{}", ty.kind, name.name),
+            ExprKind::Step(expr, op) => format!("({} {:?})", show(&expr.kind), op),
+            expr => format!("{:?}", expr),
+        }
+    }
+
+    #[test]
+    fn test_precedence() {
+        let cases = [
+            ("1 + 2 * 3", "(1 Add (2 Mul 3))"),
+            ("1 * 2 + 3", "((1 Mul 2) Add 3)"),
+            ("1 - 2 - 3", "((1 Sub 2) Sub 3)"),
+            ("a = b = c", "(a None= (b None= c))"),
+            ("a += 1 + 2", "(a Some(Add)= (1 Add 2))"),
+            ("a || b && c", "(a Or (b And c))"),
+            ("a == b && c < d", "((a Eq b) And (c Lt d))"),
+            ("a | b ^ c & d", "(a BitOr (b BitXor (c BitAnd d)))"),
+            ("1 << 2 + 3", "(1 Shl (2 Add 3))"),
+            ("-a * b", "((Neg a) Mul b)"),
+            ("!a.b", "(Not a.b)"),
+            ("-x as f32", "((Neg x) as Named(\"f32\"))"),
+            ("a as i64 + 1", "((a as Named(\"i64\")) Add 1)"),
+            ("(1 + 2) * 3", "((1 Add 2) Mul 3)"),
+            ("f(a, b + 1)(c)", "f(a, (b Add 1))(c)"),
+            ("i++", "(i Increment)"),
+            ("a.b(c).d", "a.b(c).d"),
+            ("-p.len() * 2", "((Neg p.len()) Mul 2)"),
+            ("Point::origin()", "Named(\"Point\")::origin()"),
+        ];
+
+        for (source, expected) in cases {
+            assert_eq!(show(&parse_expr(source)), expected, "{}", source);
+        }
+
+        // Calling a function in a field isn't a method call
+        assert!(matches!(parse_expr("a.b(c)"), ExprKind::MethodCall { .. }));
+        assert!(matches!(parse_expr("(a.b)(c)"), ExprKind::Call { .. }));
+    }
+
+    #[test]
+    fn test_let_and_const() {
+        let ast = parse_ok("let mut x: int = 5; let y; const Z = 1;");
+
+        assert!(matches!(
+            &ast.body.stmts[0].kind,
+            StmtKind::Let { name, mutable: true, ty: Some(TypeExpr { kind: TypeExprKind::Named(ty), .. }), value: Some(_) }
+                if name.name == "x" && ty == "int"
+        ));
+        assert!(matches!(
+            &ast.body.stmts[1].kind,
+            StmtKind::Let {
+                mutable: false,
+                ty: None,
+                value: None,
+                ..
+            }
+        ));
+        assert!(matches!(
+            &ast.body.stmts[2].kind,
+            StmtKind::Item(Item {
+                kind: ItemKind::Const { ty: None, .. },
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_function() {
+        let ast = parse_ok(
+            "/// Adds two numbers.\nfn add(a: int, mut b: [int]) -> int {\n    return a + b;\n}\nfn main() {}",
+        );
+
+        let StmtKind::Item(item) = &ast.body.stmts[0].kind else {
+            panic!("expected an item");
+        };
+        let ItemKind::Fn(add) = &item.kind else {
+            panic!("expected a function");
+        };
+        assert_eq!(item.doc.as_deref(), Some("Adds two numbers."));
+        assert_eq!(add.name.name, "add");
+        assert_eq!(add.params.len(), 2);
+        assert!(add.params[1].mutable);
+        assert!(matches!(add.params[1].ty.kind, TypeExprKind::Array(_)));
+        assert!(
+            matches!(&add.ret, Some(TypeExpr { kind: TypeExprKind::Named(ty), .. }) if ty == "int")
+        );
+        assert!(matches!(
+            &add.body.as_ref().unwrap().stmts[0].kind,
+            StmtKind::Semi(Expr {
+                kind: ExprKind::Return(Some(_)),
+                ..
+            })
+        ));
+
+        let StmtKind::Item(Item {
+            kind: ItemKind::Fn(main),
+            ..
+        }) = &ast.body.stmts[1].kind
+        else {
+            panic!("expected a function");
+        };
+        assert!(main.ret.is_none());
+    }
+
+    #[test]
+    fn test_struct() {
+        let ast = parse_ok(
+            "struct Point { x: int, y: [int], Point(); ~Point() { close(self.x); } }\nstruct Empty {}",
+        );
+
+        let StmtKind::Item(Item {
+            kind: ItemKind::Struct(point),
+            ..
+        }) = &ast.body.stmts[0].kind
+        else {
+            panic!("expected a struct");
+        };
+        assert_eq!(point.name.name, "Point");
+        let fields = point.fields.iter().map(|field| field.name.name.as_str());
+        assert_eq!(fields.collect::<Vec<_>>(), ["x", "y"]);
+        assert!(matches!(point.fields[1].ty.kind, TypeExprKind::Array(_)));
+        assert!(point.constructor.as_ref().unwrap().body.is_none());
+        assert_eq!(
+            point
+                .destructor
+                .as_ref()
+                .unwrap()
+                .body
+                .as_ref()
+                .unwrap()
+                .stmts
+                .len(),
+            1
+        );
+
+        let ast = parse_ok("struct File { fd: int, File(path: string) { self.fd = open(path); } }");
+        let StmtKind::Item(Item {
+            kind: ItemKind::Struct(file),
+            ..
+        }) = &ast.body.stmts[0].kind
+        else {
+            panic!("expected a struct");
+        };
+        let constructor = file.constructor.as_ref().unwrap();
+        assert_eq!(constructor.params[0].name.name, "path");
+        assert!(constructor.body.is_some());
+        assert!(file.destructor.is_none());
+    }
+
+    #[test]
+    fn test_docs() {
+        let ast = parse_ok(
+            "//! The file.\nstruct P {\n    /// The number.\n    x: int,\n    y: int,\n    P();\n}\nenum E {\n    /// The first.\n    A,\n    B,\n}",
+        );
+        assert_eq!(ast.doc.as_deref(), Some("The file."));
+
+        let StmtKind::Item(Item {
+            kind: ItemKind::Struct(def),
+            ..
+        }) = &ast.body.stmts[0].kind
+        else {
+            panic!("expected a struct");
+        };
+        assert_eq!(def.fields[0].doc.as_deref(), Some("The number."));
+        assert_eq!(def.fields[1].doc, None);
+
+        let StmtKind::Item(Item {
+            kind: ItemKind::Enum(def),
+            ..
+        }) = &ast.body.stmts[1].kind
+        else {
+            panic!("expected an enum");
+        };
+        assert_eq!(def.variants[0].doc.as_deref(), Some("The first."));
+        assert_eq!(def.variants[1].doc, None);
+
+        // Doc comments with nothing to document are ignored
+        let ast = parse_ok("fn f() {\n    //! Not the file.\n    1\n}");
+        assert_eq!(ast.doc, None);
+        parse_ok("let a = 1;\n/// At the end.");
+        parse_ok("let a = 1 + /** Inside. */ 2;");
+        parse_ok("fn f() {\n    let a = 1;\n    /// At the end of a block.\n}");
+        parse_ok("/// A statement.\nlet a = 1;\n/// An expression.\na + 1");
+    }
+
+    #[test]
+    fn test_impl() {
+        let ast = parse_ok(
+            "impl Point {\n    /// The length.\n    fn len(self) -> int { 0 }\n    fn shift(mut self, by: int) {}\n    fn origin() -> Point { Point(0, 0) }\n}",
+        );
+
+        let StmtKind::Item(Item {
+            kind: ItemKind::Impl(def),
+            ..
+        }) = &ast.body.stmts[0].kind
+        else {
+            panic!("expected an impl");
+        };
+        assert!(matches!(&def.ty.kind, TypeExprKind::Named(name) if name == "Point"));
+        let [len, shift, origin] = def.methods.as_slice() else {
+            panic!("expected three methods");
+        };
+        assert_eq!(len.doc.as_deref(), Some("The length."));
+        assert!(len.function.params.is_empty());
+        assert!(!len.function.receiver.as_ref().unwrap().mutable);
+        assert!(shift.function.receiver.as_ref().unwrap().mutable);
+        assert_eq!(shift.function.params[0].name.name, "by");
+        assert!(origin.function.receiver.is_none());
+
+        // `self` is only a receiver in methods, and only as the first parameter
+        let errors = errors("fn len(self) -> int { 0 }");
+        assert_eq!(
+            Diagnostic::from(errors[0].clone()).code,
+            Some(crate::diagnostic::codes::E0205)
+        );
+        assert_eq!(self::errors("impl P { fn f(a: int, self) {} }").len(), 1);
+    }
+
+    #[test]
+    fn test_trait() {
+        let ast = parse_ok(
+            "trait Shape {\n    fn area(self) -> float;\n    fn name(self) -> string { \"shape\" }\n}\nimpl Shape for Circle {}\nfn f<T: Shape + Named, U>(a: T, b: dyn Shape) {}",
+        );
+
+        let StmtKind::Item(Item {
+            kind: ItemKind::Trait(def),
+            ..
+        }) = &ast.body.stmts[0].kind
+        else {
+            panic!("expected a trait");
+        };
+        assert_eq!(def.name.name, "Shape");
+        let [area, name] = def.methods.as_slice() else {
+            panic!("expected two methods");
+        };
+        assert!(area.function.body.is_none());
+        assert!(name.function.body.is_some());
+
+        let StmtKind::Item(Item {
+            kind: ItemKind::Impl(def),
+            ..
+        }) = &ast.body.stmts[1].kind
+        else {
+            panic!("expected an impl");
+        };
+        assert!(
+            matches!(&def.trait_.as_ref().unwrap().kind, TypeExprKind::Named(name) if name == "Shape")
+        );
+        assert!(matches!(&def.ty.kind, TypeExprKind::Named(name) if name == "Circle"));
+
+        let StmtKind::Item(Item {
+            kind: ItemKind::Fn(f),
+            ..
+        }) = &ast.body.stmts[2].kind
+        else {
+            panic!("expected a function");
+        };
+        let [t, u] = f.generics.as_slice() else {
+            panic!("expected two generics");
+        };
+        assert_eq!(t.name.name, "T");
+        assert_eq!(t.bounds.len(), 2);
+        assert!(u.bounds.is_empty());
+        assert!(matches!(&f.params[1].ty.kind, TypeExprKind::Dyn(name) if name == "Shape"));
+
+        // Only the methods of a trait can leave out their body
+        assert_eq!(errors("impl P { fn f(self); }").len(), 1);
+        assert_eq!(errors("fn f();").len(), 1);
+    }
+
+    #[test]
+    fn test_struct_errors() {
+        for (source, message) in [
+            (
+                "struct P { P(); P(); }",
+                "A struct can only have one constructor",
+            ),
+            (
+                "struct P { ~P(); ~P() {} }",
+                "A struct can only have one destructor",
+            ),
+            (
+                "struct P { Q(); }",
+                "Constructors and destructors have to be named after their struct",
+            ),
+            (
+                "struct P { x: int, P(x: int); }",
+                "A constructor without a body takes the fields in order and can't have parameters",
+            ),
+        ] {
+            let errors = errors(source);
+            assert!(
+                matches!(&errors[..], [SyntaxError::Custom { code, .. }] if *code == codes::E0204),
+                "{:?}",
+                errors
+            );
+            assert_eq!(errors[0].to_string(), message);
+        }
+        assert_eq!(errors("struct P { x: int y: int }").len(), 1);
+    }
+
+    #[test]
+    fn test_literal_errors() {
+        let errors = errors("let a = 18446744073709551616;");
+        assert!(matches!(
+            &errors[..],
+            [SyntaxError::Custom { code, .. }] if *code == codes::E0203
+        ));
+        assert_eq!(errors[0].to_string(), "Integer literal is too large");
+    }
+
+    #[test]
+    fn test_closure() {
+        let ExprKind::Closure { params, ret, body } = parse_expr("|a, mut b: int| a + b * 2")
+        else {
+            panic!("expected a closure");
+        };
+        assert_eq!(params.len(), 2);
+        assert!(params[0].ty.is_none() && !params[0].mutable);
+        assert!(params[1].ty.is_some() && params[1].mutable);
+        assert!(ret.is_none());
+        assert_eq!(show(&body.kind), "(a Add (b Mul 2))");
+
+        let ExprKind::Closure { params, ret, body } = parse_expr("|| -> int { 1 }") else {
+            panic!("expected a closure");
+        };
+        assert!(params.is_empty());
+        assert!(ret.is_some());
+        assert!(matches!(body.kind, ExprKind::Block(_)));
+
+        // The body takes everything after the parameters
+        assert!(matches!(
+            parse_expr("f(|x| x = 1)"),
+            ExprKind::Call { args, .. } if matches!(args[0].kind, ExprKind::Closure { .. })
+        ));
+    }
+
+    #[test]
+    fn test_enum() {
+        let ast = parse_ok(
+            "enum Shape { Circle(float), Rect { w: float, h: float }, Empty };\nmatch s {\n    Shape::Circle(r) if r > 1.0 => r,\n    Shape::Rect { w, h: 0.0, .. } | Shape::Empty => w,\n    x @ 1..=5 => x,\n    'a'..'z' => 0,\n}",
+        );
+
+        let StmtKind::Item(Item {
+            kind: ItemKind::Enum(def),
+            ..
+        }) = &ast.body.stmts[0].kind
+        else {
+            panic!("expected an enum");
+        };
+        assert_eq!(def.name.name, "Shape");
+        let [circle, rect, empty] = def.variants.as_slice() else {
+            panic!("expected three variants");
+        };
+        assert!(matches!(&circle.kind, VariantKind::Tuple(types) if types.len() == 1));
+        assert!(matches!(&rect.kind, VariantKind::Struct(fields) if fields[1].name.name == "h"));
+        assert_eq!(empty.kind, VariantKind::Unit);
+
+        let Some(ExprKind::Match { arms, .. }) = ast.body.tail.map(|tail| tail.kind) else {
+            panic!("expected a match");
+        };
+        let PatternKind::Variant { name, payload, .. } = &arms[0].pattern.kind else {
+            panic!("expected a variant pattern");
+        };
+        assert_eq!(name.name, "Circle");
+        assert!(
+            matches!(payload, PayloadPattern::Tuple(patterns) if matches!(&patterns[0].kind, PatternKind::Binding(name, None) if name == "r"))
+        );
+        assert!(arms[0].guard.is_some());
+
+        let PatternKind::Or(alternatives) = &arms[1].pattern.kind else {
+            panic!("expected an or-pattern");
+        };
+        let PatternKind::Variant {
+            payload: PayloadPattern::Struct { fields, rest },
+            ..
+        } = &alternatives[0].kind
+        else {
+            panic!("expected a struct variant pattern");
+        };
+        assert!(rest);
+        assert!(matches!(&fields[0].pattern.kind, PatternKind::Binding(name, None) if name == "w"));
+        assert!(matches!(fields[1].pattern.kind, PatternKind::Literal(_)));
+
+        assert!(matches!(
+            &arms[2].pattern.kind,
+            PatternKind::Binding(_, Some(pattern)) if matches!(pattern.kind, PatternKind::Range { inclusive: true, .. })
+        ));
+        assert!(matches!(
+            arms[3].pattern.kind,
+            PatternKind::Range {
+                inclusive: false,
+                ..
+            }
+        ));
+
+        // Struct variants can't mix positional and named fields
+        assert_eq!(errors("enum E { A { int } }").len(), 1);
+    }
+
+    #[test]
+    fn test_control_flow() {
+        let ast = parse_ok(
+            "if a { 1 } else if b { 2 } else { 3 }
+            loop { break; }
+            while i < 10 { i++; }
+            for x in range(0, 10) { println!(\"{}\", x); }
+            match x {
+                0 => 1,
+                -1 => { 2 }
+                _ => 3,
+            }",
+        );
+        let stmts = ast
+            .body
+            .stmts
+            .iter()
+            .map(|stmt| match &stmt.kind {
+                StmtKind::Expr(expr) => &expr.kind,
+                kind => panic!("expected an expression statement, found {:?}", kind),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(stmts.len(), 4);
+
+        let ExprKind::If {
+            otherwise: Some(otherwise),
+            ..
+        } = stmts[0]
+        else {
+            panic!("expected an if");
+        };
+        assert!(matches!(
+            otherwise.kind,
+            ExprKind::If {
+                otherwise: Some(_),
+                ..
+            }
+        ));
+        assert!(matches!(stmts[1], ExprKind::Loop(_)));
+        assert!(matches!(stmts[2], ExprKind::While { .. }));
+        assert!(matches!(stmts[3], ExprKind::For { binding, .. } if binding.name == "x"));
+
+        let Some(ExprKind::Match { arms, .. }) = ast.body.tail.map(|tail| tail.kind) else {
+            panic!("expected a match");
+        };
+        assert_eq!(arms.len(), 3);
+        assert!(
+            matches!(&arms[1].pattern.kind, PatternKind::Literal(expr) if matches!(expr.kind, ExprKind::Unary(UnaryOp::Neg, _)))
+        );
+        assert!(matches!(arms[2].pattern.kind, PatternKind::Wildcard));
+    }
+
+    #[test]
+    fn test_block_value() {
+        let ast = parse_ok("let x = { let y = 1; y + 1 }; x");
+
+        let StmtKind::Let {
+            value:
+                Some(Expr {
+                    kind: ExprKind::Block(block),
+                    ..
+                }),
+            ..
+        } = &ast.body.stmts[0].kind
+        else {
+            panic!("expected a block");
+        };
+        assert_eq!(block.stmts.len(), 1);
+        assert!(block.tail.is_some());
+        assert!(matches!(
+            ast.body.tail.map(|tail| tail.kind),
+            Some(ExprKind::Ident(_))
+        ));
+    }
+
+    #[test]
+    fn test_node_ids() {
+        let ast = parse_ok("fn f(a: int) -> int { a * 2 } let x = f(1 + 2);");
+
+        let mut ids = Vec::new();
+        let StmtKind::Item(item) = &ast.body.stmts[0].kind else {
+            panic!("expected an item");
+        };
+        let ItemKind::Fn(f) = &item.kind else {
+            panic!("expected a function");
+        };
+        let body = f.body.as_ref().unwrap();
+        ids.extend([ast.body.id, ast.body.stmts[0].id, item.id, body.id]);
+        ids.extend([f.params[0].id, f.params[0].ty.id]);
+        ids.push(body.tail.as_ref().unwrap().id);
+
+        let StmtKind::Let {
+            value: Some(call), ..
+        } = &ast.body.stmts[1].kind
+        else {
+            panic!("expected a let");
+        };
+        let ExprKind::Call { func, args } = &call.kind else {
+            panic!("expected a call");
+        };
+        ids.extend([ast.body.stmts[1].id, call.id, func.id, args[0].id]);
+
+        let count = ids.len();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), count);
+    }
+
+    #[test]
+    fn test_spans() {
+        let ast = parse_ok("let x = 1 + 2;");
+
+        let StmtKind::Let {
+            name,
+            value: Some(value),
+            ..
+        } = &ast.body.stmts[0].kind
+        else {
+            panic!("expected a let");
+        };
+        assert_eq!(ast.body.stmts[0].span, FileId::TEST.span(0..14));
+        assert_eq!(name.span, FileId::TEST.span(4..5));
+        assert_eq!(value.span, FileId::TEST.span(8..13));
+    }
+
+    #[test]
+    fn test_missing_semicolon() {
+        let errors = errors("let a = 1\nlet b = 2;");
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(&errors[0], SyntaxError::Custom { code, .. } if *code == codes::E0202));
+        assert_eq!(errors[0].span(), &FileId::TEST.span(9..9));
+
+        let errors = self::errors("fn f() { const A: int = 1\nfn g() {} }");
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(&errors[0], SyntaxError::Custom { code, .. } if *code == codes::E0202));
+
+        let errors = self::errors("a + 1 b");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].span().start, 5);
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        fn too_deep(source: &str) -> bool {
+            matches!(
+                &errors(source)[..],
+                [SyntaxError::Custom { code, .. }] if *code == codes::E0206
+            )
+        }
+        let nest = |open: &str, inner: &str, close: &str, n: usize| {
+            format!("{}{}{}", open.repeat(n), inner, close.repeat(n))
+        };
+
+        parse_ok(&nest("(", "1", ")", MAX_NESTING - 1));
+        assert!(too_deep(&nest("(", "1", ")", MAX_NESTING)));
+        assert!(too_deep(&nest("(", "1", ")", 3000)));
+
+        // Every operator contains the ones before it
+        parse_ok(&format!("1{}", " + 1".repeat(MAX_NESTING - 1)));
+        assert!(too_deep(&format!("1{}", " + 1".repeat(20000))));
+        assert!(too_deep(&format!("a{}", "[0]".repeat(300))));
+        assert!(too_deep(&format!("{}1", "- ".repeat(300))));
+        assert!(too_deep(&format!("1{}", " as int".repeat(300))));
+        assert!(too_deep(&nest("(1 + ", "1", ")", 3000)));
+
+        assert!(too_deep(&nest("{", "1", "}", 300)));
+        assert!(too_deep(&nest("if a { ", "1", " }", 300)));
+        assert!(too_deep(&format!("let a: {};", nest("[", "int", "]", 300))));
+        assert!(too_deep(&format!(
+            "match a {{ {}_ => 1 }}",
+            "b @ ".repeat(300)
+        )));
+
+        // Alternatives that are backtracked out of don't count
+        parse_ok(&"let a = (b)(c) + [(d)];\n".repeat(1000));
+    }
+
+    fn recover(source: &str) -> (Ast, Vec<ParseError>) {
+        cst::assert_agrees(source);
+        let (tokens, errors) = lexer::lex(source, FileId::TEST);
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let (ast, errors) = parse(&tokens, FileId::TEST);
+        (ast.expect("the parser should recover"), errors)
+    }
+
+    fn is_error(stmt: &Stmt) -> bool {
+        matches!(
+            stmt.kind,
+            StmtKind::Semi(Expr {
+                kind: ExprKind::Error,
+                ..
+            })
+        )
+    }
+
+    #[test]
+    fn test_recover_statements() {
+        let (ast, errors) = recover("let a = ;\nlet b = 2;\nlet c = 1 +;\nlet d = 4;");
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].span(), &FileId::TEST.span(8..9));
+        assert_eq!(errors[1].span(), &FileId::TEST.span(32..33));
+
+        let stmts = &ast.body.stmts;
+        assert_eq!(stmts.len(), 4);
+        assert!(is_error(&stmts[0]));
+        assert!(matches!(&stmts[1].kind, StmtKind::Let { name, .. } if name.name == "b"));
+        assert!(is_error(&stmts[2]));
+        assert!(matches!(&stmts[3].kind, StmtKind::Let { name, .. } if name.name == "d"));
+    }
+
+    #[test]
+    fn test_recover_in_blocks() {
+        let (ast, errors) =
+            recover("fn f() {\n    if x { let = 1; }\n    let y = (2 * );\n    y\n}\nfn g() {}");
+        assert_eq!(errors.len(), 2);
+
+        // Both functions are still there, and `f` keeps its value
+        assert_eq!(ast.body.stmts.len(), 2);
+        let StmtKind::Item(Item {
+            kind: ItemKind::Fn(f),
+            ..
+        }) = &ast.body.stmts[0].kind
+        else {
+            panic!("expected a function");
+        };
+        let body = f.body.as_ref().unwrap();
+        assert_eq!(body.stmts.len(), 2);
+        assert!(matches!(
+            &body.stmts[1].kind,
+            StmtKind::Let {
+                value: Some(Expr {
+                    kind: ExprKind::Error,
+                    ..
+                }),
+                ..
+            }
+        ));
+        assert!(body.tail.is_some());
+    }
+
+    #[test]
+    fn test_recover_stray_delimiter() {
+        let (ast, errors) = recover("let a = 1; } let b = 2;");
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].span(), &FileId::TEST.span(11..12));
+        assert_eq!(ast.body.stmts.len(), 2);
+    }
+
+    #[test]
+    fn test_recover_unclosed_delimiter() {
+        // The unclosed parenthesis ends at the next statement, so the error after it is found
+        let (ast, errors) = recover("let a = (1 + ;\nlet b = f(2);\nlet c = [1, 2;\nlet d = ;");
+
+        let spans = errors.iter().map(|error| *error.span()).collect::<Vec<_>>();
+        assert_eq!(
+            spans,
+            [
+                FileId::TEST.span(13..14),
+                FileId::TEST.span(42..43),
+                FileId::TEST.span(52..53)
+            ]
+        );
+        let stmts = &ast.body.stmts;
+        assert_eq!(stmts.len(), 4);
+        assert!(is_error(&stmts[0]));
+        assert!(matches!(&stmts[1].kind, StmtKind::Let { name, .. } if name.name == "b"));
+
+        // Errors are reported once, in the order they're in the source
+        let (_, errors) = recover("fn f() {\n    g(1, ;\n}\nlet x = ;\nlet y = 1 +;");
+        assert_eq!(errors.len(), 3);
+        assert!(errors
+            .windows(2)
+            .all(|pair| pair[0].span().start < pair[1].span().start));
+    }
 }
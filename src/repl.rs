@@ -0,0 +1,435 @@
+//! Evaluates the inputs of the REPL, each continuing where the previous ones left off.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{self, Write},
+};
+
+use smol_str::SmolStr;
+
+use crate::{
+    check_errors,
+    checker::{self, Environment, Type},
+    diagnostic::Diagnostic,
+    interpreter::{Interpreter, Value},
+    parse_source,
+    parser::ast::{Ast, Ident, ItemKind, Method, NodeId, StmtKind, TypeExprKind},
+    resolver::{self, Globals, Res},
+    source::{FileId, SourceMap},
+    Error,
+};
+
+/// A variable declared at the top level of the REPL.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplVar {
+    pub ty: Type,
+    pub mutable: bool,
+    /// `None` until a variable declared without a value is assigned.
+    pub value: Option<Value>,
+}
+
+impl fmt::Display for ReplVar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "{} = {}", self.ty, value),
+            None => write!(f, "{}, not assigned yet", self.ty),
+        }
+    }
+}
+
+/// Keeps the variables, functions and types declared by the inputs of the REPL, so that later
+/// inputs can use them.
+pub struct ReplEngine {
+    sources: SourceMap,
+    /// The id of the first node of the next input. Every input's nodes get different ids, since
+    /// the side tables of the passes keep the nodes of all of them.
+    next_id: NodeId,
+    globals: Globals,
+    env: Environment,
+    interpreter: Interpreter<'static>,
+    /// The doc comments of the items declared so far, by name. Methods are named `Type::method`.
+    docs: HashMap<SmolStr, String>,
+}
+
+impl Default for ReplEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReplEngine {
+    /// An engine whose inputs print to stdout.
+    pub fn new() -> Self {
+        Self::with_output(io::stdout())
+    }
+
+    /// An engine whose inputs print to a writer.
+    pub fn with_output(output: impl Write + 'static) -> Self {
+        Self {
+            sources: SourceMap::new(),
+            next_id: NodeId(0),
+            globals: Globals::default(),
+            env: Environment::default(),
+            interpreter: Interpreter::with_output(output),
+            docs: HashMap::new(),
+        }
+    }
+
+    /// The inputs so far, which the diagnostics from [`ReplEngine::eval`] point into.
+    pub fn sources(&self) -> &SourceMap {
+        &self.sources
+    }
+
+    /// Evaluates an input. If it ends with an expression, its value and type are returned, unless
+    /// the value is `()`.
+    ///
+    /// Nothing an input declares can be used by later inputs if it has errors. The changes it
+    /// made to existing variables before a runtime error are kept.
+    pub fn eval(&mut self, input: &str) -> Result<Option<(Value, Type)>, Vec<Diagnostic>> {
+        let file = self.sources.add_repl_input(input);
+
+        self.eval_input(input, file)
+            .map_err(Error::into_diagnostics)
+    }
+
+    fn eval_input(&mut self, input: &str, file: FileId) -> Result<Option<(Value, Type)>, Error> {
+        let ast = parse_source(input, file, &mut self.next_id)?;
+
+        // Only kept once the input has run without errors
+        let mut globals = self.globals.clone();
+        let mut env = self.env.clone();
+
+        let (names, name_errors) = resolver::resolve_with(&ast, &mut globals);
+        let (types, type_errors) = checker::check_with(&ast, &names, &mut env);
+        check_errors(name_errors, type_errors)?;
+
+        let ty = ast
+            .body
+            .tail
+            .as_ref()
+            .and_then(|tail| types.expr(tail.id))
+            .filter(|ty| !matches!(ty, Type::Unit | Type::Never))
+            .cloned();
+
+        let value = self
+            .interpreter
+            .run(&ast, names, types)
+            .map_err(Error::Runtime)?;
+        self.globals = globals;
+        self.env = env;
+        self.add_docs(&ast);
+
+        Ok(ty.map(|ty| (value, ty)))
+    }
+
+    /// Keeps the doc comments of the items declared at the top level of an input. An item
+    /// declared again without a doc comment loses the one from before.
+    fn add_docs(&mut self, ast: &Ast) {
+        let mut set = |name: SmolStr, doc: &Option<String>| match doc {
+            Some(doc) => {
+                self.docs.insert(name, doc.clone());
+            }
+            None => {
+                self.docs.remove(&name);
+            }
+        };
+        let member = |ty: &str, name: &Ident, doc: &Option<String>| {
+            (SmolStr::from(format!("{}::{}", ty, name.name)), doc.clone())
+        };
+        let methods = |ty: &str, methods: &[Method]| {
+            methods
+                .iter()
+                .map(|method| member(ty, &method.function.name, &method.doc))
+                .collect::<Vec<_>>()
+        };
+
+        for stmt in &ast.body.stmts {
+            let StmtKind::Item(item) = &stmt.kind else {
+                continue;
+            };
+            // Fields, variants and methods are documented by the name of their type
+            let (name, members) = match &item.kind {
+                ItemKind::Fn(function) => (function.name.name.clone(), Vec::new()),
+                ItemKind::Const { name, .. } => (name.name.clone(), Vec::new()),
+                ItemKind::Struct(def) => {
+                    let fields = def
+                        .fields
+                        .iter()
+                        .map(|field| member(&def.name.name, &field.name, &field.doc))
+                        .collect();
+                    (def.name.name.clone(), fields)
+                }
+                ItemKind::Enum(def) => {
+                    let variants = def
+                        .variants
+                        .iter()
+                        .map(|variant| member(&def.name.name, &variant.name, &variant.doc))
+                        .collect();
+                    (def.name.name.clone(), variants)
+                }
+                ItemKind::Trait(def) => {
+                    (def.name.name.clone(), methods(&def.name.name, &def.methods))
+                }
+                // An impl has no name of its own, only its methods are documented
+                ItemKind::Impl(def) => {
+                    if let TypeExprKind::Named(ty) = &def.ty.kind {
+                        for (name, doc) in methods(ty, &def.methods) {
+                            set(name, &doc);
+                        }
+                    }
+                    continue;
+                }
+            };
+            set(name, &item.doc);
+            for (name, doc) in members {
+                set(name, &doc);
+            }
+        }
+    }
+
+    /// The doc comment of an item declared by an earlier input, like `double`, `Point::new` or
+    /// `Point::x`.
+    pub fn doc(&self, name: &str) -> Option<&str> {
+        self.docs.get(name).map(String::as_str)
+    }
+
+    /// The variables declared at the top level, sorted by name. Variables that were shadowed
+    /// aren't included.
+    pub fn get_vars(&self) -> Vec<(SmolStr, ReplVar)> {
+        let mut vars = self
+            .globals
+            .iter()
+            .filter_map(|(name, res)| {
+                let Res::Local(id) = res else {
+                    return None;
+                };
+                let (ty, mutable) = self.env.get(id)?;
+                let var = ReplVar {
+                    ty: ty.clone(),
+                    mutable,
+                    value: self.interpreter.global(id),
+                };
+                Some((name.clone(), var))
+            })
+            .collect::<Vec<_>>();
+
+        vars.sort_by(|(a, _), (b, _)| a.cmp(b));
+        vars
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::diagnostic::{codes, ErrorCode};
+
+    fn codes(result: Result<Option<(Value, Type)>, Vec<Diagnostic>>) -> Vec<ErrorCode> {
+        result
+            .unwrap_err()
+            .into_iter()
+            .map(|diagnostic| diagnostic.code.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_persistent_state() {
+        let mut engine = ReplEngine::with_output(io::sink());
+
+        assert_eq!(engine.eval("let mut x = 1;"), Ok(None));
+        assert_eq!(engine.eval("fn double(n: int) -> int { n * 2 }"), Ok(None));
+        assert_eq!(engine.eval("x += 2;"), Ok(None));
+        assert_eq!(
+            engine.eval("double(x)"),
+            Ok(Some((Value::I64(6), Type::INT)))
+        );
+        assert_eq!(
+            engine.eval("let f = |s| s + \"!\"; f(\"hi\")"),
+            Ok(Some((Value::String("hi!".into()), Type::String)))
+        );
+
+        // Functions can be redefined, and locals from before are still invisible to functions
+        assert_eq!(
+            engine.eval("fn double(n: int) -> int { n + n + 1 }"),
+            Ok(None)
+        );
+        assert_eq!(
+            engine.eval("double(1)"),
+            Ok(Some((Value::I64(3), Type::INT)))
+        );
+        assert_eq!(codes(engine.eval("fn g() -> int { x }")), [codes::E0401]);
+
+        // Methods can be added to structs from before
+        assert_eq!(engine.eval("struct P { n: int, P(); }"), Ok(None));
+        assert_eq!(
+            engine.eval("impl P { fn get(self) -> int { self.n } }"),
+            Ok(None)
+        );
+        assert_eq!(
+            engine.eval("P(4).get()"),
+            Ok(Some((Value::I64(4), Type::INT)))
+        );
+
+        // And traits from before can be implemented for them
+        assert_eq!(
+            engine.eval(
+                "trait Get { fn get(self) -> int; fn twice(self) -> int { self.get() * 2 } }"
+            ),
+            Ok(None)
+        );
+        assert_eq!(engine.eval("struct Q { Q(); }"), Ok(None));
+        assert_eq!(
+            engine.eval("impl Get for Q { fn get(self) -> int { 5 } }"),
+            Ok(None)
+        );
+        assert_eq!(
+            engine.eval("let g: dyn Get = Q(); g.twice()"),
+            Ok(Some((Value::I64(10), Type::INT)))
+        );
+        assert_eq!(codes(engine.eval("let g: dyn Get = P(1);")), [codes::E0325]);
+
+        // Enums from before can be created and matched
+        assert_eq!(engine.eval("enum E { A(int), B }"), Ok(None));
+        assert_eq!(
+            engine.eval("match E::A(3) { E::A(n) => n, E::B => 0, }"),
+            Ok(Some((Value::I64(3), Type::INT)))
+        );
+        assert_eq!(
+            codes(engine.eval("match E::B { E::A(n) => n, }")),
+            [codes::E0328]
+        );
+        assert_eq!(engine.eval("println!(\"{}\", x)"), Ok(None));
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut engine = ReplEngine::with_output(io::sink());
+
+        assert_eq!(codes(engine.eval("let a = 1 +;")), [codes::E0201]);
+        assert_eq!(codes(engine.eval("let b: int = true;")), [codes::E0301]);
+        assert_eq!(codes(engine.eval("let c = 1 / 0;")), [codes::E0501]);
+
+        // None of them declared anything
+        assert_eq!(codes(engine.eval("a")), [codes::E0401]);
+        assert_eq!(codes(engine.eval("b")), [codes::E0401]);
+        assert_eq!(codes(engine.eval("c")), [codes::E0401]);
+
+        // The diagnostics point into the input that caused them
+        let diagnostics = engine.eval("d").unwrap_err();
+        let file = engine
+            .sources()
+            .get(diagnostics[0].file().unwrap())
+            .unwrap();
+        assert_eq!(file.source(), "d");
+    }
+
+    #[test]
+    fn test_vars() {
+        let mut engine = ReplEngine::with_output(io::sink());
+        engine
+            .eval("let mut count = 3; let name = \"x\"; let later: float; const MAX = 1;")
+            .unwrap();
+        engine.eval("let count = [true];").unwrap();
+
+        let vars = engine.get_vars();
+        let names = vars
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["count", "later", "name"]);
+
+        assert_eq!(
+            vars[0].1,
+            ReplVar {
+                ty: Type::Array(Box::new(Type::Bool)),
+                mutable: false,
+                value: Some(Value::Array(vec![Value::Bool(true)])),
+            }
+        );
+        assert_eq!(vars[1].1.to_string(), "f64, not assigned yet");
+        assert_eq!(vars[2].1.to_string(), "string = x");
+    }
+
+    /// Output that the test can still read after giving it to the engine.
+    #[derive(Clone, Default)]
+    struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedOutput {
+        fn take(&self) -> String {
+            String::from_utf8(self.0.take()).unwrap()
+        }
+    }
+
+    #[test]
+    fn test_runtime_error_drops_globals() {
+        let output = SharedOutput::default();
+        let mut engine = ReplEngine::with_output(output.clone());
+        engine
+            .eval(
+                "struct H { id: int, H(); ~H() { print!(\"drop {} \", self.id); } } let a = H(1);",
+            )
+            .unwrap();
+
+        assert_eq!(
+            codes(engine.eval("let b = H(2); let c = H(3); let d = 1 / 0;")),
+            [codes::E0501]
+        );
+        assert_eq!(output.take(), "drop 3 drop 2 ");
+
+        // The globals from before the input that failed are kept
+        assert_eq!(engine.eval("a.id"), Ok(Some((Value::I64(1), Type::INT))));
+        assert_eq!(output.take(), "");
+        let names = engine
+            .get_vars()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["a"]);
+    }
+
+    #[test]
+    fn test_docs() {
+        let mut engine = ReplEngine::with_output(io::sink());
+        engine
+            .eval("/// Doubles a number.\n/// Twice.\nfn double(n: int) -> int { n * 2 }")
+            .unwrap();
+        engine
+            .eval("/// A point.\nstruct P { /// The field.\nn: int, P(); }\nimpl P { /// The number.\nfn get(self) -> int { self.n } }")
+            .unwrap();
+        engine.eval("enum E { /// The first.\nA, B }").unwrap();
+        engine
+            .eval("/// Gets.\ntrait Get { /// Required.\nfn get(self) -> int; }")
+            .unwrap();
+
+        assert_eq!(engine.doc("double"), Some("Doubles a number.\nTwice."));
+        assert_eq!(engine.doc("P"), Some("A point."));
+        assert_eq!(engine.doc("P::get"), Some("The number."));
+        assert_eq!(engine.doc("P::n"), Some("The field."));
+        assert_eq!(engine.doc("E::A"), Some("The first."));
+        assert_eq!(engine.doc("E::B"), None);
+        assert_eq!(engine.doc("Get"), Some("Gets."));
+        assert_eq!(engine.doc("Get::get"), Some("Required."));
+
+        // Inputs with errors don't change the docs, redefining an item without a doc removes it
+        assert!(engine
+            .eval("/// Broken.\nfn double() -> int { true }")
+            .is_err());
+        assert_eq!(engine.doc("double"), Some("Doubles a number.\nTwice."));
+        engine.eval("fn double(n: int) -> int { n + n }").unwrap();
+        assert_eq!(engine.doc("double"), None);
+        assert_eq!(engine.doc("missing"), None);
+    }
+}
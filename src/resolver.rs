@@ -0,0 +1,1088 @@
+//! Binds every name in a program to the declaration it refers to, before the program is checked.
+
+use std::collections::HashMap;
+
+use smol_str::SmolStr;
+
+use crate::{
+    diagnostic::{codes, Diagnostic},
+    lexer::Span,
+    parser::ast::{
+        Ast, Block, ClosureParam, Enum, Expr, ExprKind, Function, ItemKind, MatchArm, NodeId,
+        Param, Pattern, PatternKind, PayloadPattern, Stmt, StmtKind, Struct, StructField, TypeExpr,
+        TypeExprKind, VariantKind,
+    },
+};
+
+/// What a name refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Res {
+    /// A `let`, parameter, loop variable or pattern binding, identified by the node that declares
+    /// it. That's the statement for `let`s and the `for` expression for loop variables.
+    Local(NodeId),
+    /// A function, constant, struct, trait or enum, identified by its item.
+    Item(NodeId),
+    /// A type parameter of a generic function, which is only a type in that function.
+    TypeParam(NodeId),
+    Builtin(Builtin),
+}
+
+/// Functions that are always in scope unless something shadows them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Builtin {
+    /// `range(start, end)`
+    Range,
+    /// `len(value)`
+    Len,
+    /// `fmt(format, args...)`
+    Fmt,
+    /// `print(format, args...)`
+    Print,
+    /// `wrapping_add(a, b)`, which wraps around at the bounds of the type instead of overflowing.
+    WrappingAdd,
+    /// `wrapping_sub(a, b)`
+    WrappingSub,
+    /// `wrapping_mul(a, b)`
+    WrappingMul,
+    /// `saturating_add(a, b)`, which stops at the bounds of the type instead of overflowing.
+    SaturatingAdd,
+    /// `saturating_sub(a, b)`
+    SaturatingSub,
+    /// `saturating_mul(a, b)`
+    SaturatingMul,
+}
+
+impl Builtin {
+    pub const ALL: [Builtin; 10] = [
+        Builtin::Range,
+        Builtin::Len,
+        Builtin::Fmt,
+        Builtin::Print,
+        Builtin::WrappingAdd,
+        Builtin::WrappingSub,
+        Builtin::WrappingMul,
+        Builtin::SaturatingAdd,
+        Builtin::SaturatingSub,
+        Builtin::SaturatingMul,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Builtin::Range => "range",
+            Builtin::Len => "len",
+            Builtin::Fmt => "fmt",
+            Builtin::Print => "print",
+            Builtin::WrappingAdd => "wrapping_add",
+            Builtin::WrappingSub => "wrapping_sub",
+            Builtin::WrappingMul => "wrapping_mul",
+            Builtin::SaturatingAdd => "saturating_add",
+            Builtin::SaturatingSub => "saturating_sub",
+            Builtin::SaturatingMul => "saturating_mul",
+        }
+    }
+}
+
+/// The declarations that names refer to.
+#[derive(Debug, Default)]
+pub struct Resolutions {
+    names: HashMap<NodeId, Res>,
+    /// The locals from outside of each closure that it uses, by the closure's expression.
+    captures: HashMap<NodeId, Vec<NodeId>>,
+}
+
+impl Resolutions {
+    /// What an identifier expression or a named type refers to, `None` if it couldn't be
+    /// resolved. Only the types declared in the program are resolved, not the built-in ones.
+    ///
+    /// Binding patterns are resolved to the variable they declare, which is their own except in
+    /// the later alternatives of `|` patterns, where it's the first alternative's.
+    pub fn get(&self, id: NodeId) -> Option<Res> {
+        self.names.get(&id).copied()
+    }
+
+    /// Adds the resolutions of another program, for running several programs in the same
+    /// interpreter.
+    pub fn extend(&mut self, other: Resolutions) {
+        self.names.extend(other.names);
+        self.captures.extend(other.captures);
+    }
+
+    /// The declarations of the locals from around a closure that are used inside of it.
+    pub fn captures(&self, closure: NodeId) -> &[NodeId] {
+        self.captures.get(&closure).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// The names declared at the top level of programs that were already resolved, so that another
+/// program can continue where they left off, like the inputs of the REPL.
+#[derive(Debug, Clone, Default)]
+pub struct Globals {
+    names: HashMap<SmolStr, Declaration>,
+}
+
+impl Globals {
+    pub fn get(&self, name: &str) -> Option<Res> {
+        self.names.get(name).map(|declaration| declaration.res)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&SmolStr, Res)> {
+        self.names
+            .iter()
+            .map(|(name, declaration)| (name, declaration.res))
+    }
+}
+
+/// Resolves the names in the program, returning the errors for undefined and duplicate names.
+pub fn resolve(ast: &Ast) -> (Resolutions, Vec<Diagnostic>) {
+    resolve_with(ast, &mut Globals::default())
+}
+
+/// Like [`resolve`], but the program can use the names in `globals`, and the names declared at
+/// its top level are added to them. They can shadow the previous globals, even functions.
+pub fn resolve_with(ast: &Ast, globals: &mut Globals) -> (Resolutions, Vec<Diagnostic>) {
+    let mut resolver = Resolver::default();
+    resolver.scopes.push(Scope {
+        names: globals.names.clone(),
+        function: false,
+    });
+
+    resolver.scopes.push(Scope::default());
+    resolver.resolve_block_contents(&ast.body);
+    let top_level = resolver
+        .scopes
+        .pop()
+        .expect("the top level scope was pushed");
+    globals.names.extend(top_level.names);
+
+    (resolver.resolutions, resolver.diagnostics)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Declaration {
+    res: Res,
+    span: Span,
+}
+
+/// A variable bound by a pattern.
+#[derive(Debug)]
+struct PatternBinding {
+    name: SmolStr,
+    span: Span,
+    /// The binding patterns that declare it, more than one if it's in the alternatives of a `|`
+    /// pattern. The first is the variable the others refer to.
+    ids: Vec<NodeId>,
+}
+
+#[derive(Debug, Default)]
+struct Scope {
+    names: HashMap<SmolStr, Declaration>,
+    /// The scope of the parameters of a function, locals from outside of it aren't visible.
+    function: bool,
+}
+
+#[derive(Debug, Default)]
+struct Resolver {
+    scopes: Vec<Scope>,
+    /// The closures being resolved and the index of their parameters' scope.
+    closures: Vec<(NodeId, usize)>,
+    resolutions: Resolutions,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Resolver {
+    fn scope(&mut self) -> &mut Scope {
+        self.scopes.last_mut().expect("there is always a scope")
+    }
+
+    /// Declares a name that can be shadowed, like a `let`.
+    fn declare(&mut self, name: &SmolStr, res: Res, span: Span) {
+        self.scope()
+            .names
+            .insert(name.clone(), Declaration { res, span });
+    }
+
+    /// Declares a name that can only be declared once in its scope, like a function.
+    fn declare_unique(&mut self, name: &SmolStr, res: Res, span: Span) {
+        if let Some(previous) = self.scope().names.get(name).copied() {
+            self.diagnostics.push(
+                Diagnostic::error(format!("The name `{}` is defined multiple times", name))
+                    .with_code(codes::E0402)
+                    .with_label(span, format!("`{}` redefined here", name))
+                    .with_secondary_label(previous.span, format!("`{}` first defined here", name))
+                    .with_help("rename one of them"),
+            );
+            return;
+        }
+
+        self.declare(name, res, span);
+    }
+
+    fn lookup(&self, name: &str) -> Option<Res> {
+        let mut in_function = false;
+
+        for scope in self.scopes.iter().rev() {
+            if let Some(declaration) = scope.names.get(name) {
+                match declaration.res {
+                    // Type parameters aren't values, but can still be shadowed by them
+                    Res::TypeParam(_) => {}
+                    Res::Item(_) => return Some(declaration.res),
+                    _ if !in_function => return Some(declaration.res),
+                    _ => {}
+                }
+            }
+            in_function |= scope.function;
+        }
+
+        Builtin::ALL
+            .into_iter()
+            .find(|builtin| builtin.name() == name)
+            .map(Res::Builtin)
+    }
+
+    /// The item or type parameter a type name refers to. Locals with the same name don't hide
+    /// them, and like locals, the type parameters of a function can't be used in the functions
+    /// inside of it.
+    fn lookup_type(&self, name: &str) -> Option<Res> {
+        let mut in_function = false;
+
+        for scope in self.scopes.iter().rev() {
+            match scope.names.get(name).map(|declaration| declaration.res) {
+                Some(res @ Res::Item(_)) => return Some(res),
+                Some(res @ Res::TypeParam(_)) if !in_function => return Some(res),
+                _ => {}
+            }
+            in_function |= scope.function;
+        }
+
+        None
+    }
+
+    fn resolve_name(&mut self, expr: &Expr, name: &SmolStr) {
+        match self.lookup(name) {
+            Some(res) => {
+                self.resolutions.names.insert(expr.id, res);
+                if let Res::Local(declaration) = res {
+                    self.capture(name, declaration);
+                }
+            }
+            None => self.undefined(name, expr.span),
+        }
+    }
+
+    /// Records a local as captured by the closures that are between its scope and its use.
+    fn capture(&mut self, name: &str, declaration: NodeId) {
+        let Some(scope) = self.scopes.iter().rposition(|scope| {
+            scope
+                .names
+                .get(name)
+                .is_some_and(|found| found.res == Res::Local(declaration))
+        }) else {
+            return;
+        };
+
+        for (closure, start) in &self.closures {
+            if *start <= scope {
+                continue;
+            }
+            let captures = self.resolutions.captures.entry(*closure).or_default();
+            if !captures.contains(&declaration) {
+                captures.push(declaration);
+            }
+        }
+    }
+
+    /// Resolves the names in a type, the ones that aren't found are left for the checker, which
+    /// knows the built-in types.
+    fn resolve_type(&mut self, ty: &TypeExpr) {
+        match &ty.kind {
+            TypeExprKind::Unit => {}
+            TypeExprKind::Named(name) | TypeExprKind::Dyn(name) => {
+                if let Some(res) = self.lookup_type(name) {
+                    self.resolutions.names.insert(ty.id, res);
+                }
+            }
+            TypeExprKind::Array(element) => self.resolve_type(element),
+        }
+    }
+
+    fn undefined(&mut self, name: &str, span: Span) {
+        let mut diagnostic =
+            Diagnostic::error(format!("Cannot find value `{}` in this scope", name))
+                .with_code(codes::E0401)
+                .with_label(span, "not found in this scope");
+
+        let hidden = self.scopes.iter().any(|scope| {
+            scope
+                .names
+                .get(name)
+                .is_some_and(|declaration| matches!(declaration.res, Res::Local(_)))
+        });
+        if hidden {
+            diagnostic = diagnostic.with_help(format!(
+                "`{}` is a local variable outside of this function, functions can only use the \
+                 functions and constants around them",
+                name
+            ));
+        } else if let Some(similar) = self.similar_name(name) {
+            diagnostic = diagnostic.with_help(format!("did you mean `{}`?", similar));
+        }
+
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// The visible name that is closest to a misspelled name, if any is close enough.
+    fn similar_name(&self, name: &str) -> Option<SmolStr> {
+        let mut candidates = Vec::new();
+        let mut in_function = false;
+        for scope in self.scopes.iter().rev() {
+            for (candidate, declaration) in &scope.names {
+                if matches!(declaration.res, Res::TypeParam(_)) {
+                    continue;
+                }
+                if matches!(declaration.res, Res::Item(_)) || !in_function {
+                    candidates.push(candidate.clone());
+                }
+            }
+            in_function |= scope.function;
+        }
+        candidates.extend(Builtin::ALL.map(|builtin| SmolStr::from(builtin.name())));
+
+        // Sorted so that ties don't depend on the order of the hash maps
+        candidates.sort();
+        let max_distance = (name.chars().count() / 3).max(1);
+        candidates
+            .into_iter()
+            .map(|candidate| (edit_distance(name, &candidate), candidate))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, candidate)| candidate)
+    }
+
+    fn resolve_block(&mut self, block: &Block) {
+        self.scopes.push(Scope::default());
+        self.resolve_block_contents(block);
+        self.scopes.pop();
+    }
+
+    /// Resolves the statements of a block in the current scope.
+    fn resolve_block_contents(&mut self, block: &Block) {
+        // Functions, structs, traits and enums can be used before they're declared
+        for stmt in &block.stmts {
+            if let StmtKind::Item(item) = &stmt.kind {
+                let name = match &item.kind {
+                    ItemKind::Fn(function) => &function.name,
+                    ItemKind::Struct(def) => &def.name,
+                    ItemKind::Trait(def) => &def.name,
+                    ItemKind::Enum(def) => &def.name,
+                    // Methods are found through their type by the checker
+                    ItemKind::Const { .. } | ItemKind::Impl(_) => continue,
+                };
+                self.declare_unique(&name.name, Res::Item(item.id), name.span);
+            }
+        }
+
+        for stmt in &block.stmts {
+            self.resolve_stmt(stmt);
+        }
+        if let Some(tail) = &block.tail {
+            self.resolve_expr(tail);
+        }
+    }
+
+    fn resolve_stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Let {
+                name, ty, value, ..
+            } => {
+                if let Some(ty) = ty {
+                    self.resolve_type(ty);
+                }
+                // The value is resolved first, so that `let x = x;` uses the previous `x`
+                if let Some(value) = value {
+                    self.resolve_expr(value);
+                }
+                self.declare(&name.name, Res::Local(stmt.id), name.span);
+            }
+            StmtKind::Item(item) => match &item.kind {
+                ItemKind::Fn(function) => self.resolve_fn(function),
+                ItemKind::Const { name, ty, value } => {
+                    if let Some(ty) = ty {
+                        self.resolve_type(ty);
+                    }
+                    self.resolve_expr(value);
+                    self.declare_unique(&name.name, Res::Item(item.id), name.span);
+                }
+                ItemKind::Struct(def) => self.resolve_struct(def),
+                ItemKind::Impl(def) => {
+                    if let Some(trait_) = &def.trait_ {
+                        self.resolve_type(trait_);
+                    }
+                    self.resolve_type(&def.ty);
+                    for method in &def.methods {
+                        self.resolve_fn(&method.function);
+                    }
+                }
+                ItemKind::Trait(def) => {
+                    for method in &def.methods {
+                        self.resolve_fn(&method.function);
+                    }
+                }
+                ItemKind::Enum(def) => self.resolve_enum(def),
+            },
+            StmtKind::Expr(expr) | StmtKind::Semi(expr) => self.resolve_expr(expr),
+        }
+    }
+
+    fn resolve_fn(&mut self, function: &Function) {
+        // The types of the signature are resolved in the function, where its type parameters are
+        self.scopes.push(Scope {
+            function: true,
+            ..Default::default()
+        });
+        for generic in &function.generics {
+            let name = &generic.name;
+            self.declare_unique(&name.name, Res::TypeParam(generic.id), name.span);
+        }
+        for bound in function.generics.iter().flat_map(|generic| &generic.bounds) {
+            self.resolve_type(bound);
+        }
+        for param in &function.params {
+            self.resolve_type(&param.ty);
+        }
+        if let Some(ret) = &function.ret {
+            self.resolve_type(ret);
+        }
+
+        if let Some(receiver) = &function.receiver {
+            self.declare(&"self".into(), Res::Local(receiver.id), receiver.span);
+        }
+        self.declare_params(
+            function
+                .params
+                .iter()
+                .map(|Param { id, name, .. }| (*id, name.name.clone(), name.span)),
+        );
+        if let Some(body) = &function.body {
+            self.resolve_block(body);
+        }
+        self.scopes.pop();
+    }
+
+    /// Resolves the types of fields, reporting the ones with the same name.
+    fn resolve_fields(&mut self, fields: &[StructField]) {
+        // Fields are declared in a scope of their own just to find the duplicates
+        self.scopes.push(Scope::default());
+        for field in fields {
+            self.resolve_type(&field.ty);
+            self.declare_unique(&field.name.name, Res::Local(field.ty.id), field.name.span);
+        }
+        self.scopes.pop();
+    }
+
+    fn resolve_struct(&mut self, def: &Struct) {
+        self.resolve_fields(&def.fields);
+
+        // The constructor and destructor are like functions with `self` declared in them
+        let members = [
+            def.constructor
+                .as_ref()
+                .map(|c| (c.id, c.name.span, c.params.as_slice(), c.body.as_ref())),
+            def.destructor
+                .as_ref()
+                .map(|d| (d.id, d.name.span, [].as_slice(), d.body.as_ref())),
+        ];
+        for (id, span, params, body) in members.into_iter().flatten() {
+            for param in params {
+                self.resolve_type(&param.ty);
+            }
+
+            self.scopes.push(Scope {
+                function: true,
+                ..Default::default()
+            });
+            self.declare(&"self".into(), Res::Local(id), span);
+            self.declare_params(
+                params
+                    .iter()
+                    .map(|Param { id, name, .. }| (*id, name.name.clone(), name.span)),
+            );
+            if let Some(body) = body {
+                self.resolve_block(body);
+            }
+            self.scopes.pop();
+        }
+    }
+
+    fn resolve_enum(&mut self, def: &Enum) {
+        // Like fields, the variants are declared in a scope of their own to find the duplicates
+        self.scopes.push(Scope::default());
+        for variant in &def.variants {
+            let name = &variant.name;
+            self.declare_unique(&name.name, Res::Item(variant.id), name.span);
+            match &variant.kind {
+                VariantKind::Unit => {}
+                VariantKind::Tuple(types) => {
+                    for ty in types {
+                        self.resolve_type(ty);
+                    }
+                }
+                VariantKind::Struct(fields) => self.resolve_fields(fields),
+            }
+        }
+        self.scopes.pop();
+    }
+
+    fn declare_params(&mut self, params: impl Iterator<Item = (NodeId, SmolStr, Span)>) {
+        for (id, name, span) in params {
+            self.declare_unique(&name, Res::Local(id), span);
+        }
+    }
+
+    fn resolve_expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Error | ExprKind::Literal(_) | ExprKind::Continue => {}
+            ExprKind::Ident(name) => self.resolve_name(expr, name),
+            ExprKind::Array(elements) => {
+                for element in elements {
+                    self.resolve_expr(element);
+                }
+            }
+            ExprKind::Unary(_, operand) | ExprKind::Step(operand, _) => self.resolve_expr(operand),
+            ExprKind::Cast(value, ty) => {
+                self.resolve_expr(value);
+                self.resolve_type(ty);
+            }
+            ExprKind::Field(value, _) => self.resolve_expr(value),
+            ExprKind::MethodCall { receiver, args, .. } => {
+                self.resolve_expr(receiver);
+                for arg in args {
+                    self.resolve_expr(arg);
+                }
+            }
+            ExprKind::Path(ty, _) => self.resolve_type(ty),
+            ExprKind::Binary(lhs, _, rhs) => {
+                self.resolve_expr(lhs);
+                self.resolve_expr(rhs);
+            }
+            ExprKind::Assign { target, value, .. } => {
+                self.resolve_expr(target);
+                self.resolve_expr(value);
+            }
+            ExprKind::Index(value, index) => {
+                self.resolve_expr(value);
+                self.resolve_expr(index);
+            }
+            ExprKind::Call { func, args } => {
+                self.resolve_expr(func);
+                for arg in args {
+                    self.resolve_expr(arg);
+                }
+            }
+            ExprKind::Macro { args, .. } => {
+                for arg in args {
+                    self.resolve_expr(arg);
+                }
+            }
+            ExprKind::Closure { params, ret, body } => {
+                for ty in params
+                    .iter()
+                    .filter_map(|param| param.ty.as_ref())
+                    .chain(ret)
+                {
+                    self.resolve_type(ty);
+                }
+
+                // Unlike functions, closures can use the locals around them
+                self.closures.push((expr.id, self.scopes.len()));
+                self.scopes.push(Scope::default());
+                self.declare_params(
+                    params
+                        .iter()
+                        .map(|ClosureParam { id, name, .. }| (*id, name.name.clone(), name.span)),
+                );
+                self.resolve_expr(body);
+                self.scopes.pop();
+                self.closures.pop();
+            }
+            ExprKind::Block(block) | ExprKind::Loop(block) => self.resolve_block(block),
+            ExprKind::If {
+                cond,
+                then,
+                otherwise,
+            } => {
+                self.resolve_expr(cond);
+                self.resolve_block(then);
+                if let Some(otherwise) = otherwise {
+                    self.resolve_expr(otherwise);
+                }
+            }
+            ExprKind::While { cond, body } => {
+                self.resolve_expr(cond);
+                self.resolve_block(body);
+            }
+            ExprKind::For {
+                binding,
+                iter,
+                body,
+            } => {
+                self.resolve_expr(iter);
+                self.scopes.push(Scope::default());
+                self.declare(&binding.name, Res::Local(expr.id), binding.span);
+                self.resolve_block(body);
+                self.scopes.pop();
+            }
+            ExprKind::Match { scrutinee, arms } => {
+                self.resolve_expr(scrutinee);
+                for arm in arms {
+                    self.resolve_arm(arm);
+                }
+            }
+            ExprKind::Return(value) | ExprKind::Break(value) => {
+                if let Some(value) = value {
+                    self.resolve_expr(value);
+                }
+            }
+        }
+    }
+
+    fn resolve_arm(&mut self, arm: &MatchArm) {
+        self.scopes.push(Scope::default());
+        let mut bindings = Vec::new();
+        self.resolve_pattern(&arm.pattern, &mut bindings);
+        for binding in bindings {
+            self.declare(&binding.name, Res::Local(binding.ids[0]), binding.span);
+        }
+
+        if let Some(guard) = &arm.guard {
+            self.resolve_expr(guard);
+        }
+        self.resolve_expr(&arm.body);
+        self.scopes.pop();
+    }
+
+    /// Resolves the names in a pattern, adding the variables it binds to `bindings`.
+    fn resolve_pattern(&mut self, pattern: &Pattern, bindings: &mut Vec<PatternBinding>) {
+        match &pattern.kind {
+            PatternKind::Wildcard => {}
+            PatternKind::Literal(literal) => self.resolve_expr(literal),
+            PatternKind::Range { start, end, .. } => {
+                self.resolve_expr(start);
+                self.resolve_expr(end);
+            }
+            PatternKind::Binding(name, subpattern) => {
+                self.resolutions
+                    .names
+                    .insert(pattern.id, Res::Local(pattern.id));
+                let binding = PatternBinding {
+                    name: name.clone(),
+                    span: pattern.span,
+                    ids: vec![pattern.id],
+                };
+                self.bind(bindings, binding);
+                if let Some(subpattern) = subpattern {
+                    self.resolve_pattern(subpattern, bindings);
+                }
+            }
+            PatternKind::Or(alternatives) => {
+                let mut first: Option<(Span, Vec<PatternBinding>)> = None;
+                for alternative in alternatives {
+                    let mut found = Vec::new();
+                    self.resolve_pattern(alternative, &mut found);
+                    match &mut first {
+                        Some((span, first)) => {
+                            self.merge_alternative(*span, first, alternative.span, found)
+                        }
+                        None => first = Some((alternative.span, found)),
+                    }
+                }
+
+                for binding in first.map(|(_, first)| first).unwrap_or_default() {
+                    self.bind(bindings, binding);
+                }
+            }
+            PatternKind::Variant { ty, payload, .. } => {
+                self.resolve_type(ty);
+                match payload {
+                    PayloadPattern::Unit => {}
+                    PayloadPattern::Tuple(patterns) => {
+                        for pattern in patterns {
+                            self.resolve_pattern(pattern, bindings);
+                        }
+                    }
+                    PayloadPattern::Struct { fields, .. } => {
+                        for field in fields {
+                            self.resolve_pattern(&field.pattern, bindings);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Adds a variable to the ones bound by a pattern, which can only bind each name once.
+    fn bind(&mut self, bindings: &mut Vec<PatternBinding>, binding: PatternBinding) {
+        if let Some(first) = bindings.iter().find(|bound| bound.name == binding.name) {
+            self.diagnostics.push(
+                Diagnostic::error(format!(
+                    "`{}` is bound more than once in the same pattern",
+                    binding.name
+                ))
+                .with_code(codes::E0402)
+                .with_label(binding.span, "bound again here")
+                .with_secondary_label(first.span, "first bound here"),
+            );
+            return;
+        }
+        bindings.push(binding);
+    }
+
+    /// Makes the variables of a later alternative of a `|` pattern refer to the first one's,
+    /// reporting the names that only one of them binds.
+    fn merge_alternative(
+        &mut self,
+        first_span: Span,
+        first: &mut [PatternBinding],
+        span: Span,
+        found: Vec<PatternBinding>,
+    ) {
+        for binding in first.iter() {
+            if !found.iter().any(|other| other.name == binding.name) {
+                self.not_in_all_alternatives(&binding.name, span, binding.span);
+            }
+        }
+
+        for binding in found {
+            let Some(variable) = first.iter_mut().find(|other| other.name == binding.name) else {
+                self.not_in_all_alternatives(&binding.name, first_span, binding.span);
+                continue;
+            };
+            for id in binding.ids {
+                self.resolutions
+                    .names
+                    .insert(id, Res::Local(variable.ids[0]));
+                variable.ids.push(id);
+            }
+        }
+    }
+
+    fn not_in_all_alternatives(&mut self, name: &str, alternative: Span, bound: Span) {
+        self.diagnostics.push(
+            Diagnostic::error(format!(
+                "`{}` isn't bound in all alternatives of the pattern",
+                name
+            ))
+            .with_code(codes::E0403)
+            .with_label(alternative, format!("this doesn't bind `{}`", name))
+            .with_secondary_label(bound, format!("`{}` is bound here", name))
+            .with_help("every alternative of a `|` pattern has to bind the same names"),
+        );
+    }
+}
+
+/// The number of characters that have to be inserted, removed, replaced or swapped with the one
+/// next to them to turn one string into the other.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+
+    // `distances[i][j]` is the distance between the first `i` characters of `a` and the first `j`
+    // of `b`
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let replace = distances[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = replace
+                .min(distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+
+    distances[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cst, diagnostic::ErrorCode, lexer, parser, source::FileId};
+
+    fn resolve_source(source: &str) -> (Ast, Resolutions, Vec<Diagnostic>) {
+        cst::assert_agrees(source);
+        let (tokens, errors) = lexer::lex(source, FileId::TEST);
+        assert!(errors.is_empty(), "{:?}", errors);
+        let (ast, errors) = parser::parse(&tokens, FileId::TEST);
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let ast = ast.unwrap();
+        let (resolutions, errors) = resolve(&ast);
+        (ast, resolutions, errors)
+    }
+
+    fn codes(source: &str) -> Vec<ErrorCode> {
+        resolve_source(source)
+            .2
+            .into_iter()
+            .map(|diagnostic| diagnostic.code.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("count", "count"), 0);
+        assert_eq!(edit_distance("cout", "count"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("lne", "len"), 1);
+        assert_eq!(edit_distance("ç", "c"), 1);
+    }
+
+    #[test]
+    fn test_shadowing() {
+        let (ast, resolutions, errors) = resolve_source("let y = 1; let y = y; y");
+        assert!(errors.is_empty());
+
+        let stmts = &ast.body.stmts;
+        let StmtKind::Let {
+            value: Some(value), ..
+        } = &stmts[1].kind
+        else {
+            panic!("expected a let");
+        };
+        assert_eq!(resolutions.get(value.id), Some(Res::Local(stmts[0].id)));
+        assert_eq!(
+            resolutions.get(ast.body.tail.as_ref().unwrap().id),
+            Some(Res::Local(stmts[1].id))
+        );
+    }
+
+    #[test]
+    fn test_scopes() {
+        assert_eq!(codes("{ let x = 1; } x;"), [codes::E0401]);
+        assert_eq!(codes("f(); fn f() {}"), []);
+        assert_eq!(codes("let x = 1; fn f() -> int { x }"), [codes::E0401]);
+        assert_eq!(codes("const X = 1; fn f() -> int { X }"), []);
+        assert_eq!(codes("let x = 1; let f = || x;"), []);
+        assert_eq!(codes("for i in range(0, 1) {} i;"), [codes::E0401]);
+        assert_eq!(codes("match 1 { n => n, }; n;"), [codes::E0401]);
+        // Builtins can be shadowed
+        assert_eq!(codes("fn len() -> int { 1 } len();"), []);
+    }
+
+    #[test]
+    fn test_captures() {
+        let (ast, names, errors) =
+            resolve_source("let x = 1; let f = |a| { let y = 2; || x + y + a };");
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let x = ast.body.stmts[0].id;
+        let StmtKind::Let {
+            value: Some(outer), ..
+        } = &ast.body.stmts[1].kind
+        else {
+            panic!("expected a let");
+        };
+        let ExprKind::Closure { params, body, .. } = &outer.kind else {
+            panic!("expected a closure");
+        };
+        let ExprKind::Block(block) = &body.kind else {
+            panic!("expected a block");
+        };
+        let inner = block.tail.as_ref().unwrap();
+
+        // The outer closure declares `a` and `y` itself
+        assert_eq!(names.captures(outer.id), [x]);
+        assert_eq!(
+            names.captures(inner.id),
+            [x, block.stmts[0].id, params[0].id]
+        );
+    }
+
+    #[test]
+    fn test_globals() {
+        let parse = |source| {
+            cst::assert_agrees(source);
+            parser::parse(&lexer::lex(source, FileId::TEST).0, FileId::TEST)
+                .0
+                .unwrap()
+        };
+        let mut globals = Globals::default();
+
+        let first = parse("let x = 1; fn f() -> int { 1 }");
+        assert!(resolve_with(&first, &mut globals).1.is_empty());
+        assert_eq!(globals.get("x"), Some(Res::Local(first.body.stmts[0].id)));
+
+        // Functions from before can be redefined
+        let second = parse("x + f(); fn f() -> int { 2 }");
+        assert!(resolve_with(&second, &mut globals).1.is_empty());
+        let StmtKind::Item(item) = &second.body.stmts[1].kind else {
+            panic!("expected an item");
+        };
+        assert_eq!(globals.get("f"), Some(Res::Item(item.id)));
+
+        let third = parse("fn g() -> int { x }");
+        let errors = resolve_with(&third, &mut globals).1;
+        assert_eq!(errors[0].code, Some(codes::E0401));
+    }
+
+    #[test]
+    fn test_duplicates() {
+        assert_eq!(codes("fn f() {} fn f() {}"), [codes::E0402]);
+        assert_eq!(codes("fn f(a: int, a: int) {}"), [codes::E0402]);
+        assert_eq!(codes("let g = |a, a| a;"), [codes::E0402]);
+        assert_eq!(codes("const X = 1; const X = 2;"), [codes::E0402]);
+        assert_eq!(codes("fn f() {} { fn f() {} }"), []);
+        assert_eq!(codes("struct P { x: int, x: int }"), [codes::E0402]);
+        assert_eq!(codes("struct P {} fn P() {}"), [codes::E0402]);
+    }
+
+    #[test]
+    fn test_structs() {
+        let source = "let p: P = P(1); struct P { x: int, P(x: int) { self.x = x; } }";
+        let (ast, names, errors) = resolve_source(source);
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        // Structs can be used before they're declared, as types and as constructors
+        let StmtKind::Item(item) = &ast.body.stmts[1].kind else {
+            panic!("expected an item");
+        };
+        let StmtKind::Let { ty: Some(ty), .. } = &ast.body.stmts[0].kind else {
+            panic!("expected a let with a type");
+        };
+        assert_eq!(names.get(ty.id), Some(Res::Item(item.id)));
+
+        // Variables don't hide types, and `self` is only declared in constructors and destructors
+        assert_eq!(codes("struct P {} let P = 1; let p: P = P;"), []);
+        assert_eq!(codes("fn f() { self; }"), [codes::E0401]);
+    }
+
+    #[test]
+    fn test_methods() {
+        let source = "let p = P::origin(); impl P { fn origin() -> P { P() } fn get(self) -> P { self } } struct P { P(); }";
+        let (ast, names, errors) = resolve_source(source);
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        // Method names aren't declared in the block, they're found through the type
+        let StmtKind::Item(item) = &ast.body.stmts[1].kind else {
+            panic!("expected an item");
+        };
+        let ItemKind::Impl(def) = &item.kind else {
+            panic!("expected an impl");
+        };
+        let get = &def.methods[1].function;
+        let Some(tail) = &get.body.as_ref().unwrap().tail else {
+            panic!("expected a tail expression");
+        };
+        assert_eq!(
+            names.get(tail.id),
+            Some(Res::Local(get.receiver.as_ref().unwrap().id))
+        );
+        assert_eq!(codes("impl P { fn origin() {} } origin();"), [codes::E0401]);
+        assert_eq!(
+            codes("let x = 1; impl P { fn get(self) -> int { x } }"),
+            [codes::E0401]
+        );
+    }
+
+    #[test]
+    fn test_traits() {
+        let source =
+            "fn area<T: Shape>(shape: T) -> T { shape } trait Shape { fn area(self) -> float; }";
+        let (ast, names, errors) = resolve_source(source);
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        // Type parameters are declared in their function, and traits like other items
+        let (StmtKind::Item(function), StmtKind::Item(trait_)) =
+            (&ast.body.stmts[0].kind, &ast.body.stmts[1].kind)
+        else {
+            panic!("expected items");
+        };
+        let ItemKind::Fn(function) = &function.kind else {
+            panic!("expected a function");
+        };
+        let generic = &function.generics[0];
+        assert_eq!(names.get(generic.bounds[0].id), Some(Res::Item(trait_.id)));
+        assert_eq!(
+            names.get(function.params[0].ty.id),
+            Some(Res::TypeParam(generic.id))
+        );
+        assert_eq!(
+            names.get(function.ret.as_ref().unwrap().id),
+            Some(Res::TypeParam(generic.id))
+        );
+
+        // Type parameters aren't values, and aren't visible in the functions inside of them
+        assert_eq!(codes("fn f<T>() { T; }"), [codes::E0401]);
+        assert_eq!(codes("fn f<T, T>() {}"), [codes::E0402]);
+        assert_eq!(codes("trait A {} let x: dyn A = 1; impl A for int {}"), []);
+        let (ast, names, _) = resolve_source("fn f<T>() { fn g(x: T) {} }");
+        let StmtKind::Item(item) = &ast.body.stmts[0].kind else {
+            panic!("expected an item");
+        };
+        let ItemKind::Fn(f) = &item.kind else {
+            panic!("expected a function");
+        };
+        let StmtKind::Item(item) = &f.body.as_ref().unwrap().stmts[0].kind else {
+            panic!("expected an item");
+        };
+        let ItemKind::Fn(g) = &item.kind else {
+            panic!("expected a function");
+        };
+        assert_eq!(names.get(g.params[0].ty.id), None);
+    }
+
+    #[test]
+    fn test_patterns() {
+        let source = "match 1 { x | x @ 2 if x > 0 => x, _ => 0, }";
+        let (ast, names, errors) = resolve_source(source);
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        // Every alternative binds the first one's variable
+        let Some(ExprKind::Match { arms, .. }) = ast.body.tail.map(|tail| tail.kind) else {
+            panic!("expected a match");
+        };
+        let PatternKind::Or(alternatives) = &arms[0].pattern.kind else {
+            panic!("expected an or-pattern");
+        };
+        let first = alternatives[0].id;
+        assert_eq!(names.get(first), Some(Res::Local(first)));
+        assert_eq!(names.get(alternatives[1].id), Some(Res::Local(first)));
+        assert_eq!(names.get(arms[0].body.id), Some(Res::Local(first)));
+
+        assert_eq!(codes("match 1 { x | 2 => x, _ => 0, }"), [codes::E0403]);
+        assert_eq!(
+            codes("enum E { A(int, int) } match E::A(1, 2) { E::A(x, x) => x, }"),
+            [codes::E0402]
+        );
+        assert_eq!(codes("enum E { A, A }"), [codes::E0402]);
+        assert_eq!(codes("match 1 { x => y, }"), [codes::E0401]);
+    }
+
+    #[test]
+    fn test_suggestions() {
+        let (_, _, errors) = resolve_source("let count = 1; cout;");
+        assert_eq!(errors[0].help.as_deref(), Some("did you mean `count`?"));
+
+        let (_, _, errors) = resolve_source("lne([1]);");
+        assert_eq!(errors[0].help.as_deref(), Some("did you mean `len`?"));
+
+        let (_, _, errors) = resolve_source("let count = 1; something;");
+        assert_eq!(errors[0].help, None);
+
+        let (_, _, errors) = resolve_source("let total = 1; fn f() -> int { total }");
+        assert!(errors[0]
+            .help
+            .as_deref()
+            .unwrap()
+            .contains("outside of this function"));
+    }
+}
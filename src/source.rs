@@ -0,0 +1,169 @@
+//! The source files of a program. Every [`Span`] carries the [`FileId`] of the file it's in, from
+//! the tokens of the lexer to the nodes of the parser, the errors of the checker and of the
+//! interpreter, so a diagnostic can always be rendered with the file it points into.
+
+use std::{
+    fmt, io,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use chumsky::span::{SimpleSpan, Span as _};
+
+/// A byte range in a file. The line and column are only worked out when they are needed, by
+/// [`SourceFile::line_column`].
+pub type Span = SimpleSpan<usize, FileId>;
+
+/// Identifies a file in a [`SourceMap`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FileId(u32);
+
+impl FileId {
+    /// The span of a byte range in this file.
+    pub fn span(self, range: Range<usize>) -> Span {
+        Span::new(self, range)
+    }
+}
+
+#[cfg(test)]
+impl FileId {
+    /// The file of the source code in tests, which are lexed without a source map.
+    pub(crate) const TEST: FileId = FileId(0);
+}
+
+/// Where the source of a file came from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SourceName {
+    Path(PathBuf),
+    /// The nth input entered in the REPL.
+    Repl(usize),
+    /// Source handed to the library by the program embedding it, named by that program.
+    Host(String),
+}
+
+impl fmt::Display for SourceName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceName::Path(path) => write!(f, "{}", path.display()),
+            SourceName::Repl(n) => write!(f, "<repl:{}>", n),
+            SourceName::Host(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SourceFile {
+    name: SourceName,
+    source: String,
+    /// The byte offset of the start of every line.
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    fn new(name: SourceName, source: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        Self {
+            name,
+            source,
+            line_starts,
+        }
+    }
+
+    pub fn name(&self) -> &SourceName {
+        &self.name
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The line and column of a byte offset, both starting at 1. Columns count characters.
+    pub fn line_column(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.source.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let column = self.source[self.line_starts[line]..offset].chars().count();
+        (line + 1, column + 1)
+    }
+}
+
+/// All of the source files that have been loaded, so that spans only need to store a [`FileId`].
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+    repl_inputs: usize,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, name: SourceName, source: impl Into<String>) -> FileId {
+        let id = FileId(self.files.len() as u32);
+        self.files.push(SourceFile::new(name, source.into()));
+        id
+    }
+
+    /// Reads a file from disk.
+    pub fn load(&mut self, path: impl AsRef<Path>) -> io::Result<FileId> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path)?;
+        Ok(self.add(SourceName::Path(path.to_path_buf()), source))
+    }
+
+    /// Adds an input from the REPL, they are numbered in the order they're added.
+    pub fn add_repl_input(&mut self, source: impl Into<String>) -> FileId {
+        self.repl_inputs += 1;
+        self.add(SourceName::Repl(self.repl_inputs), source)
+    }
+
+    /// Gets a file, `None` if the id is from another source map.
+    pub fn get(&self, file: FileId) -> Option<&SourceFile> {
+        self.files.get(file.0 as usize)
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = (FileId, &SourceFile)> {
+        self.files
+            .iter()
+            .enumerate()
+            .map(|(i, file)| (FileId(i as u32), file))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_column() {
+        let mut sources = SourceMap::new();
+        let file = sources.add(SourceName::Host("test".into()), "ab\nçd\n\nx");
+        let file = sources.get(file).unwrap();
+
+        assert_eq!(file.line_column(0), (1, 1));
+        assert_eq!(file.line_column(2), (1, 3));
+        assert_eq!(file.line_column(3), (2, 1));
+        // `ç` is two bytes long
+        assert_eq!(file.line_column(5), (2, 2));
+        assert_eq!(file.line_column(7), (3, 1));
+        assert_eq!(file.line_column(8), (4, 1));
+        assert_eq!(file.line_column(100), (4, 2));
+    }
+
+    #[test]
+    fn test_files() {
+        let mut sources = SourceMap::new();
+        let a = sources.add_repl_input("let a = 1;");
+        let b = sources.add_repl_input("a");
+        let c = sources.add(SourceName::Host("script".into()), "");
+
+        assert_ne!(a, b);
+        assert_eq!(sources.get(b).unwrap().name().to_string(), "<repl:2>");
+        assert_eq!(sources.get(c).unwrap().name().to_string(), "script");
+        assert_eq!(sources.files().count(), 3);
+        assert!(SourceMap::new().get(a).is_none());
+    }
+}
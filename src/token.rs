@@ -1,4 +1,4 @@
-use crate::SourceLocation;
+use std::borrow::Cow;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token<'a> {
@@ -8,6 +8,16 @@ pub enum Token<'a> {
     Keyword(Keyword),
     Punctuation(Punctuation),
     Delimiter(Delimiter),
+    /// The text of a doc comment, without the `///`, `//!`, `/**` or `/*!` it started with.
+    DocComment(DocStyle, &'a str),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+pub enum DocStyle {
+    /// `///` or `/** */`, documents the item after it.
+    Outer,
+    /// `//!` or `/*! */`, documents the item it is inside of.
+    Inner,
 }
 
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
@@ -49,23 +59,96 @@ pub enum Operator {
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Literal<'a> {
-    /// Can contain underscores.
-    Integer(&'a str),
-    /// Can contain underscores and always a decimal point.
-    Float(&'a str),
-    String(&'a str),
-    Char(&'a str),
+    /// The digits can contain underscores, the radix prefix and suffix are not included.
+    Integer {
+        digits: &'a str,
+        radix: u32,
+        suffix: Option<NumberSuffix>,
+    },
+    /// The digits can contain underscores, a decimal point and an exponent.
+    /// Integers with a float suffix are also floats.
+    Float {
+        digits: &'a str,
+        suffix: Option<NumberSuffix>,
+    },
+    /// Borrowed from the source unless it had escape sequences to process.
+    String(Cow<'a, str>),
+    Char(char),
     Boolean(&'a str),
 }
 
+/// The type suffix of a number literal, like `10i64` or `2.0f32`.
+#[derive(Debug, PartialEq, Clone, Copy, Eq, Hash)]
+pub enum NumberSuffix {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    F32,
+    F64,
+}
+
+impl NumberSuffix {
+    pub fn parse(suffix: &str) -> Option<Self> {
+        match suffix {
+            "i8" => Some(NumberSuffix::I8),
+            "i16" => Some(NumberSuffix::I16),
+            "i32" => Some(NumberSuffix::I32),
+            "i64" => Some(NumberSuffix::I64),
+            "u8" => Some(NumberSuffix::U8),
+            "u16" => Some(NumberSuffix::U16),
+            "u32" => Some(NumberSuffix::U32),
+            "u64" => Some(NumberSuffix::U64),
+            "f32" => Some(NumberSuffix::F32),
+            "f64" => Some(NumberSuffix::F64),
+            _ => None,
+        }
+    }
+
+    pub fn is_float(self) -> bool {
+        matches!(self, NumberSuffix::F32 | NumberSuffix::F64)
+    }
+}
+
+impl std::fmt::Display for NumberSuffix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let suffix = match self {
+            NumberSuffix::I8 => "i8",
+            NumberSuffix::I16 => "i16",
+            NumberSuffix::I32 => "i32",
+            NumberSuffix::I64 => "i64",
+            NumberSuffix::U8 => "u8",
+            NumberSuffix::U16 => "u16",
+            NumberSuffix::U32 => "u32",
+            NumberSuffix::U64 => "u64",
+            NumberSuffix::F32 => "f32",
+            NumberSuffix::F64 => "f64",
+        };
+
+        write!(f, "{}", suffix)
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Punctuation {
     Comma,
     Dot,
     Colon,
+    /// `::`, between a type and one of its functions.
+    DoubleColon,
     Semicolon,
     Arrow,
     FatArrow,
+    /// `..`, between the bounds of a range pattern that excludes its end.
+    DotDot,
+    /// `..=`
+    DotDotEq,
+    /// `@`, between a binding and the pattern it has to match.
+    At,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -92,6 +175,9 @@ pub enum Keyword {
     Match,
     Case,
     Struct,
+    Impl,
+    Trait,
+    Dyn,
     Enum,
     Type,
     Use,
@@ -100,4 +186,160 @@ pub enum Keyword {
     Static,
     Mut,
     Const,
+    Loop,
+    In,
+    As,
+}
+
+impl std::fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Identifier(ident) => write!(f, "{}", ident),
+            Token::Literal(literal) => write!(f, "{}", literal),
+            Token::Operator(op) => write!(f, "{}", op),
+            Token::Keyword(keyword) => write!(f, "{}", keyword),
+            Token::Punctuation(punct) => write!(f, "{}", punct),
+            Token::Delimiter(delim) => write!(f, "{}", delim),
+            Token::DocComment(..) => write!(f, "doc comment"),
+        }
+    }
+}
+
+impl std::fmt::Display for Literal<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Literal::Integer {
+                digits,
+                radix,
+                suffix,
+            } => {
+                let prefix = match radix {
+                    16 => "0x",
+                    8 => "0o",
+                    2 => "0b",
+                    _ => "",
+                };
+                write!(f, "{}{}", prefix, digits)?;
+                suffix.map_or(Ok(()), |suffix| write!(f, "{}", suffix))
+            }
+            Literal::Float { digits, suffix } => {
+                write!(f, "{}", digits)?;
+                suffix.map_or(Ok(()), |suffix| write!(f, "{}", suffix))
+            }
+            Literal::String(string) => write!(f, "{:?}", string),
+            Literal::Char(char) => write!(f, "{:?}", char),
+            Literal::Boolean(boolean) => write!(f, "{}", boolean),
+        }
+    }
+}
+
+impl std::fmt::Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let op = match self {
+            Operator::Add => "+",
+            Operator::Subtract => "-",
+            Operator::Multiply => "*",
+            Operator::Divide => "/",
+            Operator::Modulo => "%",
+            Operator::LogicalAnd => "&&",
+            Operator::LogicalOr => "||",
+            Operator::LogicalNot => "!",
+            Operator::BitwiseAnd => "&",
+            Operator::BitwiseOr => "|",
+            Operator::BitwiseXor => "^",
+            Operator::BitwiseNot => "~",
+            Operator::ShiftLeft => "<<",
+            Operator::ShiftRight => ">>",
+            Operator::Equal => "==",
+            Operator::NotEqual => "!=",
+            Operator::LessThan => "<",
+            Operator::GreaterThan => ">",
+            Operator::LessThanOrEqual => "<=",
+            Operator::GreaterThanOrEqual => ">=",
+            Operator::Assign => "=",
+            Operator::AddAssign => "+=",
+            Operator::SubAssign => "-=",
+            Operator::MulAssign => "*=",
+            Operator::DivAssign => "/=",
+            Operator::ModAssign => "%=",
+            Operator::AndAssign => "&=",
+            Operator::OrAssign => "|=",
+            Operator::XorAssign => "^=",
+            Operator::ShlAssign => "<<=",
+            Operator::ShrAssign => ">>=",
+            Operator::Increment => "++",
+            Operator::Decrement => "--",
+        };
+
+        write!(f, "{}", op)
+    }
+}
+
+impl std::fmt::Display for Punctuation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let punct = match self {
+            Punctuation::Comma => ",",
+            Punctuation::Dot => ".",
+            Punctuation::Colon => ":",
+            Punctuation::DoubleColon => "::",
+            Punctuation::Semicolon => ";",
+            Punctuation::Arrow => "->",
+            Punctuation::FatArrow => "=>",
+            Punctuation::DotDot => "..",
+            Punctuation::DotDotEq => "..=",
+            Punctuation::At => "@",
+        };
+
+        write!(f, "{}", punct)
+    }
+}
+
+impl std::fmt::Display for Delimiter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let delim = match self {
+            Delimiter::OpenParen => "(",
+            Delimiter::CloseParen => ")",
+            Delimiter::OpenBrace => "{",
+            Delimiter::CloseBrace => "}",
+            Delimiter::OpenBracket => "[",
+            Delimiter::CloseBracket => "]",
+        };
+
+        write!(f, "{}", delim)
+    }
+}
+
+impl std::fmt::Display for Keyword {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let keyword = match self {
+            Keyword::Fn => "fn",
+            Keyword::Let => "let",
+            Keyword::If => "if",
+            Keyword::Else => "else",
+            Keyword::While => "while",
+            Keyword::For => "for",
+            Keyword::Return => "return",
+            Keyword::Break => "break",
+            Keyword::Continue => "continue",
+            Keyword::Match => "match",
+            Keyword::Case => "case",
+            Keyword::Struct => "struct",
+            Keyword::Impl => "impl",
+            Keyword::Trait => "trait",
+            Keyword::Dyn => "dyn",
+            Keyword::Enum => "enum",
+            Keyword::Type => "type",
+            Keyword::Use => "use",
+            Keyword::Mod => "mod",
+            Keyword::Extern => "extern",
+            Keyword::Static => "static",
+            Keyword::Mut => "mut",
+            Keyword::Const => "const",
+            Keyword::Loop => "loop",
+            Keyword::In => "in",
+            Keyword::As => "as",
+        };
+
+        write!(f, "{}", keyword)
+    }
 }